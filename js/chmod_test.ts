@@ -0,0 +1,62 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { testPerm, assertEqual } from "./test_util.ts";
+import * as deno from "deno";
+
+testPerm({ read: true, write: true }, function chmodSyncSuccess() {
+  const enc = new TextEncoder();
+  const data = enc.encode("Hello");
+  const filename = deno.makeTempDirSync() + "/test.txt";
+  deno.writeFileSync(filename, data, 0o666);
+  deno.chmodSync(filename, 0o444);
+  const fileInfo = deno.statSync(filename);
+  // Windows has no Unix permissions, so there chmod does nothing and
+  // stat reports no mode.
+  if (fileInfo.mode !== null) {
+    assertEqual(fileInfo.mode & 0o777, 0o444);
+  }
+});
+
+testPerm({ write: true }, function chmodSyncNotFound() {
+  let err;
+  try {
+    deno.chmodSync("/badfile.txt", 0o777);
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.NotFound);
+  assertEqual(err.name, "NotFound");
+});
+
+testPerm({ write: false }, function chmodSyncPerm() {
+  let err;
+  try {
+    deno.chmodSync("/somefile.txt", 0o777);
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
+
+testPerm({ read: true, write: true }, async function chmodSuccess() {
+  const enc = new TextEncoder();
+  const data = enc.encode("Hello");
+  const filename = deno.makeTempDirSync() + "/test.txt";
+  deno.writeFileSync(filename, data, 0o666);
+  await deno.chmod(filename, 0o444);
+  const fileInfo = deno.statSync(filename);
+  if (fileInfo.mode !== null) {
+    assertEqual(fileInfo.mode & 0o777, 0o444);
+  }
+});
+
+testPerm({ write: true }, async function chmodNotFound() {
+  let err;
+  try {
+    await deno.chmod("/badfile.txt", 0o777);
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.NotFound);
+  assertEqual(err.name, "NotFound");
+});
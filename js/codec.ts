@@ -0,0 +1,114 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import * as msg from "gen/msg_generated";
+import { flatbuffers } from "flatbuffers";
+import { assert } from "./util";
+import * as dispatch from "./dispatch";
+import { DenoError, ErrorKind } from "./errors";
+import { TextDecoder, TextEncoder } from "./text_encoding";
+
+/** The request and response of a record op: named string fields. */
+export interface OpRecord {
+  [field: string]: string;
+}
+
+// Must match the codecs of the same name in src/codec.rs.
+interface Codec {
+  encode(record: OpRecord): Uint8Array;
+  decode(bytes: Uint8Array): OpRecord;
+}
+
+const encoder = new TextEncoder();
+const decoder = new TextDecoder();
+
+// Each field is the key and then the value, both as a little endian u32
+// length followed by that many bytes of UTF-8.
+const recordCodec: Codec = {
+  encode(record: OpRecord): Uint8Array {
+    const strings: Uint8Array[] = [];
+    for (const key of Object.keys(record)) {
+      strings.push(encoder.encode(key), encoder.encode(record[key]));
+    }
+    let len = 0;
+    for (const s of strings) {
+      len += 4 + s.byteLength;
+    }
+    const bytes = new Uint8Array(len);
+    const view = new DataView(bytes.buffer);
+    let offset = 0;
+    for (const s of strings) {
+      view.setUint32(offset, s.byteLength, true);
+      bytes.set(s, offset + 4);
+      offset += 4 + s.byteLength;
+    }
+    return bytes;
+  },
+
+  decode(bytes: Uint8Array): OpRecord {
+    const view = new DataView(bytes.buffer, bytes.byteOffset);
+    const take = (): string => {
+      assert(offset + 4 <= bytes.byteLength);
+      const len = view.getUint32(offset, true);
+      const start = offset + 4;
+      offset = start + len;
+      assert(offset <= bytes.byteLength);
+      return decoder.decode(bytes.subarray(start, offset));
+    };
+    const record: OpRecord = {};
+    let offset = 0;
+    while (offset < bytes.byteLength) {
+      const key = take();
+      record[key] = take();
+    }
+    return record;
+  }
+};
+
+// A JSON object whose values are all strings.
+const jsonCodec: Codec = {
+  encode(record: OpRecord): Uint8Array {
+    return encoder.encode(JSON.stringify(record));
+  },
+
+  decode(bytes: Uint8Array): OpRecord {
+    return JSON.parse(decoder.decode(bytes));
+  }
+};
+
+const codecs: { [name: string]: Codec } = {
+  record: recordCodec,
+  json: jsonCodec
+};
+
+let codec = recordCodec;
+
+// Picks the codec named in the start message.
+// @internal
+export function setCodec(name: string): void {
+  assert(name in codecs, `Unknown codec ${name}`);
+  codec = codecs[name];
+}
+
+/**
+ * Calls the op that the embedder registered under the given name with
+ * Isolate::register_record_op(). Throws a DenoError if the op fails or if
+ * there is no such op.
+ *
+ *     import { callRecordOp } from "deno";
+ *     const { sum } = callRecordOp("add", { a: "40", b: "2" });
+ */
+export function callRecordOp(op: string, fields: OpRecord = {}): OpRecord {
+  const request = codec.encode({ ...fields, op });
+  const builder = new flatbuffers.Builder();
+  msg.Record.startRecord(builder);
+  const inner = msg.Record.endRecord(builder);
+  const baseRes = dispatch.sendSync(builder, msg.Any.Record, inner, request)!;
+  assert(msg.Any.RecordRes === baseRes.innerType());
+  const res = new msg.RecordRes();
+  assert(baseRes.inner(res) != null);
+  const response = codec.decode(res.dataArray()!);
+  if ("error" in response && "kind" in response) {
+    const kind = ErrorKind[response.kind as keyof typeof ErrorKind];
+    throw new DenoError(kind, response.error);
+  }
+  return response;
+}
@@ -0,0 +1,17 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { test, assertEqual } from "./test_util.ts";
+import * as deno from "deno";
+
+test(function callRecordOpUnknown() {
+  // The deno binary registers no record ops, so this makes the round trip
+  // through the codec and comes back as an error record.
+  let err;
+  try {
+    deno.callRecordOp("noSuchOp", { quote: "\"", unicode: "é🦕" });
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.InvalidInput);
+  assertEqual(err.name, "InvalidInput");
+  assertEqual(err.message, "Invalid record: no such op");
+});
@@ -3,6 +3,7 @@ import * as ts from "typescript";
 import { assetSourceCode } from "./assets";
 // tslint:disable-next-line:no-circular-imports
 import * as deno from "./deno";
+import { DenoError, ErrorKind } from "./errors";
 import { globalEval } from "./global_eval";
 import { libdeno } from "./libdeno";
 import { window } from "./globals";
@@ -144,11 +145,17 @@ export class DenoCompiler
   // arguments
   private readonly _options: Readonly<ts.CompilerOptions> = {
     allowJs: true,
+    // JSON modules only have a default export
+    allowSyntheticDefaultImports: true,
     module: ts.ModuleKind.AMD,
+    // Modules are resolved by `resolveModuleNames()`, but TypeScript won't
+    // type check imports of JSON without this
+    moduleResolution: ts.ModuleResolutionKind.NodeJs,
     outDir: "$deno$",
     // TODO https://github.com/denoland/deno/issues/23
     inlineSourceMap: true,
     inlineSources: true,
+    resolveJsonModule: true,
     stripComments: true,
     target: ts.ScriptTarget.ESNext
   };
@@ -172,6 +179,37 @@ export class DenoCompiler
   // Flags forcing recompilation of TS code
   public recompile = false;
 
+  /**
+   * JSON modules are not emitted by TypeScript. Instead the source is parsed
+   * and turned into an AMD module whose default export is the parsed value.
+   */
+  private _compileJson(moduleMetaData: ModuleMetaData): OutputCode {
+    const { fileName, sourceCode } = moduleMetaData;
+    let json: string;
+    try {
+      json = JSON.stringify(JSON.parse(sourceCode));
+    } catch (e) {
+      throw new DenoError(
+        ErrorKind.InvalidData,
+        `Invalid JSON in "${fileName}": ${e.message}`
+      );
+    }
+    // JSON strings can contain line and paragraph separators, JavaScript
+    // strings can't
+    json = json.replace(/\u2028/g, "\\u2028").replace(/\u2029/g, "\\u2029");
+    const outputCode = (moduleMetaData.outputCode = [
+      `define(["require", "exports"], function (require, exports) {`,
+      `    "use strict";`,
+      `    Object.defineProperty(exports, "__esModule", { value: true });`,
+      `    exports.default = ${json};`,
+      `});`,
+      `//# sourceURL=${fileName}`
+    ].join(EOL));
+    moduleMetaData.scriptVersion = "1";
+    this._os.codeCache(fileName, sourceCode, outputCode);
+    return outputCode;
+  }
+
   /**
    * Drain the run queue, retrieving the arguments for the module
    * factory and calling the module's factory.
@@ -422,6 +460,9 @@ export class DenoCompiler
       return moduleMetaData.outputCode;
     }
     const { fileName, sourceCode, moduleId } = moduleMetaData;
+    if (fileName.endsWith(".json")) {
+      return this._compileJson(moduleMetaData);
+    }
     console.warn("Compiling", moduleId);
     const service = this._service;
     const output = service.getEmitOutput(fileName);
@@ -461,6 +502,14 @@ export class DenoCompiler
     return moduleMetaData.outputCode;
   }
 
+  /**
+   * Returns the source code of a module that has been resolved, if any.
+   */
+  getSource(fileName: ModuleFileName): SourceCode | undefined {
+    const moduleMetaData = this._moduleMetaDataMap.get(fileName);
+    return moduleMetaData && moduleMetaData.sourceCode;
+  }
+
   /**
    * For a given module specifier and containing file, return a list of absolute
    * identifiers for dependent modules that are required by this module.
@@ -100,6 +100,20 @@ const fooBazTsOutput = `define(["require", "exports", "./bar.ts"], function (req
 //# sourceURL=/root/project/foo/baz.ts`;
 // tslint:enable:max-line-length
 
+const dataJsonModuleInfo = mockModuleInfo(
+  "/root/project/data.json",
+  "/root/project/data.json",
+  `{ "name": "deno", "versions": [1, 2] }\n`,
+  null
+);
+
+const badJsonModuleInfo = mockModuleInfo(
+  "/root/project/bad.json",
+  "/root/project/bad.json",
+  `{ "name": "deno", }\n`,
+  null
+);
+
 const moduleMap: {
   [containFile: string]: { [moduleSpecifier: string]: ModuleInfo };
 } = {
@@ -116,7 +130,9 @@ const moduleMap: {
       fooBazTsSource,
       fooBazTsOutput
     ),
-    "modA.ts": modAModuleInfo
+    "modA.ts": modAModuleInfo,
+    "data.json": dataJsonModuleInfo,
+    "bad.json": badJsonModuleInfo
   },
   "/root/project/foo/baz.ts": {
     "./bar.ts": mockModuleInfo(
@@ -427,6 +443,41 @@ test(function compilerGetModuleDependencies() {
   teardown();
 });
 
+test(function compilerRunJson() {
+  setup();
+  // The output is evaluated for real, so that `define` is called with the
+  // synthesized factory
+  (compilerInstance as any)._globalEval = originals._globalEval;
+  (compilerInstance as any)._window = originals._window;
+  const moduleMetaData = compilerInstance.run("data.json", "/root/project");
+  assert(moduleMetaData.hasRun);
+  assertEqual(moduleMetaData.exports.default.name, "deno");
+  assertEqual(moduleMetaData.exports.default.versions, [1, 2]);
+  assertEqual(getEmitOutputStack.length, 0, "JSON should not be emitted.");
+  assertEqual(codeCacheStack.length, 1);
+  assertEqual(codeCacheStack[0].fileName, "/root/project/data.json");
+  teardown();
+});
+
+test(function compilerCompileInvalidJson() {
+  setup();
+  const moduleMetaData = compilerInstance.resolveModule(
+    "bad.json",
+    "/root/project"
+  );
+  let err;
+  try {
+    compilerInstance.compile(moduleMetaData);
+  } catch (e) {
+    err = e;
+  }
+  assert(err instanceof deno.DenoError);
+  assertEqual(err.kind, deno.ErrorKind.InvalidData);
+  assert(err.message.includes(`"/root/project/bad.json"`));
+  assertEqual(codeCacheStack.length, 0);
+  teardown();
+});
+
 // TypeScript LanguageServiceHost APIs
 
 test(function compilerGetCompilationSettings() {
@@ -437,6 +488,7 @@ test(function compilerGetCompilationSettings() {
     "outDir",
     "inlineSourceMap",
     "inlineSources",
+    "resolveJsonModule",
     "stripComments",
     "target"
   ]) {
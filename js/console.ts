@@ -4,11 +4,38 @@ type ConsoleOptions = Partial<{
   showHidden: boolean;
   depth: number;
   colors: boolean;
+  maxLength: number;
 }>;
 
+interface StringifyOptions {
+  depth: number;
+  colors: boolean;
+  // At most this many entries of each object and array are shown, or all of
+  // them if it is 0.
+  maxLength: number;
+}
+
 // Default depth of logging nested objects
 const DEFAULT_MAX_DEPTH = 2;
 
+// Wraps s in the ANSI escapes for the given color, if colors are on.
+function paint(s: string, color: number, options: StringifyOptions): string {
+  return options.colors ? `\x1b[${color}m${s}\x1b[39m` : s;
+}
+
+// How many of count entries are shown, and what is shown instead of the rest.
+function shownEntries(
+  count: number,
+  options: StringifyOptions
+): [number, string | null] {
+  const max = options.maxLength;
+  if (max <= 0 || count <= max) {
+    return [count, null];
+  }
+  const more = count - max;
+  return [max, `... ${more} more ${more === 1 ? "item" : "items"}`];
+}
+
 // tslint:disable-next-line:no-any
 function getClassInstanceName(instance: any): string {
   if (typeof instance !== "object") {
@@ -38,11 +65,15 @@ function createArrayString(
   value: any[],
   ctx: ConsoleContext,
   level: number,
-  maxLevel: number
+  options: StringifyOptions
 ): string {
   const entries: string[] = [];
-  for (const el of value) {
-    entries.push(stringifyWithQuotes(ctx, el, level + 1, maxLevel));
+  const [shown, rest] = shownEntries(value.length, options);
+  for (let i = 0; i < shown; i++) {
+    entries.push(stringifyWithQuotes(ctx, value[i], level + 1, options));
+  }
+  if (rest != null) {
+    entries.push(rest);
   }
   ctx.delete(value);
   if (entries.length === 0) {
@@ -56,7 +87,7 @@ function createObjectString(
   value: any,
   ctx: ConsoleContext,
   level: number,
-  maxLevel: number
+  options: StringifyOptions
 ): string {
   const entries: string[] = [];
   let baseString = "";
@@ -67,11 +98,16 @@ function createObjectString(
     shouldShowClassName = true;
   }
 
-  for (const key of Object.keys(value)) {
+  const keys = Object.keys(value);
+  const [shown, rest] = shownEntries(keys.length, options);
+  for (const key of keys.slice(0, shown)) {
     entries.push(
-      `${key}: ${stringifyWithQuotes(ctx, value[key], level + 1, maxLevel)}`
+      `${key}: ${stringifyWithQuotes(ctx, value[key], level + 1, options)}`
     );
   }
+  if (rest != null) {
+    entries.push(rest);
+  }
 
   ctx.delete(value);
 
@@ -93,29 +129,30 @@ function stringify(
   // tslint:disable-next-line:no-any
   value: any,
   level: number,
-  maxLevel: number
+  options: StringifyOptions
 ): string {
   switch (typeof value) {
     case "string":
       return value;
     case "number":
     case "boolean":
+      return paint(String(value), 33, options);
     case "undefined":
     case "symbol":
       return String(value);
     case "function":
-      return createFunctionString(value as Function, ctx);
+      return paint(createFunctionString(value as Function, ctx), 36, options);
     case "object":
       if (value === null) {
         return "null";
       }
 
       if (ctx.has(value)) {
-        return "[Circular]";
+        return paint("[Circular]", 36, options);
       }
 
-      if (level >= maxLevel) {
-        return `[object]`;
+      if (level >= options.depth) {
+        return paint(`[object]`, 36, options);
       }
 
       ctx.add(value);
@@ -124,9 +161,9 @@ function stringify(
         return value.stack! || "";
       } else if (Array.isArray(value)) {
         // tslint:disable-next-line:no-any
-        return createArrayString(value as any[], ctx, level, maxLevel);
+        return createArrayString(value as any[], ctx, level, options);
       } else {
-        return createObjectString(value, ctx, level, maxLevel);
+        return createObjectString(value, ctx, level, options);
       }
     default:
       return "[Not Implemented]";
@@ -139,13 +176,13 @@ function stringifyWithQuotes(
   // tslint:disable-next-line:no-any
   value: any,
   level: number,
-  maxLevel: number
+  options: StringifyOptions
 ): string {
   switch (typeof value) {
     case "string":
-      return `"${value}"`;
+      return paint(`"${value}"`, 32, options);
     default:
-      return stringify(ctx, value, level, maxLevel);
+      return stringify(ctx, value, level, options);
   }
 }
 
@@ -155,22 +192,20 @@ export function stringifyArgs(
   args: any[],
   options: ConsoleOptions = {}
 ): string {
+  const stringifyOptions: StringifyOptions = {
+    // use default maximum depth for null or undefined argument
+    // tslint:disable-next-line:triple-equals
+    depth: options.depth != undefined ? options.depth : DEFAULT_MAX_DEPTH,
+    colors: !!options.colors,
+    maxLength: options.maxLength || 0
+  };
   const out: string[] = [];
   for (const a of args) {
     if (typeof a === "string") {
       out.push(a);
     } else {
-      out.push(
-        // use default maximum depth for null or undefined argument
-        stringify(
-          // tslint:disable-next-line:no-any
-          new Set<any>(),
-          a,
-          0,
-          // tslint:disable-next-line:triple-equals
-          options.depth != undefined ? options.depth : DEFAULT_MAX_DEPTH
-        )
-      );
+      // tslint:disable-next-line:no-any
+      out.push(stringify(new Set<any>(), a, 0, stringifyOptions));
     }
   }
   return out.join(" ");
@@ -0,0 +1,29 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import * as msg from "gen/msg_generated";
+import { flatbuffers } from "flatbuffers";
+import { assert } from "./util";
+import * as dispatch from "./dispatch";
+
+export interface ConsoleSize {
+  columns: number;
+  rows: number;
+}
+
+/**
+ * Returns the size of the terminal that the stdio resource rid is. Throws a
+ * NotATerminal error if it isn't one, for example because stdout is piped.
+ *
+ *     import { consoleSize, stdout } from "deno";
+ *     const { columns } = consoleSize(stdout.fd);
+ */
+export function consoleSize(rid: number): ConsoleSize {
+  const builder = new flatbuffers.Builder();
+  msg.ConsoleSize.startConsoleSize(builder);
+  msg.ConsoleSize.addRid(builder, rid);
+  const inner = msg.ConsoleSize.endConsoleSize(builder);
+  const baseRes = dispatch.sendSync(builder, msg.Any.ConsoleSize, inner)!;
+  assert(msg.Any.ConsoleSizeRes === baseRes.innerType());
+  const res = new msg.ConsoleSizeRes();
+  assert(baseRes.inner(res) != null);
+  return { columns: res.columns(), rows: res.rows() };
+}
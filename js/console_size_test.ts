@@ -0,0 +1,25 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { test, assert, assertEqual } from "./test_util.ts";
+import * as deno from "deno";
+
+test(function consoleSizeStdout() {
+  // The tests may or may not be run in a terminal.
+  try {
+    const { columns, rows } = deno.consoleSize(deno.stdout.fd);
+    assert(columns >= 0);
+    assert(rows >= 0);
+  } catch (e) {
+    assertEqual(e.kind, deno.ErrorKind.NotATerminal);
+    assertEqual(e.name, "NotATerminal");
+  }
+});
+
+test(function consoleSizeBadResource() {
+  let err;
+  try {
+    deno.consoleSize(12345);
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.BadFileDescriptor);
+});
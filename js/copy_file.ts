@@ -8,7 +8,8 @@ import * as dispatch from "./dispatch";
  * Creates a new file if target does not exists, and if target exists,
  * overwrites original content of the target file.
  * It would also copy the permission of the original file
- * to the destination.
+ * to the destination. Requires read permission for from and write
+ * permission for to.
  *
  *     import { copyFileSync } from "deno";
  *     copyFileSync("from.txt", "to.txt");
@@ -22,7 +23,8 @@ export function copyFileSync(from: string, to: string): void {
  * Creates a new file if target does not exists, and if target exists,
  * overwrites original content of the target file.
  * It would also copy the permission of the original file
- * to the destination.
+ * to the destination. Requires read permission for from and write
+ * permission for to.
  *
  *     import { copyFile } from "deno";
  *     await copyFile("from.txt", "to.txt");
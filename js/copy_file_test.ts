@@ -19,7 +19,7 @@ function assertSameContent(filename1: string, filename2: string) {
   assertEqual(data1, data2);
 }
 
-testPerm({ write: true }, function copyFileSyncSuccess() {
+testPerm({ read: true, write: true }, function copyFileSyncSuccess() {
   const tempDir = deno.makeTempDirSync();
   const fromFilename = tempDir + "/from.txt";
   const toFilename = tempDir + "/to.txt";
@@ -31,7 +31,7 @@ testPerm({ write: true }, function copyFileSyncSuccess() {
   assertSameContent(fromFilename, toFilename);
 });
 
-testPerm({ write: true }, function copyFileSyncFailure() {
+testPerm({ read: true, write: true }, function copyFileSyncFailure() {
   const tempDir = deno.makeTempDirSync();
   const fromFilename = tempDir + "/from.txt";
   const toFilename = tempDir + "/to.txt";
@@ -43,18 +43,11 @@ testPerm({ write: true }, function copyFileSyncFailure() {
     err = e;
   }
   assert(!!err);
-  if (deno.platform.os === "win") {
-    assertEqual(err.kind, deno.ErrorKind.NotFound);
-    assertEqual(err.name, "NotFound");
-  } else {
-    // On *nix, Rust deem non-existent path as invalid input
-    // See https://github.com/rust-lang/rust/issues/54800
-    assertEqual(err.kind, deno.ErrorKind.InvalidInput);
-    assertEqual(err.name, "InvalidInput");
-  }
+  assertEqual(err.kind, deno.ErrorKind.NotFound);
+  assertEqual(err.name, "NotFound");
 });
 
-testPerm({ write: true }, function copyFileSyncOverwrite() {
+testPerm({ read: true, write: true }, function copyFileSyncOverwrite() {
   const tempDir = deno.makeTempDirSync();
   const fromFilename = tempDir + "/from.txt";
   const toFilename = tempDir + "/to.txt";
@@ -68,7 +61,19 @@ testPerm({ write: true }, function copyFileSyncOverwrite() {
   assertSameContent(fromFilename, toFilename);
 });
 
-testPerm({ write: false }, function copyFileSyncPerm() {
+testPerm({ read: false, write: true }, function copyFileSyncReadPerm() {
+  let err;
+  try {
+    deno.copyFileSync("/from.txt", "/to.txt");
+  } catch (e) {
+    err = e;
+  }
+  assert(!!err);
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
+
+testPerm({ read: true, write: false }, function copyFileSyncPerm() {
   let err;
   try {
     deno.copyFileSync("/from.txt", "/to.txt");
@@ -80,7 +85,7 @@ testPerm({ write: false }, function copyFileSyncPerm() {
   assertEqual(err.name, "PermissionDenied");
 });
 
-testPerm({ write: true }, async function copyFileSuccess() {
+testPerm({ read: true, write: true }, async function copyFileSuccess() {
   const tempDir = deno.makeTempDirSync();
   const fromFilename = tempDir + "/from.txt";
   const toFilename = tempDir + "/to.txt";
@@ -92,7 +97,7 @@ testPerm({ write: true }, async function copyFileSuccess() {
   assertSameContent(fromFilename, toFilename);
 });
 
-testPerm({ write: true }, async function copyFileFailure() {
+testPerm({ read: true, write: true }, async function copyFileFailure() {
   const tempDir = deno.makeTempDirSync();
   const fromFilename = tempDir + "/from.txt";
   const toFilename = tempDir + "/to.txt";
@@ -104,18 +109,11 @@ testPerm({ write: true }, async function copyFileFailure() {
     err = e;
   }
   assert(!!err);
-  if (deno.platform.os === "win") {
-    assertEqual(err.kind, deno.ErrorKind.NotFound);
-    assertEqual(err.name, "NotFound");
-  } else {
-    // On *nix, Rust deem non-existent path as invalid input
-    // See https://github.com/rust-lang/rust/issues/54800
-    assertEqual(err.kind, deno.ErrorKind.InvalidInput);
-    assertEqual(err.name, "InvalidInput");
-  }
+  assertEqual(err.kind, deno.ErrorKind.NotFound);
+  assertEqual(err.name, "NotFound");
 });
 
-testPerm({ write: true }, async function copyFileOverwrite() {
+testPerm({ read: true, write: true }, async function copyFileOverwrite() {
   const tempDir = deno.makeTempDirSync();
   const fromFilename = tempDir + "/from.txt";
   const toFilename = tempDir + "/to.txt";
@@ -129,7 +127,19 @@ testPerm({ write: true }, async function copyFileOverwrite() {
   assertSameContent(fromFilename, toFilename);
 });
 
-testPerm({ write: false }, async function copyFilePerm() {
+testPerm({ read: false, write: true }, async function copyFileReadPerm() {
+  let err;
+  try {
+    await deno.copyFile("/from.txt", "/to.txt");
+  } catch (e) {
+    err = e;
+  }
+  assert(!!err);
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
+
+testPerm({ read: true, write: false }, async function copyFilePerm() {
   let err;
   try {
     await deno.copyFile("/from.txt", "/to.txt");
@@ -1,8 +1,31 @@
 // Copyright 2018 the Deno authors. All rights reserved. MIT license.
 // Public deno module.
 /// <amd-module name="deno"/>
-export { env, exit } from "./os";
-export { File, open, stdin, stdout, stderr, read, write, close } from "./files";
+export {
+  env,
+  getEnv,
+  exit,
+  setExitCode,
+  hostname,
+  osRelease,
+  buildInfo,
+  BuildInfo
+} from "./os";
+export {
+  File,
+  open,
+  OpenMode,
+  stdin,
+  stdout,
+  stderr,
+  read,
+  write,
+  seek,
+  SeekMode,
+  fsync,
+  fdatasync,
+  close
+} from "./files";
 export {
   copy,
   ReadResult,
@@ -17,27 +40,58 @@ export {
   ReadWriteCloser,
   ReadWriteSeeker
 } from "./io";
-export { mkdirSync, mkdir } from "./mkdir";
+export { mkdirSync, mkdir, MkdirOptions } from "./mkdir";
 export { makeTempDirSync, makeTempDir } from "./make_temp_dir";
+export { makeTempFileSync, makeTempFile } from "./make_temp_file";
 export { removeSync, remove, removeAllSync, removeAll } from "./remove";
 export { renameSync, rename } from "./rename";
-export { readFileSync, readFile } from "./read_file";
+export {
+  readFileSync,
+  readFile,
+  readFiles,
+  readTextFileSync,
+  readTextFile
+} from "./read_file";
+export { openLines, Lines } from "./read_lines";
 export { readDirSync, readDir } from "./read_dir";
 export { copyFileSync, copyFile } from "./copy_file";
+export { chmodSync, chmod } from "./chmod";
+export { callRecordOp, OpRecord } from "./codec";
+export { consoleSize, ConsoleSize } from "./console_size";
+export { diskUsage, DiskUsage } from "./disk_usage";
 export { readlinkSync, readlink } from "./read_link";
+export { realpathSync, realpath } from "./realpath";
 export { statSync, lstatSync, stat, lstat } from "./stat";
 export { symlinkSync, symlink } from "./symlink";
-export { writeFileSync, writeFile } from "./write_file";
+export { linkSync, link } from "./link";
+export { watchFs, FsWatcher, FsEvent } from "./watch_fs";
+export { writeFileSync, writeFile, WriteFileOptions } from "./write_file";
 export { ErrorKind, DenoError } from "./errors";
 export { libdeno } from "./libdeno";
 export { platform } from "./platform";
 export { trace } from "./trace";
-export { truncateSync, truncate } from "./truncate";
+export { truncateSync, truncate, ftruncate } from "./truncate";
+export { utimeSync, utime } from "./utime";
+export { compileWasm } from "./wasm";
+export { hashSync, hash, HashAlgorithm } from "./hash";
+export { inspect, InspectOptions } from "./inspect";
+export { isolateStatus, IsolateStatus, backpressure } from "./status";
 export { FileInfo } from "./file_info";
 export { connect, dial, listen, Listener, Conn } from "./net";
+export {
+  run,
+  RunOptions,
+  Process,
+  ProcessStatus,
+  ProcessStdio
+} from "./process";
 export const args: string[] = [];
+export { version } from "./version";
 
 // Provide the compiler API in an obfuscated way
 import * as compiler from "./compiler";
 // @internal
 export const _compiler = compiler;
+import * as formatError from "./format_error";
+// @internal
+export const _formatError = formatError;
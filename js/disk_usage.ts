@@ -0,0 +1,34 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import * as msg from "gen/msg_generated";
+import { flatbuffers } from "flatbuffers";
+import { assert } from "./util";
+import * as dispatch from "./dispatch";
+
+export interface DiskUsage {
+  // Bytes the current user can still use.
+  available: number;
+  total: number;
+}
+
+/**
+ * Returns the space on the file system that path is on, in bytes. Requires
+ * the read permission, and throws a NotFound error if path doesn't exist.
+ *
+ *     import { diskUsage } from "deno";
+ *     const { available, total } = diskUsage("/tmp");
+ */
+export function diskUsage(path: string): DiskUsage {
+  const builder = new flatbuffers.Builder();
+  const path_ = builder.createString(path);
+  msg.DiskUsage.startDiskUsage(builder);
+  msg.DiskUsage.addPath(builder, path_);
+  const inner = msg.DiskUsage.endDiskUsage(builder);
+  const baseRes = dispatch.sendSync(builder, msg.Any.DiskUsage, inner)!;
+  assert(msg.Any.DiskUsageRes === baseRes.innerType());
+  const res = new msg.DiskUsageRes();
+  assert(baseRes.inner(res) != null);
+  return {
+    available: res.available().toFloat64(),
+    total: res.total().toFloat64()
+  };
+}
@@ -0,0 +1,31 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { testPerm, assert, assertEqual } from "./test_util.ts";
+import * as deno from "deno";
+
+testPerm({ read: true }, function diskUsageSuccess() {
+  const { available, total } = deno.diskUsage(".");
+  assert(total > 0);
+  assert(available <= total);
+});
+
+testPerm({ read: true }, function diskUsageNotFound() {
+  let err;
+  try {
+    deno.diskUsage("/baddir/badfile");
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.NotFound);
+  assertEqual(err.name, "NotFound");
+});
+
+testPerm({ read: false }, function diskUsagePerm() {
+  let err;
+  try {
+    deno.diskUsage(".");
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
@@ -7,7 +7,10 @@ import * as util from "./util";
 import { maybePushTrace } from "./trace";
 
 let nextCmdId = 0;
-const promiseTable = new Map<number, util.Resolvable<msg.Base>>();
+// By the reqId libdeno gave the request.
+const promiseTable = new Map<number, util.Resolvable<null | msg.Base>>();
+// The requests sent with sendAsyncChunked() that are still receiving chunks.
+const chunkHandlers = new Map<number, (chunk: msg.ResponseChunk) => void>();
 
 let fireTimers: () => void;
 
@@ -15,21 +18,38 @@ export function setFireTimersCallback(fn: () => void) {
   fireTimers = fn;
 }
 
-export function handleAsyncMsgFromRust(ui8: Uint8Array) {
-  // If a the buffer is empty, recv() on the native side timed out and we
-  // did not receive a message.
-  if (ui8.length) {
+function takePromise(reqId: number): util.Resolvable<null | msg.Base> {
+  const promise = promiseTable.get(reqId);
+  util.assert(promise != null, `Expecting promise in table. ${reqId}`);
+  promiseTable.delete(reqId);
+  return promise!;
+}
+
+export function handleAsyncMsgFromRust(ui8: null | Uint8Array, reqId: number) {
+  if (ui8 == null) {
+    // An op that completed with no data. Otherwise the global timer fired,
+    // and we did not receive a message.
+    if (reqId >= 0) {
+      takePromise(reqId).resolve(null);
+    }
+  } else {
     const bb = new flatbuffers.ByteBuffer(ui8);
     const base = msg.Base.getRootAsBase(bb);
     const cmdId = base.cmdId();
-    const promise = promiseTable.get(cmdId);
-    util.assert(promise != null, `Expecting promise in table. ${cmdId}`);
-    promiseTable.delete(cmdId);
-    const err = errors.maybeError(base);
-    if (err != null) {
-      promise!.reject(err);
+    const chunk = maybeChunk(base);
+    if (chunk != null && !chunk.end()) {
+      const onChunk = chunkHandlers.get(cmdId);
+      util.assert(onChunk != null, `Expecting chunk handler. ${cmdId}`);
+      onChunk!(chunk);
     } else {
-      promise!.resolve(base);
+      chunkHandlers.delete(cmdId);
+      const promise = takePromise(reqId);
+      const err = errors.maybeError(base);
+      if (err != null) {
+        promise.reject(err);
+      } else {
+        promise.resolve(base);
+      }
     }
   }
   // Fire timers that have become runnable.
@@ -42,15 +62,53 @@ export function sendAsync(
   innerType: msg.Any,
   inner: flatbuffers.Offset,
   data?: ArrayBufferView
-): Promise<msg.Base> {
+): Promise<null | msg.Base> {
+  maybePushTrace(innerType, false); // add to trace if tracing
+  const [, reqId] = sendInternal(builder, innerType, inner, data, false);
+  util.assert(typeof reqId === "number");
+  const promise = util.createResolvable<null | msg.Base>();
+  promiseTable.set(reqId as number, promise);
+  return promise;
+}
+
+// @internal
+// Like sendAsync(), for ops that respond in chunks. onChunk is called with the
+// data of each chunk, in order. The promise resolves with the last chunk,
+// which has no data, or rejects if the op fails part way.
+export function sendAsyncChunked(
+  builder: flatbuffers.Builder,
+  innerType: msg.Any,
+  inner: flatbuffers.Offset,
+  onChunk: (data: Uint8Array) => void
+): Promise<null | msg.Base> {
   maybePushTrace(innerType, false); // add to trace if tracing
-  const [cmdId, resBuf] = sendInternal(builder, innerType, inner, data, false);
-  util.assert(resBuf == null);
-  const promise = util.createResolvable<msg.Base>();
-  promiseTable.set(cmdId, promise);
+  const [cmdId, reqId] = sendInternal(
+    builder,
+    innerType,
+    inner,
+    undefined,
+    false
+  );
+  util.assert(typeof reqId === "number");
+  let seq = 0;
+  chunkHandlers.set(cmdId, (chunk: msg.ResponseChunk) => {
+    util.assert(chunk.seq() === seq++, `Chunk out of order. ${cmdId}`);
+    onChunk(chunk.dataArray()!);
+  });
+  const promise = util.createResolvable<null | msg.Base>();
+  promiseTable.set(reqId as number, promise);
   return promise;
 }
 
+function maybeChunk(base: msg.Base): null | msg.ResponseChunk {
+  if (base.innerType() !== msg.Any.ResponseChunk) {
+    return null;
+  }
+  const chunk = new msg.ResponseChunk();
+  util.assert(base.inner(chunk) != null);
+  return chunk;
+}
+
 // @internal
 export function sendSync(
   builder: flatbuffers.Builder,
@@ -61,10 +119,11 @@ export function sendSync(
   maybePushTrace(innerType, true); // add to trace if tracing
   const [cmdId, resBuf] = sendInternal(builder, innerType, inner, data, true);
   util.assert(cmdId >= 0);
+  util.assert(typeof resBuf !== "number");
   if (resBuf == null) {
     return null;
   } else {
-    const u8 = new Uint8Array(resBuf!);
+    const u8 = new Uint8Array(resBuf as Uint8Array);
     const bb = new flatbuffers.ByteBuffer(u8);
     const baseRes = msg.Base.getRootAsBase(bb);
     errors.maybeThrowError(baseRes);
@@ -78,7 +137,7 @@ function sendInternal(
   inner: flatbuffers.Offset,
   data: undefined | ArrayBufferView,
   sync = true
-): [number, null | Uint8Array] {
+): [number, null | Uint8Array | number] {
   const cmdId = nextCmdId++;
   msg.Base.startBase(builder);
   msg.Base.addInner(builder, inner);
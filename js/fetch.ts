@@ -197,9 +197,10 @@ export async function fetch(
   );
 
   // Decode FetchRes
-  assert(msg.Any.FetchRes === resBase.innerType());
+  assert(resBase != null);
+  assert(msg.Any.FetchRes === resBase!.innerType());
   const inner = new msg.FetchRes();
-  assert(resBase.inner(inner) != null);
+  assert(resBase!.inner(inner) != null);
 
   const status = inner.status();
   const bodyArray = inner.bodyArray();
@@ -1,12 +1,12 @@
 // Copyright 2018 the Deno authors. All rights reserved. MIT license.
 
-import { Reader, Writer, Closer, ReadResult } from "./io";
+import { Reader, Writer, Closer, Seeker, ReadResult } from "./io";
 import * as dispatch from "./dispatch";
 import * as msg from "gen/msg_generated";
-import { assert } from "./util";
+import { assert, toLong } from "./util";
 import { flatbuffers } from "flatbuffers";
 
-export class File implements Reader, Writer, Closer {
+export class File implements Reader, Writer, Closer, Seeker {
   constructor(readonly fd: number) {}
 
   write(p: ArrayBufferView): Promise<number> {
@@ -17,6 +17,10 @@ export class File implements Reader, Writer, Closer {
     return read(this.fd, p);
   }
 
+  seek(offset: number, whence: SeekMode): Promise<number> {
+    return seek(this.fd, offset, whence);
+  }
+
   close(): void {
     close(this.fd);
   }
@@ -26,21 +30,48 @@ export const stdin = new File(0);
 export const stdout = new File(1);
 export const stderr = new File(2);
 
-// TODO This is just a placeholder - not final API.
-export type OpenMode = "r" | "w" | "w+" | "x";
+/**
+ * How open() opens a file:
+ * "r" reads it.
+ * "w" writes it, creating it or emptying it first.
+ * "w+" is "w" but reads it as well.
+ * "a" writes to its end, creating it if it doesn't exist.
+ * "a+" is "a" but reads it as well.
+ * "x" writes it, failing if it already exists.
+ * Reading needs the read permission, and the others the write permission.
+ */
+export type OpenMode = "r" | "w" | "w+" | "a" | "a+" | "x";
 
 export function create(filename: string): Promise<File> {
   return open(filename, "x");
 }
 
+/**
+ * Opens filename with mode, resolving to the open File. A file that is
+ * created gets the permissions perm, before the umask. perm is ignored on
+ * Windows.
+ *
+ *     import { open } from "deno";
+ *     const file = await open("hello.txt", "w");
+ *     await file.write(new TextEncoder().encode("Hello world!\n"));
+ *     file.close();
+ */
 export async function open(
   filename: string,
-  mode: OpenMode = "r"
+  mode: OpenMode = "r",
+  perm = 0o666
 ): Promise<File> {
   const builder = new flatbuffers.Builder();
   const filename_ = builder.createString(filename);
   msg.Open.startOpen(builder);
   msg.Open.addFilename(builder, filename_);
+  msg.Open.addPerm(builder, perm);
+  msg.Open.addRead(builder, mode === "r" || mode.endsWith("+"));
+  msg.Open.addWrite(builder, mode.startsWith("w") || mode === "x");
+  msg.Open.addAppend(builder, mode.startsWith("a"));
+  msg.Open.addTruncate(builder, mode.startsWith("w"));
+  msg.Open.addCreate(builder, mode !== "r" && mode !== "x");
+  msg.Open.addCreateNew(builder, mode === "x");
   const inner = msg.Open.endOpen(builder);
   const baseRes = await dispatch.sendAsync(builder, msg.Any.Open, inner);
   assert(baseRes != null);
@@ -80,6 +111,70 @@ export async function write(fd: number, p: ArrayBufferView): Promise<number> {
   return res.nbyte();
 }
 
+/** What the offset given to seek() is relative to. */
+export const SeekMode = msg.SeekMode;
+export type SeekMode = msg.SeekMode;
+
+/**
+ * Moves the position of the open file fd to offset, relative to whence, and
+ * resolves to the new position from the start of the file. Seeking before the
+ * start is an error.
+ *
+ *     import { open, seek, SeekMode } from "deno";
+ *     const file = await open("hello.txt");
+ *     await seek(file.fd, 6, SeekMode.Start);
+ */
+export async function seek(
+  fd: number,
+  offset: number,
+  whence: SeekMode
+): Promise<number> {
+  const builder = new flatbuffers.Builder();
+  msg.Seek.startSeek(builder);
+  msg.Seek.addRid(builder, fd);
+  msg.Seek.addOffset(builder, toLong(offset));
+  msg.Seek.addWhence(builder, whence);
+  const inner = msg.Seek.endSeek(builder);
+  const baseRes = await dispatch.sendAsync(builder, msg.Any.Seek, inner);
+  assert(baseRes != null);
+  assert(msg.Any.SeekRes === baseRes!.innerType());
+  const res = new msg.SeekRes();
+  assert(baseRes!.inner(res) != null);
+  return res.position().toFloat64();
+}
+
+async function sync(fd: number, dataOnly: boolean): Promise<void> {
+  const builder = new flatbuffers.Builder();
+  msg.Fsync.startFsync(builder);
+  msg.Fsync.addRid(builder, fd);
+  msg.Fsync.addDataOnly(builder, dataOnly);
+  const inner = msg.Fsync.endFsync(builder);
+  const baseRes = await dispatch.sendAsync(builder, msg.Any.Fsync, inner);
+  assert(baseRes == null);
+}
+
+/**
+ * Flushes what has been written to the open file fd to disk, with its
+ * metadata, like the modification time. Fails with InvalidInput if fd isn't a
+ * file.
+ *
+ *     import { open, fsync } from "deno";
+ *     const file = await open("hello.txt", "w");
+ *     await file.write(new TextEncoder().encode("Hello world!\n"));
+ *     await fsync(file.fd);
+ */
+export function fsync(fd: number): Promise<void> {
+  return sync(fd, false);
+}
+
+/**
+ * Like fsync(), but only flushes the metadata that is needed to read the
+ * contents back, like the length. That can be less writing to disk.
+ */
+export function fdatasync(fd: number): Promise<void> {
+  return sync(fd, true);
+}
+
 export function close(fd: number): void {
   const builder = new flatbuffers.Builder();
   msg.Close.startClose(builder);
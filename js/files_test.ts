@@ -1,7 +1,7 @@
 // Copyright 2018 the Deno authors. All rights reserved. MIT license.
 
 import * as deno from "deno";
-import { test, assert, assertEqual } from "./test_util.ts";
+import { test, testPerm, assert, assertEqual } from "./test_util.ts";
 
 test(function filesStdioFileDescriptors() {
   assertEqual(deno.stdin.fd, 0);
@@ -9,7 +9,7 @@ test(function filesStdioFileDescriptors() {
   assertEqual(deno.stderr.fd, 2);
 });
 
-test(async function filesCopyToStdout() {
+testPerm({ read: true }, async function filesCopyToStdout() {
   const filename = "package.json";
   const file = await deno.open(filename);
   assert(file.fd > 2);
@@ -18,3 +18,103 @@ test(async function filesCopyToStdout() {
   assertEqual(bytesWritten, fileSize);
   console.log("bytes written", bytesWritten);
 });
+
+testPerm({ read: true, write: true }, async function filesSeek() {
+  const encoder = new TextEncoder();
+  const filename = deno.makeTempDirSync() + "/seek.txt";
+  deno.writeFileSync(filename, encoder.encode("hello world"));
+  const file = await deno.open(filename);
+  assertEqual(await file.seek(6, deno.SeekMode.Start), 6);
+  const buf = new Uint8Array(5);
+  await file.read(buf);
+  assertEqual(new TextDecoder().decode(buf), "world");
+  assertEqual(await file.seek(-11, deno.SeekMode.End), 0);
+  await file.read(buf);
+  assertEqual(new TextDecoder().decode(buf), "hello");
+
+  let err;
+  try {
+    await file.seek(-6, deno.SeekMode.Current);
+  } catch (e) {
+    err = e;
+  }
+  assert(!!err);
+  assertEqual(err.kind, deno.ErrorKind.InvalidInput);
+  file.close();
+});
+
+testPerm({ read: true, write: true }, async function filesOpenModes() {
+  const encoder = new TextEncoder();
+  const decoder = new TextDecoder();
+  const filename = deno.makeTempDirSync() + "/modes.txt";
+
+  let file = await deno.open(filename, "w");
+  await file.write(encoder.encode("hello"));
+  file.close();
+  file = await deno.open(filename, "a");
+  await file.write(encoder.encode(" world"));
+  file.close();
+  assertEqual(decoder.decode(deno.readFileSync(filename)), "hello world");
+
+  file = await deno.open(filename, "w+");
+  await file.write(encoder.encode("bye"));
+  await file.seek(0, deno.SeekMode.Start);
+  const buf = new Uint8Array(5);
+  const { nread } = await file.read(buf);
+  assertEqual(decoder.decode(buf.subarray(0, nread)), "bye");
+  file.close();
+
+  let err;
+  try {
+    await deno.open(filename, "x");
+  } catch (e) {
+    err = e;
+  }
+  assert(!!err);
+  assertEqual(err.kind, deno.ErrorKind.AlreadyExists);
+});
+
+testPerm({ read: true, write: false }, async function filesOpenWritePerm() {
+  for (const mode of ["w", "w+", "a", "a+", "x"] as deno.OpenMode[]) {
+    let err;
+    try {
+      await deno.open("package.json", mode);
+    } catch (e) {
+      err = e;
+    }
+    assert(!!err);
+    assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+    assertEqual(err.name, "PermissionDenied");
+  }
+});
+
+testPerm({ read: false }, async function filesOpenReadPerm() {
+  let err;
+  try {
+    await deno.open("package.json");
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
+
+testPerm({ read: true, write: true }, async function filesFsync() {
+  const filename = deno.makeTempDirSync() + "/fsync.txt";
+  const file = await deno.open(filename, "w");
+  await file.write(new TextEncoder().encode("hello"));
+  await deno.fsync(file.fd);
+  await deno.fdatasync(file.fd);
+  file.close();
+  const data = deno.readFileSync(filename);
+  assertEqual(new TextDecoder().decode(data), "hello");
+
+  let err;
+  try {
+    await deno.fsync(deno.stdin.fd);
+  } catch (e) {
+    err = e;
+  }
+  assert(!!err);
+  assertEqual(err.kind, deno.ErrorKind.InvalidInput);
+});
@@ -0,0 +1,117 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import * as msg from "gen/msg_generated";
+
+export const ErrorFormat = msg.ErrorFormat;
+
+// Returns the source code of fileName, if it is known.
+export type SourceLookup = (fileName: string) => string | undefined;
+
+interface ErrorReport {
+  name: string;
+  message: string;
+  // The stack, as V8 formats it after source maps have been applied.
+  stack?: string;
+  // Where the error was thrown, if the source of that file is known.
+  fileName?: string;
+  lineNumber?: number;
+  columnNumber?: number;
+  sourceLine?: string;
+}
+
+// Matches the location at the end of a stack frame, like
+// "    at foo (file:///a.ts:2:9)" or "    at file:///a.ts:2:9".
+const frameLocation = /\(?([^\s()]+):(\d+):(\d+)\)?$/;
+
+// Stack frames refer to local modules by URL, the compiler by path.
+function stripFileUrl(fileName: string): string {
+  return fileName.startsWith("file://")
+    ? fileName.slice("file://".length)
+    : fileName;
+}
+
+// tslint:disable-next-line:no-any
+function report(error: any, getSource: SourceLookup): ErrorReport {
+  if (!(error instanceof Error)) {
+    return { name: "Thrown", message: String(error) };
+  }
+  const r: ErrorReport = {
+    name: error.name,
+    message: error.message,
+    stack: error.stack
+  };
+  const frames = (error.stack || "").split("\n").slice(1);
+  // The topmost frame in a module we have the source of, which skips frames
+  // in the runtime.
+  for (const frame of frames) {
+    const match = frameLocation.exec(frame.trim());
+    if (match == null) {
+      continue;
+    }
+    const [, fileName, lineNumber, columnNumber] = match;
+    const source = getSource(stripFileUrl(fileName));
+    const line = source && source.split("\n")[Number(lineNumber) - 1];
+    if (line !== undefined) {
+      r.fileName = fileName;
+      r.lineNumber = Number(lineNumber);
+      r.columnNumber = Number(columnNumber);
+      r.sourceLine = line;
+      break;
+    }
+  }
+  return r;
+}
+
+// The colors TypeScript uses for diagnostics.
+function cyan(s: string, color: boolean): string {
+  return color ? `\x1b[96m${s}\x1b[0m` : s;
+}
+
+function red(s: string, color: boolean): string {
+  return color ? `\x1b[91m${s}\x1b[0m` : s;
+}
+
+function formatHuman(r: ErrorReport, color: boolean): string {
+  if (r.stack === undefined) {
+    return `${r.name}: ${r.message}`;
+  }
+  if (r.sourceLine === undefined) {
+    return r.stack;
+  }
+  const location = `${r.fileName}:${r.lineNumber}:${r.columnNumber}`;
+  const caret = " ".repeat(r.columnNumber! - 1) + red("^", color);
+  return [cyan(location, color), r.sourceLine, caret, r.stack].join("\n");
+}
+
+function formatJson(r: ErrorReport): string {
+  const stack = r.stack === undefined ? [] : r.stack.split("\n").slice(1);
+  return JSON.stringify({
+    name: r.name,
+    message: r.message,
+    fileName: r.fileName,
+    lineNumber: r.lineNumber,
+    columnNumber: r.columnNumber,
+    sourceLine: r.sourceLine,
+    frames: stack.map(frame => frame.trim())
+  });
+}
+
+/**
+ * Formats an uncaught error for printing. Human is the stack, below the
+ * source line that threw and a caret pointing at the column that threw if
+ * getSource knows the file. Json is an object with the name, message,
+ * location, source line and stack frames of the error, for tools.
+ * Exceptions that aren't Errors are reported with the name "Thrown". Human
+ * is only colored if color is true.
+ */
+export function formatError(
+  // tslint:disable-next-line:no-any
+  error: any,
+  format: msg.ErrorFormat,
+  getSource: SourceLookup,
+  color = false
+): string {
+  const r = report(error, getSource);
+  return format === msg.ErrorFormat.Json
+    ? formatJson(r)
+    : formatHuman(r, color);
+}
@@ -0,0 +1,72 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { test, assert, assertEqual } from "./test_util.ts";
+import * as deno from "deno";
+
+// tslint:disable-next-line:no-any
+const { ErrorFormat, formatError } = (deno as any)._formatError;
+
+const source = `function foo() {
+  throw Error("bad");
+}`;
+
+function getSource(fileName: string): string | undefined {
+  return fileName === "/tests/bad.ts" ? source : undefined;
+}
+
+function makeError(): Error {
+  const err = new Error("bad");
+  err.stack = [
+    "Error: bad",
+    "    at foo (file:///tests/bad.ts:2:9)",
+    "    at file:///tests/bad.ts:4:1",
+    "    at denoMain (deno/js/main.ts:10:3)"
+  ].join("\n");
+  return err;
+}
+
+test(function formatErrorHuman() {
+  const err = makeError();
+  const lines = formatError(err, ErrorFormat.Human, getSource).split("\n");
+  assertEqual(lines[0], "file:///tests/bad.ts:2:9");
+  assertEqual(lines[1], `  throw Error("bad");`);
+  assertEqual(lines[2], "        ^");
+  assertEqual(lines.slice(3).join("\n"), err.stack);
+});
+
+test(function formatErrorHumanColor() {
+  const err = makeError();
+  const formatted = formatError(err, ErrorFormat.Human, getSource, true);
+  const lines = formatted.split("\n");
+  assertEqual(lines[0], "\x1b[96mfile:///tests/bad.ts:2:9\x1b[0m");
+  assertEqual(lines[2], "        \x1b[91m^\x1b[0m");
+});
+
+test(function formatErrorHumanUnknownSource() {
+  const err = makeError();
+  const formatted = formatError(err, ErrorFormat.Human, () => undefined);
+  assertEqual(formatted, err.stack);
+  assertEqual(
+    formatError({}, ErrorFormat.Human, getSource),
+    "Thrown: [object Object]"
+  );
+});
+
+test(function formatErrorJson() {
+  const report = JSON.parse(
+    formatError(makeError(), ErrorFormat.Json, getSource)
+  );
+  assertEqual(report.name, "Error");
+  assertEqual(report.message, "bad");
+  assertEqual(report.fileName, "file:///tests/bad.ts");
+  assertEqual(report.lineNumber, 2);
+  assertEqual(report.columnNumber, 9);
+  assertEqual(report.sourceLine, `  throw Error("bad");`);
+  assertEqual(report.frames.length, 3);
+  assertEqual(report.frames[0], "at foo (file:///tests/bad.ts:2:9)");
+
+  const thrown = JSON.parse(formatError(42, ErrorFormat.Json, getSource));
+  assertEqual(thrown.name, "Thrown");
+  assertEqual(thrown.message, "42");
+  assert(thrown.fileName === undefined);
+  assertEqual(thrown.frames.length, 0);
+});
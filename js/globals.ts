@@ -8,6 +8,7 @@ import { libdeno } from "./libdeno";
 import { globalEval } from "./global_eval";
 import { DenoHeaders } from "./fetch";
 import { DenoBlob } from "./blob";
+import { Performance } from "./performance";
 
 declare global {
   interface Window {
@@ -30,6 +31,8 @@ declare global {
 
     Headers: typeof Headers;
     Blob: typeof Blob;
+
+    performance: Performance;
   }
 
   const clearTimeout: typeof timers.clearTimer;
@@ -50,6 +53,8 @@ declare global {
   const Headers: typeof DenoHeaders;
   const Blob: typeof DenoBlob;
   // tslint:enable:variable-name
+
+  const performance: Performance;
 }
 
 // A reference to the global object.
@@ -71,3 +76,5 @@ window.fetch = fetch_.fetch;
 
 window.Headers = DenoHeaders;
 window.Blob = DenoBlob;
+
+window.performance = new Performance();
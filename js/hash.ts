@@ -0,0 +1,57 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import * as msg from "gen/msg_generated";
+import { flatbuffers } from "flatbuffers";
+import { assert } from "./util";
+import * as dispatch from "./dispatch";
+
+/** The algorithms hash() and hashSync() support. */
+export type HashAlgorithm = "sha1" | "sha256";
+
+/**
+ * Returns the digest of data using algorithm, synchronously.
+ *
+ *     import { hashSync } from "deno";
+ *     const digest = hashSync("sha256", new TextEncoder().encode("abc"));
+ */
+export function hashSync(
+  algorithm: HashAlgorithm,
+  data: ArrayBufferView
+): Uint8Array {
+  return res(dispatch.sendSync(...req(algorithm, data)));
+}
+
+/**
+ * Returns the digest of data using algorithm. Large inputs are hashed off the
+ * main thread.
+ *
+ *     import { hash } from "deno";
+ *     const digest = await hash("sha1", new TextEncoder().encode("abc"));
+ */
+export async function hash(
+  algorithm: HashAlgorithm,
+  data: ArrayBufferView
+): Promise<Uint8Array> {
+  return res(await dispatch.sendAsync(...req(algorithm, data)));
+}
+
+function req(
+  algorithm: HashAlgorithm,
+  data: ArrayBufferView
+): [flatbuffers.Builder, msg.Any, flatbuffers.Offset, ArrayBufferView] {
+  const builder = new flatbuffers.Builder();
+  const algorithm_ = builder.createString(algorithm);
+  msg.Hash.startHash(builder);
+  msg.Hash.addAlgorithm(builder, algorithm_);
+  const inner = msg.Hash.endHash(builder);
+  return [builder, msg.Any.Hash, inner, data];
+}
+
+function res(baseRes: null | msg.Base): Uint8Array {
+  assert(baseRes !== null);
+  assert(msg.Any.HashRes === baseRes!.innerType());
+  const res = new msg.HashRes();
+  assert(baseRes!.inner(res) !== null);
+  const digest = res.digestArray();
+  assert(digest !== null);
+  return new Uint8Array(digest!);
+}
@@ -0,0 +1,39 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { test, assert, assertEqual } from "./test_util.ts";
+import * as deno from "deno";
+
+function hex(digest: Uint8Array): string {
+  let s = "";
+  for (const b of digest) {
+    s += ("0" + b.toString(16)).slice(-2);
+  }
+  return s;
+}
+
+const abc = new TextEncoder().encode("abc");
+
+test(function hashSyncSha256() {
+  assertEqual(
+    hex(deno.hashSync("sha256", abc)),
+    "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+  );
+});
+
+test(async function hashSha1() {
+  assertEqual(
+    hex(await deno.hash("sha1", abc)),
+    "a9993e364706816aba3e25717850c26c9cd0d89d"
+  );
+});
+
+test(function hashSyncUnsupported() {
+  let caughtError = false;
+  try {
+    // tslint:disable-next-line:no-any
+    deno.hashSync("md5" as any, abc);
+  } catch (e) {
+    caughtError = true;
+    assertEqual(e.kind, deno.ErrorKind.InvalidInput);
+  }
+  assert(caughtError);
+});
@@ -0,0 +1,30 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { stringifyArgs } from "./console";
+
+export interface InspectOptions {
+  // How many levels of objects and arrays are shown. The default is 2.
+  depth?: number;
+  // Whether to color the output with ANSI escapes.
+  colors?: boolean;
+  // At most this many entries of each object and array are shown, or all of
+  // them if it is 0. The default is 100.
+  maxLength?: number;
+}
+
+const DEFAULT_MAX_LENGTH = 100;
+
+/**
+ * Formats value for printing, the way console.log does. Objects that contain
+ * themselves are shown as [Circular].
+ *
+ *     import { inspect } from "deno";
+ *     const a = { name: "a" };
+ *     a.self = a;
+ *     inspect(a); // { name: "a", self: [Circular] }
+ */
+// tslint:disable-next-line:no-any
+export function inspect(value: any, options: InspectOptions = {}): string {
+  const maxLength =
+    options.maxLength !== undefined ? options.maxLength : DEFAULT_MAX_LENGTH;
+  return stringifyArgs([value], { ...options, maxLength });
+}
@@ -0,0 +1,41 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { test, assertEqual } from "./test_util.ts";
+import * as deno from "deno";
+
+test(function inspectNested() {
+  const value = { a: { b: { c: { d: 1 } } }, e: "f" };
+  assertEqual(deno.inspect(value), `{ a: { b: [object] }, e: "f" }`);
+  assertEqual(
+    deno.inspect(value, { depth: 4 }),
+    `{ a: { b: { c: { d: 1 } } }, e: "f" }`
+  );
+});
+
+test(function inspectArray() {
+  assertEqual(deno.inspect([]), "[]");
+  assertEqual(deno.inspect([1, "two", null, [3]]), `[ 1, "two", null, [ 3 ] ]`);
+  assertEqual(
+    deno.inspect([1, 2, 3], { maxLength: 2 }),
+    "[ 1, 2, ... 1 more item ]"
+  );
+  assertEqual(
+    deno.inspect(["a", 1], { colors: true }),
+    `[ \x1b[32m"a"\x1b[39m, \x1b[33m1\x1b[39m ]`
+  );
+});
+
+test(function inspectCircular() {
+  // tslint:disable-next-line:no-any
+  const a: any = { name: "a", list: [] };
+  a.list.push(a);
+  a.self = a;
+  assertEqual(
+    deno.inspect(a),
+    `{ name: "a", list: [ [Circular] ], self: [Circular] }`
+  );
+  // Shared objects that don't contain themselves are shown each time.
+  const b = { x: 1 };
+  assertEqual(deno.inspect([b, b]), "[ { x: 1 }, { x: 1 } ]");
+  // A property named like a back-reference is just a property.
+  assertEqual(deno.inspect({ $ref: 0 }), "{ $ref: 0 }");
+});
@@ -70,7 +70,7 @@ export interface Seeker {
   // Seeking to an offset before the start of the file is an error. Seeking to
   // any positive offset is legal, but the behavior of subsequent I/O operations
   // on the underlying object is implementation-dependent.
-  seek(offset: number, whence: number): Promise<void>;
+  seek(offset: number, whence: number): Promise<number>;
 }
 
 // https://golang.org/pkg/io/#ReadCloser
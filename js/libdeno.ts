@@ -2,11 +2,17 @@ import { RawSourceMap } from "./types";
 import { globalEval } from "./global_eval";
 
 // The libdeno functions are moved so that users can't access them.
-type MessageCallback = (msg: Uint8Array) => void;
+// Gets null if the op completed with no data, or for the timer, whose reqId is
+// negative.
+type MessageCallback = (msg: null | Uint8Array, reqId: number) => void;
 interface Libdeno {
   recv(cb: MessageCallback): void;
 
-  send(control: ArrayBufferView, data?: ArrayBufferView): null | Uint8Array;
+  // Returns the response of a sync op, or the reqId of an async one.
+  send(
+    control: ArrayBufferView,
+    data?: ArrayBufferView
+  ): null | Uint8Array | number;
 
   print(x: string, isErr?: boolean): void;
 
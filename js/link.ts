@@ -0,0 +1,40 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import * as msg from "gen/msg_generated";
+import { flatbuffers } from "flatbuffers";
+import * as dispatch from "./dispatch";
+
+/**
+ * Synchronously creates newname as a hard link to oldname. Requires read
+ * permission for oldname and write permission for newname.
+ *
+ *     import { linkSync } from "deno";
+ *     linkSync("old/name", "new/name");
+ */
+export function linkSync(oldname: string, newname: string): void {
+  dispatch.sendSync(...req(oldname, newname));
+}
+
+/**
+ * Creates newname as a hard link to oldname. Requires read permission for
+ * oldname and write permission for newname.
+ *
+ *     import { link } from "deno";
+ *     await link("old/name", "new/name");
+ */
+export async function link(oldname: string, newname: string): Promise<void> {
+  await dispatch.sendAsync(...req(oldname, newname));
+}
+
+function req(
+  oldname: string,
+  newname: string
+): [flatbuffers.Builder, msg.Any, flatbuffers.Offset] {
+  const builder = new flatbuffers.Builder();
+  const oldname_ = builder.createString(oldname);
+  const newname_ = builder.createString(newname);
+  msg.Link.startLink(builder);
+  msg.Link.addOldname(builder, oldname_);
+  msg.Link.addNewname(builder, newname_);
+  const inner = msg.Link.endLink(builder);
+  return [builder, msg.Any.Link, inner];
+}
@@ -0,0 +1,60 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { testPerm, assert, assertEqual } from "./test_util.ts";
+import * as deno from "deno";
+
+testPerm({ read: true, write: true }, function linkSyncSuccess() {
+  const testDir = deno.makeTempDirSync();
+  const oldname = testDir + "/oldname";
+  const newname = testDir + "/newname";
+  const enc = new TextEncoder();
+  const dec = new TextDecoder();
+  deno.writeFileSync(oldname, enc.encode("hello"));
+  deno.linkSync(oldname, newname);
+  assert(!deno.lstatSync(newname).isSymlink());
+  // Both names are the same file.
+  deno.writeFileSync(newname, enc.encode("changed"));
+  assertEqual(dec.decode(deno.readFileSync(oldname)), "changed");
+});
+
+testPerm({ read: true, write: true }, function linkSyncNotFound() {
+  const testDir = deno.makeTempDirSync();
+  let err;
+  try {
+    deno.linkSync(testDir + "/missing", testDir + "/newname");
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.NotFound);
+});
+
+testPerm({ read: false, write: true }, function linkSyncReadPerm() {
+  let err;
+  try {
+    deno.linkSync("oldbaddir", "newbaddir");
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
+
+testPerm({ read: true, write: false }, function linkSyncWritePerm() {
+  let err;
+  try {
+    deno.linkSync("oldbaddir", "newbaddir");
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
+
+testPerm({ read: true, write: true }, async function linkSuccess() {
+  const testDir = deno.makeTempDirSync();
+  const oldname = testDir + "/oldname";
+  const newname = testDir + "/newname";
+  deno.writeFileSync(oldname, new TextEncoder().encode("hello"));
+  await deno.link(oldname, newname);
+  const data = new TextDecoder().decode(deno.readFileSync(newname));
+  assertEqual(data, "hello");
+});
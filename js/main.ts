@@ -7,6 +7,12 @@ import { DenoCompiler } from "./compiler";
 import { libdeno } from "./libdeno";
 import { args } from "./deno";
 import { sendSync, handleAsyncMsgFromRust } from "./dispatch";
+import { formatError } from "./format_error";
+import { setVersions } from "./version";
+import { setCodec } from "./codec";
+
+let errorFormat = msg.ErrorFormat.Human;
+let errorColor = false;
 
 function sendStart(): msg.StartRes {
   const builder = new flatbuffers.Builder();
@@ -27,11 +33,9 @@ function onGlobalError(
   colno: number,
   error: any // tslint:disable-line:no-any
 ) {
-  if (error instanceof Error) {
-    console.log(error.stack);
-  } else {
-    console.log(`Thrown: ${String(error)}`);
-  }
+  const compiler = DenoCompiler.instance();
+  const getSource = (fileName: string) => compiler.getSource(fileName);
+  console.log(formatError(error, errorFormat, getSource, errorColor));
   os.exit(1);
 }
 
@@ -47,6 +51,10 @@ export default function denoMain() {
   const startResMsg = sendStart();
 
   setLogDebug(startResMsg.debugFlag());
+  errorFormat = startResMsg.errorFormat();
+  errorColor = startResMsg.colorFlag();
+  setVersions(startResMsg.denoVersion()!, startResMsg.v8Version()!);
+  setCodec(startResMsg.codec()!);
 
   const cwd = startResMsg.cwd();
   log("cwd", cwd);
@@ -0,0 +1,71 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import * as msg from "gen/msg_generated";
+import { flatbuffers } from "flatbuffers";
+import * as dispatch from "./dispatch";
+import { assert } from "./util";
+import { MakeTempDirOptions } from "./make_temp_dir";
+
+export type MakeTempFileOptions = MakeTempDirOptions;
+
+/**
+ * makeTempFileSync is the synchronous version of `makeTempFile`.
+ *
+ *     import { makeTempFileSync } from "deno";
+ *     const tempFileName0 = makeTempFileSync();
+ *     const tempFileName1 = makeTempFileSync({ prefix: 'my_temp' });
+ */
+export function makeTempFileSync(options: MakeTempFileOptions = {}): string {
+  return res(dispatch.sendSync(...req(options)));
+}
+
+/**
+ * makeTempFile creates a new empty temporary file in the directory `dir`, its
+ * name beginning with `prefix` and ending with `suffix`. Only the current
+ * user can read and write it. It returns the full path to the newly created
+ * file. If `dir` is unspecified, it uses the default directory for temporary
+ * files. Multiple programs calling makeTempFile simultaneously will not
+ * choose the same file. It is the caller's responsibility to remove the file
+ * when no longer needed.
+ *
+ *     import { makeTempFile } from "deno";
+ *     const tempFileName0 = await makeTempFile();
+ *     const tempFileName1 = await makeTempFile({ prefix: 'my_temp' });
+ */
+export async function makeTempFile(
+  options: MakeTempFileOptions = {}
+): Promise<string> {
+  return res(await dispatch.sendAsync(...req(options)));
+}
+
+function req({
+  dir,
+  prefix,
+  suffix
+}: MakeTempFileOptions): [flatbuffers.Builder, msg.Any, flatbuffers.Offset] {
+  const builder = new flatbuffers.Builder();
+  const fbDir = dir == null ? -1 : builder.createString(dir);
+  const fbPrefix = prefix == null ? -1 : builder.createString(prefix);
+  const fbSuffix = suffix == null ? -1 : builder.createString(suffix);
+  msg.MakeTempFile.startMakeTempFile(builder);
+  if (dir != null) {
+    msg.MakeTempFile.addDir(builder, fbDir);
+  }
+  if (prefix != null) {
+    msg.MakeTempFile.addPrefix(builder, fbPrefix);
+  }
+  if (suffix != null) {
+    msg.MakeTempFile.addSuffix(builder, fbSuffix);
+  }
+  const inner = msg.MakeTempFile.endMakeTempFile(builder);
+  return [builder, msg.Any.MakeTempFile, inner];
+}
+
+function res(baseRes: null | msg.Base): string {
+  assert(baseRes != null);
+  assert(msg.Any.MakeTempFileRes === baseRes!.innerType());
+  const res = new msg.MakeTempFileRes();
+  assert(baseRes!.inner(res) != null);
+  const path = res.path();
+  assert(path != null);
+  return path!;
+}
@@ -0,0 +1,70 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { test, testPerm, assert, assertEqual } from "./test_util.ts";
+import * as deno from "deno";
+
+testPerm({ read: true, write: true }, function makeTempFileSyncSuccess() {
+  const dir = deno.makeTempDirSync();
+  const files = new Set<string>();
+  for (let i = 0; i < 10; i++) {
+    const file = deno.makeTempFileSync({
+      dir,
+      prefix: "hello",
+      suffix: "world"
+    });
+    // Check that the prefix and suffix are applied.
+    const lastPart = file.replace(/^.*[\\\/]/, "");
+    assert(lastPart.startsWith("hello"));
+    assert(lastPart.endsWith("world"));
+    // Check that it is an empty file.
+    const info = deno.statSync(file);
+    assert(info.isFile());
+    assertEqual(info.len, 0);
+    files.add(file);
+  }
+  // Check that the files are all different.
+  assertEqual(files.size, 10);
+  // Check that creating a temp file inside a nonexisting directory fails.
+  let err;
+  try {
+    deno.makeTempFileSync({ dir: "/baddir" });
+  } catch (err_) {
+    err = err_;
+  }
+  assertEqual(err.kind, deno.ErrorKind.NotFound);
+  assertEqual(err.name, "NotFound");
+});
+
+test(function makeTempFileSyncPerm() {
+  let err;
+  try {
+    deno.makeTempFileSync();
+  } catch (err_) {
+    err = err_;
+  }
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
+
+testPerm({ read: true, write: true }, async function makeTempFileSuccess() {
+  const dir = await deno.makeTempDir();
+  const files = await Promise.all(
+    [1, 2, 3, 4, 5].map(() => deno.makeTempFile({ dir }))
+  );
+  // Check that the files are all different, even when made at once.
+  assertEqual(new Set(files).size, files.length);
+  for (const file of files) {
+    assert(file.startsWith(dir));
+    assert(deno.statSync(file).isFile());
+  }
+});
+
+test(async function makeTempFilePerm() {
+  let err;
+  try {
+    await deno.makeTempFile();
+  } catch (err_) {
+    err = err_;
+  }
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
@@ -3,35 +3,62 @@ import * as msg from "gen/msg_generated";
 import { flatbuffers } from "flatbuffers";
 import * as dispatch from "./dispatch";
 
+export interface MkdirOptions {
+  // Creates parent directories as needed, and doesn't fail if the directory
+  // already exists, like `mkdir -p`.
+  recursive?: boolean;
+  // The permission bits of the new directory, before the umask. The default
+  // is 0o777.
+  mode?: number;
+}
+
+// A number is the mode, as mkdir() used to take it.
+function toOptions(options: number | MkdirOptions): MkdirOptions {
+  return typeof options === "number" ? { mode: options } : options;
+}
+
 /**
  * Creates a new directory with the specified path and permission synchronously.
+ * Requires write permission.
  *
  *     import { mkdirSync } from "deno";
  *     mkdirSync("new_dir");
+ *     mkdirSync("nested/directories", { recursive: true });
+ *     mkdirSync("private_dir", { mode: 0o700 });
  */
-export function mkdirSync(path: string, mode = 0o777): void {
-  dispatch.sendSync(...req(path, mode));
+export function mkdirSync(
+  path: string,
+  options: number | MkdirOptions = {}
+): void {
+  dispatch.sendSync(...req(path, toOptions(options)));
 }
 
 /**
  * Creates a new directory with the specified path and permission.
+ * Requires write permission.
  *
  *     import { mkdir } from "deno";
  *     await mkdir("new_dir");
+ *     await mkdir("nested/directories", { recursive: true });
+ *     await mkdir("private_dir", { mode: 0o700 });
  */
-export async function mkdir(path: string, mode = 0o777): Promise<void> {
-  await dispatch.sendAsync(...req(path, mode));
+export async function mkdir(
+  path: string,
+  options: number | MkdirOptions = {}
+): Promise<void> {
+  await dispatch.sendAsync(...req(path, toOptions(options)));
 }
 
 function req(
   path: string,
-  mode: number
+  options: MkdirOptions
 ): [flatbuffers.Builder, msg.Any, flatbuffers.Offset] {
   const builder = new flatbuffers.Builder();
   const path_ = builder.createString(path);
   msg.Mkdir.startMkdir(builder);
   msg.Mkdir.addPath(builder, path_);
-  msg.Mkdir.addMode(builder, mode);
+  msg.Mkdir.addRecursive(builder, !!options.recursive);
+  msg.Mkdir.addMode(builder, options.mode === undefined ? 0o777 : options.mode);
   const inner = msg.Mkdir.endMkdir(builder);
   return [builder, msg.Any.Mkdir, inner];
 }
@@ -2,16 +2,16 @@
 import { test, testPerm, assert, assertEqual } from "./test_util.ts";
 import * as deno from "deno";
 
-testPerm({ write: true }, function mkdirSyncSuccess() {
-  const path = deno.makeTempDirSync() + "/dir/subdir";
+testPerm({ read: true, write: true }, function mkdirSyncSuccess() {
+  const path = deno.makeTempDirSync() + "/dir";
   deno.mkdirSync(path);
   const pathInfo = deno.statSync(path);
   assert(pathInfo.isDirectory());
 });
 
-testPerm({ write: true }, function mkdirSyncMode() {
-  const path = deno.makeTempDirSync() + "/dir/subdir";
-  deno.mkdirSync(path, 0o755); // no perm for x
+testPerm({ read: true, write: true }, function mkdirSyncMode() {
+  const path = deno.makeTempDirSync() + "/dir";
+  deno.mkdirSync(path, { mode: 0o755 }); // no perm for x
   const pathInfo = deno.statSync(path);
   if (pathInfo.mode !== null) {
     // Skip windows
@@ -30,9 +30,46 @@ testPerm({ write: false }, function mkdirSyncPerm() {
   assertEqual(err.name, "PermissionDenied");
 });
 
-testPerm({ write: true }, async function mkdirSuccess() {
-  const path = deno.makeTempDirSync() + "/dir/subdir";
+testPerm({ read: true, write: true }, async function mkdirSuccess() {
+  const path = deno.makeTempDirSync() + "/dir";
   await deno.mkdir(path);
   const pathInfo = deno.statSync(path);
   assert(pathInfo.isDirectory());
 });
+
+testPerm({ write: true }, function mkdirSyncErrors() {
+  const testDir = deno.makeTempDirSync();
+  let err;
+  try {
+    // The parent is missing.
+    deno.mkdirSync(testDir + "/nested/dir");
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.NotFound);
+  assertEqual(err.name, "NotFound");
+  err = undefined;
+  try {
+    deno.mkdirSync(testDir);
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.AlreadyExists);
+  assertEqual(err.name, "AlreadyExists");
+});
+
+testPerm({ read: true, write: true }, function mkdirSyncRecursive() {
+  const path = deno.makeTempDirSync() + "/nested/directory";
+  deno.mkdirSync(path, { recursive: true });
+  const pathInfo = deno.statSync(path);
+  assert(pathInfo.isDirectory());
+  // Doesn't fail if the directory exists.
+  deno.mkdirSync(path, { recursive: true });
+});
+
+testPerm({ read: true, write: true }, async function mkdirRecursive() {
+  const path = deno.makeTempDirSync() + "/nested/directory";
+  await deno.mkdir(path, { recursive: true });
+  const pathInfo = deno.statSync(path);
+  assert(pathInfo.isDirectory());
+});
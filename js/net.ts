@@ -28,7 +28,7 @@ export interface Listener {
 }
 
 class ListenerImpl implements Listener {
-  constructor(readonly fd: number) {}
+  constructor(readonly fd: number, private readonly localAddr: Addr) {}
 
   async accept(): Promise<Conn> {
     const builder = new flatbuffers.Builder();
@@ -48,7 +48,7 @@ class ListenerImpl implements Listener {
   }
 
   addr(): Addr {
-    return notImplemented();
+    return this.localAddr;
   }
 }
 
@@ -139,7 +139,7 @@ export function listen(network: Network, address: string): Listener {
   assert(msg.Any.ListenRes === baseRes!.innerType());
   const res = new msg.ListenRes();
   assert(baseRes!.inner(res) != null);
-  return new ListenerImpl(res.rid());
+  return new ListenerImpl(res.rid(), res.localAddr()!);
 }
 
 /** Dial connects to the address on the named network.
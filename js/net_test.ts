@@ -1,7 +1,7 @@
 // Copyright 2018 the Deno authors. All rights reserved. MIT license.
 
 import * as deno from "deno";
-import { testPerm, assert, assertEqual } from "./test_util.ts";
+import { test, testPerm, assert, assertEqual } from "./test_util.ts";
 import { deferred } from "./util.ts";
 
 testPerm({ net: true }, function netListenClose() {
@@ -9,6 +9,35 @@ testPerm({ net: true }, function netListenClose() {
   listener.close();
 });
 
+testPerm({ net: true }, async function netListenAddr() {
+  const listener = deno.listen("tcp", "127.0.0.1:0");
+  const addr = listener.addr();
+  assert(addr.startsWith("127.0.0.1:"));
+  assert(addr !== "127.0.0.1:0");
+  const accepted = listener.accept();
+  const conn = await deno.dial("tcp", addr);
+  assertEqual(conn.remoteAddr, addr);
+  const serverConn = await accepted;
+  assertEqual(serverConn.localAddr, addr);
+  serverConn.close();
+  conn.close();
+  listener.close();
+});
+
+testPerm({ net: true }, async function netAcceptClose() {
+  const listener = deno.listen("tcp", "127.0.0.1:0");
+  const accepted = listener.accept();
+  listener.close();
+  let err;
+  try {
+    await accepted;
+  } catch (e) {
+    err = e;
+  }
+  assert(!!err);
+  assertEqual(err.kind, deno.ErrorKind.BadFileDescriptor);
+});
+
 testPerm({ net: true }, async function netDialListen() {
   const addr = "127.0.0.1:4500";
   const listener = deno.listen("tcp", addr);
@@ -17,6 +46,8 @@ testPerm({ net: true }, async function netDialListen() {
     conn.close();
   });
   const conn = await deno.dial("tcp", addr);
+  assertEqual(conn.remoteAddr, addr);
+  assert(conn.localAddr.startsWith("127.0.0.1:"));
   const buf = new Uint8Array(1024);
   const readResult = await conn.read(buf);
   assertEqual(3, readResult.nread);
@@ -37,6 +68,29 @@ testPerm({ net: true }, async function netDialListen() {
   conn.close();
 });
 
+test(async function netDialPerm() {
+  let err;
+  try {
+    await deno.dial("tcp", "127.0.0.1:4500");
+  } catch (e) {
+    err = e;
+  }
+  assert(!!err);
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
+
+testPerm({ net: true }, async function netDialInvalidAddress() {
+  let err;
+  try {
+    await deno.dial("tcp", "localhost");
+  } catch (e) {
+    err = e;
+  }
+  assert(!!err);
+  assertEqual(err.kind, deno.ErrorKind.InvalidInput);
+});
+
 /* TODO Fix broken test.
 testPerm({ net: true }, async function netCloseReadSuccess() {
   const addr = "127.0.0.1:4500";
@@ -5,6 +5,7 @@ import { assert } from "./util";
 import * as util from "./util";
 import { flatbuffers } from "flatbuffers";
 import { sendSync } from "./dispatch";
+import { platform } from "./platform";
 
 export function exit(exitCode = 0): never {
   const builder = new flatbuffers.Builder();
@@ -15,6 +16,21 @@ export function exit(exitCode = 0): never {
   return util.unreachable();
 }
 
+/**
+ * Sets the code that the process exits with once it has nothing left to do,
+ * without exiting now like exit() does. The last code set wins.
+ *
+ *     import { setExitCode } from "deno";
+ *     setExitCode(1);
+ */
+export function setExitCode(exitCode: number): void {
+  const builder = new flatbuffers.Builder();
+  msg.SetExitCode.startSetExitCode(builder);
+  msg.SetExitCode.addCode(builder, exitCode);
+  const inner = msg.SetExitCode.endSetExitCode(builder);
+  sendSync(builder, msg.Any.SetExitCode, inner);
+}
+
 // @internal
 export function codeFetch(
   moduleSpecifier: string,
@@ -93,6 +109,76 @@ function setEnv(key: string, value: string): void {
   sendSync(builder, msg.Any.SetEnv, inner);
 }
 
+/**
+ * Returns the value of the environment variable key, or undefined if it isn't
+ * set. Unlike env(), this doesn't copy the whole environment.
+ *
+ *     import { getEnv } from "deno";
+ *     console.log(getEnv("HOME"));
+ */
+export function getEnv(key: string): string | undefined {
+  const builder = new flatbuffers.Builder();
+  const _key = builder.createString(key);
+  msg.GetEnv.startGetEnv(builder);
+  msg.GetEnv.addKey(builder, _key);
+  const inner = msg.GetEnv.endGetEnv(builder);
+  const baseRes = sendSync(builder, msg.Any.GetEnv, inner)!;
+  assert(msg.Any.GetEnvRes === baseRes.innerType());
+  const res = new msg.GetEnvRes();
+  assert(baseRes.inner(res) != null);
+  const value = res.value();
+  return value == null ? undefined : value;
+}
+
+/**
+ * Returns the name of the machine. Requires the env permission.
+ *
+ *     import { hostname } from "deno";
+ *     console.log(hostname());
+ */
+export function hostname(): string {
+  const builder = new flatbuffers.Builder();
+  msg.Hostname.startHostname(builder);
+  const inner = msg.Hostname.endHostname(builder);
+  const baseRes = sendSync(builder, msg.Any.Hostname, inner)!;
+  assert(msg.Any.HostnameRes === baseRes.innerType());
+  const res = new msg.HostnameRes();
+  assert(baseRes.inner(res) != null);
+  return res.hostname()!;
+}
+
+/**
+ * Returns the release of the operating system, like "4.15.0-45-generic" on
+ * Linux, or "unknown" where it can't be found. Requires the env permission.
+ */
+export function osRelease(): string {
+  const builder = new flatbuffers.Builder();
+  msg.OsRelease.startOsRelease(builder);
+  const inner = msg.OsRelease.endOsRelease(builder);
+  const baseRes = sendSync(builder, msg.Any.OsRelease, inner)!;
+  assert(msg.Any.OsReleaseRes === baseRes.innerType());
+  const res = new msg.OsReleaseRes();
+  assert(baseRes.inner(res) != null);
+  return res.release()!;
+}
+
+export interface BuildInfo {
+  /** Like "linux", "macos" or "windows". */
+  os: string;
+  /** Like "x86_64" or "aarch64". */
+  arch: string;
+}
+
+/**
+ * Returns what deno was compiled for. This is deno.platform with the names
+ * Rust uses for them.
+ */
+export function buildInfo(): BuildInfo {
+  const os = { mac: "macos", win: "windows", linux: "linux" }[platform.os];
+  const arch = { x64: "x86_64" }[platform.arch];
+  return { os, arch };
+}
+
 /**
  * Returns a snapshot of the environment variables at invocation. Mutating a
  * property in the object will set that variable in the environment for
@@ -22,3 +22,48 @@ test(async function envFailure() {
 
   assert(caughtError);
 });
+
+testPerm({ env: true }, function getEnvSuccess() {
+  const env = deno.env();
+  assertEqual(deno.getEnv("PATH"), env.PATH);
+  env.test_get_env = "Hello Get";
+  assertEqual(deno.getEnv("test_get_env"), "Hello Get");
+  assertEqual(deno.getEnv("test_get_env_never_set"), undefined);
+});
+
+test(function getEnvPerm() {
+  let err;
+  try {
+    deno.getEnv("PATH");
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
+
+testPerm({ env: true }, function hostnameSuccess() {
+  assert(deno.hostname().length > 0);
+  assert(deno.osRelease().length > 0);
+});
+
+test(function hostnamePerm() {
+  let err;
+  try {
+    deno.hostname();
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
+
+test(function buildInfoArch() {
+  const { os, arch } = deno.buildInfo();
+  // deno.platform has the names rollup was given for the same build.
+  assertEqual(arch, { x64: "x86_64" }[deno.platform.arch]);
+  assertEqual(
+    os,
+    { mac: "macos", win: "windows", linux: "linux" }[deno.platform.os]
+  );
+});
@@ -0,0 +1,72 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import * as msg from "gen/msg_generated";
+import { flatbuffers } from "flatbuffers";
+import { assert } from "./util";
+import * as dispatch from "./dispatch";
+
+function res(baseRes: null | msg.Base): number {
+  assert(baseRes !== null);
+  assert(msg.Any.PerfNowRes === baseRes!.innerType());
+  const res = new msg.PerfNowRes();
+  assert(baseRes!.inner(res) !== null);
+  return res.now();
+}
+
+export class Performance {
+  private marks = new Map<string, number>();
+
+  /**
+   * Returns a monotonic high resolution timestamp, in milliseconds since the
+   * runtime was started.
+   *
+   *     const t = performance.now();
+   */
+  now(): number {
+    const builder = new flatbuffers.Builder();
+    msg.PerfNow.startPerfNow(builder);
+    const inner = msg.PerfNow.endPerfNow(builder);
+    return res(dispatch.sendSync(builder, msg.Any.PerfNow, inner));
+  }
+
+  /**
+   * Records a named mark. The privileged side keeps a copy of every mark so
+   * that embedders can extract timings.
+   *
+   *     performance.mark("start");
+   */
+  mark(name: string): void {
+    const builder = new flatbuffers.Builder();
+    const name_ = builder.createString(name);
+    msg.PerfMark.startPerfMark(builder);
+    msg.PerfMark.addName(builder, name_);
+    const inner = msg.PerfMark.endPerfMark(builder);
+    const t = res(dispatch.sendSync(builder, msg.Any.PerfMark, inner));
+    this.marks.set(name, t);
+  }
+
+  /**
+   * Returns the number of milliseconds elapsed between two marks. If endMark
+   * is omitted, the current time is used instead.
+   *
+   *     performance.mark("start");
+   *     doWork();
+   *     performance.mark("end");
+   *     const ms = performance.measure("work", "start", "end");
+   */
+  measure(name: string, startMark: string, endMark?: string): number {
+    const start = this.marks.get(startMark);
+    if (start === undefined) {
+      throw new Error(`No mark named "${startMark}" (measure ${name}).`);
+    }
+    let end: number | undefined;
+    if (endMark === undefined) {
+      end = this.now();
+    } else {
+      end = this.marks.get(endMark);
+      if (end === undefined) {
+        throw new Error(`No mark named "${endMark}" (measure ${name}).`);
+      }
+    }
+    return end - start;
+  }
+}
@@ -0,0 +1,27 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { test, assert } from "./test_util.ts";
+
+test(function performanceNowIsMonotonic() {
+  const t1 = performance.now();
+  const t2 = performance.now();
+  assert(t1 >= 0);
+  assert(t2 >= t1);
+});
+
+test(function performanceMeasure() {
+  performance.mark("a");
+  performance.mark("b");
+  const ms = performance.measure("a to b", "a", "b");
+  assert(ms >= 0);
+  assert(performance.measure("a to now", "a") >= ms);
+});
+
+test(function performanceMeasureUnknownMark() {
+  let caughtError = false;
+  try {
+    performance.measure("bad", "no such mark");
+  } catch (e) {
+    caughtError = true;
+  }
+  assert(caughtError);
+});
@@ -0,0 +1,132 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import * as dispatch from "./dispatch";
+import * as msg from "gen/msg_generated";
+import { flatbuffers } from "flatbuffers";
+import { assert, unreachable } from "./util";
+import { File, close } from "./files";
+import { ReaderCloser, WriteCloser } from "./io";
+
+/**
+ * How a stdio stream of a subprocess is set up. "inherit" shares deno's own
+ * stream, "piped" connects it to the Process and "null" is like /dev/null.
+ */
+export type ProcessStdio = "inherit" | "piped" | "null";
+
+export interface RunOptions {
+  /** The program to run, followed by its arguments. */
+  args: string[];
+  cwd?: string;
+  stdin?: ProcessStdio;
+  stdout?: ProcessStdio;
+  stderr?: ProcessStdio;
+}
+
+export interface ProcessStatus {
+  success: boolean;
+  /** The exit code, unless the process was killed by a signal. */
+  code?: number;
+  signal?: number;
+}
+
+export class Process {
+  readonly rid: number;
+  readonly pid: number;
+  readonly stdin?: WriteCloser;
+  readonly stdout?: ReaderCloser;
+  readonly stderr?: ReaderCloser;
+
+  // @internal
+  constructor(res: msg.RunRes) {
+    this.rid = res.rid();
+    this.pid = res.pid();
+    if (res.stdinRid() >= 0) {
+      this.stdin = new File(res.stdinRid());
+    }
+    if (res.stdoutRid() >= 0) {
+      this.stdout = new File(res.stdoutRid());
+    }
+    if (res.stderrRid() >= 0) {
+      this.stderr = new File(res.stderrRid());
+    }
+  }
+
+  /** Waits for the process to exit. */
+  status(): Promise<ProcessStatus> {
+    return runStatus(this.rid);
+  }
+
+  /**
+   * Releases the process. It isn't killed, and any piped streams have to be
+   * closed separately.
+   */
+  close(): void {
+    close(this.rid);
+  }
+}
+
+function stdioMap(s: ProcessStdio): msg.ProcessStdio {
+  switch (s) {
+    case "inherit":
+      return msg.ProcessStdio.Inherit;
+    case "piped":
+      return msg.ProcessStdio.Piped;
+    case "null":
+      return msg.ProcessStdio.Null;
+    default:
+      return unreachable();
+  }
+}
+
+/**
+ * Starts a subprocess. Requires run permission. Streams that aren't given in
+ * opt are inherited.
+ *
+ *     import { run } from "deno";
+ *     const p = run({ args: ["echo", "hello"], stdout: "piped" });
+ */
+export function run(opt: RunOptions): Process {
+  const builder = new flatbuffers.Builder();
+  const argsOffset = msg.Run.createArgsVector(
+    builder,
+    opt.args.map(a => builder.createString(a))
+  );
+  const cwdOffset = opt.cwd != null ? builder.createString(opt.cwd) : null;
+  msg.Run.startRun(builder);
+  msg.Run.addArgs(builder, argsOffset);
+  if (cwdOffset != null) {
+    msg.Run.addCwd(builder, cwdOffset);
+  }
+  if (opt.stdin) {
+    msg.Run.addStdin(builder, stdioMap(opt.stdin));
+  }
+  if (opt.stdout) {
+    msg.Run.addStdout(builder, stdioMap(opt.stdout));
+  }
+  if (opt.stderr) {
+    msg.Run.addStderr(builder, stdioMap(opt.stderr));
+  }
+  const inner = msg.Run.endRun(builder);
+  const baseRes = dispatch.sendSync(builder, msg.Any.Run, inner);
+  assert(baseRes != null);
+  assert(msg.Any.RunRes === baseRes!.innerType());
+  const res = new msg.RunRes();
+  assert(baseRes!.inner(res) != null);
+  return new Process(res);
+}
+
+async function runStatus(rid: number): Promise<ProcessStatus> {
+  const builder = new flatbuffers.Builder();
+  msg.RunStatus.startRunStatus(builder);
+  msg.RunStatus.addRid(builder, rid);
+  const inner = msg.RunStatus.endRunStatus(builder);
+  const baseRes = await dispatch.sendAsync(builder, msg.Any.RunStatus, inner);
+  assert(baseRes != null);
+  assert(msg.Any.RunStatusRes === baseRes!.innerType());
+  const res = new msg.RunStatusRes();
+  assert(baseRes!.inner(res) != null);
+  if (res.gotSignal()) {
+    return { success: false, signal: res.exitSignal() };
+  }
+  const code = res.exitCode();
+  return { success: code === 0, code };
+}
@@ -0,0 +1,70 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { test, testPerm, assert, assertEqual } from "./test_util.ts";
+import * as deno from "deno";
+
+async function readAll(r: deno.Reader): Promise<string> {
+  const dec = new TextDecoder();
+  const buf = new Uint8Array(1024);
+  let out = "";
+  while (true) {
+    const { nread, eof } = await r.read(buf);
+    out += dec.decode(buf.subarray(0, nread));
+    if (eof) {
+      return out;
+    }
+  }
+}
+
+test(function runPermissions() {
+  let caughtError = false;
+  try {
+    deno.run({ args: ["echo", "hello"] });
+  } catch (e) {
+    caughtError = true;
+    assertEqual(e.kind, deno.ErrorKind.PermissionDenied);
+    assertEqual(e.name, "PermissionDenied");
+  }
+  assert(caughtError);
+});
+
+testPerm({ run: true }, async function runSuccess() {
+  const p = deno.run({ args: ["echo", "hello"], stdout: "piped" });
+  assert(p.pid > 0);
+  assert(p.stdin == null);
+  assertEqual(await readAll(p.stdout!), "hello\n");
+  const status = await p.status();
+  assertEqual(status.success, true);
+  assertEqual(status.code, 0);
+  assertEqual(status.signal, undefined);
+  p.stdout!.close();
+  p.close();
+});
+
+testPerm({ run: true }, async function runExitCode() {
+  const p = deno.run({ args: ["sh", "-c", "exit 3"] });
+  const status = await p.status();
+  assertEqual(status.success, false);
+  assertEqual(status.code, 3);
+  p.close();
+});
+
+testPerm({ run: true }, async function runStdinPiped() {
+  const p = deno.run({ args: ["cat"], stdin: "piped", stdout: "piped" });
+  await p.stdin!.write(new TextEncoder().encode("hello"));
+  p.stdin!.close();
+  assertEqual(await readAll(p.stdout!), "hello");
+  assertEqual((await p.status()).code, 0);
+  p.stdout!.close();
+  p.close();
+});
+
+testPerm({ run: true }, function runNotFound() {
+  let caughtError = false;
+  try {
+    deno.run({ args: ["this file hopefully doesn't exist"] });
+  } catch (e) {
+    caughtError = true;
+    assertEqual(e.kind, deno.ErrorKind.NotFound);
+  }
+  assert(caughtError);
+});
@@ -7,7 +7,7 @@ import { assert } from "./util";
 
 /**
  * Reads the directory given by path and returns
- * a list of file info synchronously.
+ * a list of file info synchronously. Requires read permission.
  *
  *     import { readDirSync } from "deno";
  *     const files = readDirSync("/");
@@ -18,6 +18,7 @@ export function readDirSync(path: string): FileInfo[] {
 
 /**
  * Reads the directory given by path and returns a list of file info.
+ * Requires read permission.
  *
  *     import { readDir } from "deno";
  *     const files = await readDir("/");
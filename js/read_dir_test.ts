@@ -21,12 +21,12 @@ function assertSameContent(files: FileInfo[]) {
   assertEqual(counter, 2);
 }
 
-testPerm({ write: true }, function readDirSyncSuccess() {
+testPerm({ read: true }, function readDirSyncSuccess() {
   const files = deno.readDirSync("tests/");
   assertSameContent(files);
 });
 
-test(function readDirSyncNotDir() {
+testPerm({ read: true }, function readDirSyncNotDir() {
   let caughtError = false;
   let src;
 
@@ -40,7 +40,7 @@ test(function readDirSyncNotDir() {
   assertEqual(src, undefined);
 });
 
-test(function readDirSyncNotFound() {
+testPerm({ read: true }, function readDirSyncNotFound() {
   let caughtError = false;
   let src;
 
@@ -54,7 +54,19 @@ test(function readDirSyncNotFound() {
   assertEqual(src, undefined);
 });
 
-testPerm({ write: true }, async function readDirSuccess() {
+testPerm({ read: true }, async function readDirSuccess() {
   const files = await deno.readDir("tests/");
   assertSameContent(files);
 });
+
+test(function readDirSyncPerm() {
+  let caughtError = false;
+  try {
+    deno.readDirSync("tests/");
+  } catch (e) {
+    caughtError = true;
+    assertEqual(e.kind, deno.ErrorKind.PermissionDenied);
+    assertEqual(e.name, "PermissionDenied");
+  }
+  assert(caughtError);
+});
@@ -3,6 +3,7 @@ import * as msg from "gen/msg_generated";
 import { flatbuffers } from "flatbuffers";
 import { assert } from "./util";
 import * as dispatch from "./dispatch";
+import { DenoError, ErrorKind } from "./errors";
 
 /**
  * Read the entire contents of a file synchronously.
@@ -17,24 +18,116 @@ export function readFileSync(filename: string): Uint8Array {
 }
 
 /**
- * Read the entire contents of a file.
+ * Read the entire contents of a file. If chunkSize is given, the contents
+ * are sent over in pieces of at most that many bytes, so that no buffer of
+ * the whole file has to be allocated on the way.
  *
  *     import { readFile } from "deno";
  *     const decoder = new TextDecoder("utf-8");
  *     const data = await readFile("hello.txt");
  *     console.log(decoder.decode(data));
  */
-export async function readFile(filename: string): Promise<Uint8Array> {
-  return res(await dispatch.sendAsync(...req(filename)));
+export async function readFile(
+  filename: string,
+  chunkSize?: number
+): Promise<Uint8Array> {
+  if (chunkSize === undefined) {
+    return res(await dispatch.sendAsync(...req(filename)));
+  }
+  assert(chunkSize > 0);
+  const chunks: Uint8Array[] = [];
+  const [builder, innerType, inner] = req(filename, chunkSize);
+  const baseRes = await dispatch.sendAsyncChunked(
+    builder,
+    innerType,
+    inner,
+    data => chunks.push(data)
+  );
+  assert(baseRes != null);
+  assert(msg.Any.ResponseChunk === baseRes!.innerType());
+  let len = 0;
+  for (const chunk of chunks) {
+    len += chunk.byteLength;
+  }
+  const contents = new Uint8Array(len);
+  let offset = 0;
+  for (const chunk of chunks) {
+    contents.set(chunk, offset);
+    offset += chunk.byteLength;
+  }
+  return contents;
+}
+
+/**
+ * Read the entire contents of several files. The files are read concurrently,
+ * which is faster than calling readFile() for each of them. Each element of
+ * the result is either the contents of the file with the same index in
+ * filenames, or the error that occurred reading it.
+ *
+ *     import { readFiles } from "deno";
+ *     const [a, b] = await readFiles(["a.txt", "b.txt"]);
+ */
+export async function readFiles(
+  filenames: string[]
+): Promise<Array<Uint8Array | DenoError<ErrorKind>>> {
+  const builder = new flatbuffers.Builder();
+  const filenames_ = msg.ReadFiles.createFilenamesVector(
+    builder,
+    filenames.map(filename => builder.createString(filename))
+  );
+  msg.ReadFiles.startReadFiles(builder);
+  msg.ReadFiles.addFilenames(builder, filenames_);
+  const inner = msg.ReadFiles.endReadFiles(builder);
+  const baseRes = await dispatch.sendAsync(builder, msg.Any.ReadFiles, inner);
+  assert(baseRes != null);
+  assert(msg.Any.ReadFilesRes === baseRes!.innerType());
+  const res = new msg.ReadFilesRes();
+  assert(baseRes!.inner(res) != null);
+  const results: Array<Uint8Array | DenoError<ErrorKind>> = [];
+  for (let i = 0; i < res.resultsLength(); i++) {
+    const result = res.results(i)!;
+    const kind = result.errorKind();
+    if (kind !== ErrorKind.NoError) {
+      results.push(new DenoError(kind, result.error()!));
+    } else {
+      results.push(new Uint8Array(result.dataArray()!));
+    }
+  }
+  return results;
+}
+
+/**
+ * Read the entire contents of a file synchronously as UTF-8 text. Throws an
+ * error of kind InvalidUtf8, with the offset of the first bad byte in its
+ * message, if the file isn't valid UTF-8.
+ *
+ *     import { readTextFileSync } from "deno";
+ *     console.log(readTextFileSync("hello.txt"));
+ */
+export function readTextFileSync(filename: string): string {
+  return resText(dispatch.sendSync(...reqText(filename)));
+}
+
+/**
+ * Read the entire contents of a file as UTF-8 text. Rejects with an error of
+ * kind InvalidUtf8 if the file isn't valid UTF-8, like readTextFileSync().
+ *
+ *     import { readTextFile } from "deno";
+ *     console.log(await readTextFile("hello.txt"));
+ */
+export async function readTextFile(filename: string): Promise<string> {
+  return resText(await dispatch.sendAsync(...reqText(filename)));
 }
 
 function req(
-  filename: string
+  filename: string,
+  chunkSize = 0
 ): [flatbuffers.Builder, msg.Any, flatbuffers.Offset] {
   const builder = new flatbuffers.Builder();
   const filename_ = builder.createString(filename);
   msg.ReadFile.startReadFile(builder);
   msg.ReadFile.addFilename(builder, filename_);
+  msg.ReadFile.addChunkSize(builder, chunkSize);
   const inner = msg.ReadFile.endReadFile(builder);
   return [builder, msg.Any.ReadFile, inner];
 }
@@ -48,3 +141,24 @@ function res(baseRes: null | msg.Base): Uint8Array {
   assert(dataArray != null);
   return new Uint8Array(dataArray!);
 }
+
+function reqText(
+  filename: string
+): [flatbuffers.Builder, msg.Any, flatbuffers.Offset] {
+  const builder = new flatbuffers.Builder();
+  const filename_ = builder.createString(filename);
+  msg.ReadTextFile.startReadTextFile(builder);
+  msg.ReadTextFile.addFilename(builder, filename_);
+  const inner = msg.ReadTextFile.endReadTextFile(builder);
+  return [builder, msg.Any.ReadTextFile, inner];
+}
+
+function resText(baseRes: null | msg.Base): string {
+  assert(baseRes != null);
+  assert(msg.Any.ReadTextFileRes === baseRes!.innerType());
+  const inner = new msg.ReadTextFileRes();
+  assert(baseRes!.inner(inner) != null);
+  const text = inner.text();
+  assert(text != null);
+  return text!;
+}
@@ -1,8 +1,8 @@
 // Copyright 2018 the Deno authors. All rights reserved. MIT license.
-import { test, assert, assertEqual } from "./test_util.ts";
+import { testPerm, assert, assertEqual } from "./test_util.ts";
 import * as deno from "deno";
 
-test(function readFileSyncSuccess() {
+testPerm({ read: true }, function readFileSyncSuccess() {
   const data = deno.readFileSync("package.json");
   assert(data.byteLength > 0);
   const decoder = new TextDecoder("utf-8");
@@ -11,7 +11,7 @@ test(function readFileSyncSuccess() {
   assertEqual(pkg.name, "deno");
 });
 
-test(function readFileSyncNotFound() {
+testPerm({ read: true }, function readFileSyncNotFound() {
   let caughtError = false;
   let data;
   try {
@@ -24,7 +24,18 @@ test(function readFileSyncNotFound() {
   assert(data === undefined);
 });
 
-test(async function readFileSuccess() {
+testPerm({ read: false }, function readFileSyncPerm() {
+  let err;
+  try {
+    deno.readFileSync("package.json");
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
+
+testPerm({ read: true }, async function readFileSuccess() {
   const data = await deno.readFile("package.json");
   assert(data.byteLength > 0);
   const decoder = new TextDecoder("utf-8");
@@ -32,3 +43,141 @@ test(async function readFileSuccess() {
   const pkg = JSON.parse(json);
   assertEqual(pkg.name, "deno");
 });
+
+testPerm({ read: true }, async function readFileNotFound() {
+  let err;
+  try {
+    await deno.readFile("bad_filename");
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.NotFound);
+});
+
+testPerm({ read: false }, async function readFilePerm() {
+  let err;
+  try {
+    await deno.readFile("package.json");
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
+
+testPerm({ read: true }, async function readFilesSuccess() {
+  const results = await deno.readFiles([
+    "package.json",
+    "bad_filename",
+    "js/read_file_test.ts"
+  ]);
+  assertEqual(results.length, 3);
+  const decoder = new TextDecoder("utf-8");
+  assert(results[0] instanceof Uint8Array);
+  const pkg = JSON.parse(decoder.decode(results[0] as Uint8Array));
+  assertEqual(pkg.name, "deno");
+  assert(results[1] instanceof deno.DenoError);
+  assertEqual(
+    (results[1] as deno.DenoError<deno.ErrorKind>).kind,
+    deno.ErrorKind.NotFound
+  );
+  assert(results[2] instanceof Uint8Array);
+  const source = decoder.decode(results[2] as Uint8Array);
+  assert(source.includes("readFilesSuccess"));
+});
+
+testPerm({ read: false }, async function readFilesPerm() {
+  const results = await deno.readFiles(["package.json", "README.md"]);
+  assertEqual(results.length, 2);
+  for (const result of results) {
+    assert(result instanceof deno.DenoError);
+    assertEqual(
+      (result as deno.DenoError<deno.ErrorKind>).kind,
+      deno.ErrorKind.PermissionDenied
+    );
+  }
+});
+
+testPerm({ read: true, write: true }, async function readFileChunked() {
+  const contents = new Uint8Array(100000);
+  for (let i = 0; i < contents.length; i++) {
+    contents[i] = i % 251;
+  }
+  const filename = deno.makeTempDirSync() + "/big.bin";
+  deno.writeFileSync(filename, contents);
+  const data = await deno.readFile(filename, 4096);
+  assertEqual(data.byteLength, contents.byteLength);
+  for (let i = 0; i < contents.length; i++) {
+    if (data[i] !== contents[i]) {
+      throw Error(`Byte ${i} differs`);
+    }
+  }
+
+  // Files that are smaller than a chunk, or empty, have one chunk or none.
+  const small = await deno.readFile("package.json", 4096);
+  assertEqual(small.byteLength, deno.readFileSync("package.json").byteLength);
+  const emptyFilename = deno.makeTempDirSync() + "/empty.bin";
+  deno.writeFileSync(emptyFilename, new Uint8Array(0));
+  assertEqual((await deno.readFile(emptyFilename, 16)).byteLength, 0);
+});
+
+testPerm({ read: true }, async function readFileChunkedNotFound() {
+  let err;
+  try {
+    await deno.readFile("bad_filename", 4096);
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.NotFound);
+});
+
+testPerm({ read: true, write: true }, function readTextFileSyncSuccess() {
+  const json = deno.readTextFileSync("package.json");
+  const pkg = JSON.parse(json);
+  assertEqual(pkg.name, "deno");
+
+  const filename = deno.makeTempDirSync() + "/dino.txt";
+  deno.writeFileSync(filename, new TextEncoder().encode("héllo 🦕"));
+  assertEqual(deno.readTextFileSync(filename), "héllo 🦕");
+});
+
+testPerm({ read: true, write: true }, function readTextFileSyncInvalidUtf8() {
+  const filename = deno.makeTempDirSync() + "/bad.txt";
+  deno.writeFileSync(filename, new Uint8Array([0x61, 0x62, 0xff, 0x63]));
+  let err;
+  try {
+    deno.readTextFileSync(filename);
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.InvalidUtf8);
+  assertEqual(err.name, "InvalidUtf8");
+  assert(err.message.includes("offset 2"));
+});
+
+testPerm({ read: false }, function readTextFileSyncPerm() {
+  let err;
+  try {
+    deno.readTextFileSync("package.json");
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
+
+testPerm({ read: true, write: true }, async function readTextFileSuccess() {
+  const pkg = JSON.parse(await deno.readTextFile("package.json"));
+  assertEqual(pkg.name, "deno");
+
+  const filename = deno.makeTempDirSync() + "/bad.txt";
+  deno.writeFileSync(filename, new Uint8Array([0x63, 0x61, 0x66, 0xc3]));
+  let err;
+  try {
+    await deno.readTextFile(filename);
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.InvalidUtf8);
+  assert(err.message.includes("offset 3"));
+});
@@ -0,0 +1,61 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import * as msg from "gen/msg_generated";
+import { flatbuffers } from "flatbuffers";
+import { assert } from "./util";
+import * as dispatch from "./dispatch";
+import { close } from "./files";
+import { TextDecoder } from "./text_encoding";
+
+const decoder = new TextDecoder("utf-8");
+
+/** A file opened with openLines(), read one line at a time. */
+export class Lines {
+  constructor(readonly rid: number) {}
+
+  /**
+   * Resolves to the next line, without its "\n" or "\r\n", or to null at the
+   * end of the file.
+   */
+  async readLine(): Promise<string | null> {
+    const builder = new flatbuffers.Builder();
+    msg.ReadLine.startReadLine(builder);
+    msg.ReadLine.addRid(builder, this.rid);
+    const inner = msg.ReadLine.endReadLine(builder);
+    const baseRes = await dispatch.sendAsync(builder, msg.Any.ReadLine, inner);
+    assert(baseRes != null);
+    assert(msg.Any.ReadLineRes === baseRes!.innerType());
+    const res = new msg.ReadLineRes();
+    assert(baseRes!.inner(res) != null);
+    const line = res.lineArray();
+    return line == null ? null : decoder.decode(line);
+  }
+
+  close(): void {
+    close(this.rid);
+  }
+}
+
+/**
+ * Open a file to read it line by line. Requires read permission.
+ *
+ *     import { openLines } from "deno";
+ *     const lines = await openLines("hello.txt");
+ *     let line;
+ *     while ((line = await lines.readLine()) !== null) {
+ *       console.log(line);
+ *     }
+ *     lines.close();
+ */
+export async function openLines(filename: string): Promise<Lines> {
+  const builder = new flatbuffers.Builder();
+  const filename_ = builder.createString(filename);
+  msg.OpenLines.startOpenLines(builder);
+  msg.OpenLines.addFilename(builder, filename_);
+  const inner = msg.OpenLines.endOpenLines(builder);
+  const baseRes = await dispatch.sendAsync(builder, msg.Any.OpenLines, inner);
+  assert(baseRes != null);
+  assert(msg.Any.OpenLinesRes === baseRes!.innerType());
+  const res = new msg.OpenLinesRes();
+  assert(baseRes!.inner(res) != null);
+  return new Lines(res.rid());
+}
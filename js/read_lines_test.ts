@@ -0,0 +1,60 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { testPerm, assert, assertEqual } from "./test_util.ts";
+import * as deno from "deno";
+
+async function readAll(filename: string): Promise<string[]> {
+  const lines = await deno.openLines(filename);
+  const result: string[] = [];
+  let line;
+  while ((line = await lines.readLine()) !== null) {
+    result.push(line);
+  }
+  // Stays at the end.
+  assertEqual(await lines.readLine(), null);
+  lines.close();
+  return result;
+}
+
+testPerm({ read: true, write: true }, async function readLinesSuccess() {
+  const encoder = new TextEncoder();
+  const filename = deno.makeTempDirSync() + "/lines.txt";
+  deno.writeFileSync(filename, encoder.encode("one\ntwo\r\n\nthree\n"));
+  assertEqual(await readAll(filename), ["one", "two", "", "three"]);
+});
+
+testPerm({ read: true, write: true }, async function readLinesNoNewline() {
+  const encoder = new TextEncoder();
+  const filename = deno.makeTempDirSync() + "/lines.txt";
+  deno.writeFileSync(filename, encoder.encode("one\ntwo"));
+  assertEqual(await readAll(filename), ["one", "two"]);
+});
+
+testPerm({ read: true, write: true }, async function readLinesEmpty() {
+  const filename = deno.makeTempDirSync() + "/lines.txt";
+  deno.writeFileSync(filename, new Uint8Array(0));
+  assertEqual(await readAll(filename), []);
+});
+
+testPerm({ read: true }, async function readLinesNotFound() {
+  let err;
+  try {
+    await deno.openLines("bad_filename");
+  } catch (e) {
+    err = e;
+  }
+  assert(!!err);
+  assertEqual(err.kind, deno.ErrorKind.NotFound);
+  assertEqual(err.name, "NotFound");
+});
+
+testPerm({ read: false }, async function readLinesPerm() {
+  let err;
+  try {
+    await deno.openLines("package.json");
+  } catch (e) {
+    err = e;
+  }
+  assert(!!err);
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
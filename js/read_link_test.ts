@@ -2,11 +2,11 @@
 import { test, testPerm, assert, assertEqual } from "./test_util.ts";
 import * as deno from "deno";
 
-testPerm({ write: true }, function readlinkSyncSuccess() {
+testPerm({ read: true, write: true }, function readlinkSyncSuccess() {
   const testDir = deno.makeTempDirSync() + "/test-readlink-sync";
   const target = testDir + "/target";
   const symlink = testDir + "/symln";
-  deno.mkdirSync(target);
+  deno.mkdirSync(target, { recursive: true });
   // TODO Add test for Windows once symlink is implemented for Windows.
   // See https://github.com/denoland/deno/issues/815.
   if (deno.platform.os !== "win") {
@@ -16,7 +16,7 @@ testPerm({ write: true }, function readlinkSyncSuccess() {
   }
 });
 
-test(function readlinkSyncNotFound() {
+testPerm({ read: true }, function readlinkSyncNotFound() {
   let caughtError = false;
   let data;
   try {
@@ -29,11 +29,11 @@ test(function readlinkSyncNotFound() {
   assertEqual(data, undefined);
 });
 
-testPerm({ write: true }, async function readlinkSuccess() {
+testPerm({ read: true, write: true }, async function readlinkSuccess() {
   const testDir = deno.makeTempDirSync() + "/test-readlink";
   const target = testDir + "/target";
   const symlink = testDir + "/symln";
-  deno.mkdirSync(target);
+  deno.mkdirSync(target, { recursive: true });
   // TODO Add test for Windows once symlink is implemented for Windows.
   // See https://github.com/denoland/deno/issues/815.
   if (deno.platform.os !== "win") {
@@ -42,3 +42,15 @@ testPerm({ write: true }, async function readlinkSuccess() {
     assertEqual(targetPath, target);
   }
 });
+
+test(function readlinkSyncPerm() {
+  let caughtError = false;
+  try {
+    deno.readlinkSync("/symlink");
+  } catch (e) {
+    caughtError = true;
+    assertEqual(e.kind, deno.ErrorKind.PermissionDenied);
+    assertEqual(e.name, "PermissionDenied");
+  }
+  assert(caughtError);
+});
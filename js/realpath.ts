@@ -0,0 +1,46 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import * as msg from "gen/msg_generated";
+import { flatbuffers } from "flatbuffers";
+import { assert } from "./util";
+import * as dispatch from "./dispatch";
+
+/**
+ * Returns the absolute path of path, with symbolic links, "." and ".."
+ * resolved, synchronously. Requires read permission.
+ *
+ *     import { realpathSync } from "deno";
+ *     const realPath = realpathSync("./some/../path");
+ */
+export function realpathSync(path: string): string {
+  return res(dispatch.sendSync(...req(path)));
+}
+
+/**
+ * Returns the absolute path of path, with symbolic links, "." and ".."
+ * resolved. Requires read permission.
+ *
+ *     import { realpath } from "deno";
+ *     const realPath = await realpath("./some/../path");
+ */
+export async function realpath(path: string): Promise<string> {
+  return res(await dispatch.sendAsync(...req(path)));
+}
+
+function req(path: string): [flatbuffers.Builder, msg.Any, flatbuffers.Offset] {
+  const builder = new flatbuffers.Builder();
+  const path_ = builder.createString(path);
+  msg.Realpath.startRealpath(builder);
+  msg.Realpath.addPath(builder, path_);
+  const inner = msg.Realpath.endRealpath(builder);
+  return [builder, msg.Any.Realpath, inner];
+}
+
+function res(baseRes: null | msg.Base): string {
+  assert(baseRes !== null);
+  assert(msg.Any.RealpathRes === baseRes!.innerType());
+  const res = new msg.RealpathRes();
+  assert(baseRes!.inner(res) !== null);
+  const path = res.path();
+  assert(path !== null);
+  return path!;
+}
@@ -0,0 +1,38 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { test, testPerm, assert, assertEqual } from "./test_util.ts";
+import * as deno from "deno";
+
+testPerm({ read: true }, function realpathSyncSuccess() {
+  const realPath = deno.realpathSync("js/../package.json");
+  assert(realPath.startsWith("/"));
+  assert(realPath.endsWith("/package.json"));
+  assert(!realPath.includes(".."));
+});
+
+testPerm({ read: true }, async function realpathSuccess() {
+  const realPath = await deno.realpath("./js/..");
+  assertEqual(realPath + "/package.json", deno.realpathSync("package.json"));
+});
+
+testPerm({ read: true }, function realpathSyncNotFound() {
+  let caughtError = false;
+  try {
+    deno.realpathSync("bad_filename");
+  } catch (e) {
+    caughtError = true;
+    assertEqual(e.kind, deno.ErrorKind.NotFound);
+  }
+  assert(caughtError);
+});
+
+test(function realpathSyncPerm() {
+  let caughtError = false;
+  try {
+    deno.realpathSync("package.json");
+  } catch (e) {
+    caughtError = true;
+    assertEqual(e.kind, deno.ErrorKind.PermissionDenied);
+    assertEqual(e.name, "PermissionDenied");
+  }
+  assert(caughtError);
+});
@@ -4,10 +4,10 @@ import * as deno from "deno";
 
 // SYNC
 
-testPerm({ write: true }, function removeSyncDirSuccess() {
+testPerm({ read: true, write: true }, function removeSyncDirSuccess() {
   // REMOVE EMPTY DIRECTORY
   const path = deno.makeTempDirSync() + "/dir/subdir";
-  deno.mkdirSync(path);
+  deno.mkdirSync(path, { recursive: true });
   const pathInfo = deno.statSync(path);
   assert(pathInfo.isDirectory()); // check exist first
   deno.removeSync(path); // remove
@@ -23,7 +23,7 @@ testPerm({ write: true }, function removeSyncDirSuccess() {
   assertEqual(err.name, "NotFound");
 });
 
-testPerm({ write: true }, function removeSyncFileSuccess() {
+testPerm({ read: true, write: true }, function removeSyncFileSuccess() {
   // REMOVE FILE
   const enc = new TextEncoder();
   const data = enc.encode("Hello");
@@ -44,11 +44,11 @@ testPerm({ write: true }, function removeSyncFileSuccess() {
   assertEqual(err.name, "NotFound");
 });
 
-testPerm({ write: true }, function removeSyncFail() {
+testPerm({ read: true, write: true }, function removeSyncFail() {
   // NON-EMPTY DIRECTORY
   const path = deno.makeTempDirSync() + "/dir/subdir";
   const subPath = path + "/subsubdir";
-  deno.mkdirSync(path);
+  deno.mkdirSync(path, { recursive: true });
   deno.mkdirSync(subPath);
   const pathInfo = deno.statSync(path);
   assert(pathInfo.isDirectory()); // check exist first
@@ -86,10 +86,10 @@ testPerm({ write: false }, function removeSyncPerm() {
   assertEqual(err.name, "PermissionDenied");
 });
 
-testPerm({ write: true }, function removeAllSyncDirSuccess() {
+testPerm({ read: true, write: true }, function removeAllSyncDirSuccess() {
   // REMOVE EMPTY DIRECTORY
   let path = deno.makeTempDirSync() + "/dir/subdir";
-  deno.mkdirSync(path);
+  deno.mkdirSync(path, { recursive: true });
   let pathInfo = deno.statSync(path);
   assert(pathInfo.isDirectory()); // check exist first
   deno.removeAllSync(path); // remove
@@ -106,7 +106,7 @@ testPerm({ write: true }, function removeAllSyncDirSuccess() {
   // REMOVE NON-EMPTY DIRECTORY
   path = deno.makeTempDirSync() + "/dir/subdir";
   const subPath = path + "/subsubdir";
-  deno.mkdirSync(path);
+  deno.mkdirSync(path, { recursive: true });
   deno.mkdirSync(subPath);
   pathInfo = deno.statSync(path);
   assert(pathInfo.isDirectory()); // check exist first
@@ -124,7 +124,7 @@ testPerm({ write: true }, function removeAllSyncDirSuccess() {
   assertEqual(err.name, "NotFound");
 });
 
-testPerm({ write: true }, function removeAllSyncFileSuccess() {
+testPerm({ read: true, write: true }, function removeAllSyncFileSuccess() {
   // REMOVE FILE
   const enc = new TextEncoder();
   const data = enc.encode("Hello");
@@ -171,10 +171,10 @@ testPerm({ write: false }, function removeAllSyncPerm() {
 
 // ASYNC
 
-testPerm({ write: true }, async function removeDirSuccess() {
+testPerm({ read: true, write: true }, async function removeDirSuccess() {
   // REMOVE EMPTY DIRECTORY
   const path = deno.makeTempDirSync() + "/dir/subdir";
-  deno.mkdirSync(path);
+  deno.mkdirSync(path, { recursive: true });
   const pathInfo = deno.statSync(path);
   assert(pathInfo.isDirectory()); // check exist first
   await deno.remove(path); // remove
@@ -190,7 +190,7 @@ testPerm({ write: true }, async function removeDirSuccess() {
   assertEqual(err.name, "NotFound");
 });
 
-testPerm({ write: true }, async function removeFileSuccess() {
+testPerm({ read: true, write: true }, async function removeFileSuccess() {
   // REMOVE FILE
   const enc = new TextEncoder();
   const data = enc.encode("Hello");
@@ -211,11 +211,11 @@ testPerm({ write: true }, async function removeFileSuccess() {
   assertEqual(err.name, "NotFound");
 });
 
-testPerm({ write: true }, async function removeFail() {
+testPerm({ read: true, write: true }, async function removeFail() {
   // NON-EMPTY DIRECTORY
   const path = deno.makeTempDirSync() + "/dir/subdir";
   const subPath = path + "/subsubdir";
-  deno.mkdirSync(path);
+  deno.mkdirSync(path, { recursive: true });
   deno.mkdirSync(subPath);
   const pathInfo = deno.statSync(path);
   assert(pathInfo.isDirectory()); // check exist first
@@ -252,10 +252,10 @@ testPerm({ write: false }, async function removePerm() {
   assertEqual(err.name, "PermissionDenied");
 });
 
-testPerm({ write: true }, async function removeAllDirSuccess() {
+testPerm({ read: true, write: true }, async function removeAllDirSuccess() {
   // REMOVE EMPTY DIRECTORY
   let path = deno.makeTempDirSync() + "/dir/subdir";
-  deno.mkdirSync(path);
+  deno.mkdirSync(path, { recursive: true });
   let pathInfo = deno.statSync(path);
   assert(pathInfo.isDirectory()); // check exist first
   await deno.removeAll(path); // remove
@@ -272,7 +272,7 @@ testPerm({ write: true }, async function removeAllDirSuccess() {
   // REMOVE NON-EMPTY DIRECTORY
   path = deno.makeTempDirSync() + "/dir/subdir";
   const subPath = path + "/subsubdir";
-  deno.mkdirSync(path);
+  deno.mkdirSync(path, { recursive: true });
   deno.mkdirSync(subPath);
   pathInfo = deno.statSync(path);
   assert(pathInfo.isDirectory()); // check exist first
@@ -290,7 +290,7 @@ testPerm({ write: true }, async function removeAllDirSuccess() {
   assertEqual(err.name, "NotFound");
 });
 
-testPerm({ write: true }, async function removeAllFileSuccess() {
+testPerm({ read: true, write: true }, async function removeAllFileSuccess() {
   // REMOVE FILE
   const enc = new TextEncoder();
   const data = enc.encode("Hello");
@@ -6,7 +6,9 @@ import * as dispatch from "./dispatch";
 /**
  * Synchronously renames (moves) oldpath to newpath. If newpath already exists
  * and is not a directory, Rename replaces it. OS-specific restrictions may
- * apply when oldpath and newpath are in different directories.
+ * apply when oldpath and newpath are in different directories. Moves across
+ * file systems copy oldpath and then remove it. Requires read permission for
+ * oldpath and write permission for newpath.
  *
  *     import { renameSync } from "deno";
  *     renameSync("old/path", "new/path");
@@ -18,7 +20,9 @@ export function renameSync(oldpath: string, newpath: string): void {
 /**
  * Renames (moves) oldpath to newpath. If newpath already exists
  * and is not a directory, Rename replaces it. OS-specific restrictions may
- * apply when oldpath and newpath are in different directories.
+ * apply when oldpath and newpath are in different directories. Moves across
+ * file systems copy oldpath and then remove it. Requires read permission for
+ * oldpath and write permission for newpath.
  *
  *     import { rename } from "deno";
  *     await rename("old/path", "new/path");
@@ -2,11 +2,11 @@
 import { test, testPerm, assert, assertEqual } from "./test_util.ts";
 import * as deno from "deno";
 
-testPerm({ write: true }, function renameSyncSuccess() {
+testPerm({ read: true, write: true }, function renameSyncSuccess() {
   const testDir = deno.makeTempDirSync() + "/test-rename-sync";
   const oldpath = testDir + "/oldpath";
   const newpath = testDir + "/newpath";
-  deno.mkdirSync(oldpath);
+  deno.mkdirSync(oldpath, { recursive: true });
   deno.renameSync(oldpath, newpath);
   const newPathInfo = deno.statSync(newpath);
   assert(newPathInfo.isDirectory());
@@ -24,6 +24,40 @@ testPerm({ write: true }, function renameSyncSuccess() {
   assertEqual(oldPathInfo, undefined);
 });
 
+testPerm({ read: true, write: true }, function renameSyncCrossDirectory() {
+  const testDir = deno.makeTempDirSync();
+  const oldpath = testDir + "/oldfile";
+  const newpath = testDir + "/subdir/newfile";
+  deno.writeFileSync(oldpath, new TextEncoder().encode("hello"));
+  deno.mkdirSync(testDir + "/subdir");
+  deno.renameSync(oldpath, newpath);
+  const data = new TextDecoder().decode(deno.readFileSync(newpath));
+  assertEqual(data, "hello");
+});
+
+testPerm({ read: true, write: true }, function renameSyncNotFound() {
+  const testDir = deno.makeTempDirSync();
+  let err;
+  try {
+    deno.renameSync(testDir + "/missing", testDir + "/newpath");
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.NotFound);
+  assertEqual(err.name, "NotFound");
+});
+
+testPerm({ read: false, write: true }, function renameSyncReadPerm() {
+  let err;
+  try {
+    deno.renameSync("/oldbaddir", "/newbaddir");
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
+
 testPerm({ write: false }, function renameSyncPerm() {
   let err;
   try {
@@ -37,11 +71,11 @@ testPerm({ write: false }, function renameSyncPerm() {
   assertEqual(err.name, "PermissionDenied");
 });
 
-testPerm({ write: true }, async function renameSuccess() {
+testPerm({ read: true, write: true }, async function renameSuccess() {
   const testDir = deno.makeTempDirSync() + "/test-rename";
   const oldpath = testDir + "/oldpath";
   const newpath = testDir + "/newpath";
-  deno.mkdirSync(oldpath);
+  deno.mkdirSync(oldpath, { recursive: true });
   await deno.rename(oldpath, newpath);
   const newPathInfo = deno.statSync(newpath);
   assert(newPathInfo.isDirectory());
@@ -8,7 +8,7 @@ import { FileInfo, FileInfoImpl } from "./file_info";
 /**
  * Queries the file system for information on the path provided.
  * If the given path is a symlink information about the symlink will
- * be returned.
+ * be returned. Requires read permission.
  *
  *     import { lstat } from "deno";
  *     const fileInfo = await lstat("hello.txt");
@@ -21,7 +21,7 @@ export async function lstat(filename: string): Promise<FileInfo> {
 /**
  * Queries the file system for information on the path provided synchronously.
  * If the given path is a symlink information about the symlink will
- * be returned.
+ * be returned. Requires read permission.
  *
  *     import { lstatSync } from "deno";
  *     const fileInfo = lstatSync("hello.txt");
@@ -33,7 +33,7 @@ export function lstatSync(filename: string): FileInfo {
 
 /**
  * Queries the file system for information on the path provided.
- * `stat` Will always follow symlinks.
+ * `stat` Will always follow symlinks. Requires read permission.
  *
  *     import { stat } from "deno";
  *     const fileInfo = await stat("hello.txt");
@@ -45,7 +45,7 @@ export async function stat(filename: string): Promise<FileInfo> {
 
 /**
  * Queries the file system for information on the path provided synchronously.
- * `statSync` Will always follow symlinks.
+ * `statSync` Will always follow symlinks. Requires read permission.
  *
  *     import { statSync } from "deno";
  *     const fileInfo = statSync("hello.txt");
@@ -1,10 +1,10 @@
 // Copyright 2018 the Deno authors. All rights reserved. MIT license.
-import { test, assert, assertEqual } from "./test_util.ts";
+import { testPerm, assert, assertEqual } from "./test_util.ts";
 import * as deno from "deno";
 
 // TODO Add tests for modified, accessed, and created fields once there is a way
 // to create temp files.
-test(async function statSyncSuccess() {
+testPerm({ read: true }, async function statSyncSuccess() {
   const packageInfo = deno.statSync("package.json");
   assert(packageInfo.isFile());
   assert(!packageInfo.isSymlink());
@@ -18,7 +18,7 @@ test(async function statSyncSuccess() {
   assert(!srcInfo.isSymlink());
 });
 
-test(async function statSyncNotFound() {
+testPerm({ read: true }, async function statSyncNotFound() {
   let caughtError = false;
   let badInfo;
 
@@ -34,7 +34,7 @@ test(async function statSyncNotFound() {
   assertEqual(badInfo, undefined);
 });
 
-test(async function lstatSyncSuccess() {
+testPerm({ read: true }, async function lstatSyncSuccess() {
   const packageInfo = deno.lstatSync("package.json");
   assert(packageInfo.isFile());
   assert(!packageInfo.isSymlink());
@@ -48,7 +48,7 @@ test(async function lstatSyncSuccess() {
   assert(!srcInfo.isSymlink());
 });
 
-test(async function lstatSyncNotFound() {
+testPerm({ read: true }, async function lstatSyncNotFound() {
   let caughtError = false;
   let badInfo;
 
@@ -64,7 +64,7 @@ test(async function lstatSyncNotFound() {
   assertEqual(badInfo, undefined);
 });
 
-test(async function statSuccess() {
+testPerm({ read: true }, async function statSuccess() {
   const packageInfo = await deno.stat("package.json");
   assert(packageInfo.isFile());
   assert(!packageInfo.isSymlink());
@@ -78,7 +78,7 @@ test(async function statSuccess() {
   assert(!srcInfo.isSymlink());
 });
 
-test(async function statNotFound() {
+testPerm({ read: true }, async function statNotFound() {
   let caughtError = false;
   let badInfo;
 
@@ -94,7 +94,7 @@ test(async function statNotFound() {
   assertEqual(badInfo, undefined);
 });
 
-test(async function lstatSuccess() {
+testPerm({ read: true }, async function lstatSuccess() {
   const packageInfo = await deno.lstat("package.json");
   assert(packageInfo.isFile());
   assert(!packageInfo.isSymlink());
@@ -108,7 +108,7 @@ test(async function lstatSuccess() {
   assert(!srcInfo.isSymlink());
 });
 
-test(async function lstatNotFound() {
+testPerm({ read: true }, async function lstatNotFound() {
   let caughtError = false;
   let badInfo;
 
@@ -123,3 +123,27 @@ test(async function lstatNotFound() {
   assert(caughtError);
   assertEqual(badInfo, undefined);
 });
+
+testPerm({ read: false }, function statSyncPerm() {
+  let caughtError = false;
+  try {
+    deno.statSync("package.json");
+  } catch (e) {
+    caughtError = true;
+    assertEqual(e.kind, deno.ErrorKind.PermissionDenied);
+    assertEqual(e.name, "PermissionDenied");
+  }
+  assert(caughtError);
+});
+
+testPerm({ read: false }, async function lstatPerm() {
+  let caughtError = false;
+  try {
+    await deno.lstat("package.json");
+  } catch (e) {
+    caughtError = true;
+    assertEqual(e.kind, deno.ErrorKind.PermissionDenied);
+    assertEqual(e.name, "PermissionDenied");
+  }
+  assert(caughtError);
+});
@@ -0,0 +1,55 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import * as msg from "gen/msg_generated";
+import { flatbuffers } from "flatbuffers";
+import { assert } from "./util";
+import { sendSync } from "./dispatch";
+
+export interface IsolateStatus {
+  uptime_ms: number;
+  // Async ops that haven't completed yet, including queued ones.
+  pending_ops: number;
+  heap: {
+    total_heap_size: number;
+    used_heap_size: number;
+    heap_size_limit: number;
+  };
+  // Bytes of requests and responses, by op id.
+  op_bytes: { [opId: string]: number };
+}
+
+/**
+ * Returns a snapshot of the health of the isolate, for monitoring.
+ *
+ *     import { isolateStatus } from "deno";
+ *     console.log(isolateStatus().heap.used_heap_size);
+ */
+export function isolateStatus(): IsolateStatus {
+  const builder = new flatbuffers.Builder();
+  msg.IsolateStatus.startIsolateStatus(builder);
+  const inner = msg.IsolateStatus.endIsolateStatus(builder);
+  const baseRes = sendSync(builder, msg.Any.IsolateStatus, inner)!;
+  assert(msg.Any.IsolateStatusRes === baseRes.innerType());
+  const res = new msg.IsolateStatusRes();
+  assert(baseRes.inner(res) != null);
+  return JSON.parse(res.json()!);
+}
+
+/**
+ * Returns true when so many async ops are pending that new ones should wait.
+ * The limit is set by the embedder; without one this is always false.
+ *
+ *     import { backpressure } from "deno";
+ *     while (backpressure()) {
+ *       await pendingOps.shift();
+ *     }
+ */
+export function backpressure(): boolean {
+  const builder = new flatbuffers.Builder();
+  msg.Backpressure.startBackpressure(builder);
+  const inner = msg.Backpressure.endBackpressure(builder);
+  const baseRes = sendSync(builder, msg.Any.Backpressure, inner)!;
+  assert(msg.Any.BackpressureRes === baseRes.innerType());
+  const res = new msg.BackpressureRes();
+  assert(baseRes.inner(res) != null);
+  return res.active();
+}
@@ -0,0 +1,16 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { test, assert } from "./test_util.ts";
+import * as deno from "deno";
+
+test(function isolateStatusSuccess() {
+  const status = deno.isolateStatus();
+  assert(status.uptime_ms >= 0);
+  assert(status.pending_ops >= 0);
+  assert(status.heap.used_heap_size > 0);
+  assert(status.heap.used_heap_size <= status.heap.total_heap_size);
+});
+
+test(function backpressureInactive() {
+  // No high-water mark is set when running the unit tests.
+  assert(!deno.backpressure());
+});
@@ -2,12 +2,13 @@
 import * as msg from "gen/msg_generated";
 import { flatbuffers } from "flatbuffers";
 import * as dispatch from "./dispatch";
-import * as util from "./util";
 
 /**
  * Synchronously creates newname as a symbolic link to oldname.
  * The type argument can be set to 'dir' or 'file' and is only
- * available on Windows (ignored on other platforms).
+ * used on Windows (ignored on other platforms). If it isn't given, it is
+ * what oldname is. Requires read permission for oldname and write permission
+ * for newname.
  *
  *     import { symlinkSync } from "deno";
  *     symlinkSync("old/name", "new/name");
@@ -23,7 +24,9 @@ export function symlinkSync(
 /**
  * Creates newname as a symbolic link to oldname.
  * The type argument can be set to 'dir' or 'file' and is only
- * available on Windows (ignored on other platforms).
+ * used on Windows (ignored on other platforms). If it isn't given, it is
+ * what oldname is. Requires read permission for oldname and write permission
+ * for newname.
  *
  *     import { symlink } from "deno";
  *     await symlink("old/name", "new/name");
@@ -41,16 +44,16 @@ function req(
   newname: string,
   type?: string
 ): [flatbuffers.Builder, msg.Any, flatbuffers.Offset] {
-  // TODO Use type for Windows.
-  if (type) {
-    return util.notImplemented();
-  }
   const builder = new flatbuffers.Builder();
   const oldname_ = builder.createString(oldname);
   const newname_ = builder.createString(newname);
+  const kind_ = type === undefined ? undefined : builder.createString(type);
   msg.Symlink.startSymlink(builder);
   msg.Symlink.addOldname(builder, oldname_);
   msg.Symlink.addNewname(builder, newname_);
+  if (kind_ !== undefined) {
+    msg.Symlink.addKind(builder, kind_);
+  }
   const inner = msg.Symlink.endSymlink(builder);
   return [builder, msg.Any.Symlink, inner];
 }
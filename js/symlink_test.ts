@@ -2,11 +2,11 @@
 import { test, testPerm, assert, assertEqual } from "./test_util.ts";
 import * as deno from "deno";
 
-testPerm({ write: true }, function symlinkSyncSuccess() {
+testPerm({ read: true, write: true }, function symlinkSyncSuccess() {
   const testDir = deno.makeTempDirSync() + "/test-symlink-sync";
   const oldname = testDir + "/oldname";
   const newname = testDir + "/newname";
-  deno.mkdirSync(oldname);
+  deno.mkdirSync(oldname, { recursive: true });
   let errOnWindows;
   // Just for now, until we implement symlink for Windows.
   try {
@@ -36,22 +36,38 @@ testPerm({ write: false }, function symlinkSyncPerm() {
   assertEqual(err.name, "PermissionDenied");
 });
 
-// Just for now, until we implement symlink for Windows.
-testPerm({ write: true }, function symlinkSyncNotImplemented() {
+testPerm({ read: false, write: true }, function symlinkSyncReadPerm() {
   let err;
   try {
-    deno.symlinkSync("oldname", "newname", "dir");
+    deno.symlinkSync("oldbaddir", "newbaddir");
   } catch (e) {
     err = e;
   }
-  assertEqual(err.message, "Not implemented");
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
 });
 
-testPerm({ write: true }, async function symlinkSuccess() {
+testPerm({ read: true, write: true }, function symlinkSyncType() {
+  const testDir = deno.makeTempDirSync();
+  const oldname = testDir + "/oldname";
+  deno.mkdirSync(oldname);
+  deno.symlinkSync(oldname, testDir + "/dirlink", "dir");
+  assert(deno.statSync(testDir + "/dirlink").isDirectory());
+
+  let err;
+  try {
+    deno.symlinkSync(oldname, testDir + "/badlink", "pipe");
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.InvalidInput);
+});
+
+testPerm({ read: true, write: true }, async function symlinkSuccess() {
   const testDir = deno.makeTempDirSync() + "/test-symlink";
   const oldname = testDir + "/oldname";
   const newname = testDir + "/newname";
-  deno.mkdirSync(oldname);
+  deno.mkdirSync(oldname, { recursive: true });
   let errOnWindows;
   // Just for now, until we implement symlink for Windows.
   try {
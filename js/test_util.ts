@@ -2,9 +2,10 @@
 //
 // We want to test many ops in deno which have different behavior depending on
 // the permissions set. These tests can specify which permissions they expect,
-// which appends a special string like "permW1N0" to the end of the test name.
-// Here we run several copies of deno with different permissions, filtering the
-// tests by the special string. permW0N0 means allow-write but not allow-net.
+// which appends a special string like "permR0W1N0E0U0" to the end of the test
+// name. Here we run several copies of deno with different permissions,
+// filtering the tests by the special string. permR0W1N0E0U0 means allow-write
+// but not allow-read, allow-net, allow-env or allow-run.
 // See tools/unit_tests.py for more details.
 
 import * as deno from "deno";
@@ -15,28 +16,34 @@ export { assert, assertEqual } from "./testing/testing.ts";
 testing.setFilter(deno.args[1]);
 
 interface DenoPermissions {
+  read?: boolean;
   write?: boolean;
   net?: boolean;
   env?: boolean;
+  run?: boolean;
 }
 
 function permToString(perms: DenoPermissions): string {
+  const r = perms.read ? 1 : 0;
   const w = perms.write ? 1 : 0;
   const n = perms.net ? 1 : 0;
   const e = perms.env ? 1 : 0;
-  return `permW${w}N${n}E${e}`;
+  const u = perms.run ? 1 : 0;
+  return `permR${r}W${w}N${n}E${e}U${u}`;
 }
 
 function permFromString(s: string): DenoPermissions {
-  const re = /^permW([01])N([01])E([01])$/;
+  const re = /^permR([01])W([01])N([01])E([01])U([01])$/;
   const found = s.match(re);
   if (!found) {
     throw Error("Not a permission string");
   }
   return {
-    write: Boolean(Number(found[1])),
-    net: Boolean(Number(found[2])),
-    env: Boolean(Number(found[3]))
+    read: Boolean(Number(found[1])),
+    write: Boolean(Number(found[2])),
+    net: Boolean(Number(found[3])),
+    env: Boolean(Number(found[4])),
+    run: Boolean(Number(found[5]))
   };
 }
 
@@ -46,15 +53,22 @@ export function testPerm(perms: DenoPermissions, fn: testing.TestFunction) {
 }
 
 export function test(fn: testing.TestFunction) {
-  testPerm({ write: false, net: false, env: false }, fn);
+  testPerm(
+    { read: false, write: false, net: false, env: false, run: false },
+    fn
+  );
 }
 
 test(function permSerialization() {
-  for (const write of [true, false]) {
-    for (const net of [true, false]) {
-      for (const env of [true, false]) {
-        const perms: DenoPermissions = { write, net, env };
-        testing.assertEqual(perms, permFromString(permToString(perms)));
+  for (const read of [true, false]) {
+    for (const write of [true, false]) {
+      for (const net of [true, false]) {
+        for (const env of [true, false]) {
+          for (const run of [true, false]) {
+            const perms: DenoPermissions = { read, write, net, env, run };
+            testing.assertEqual(perms, permFromString(permToString(perms)));
+          }
+        }
       }
     }
   }
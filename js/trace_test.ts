@@ -35,7 +35,7 @@ testPerm({ write: true }, async function tracePromiseSuccess() {
   assertEqual(op[2], { sync: true, name: "Remove" });
 });
 
-testPerm({ write: true }, async function traceRepeatSuccess() {
+testPerm({ read: true, write: true }, async function traceRepeatSuccess() {
   const op1 = await deno.trace(async () => await deno.makeTempDir());
   assertEqual(op1.length, 1);
   assertEqual(op1[0], { sync: false, name: "MakeTempDir" });
@@ -44,7 +44,7 @@ testPerm({ write: true }, async function traceRepeatSuccess() {
   assertEqual(op2[0], { sync: true, name: "Stat" });
 });
 
-testPerm({ write: true }, async function traceIdempotence() {
+testPerm({ read: true, write: true }, async function traceIdempotence() {
   let op1, op2, op3;
   op1 = await deno.trace(async () => {
     const filename = (await deno.makeTempDir()) + "/test.txt";
@@ -5,7 +5,8 @@ import * as dispatch from "./dispatch";
 
 /**
  * Truncates or extends the specified file synchronously,
- * updating the size of this file to become size.
+ * updating the size of this file to become size. Extending fills the file
+ * with zeros.
  *
  *     import { truncateSync } from "deno";
  *
@@ -27,9 +28,22 @@ export async function truncate(name: string, len?: number): Promise<void> {
   await dispatch.sendAsync(...req(name, len));
 }
 
+/**
+ * Truncates or extends the file with the given rid, which must have been
+ * opened for writing.
+ *
+ *     import { ftruncate } from "deno";
+ *
+ *     await ftruncate(file.fd, 10);
+ */
+export async function ftruncate(rid: number, len?: number): Promise<void> {
+  await dispatch.sendAsync(...req("", len, rid));
+}
+
 function req(
   name: string,
-  len?: number
+  len?: number,
+  rid = -1
 ): [flatbuffers.Builder, msg.Any, flatbuffers.Offset] {
   const builder = new flatbuffers.Builder();
   const name_ = builder.createString(name);
@@ -37,6 +51,7 @@ function req(
   msg.Truncate.startTruncate(builder);
   msg.Truncate.addName(builder, name_);
   msg.Truncate.addLen(builder, len);
+  msg.Truncate.addRid(builder, rid);
   const inner = msg.Truncate.endTruncate(builder);
   return [builder, msg.Any.Truncate, inner];
 }
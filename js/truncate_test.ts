@@ -16,7 +16,7 @@ async function readData(name: string): Promise<string> {
   return text;
 }
 
-testPerm({ write: true }, function truncateSyncSuccess() {
+testPerm({ read: true, write: true }, function truncateSyncSuccess() {
   const enc = new TextEncoder();
   const d = enc.encode("Hello");
   const filename = deno.makeTempDirSync() + "/test_truncateSync.txt";
@@ -33,7 +33,7 @@ testPerm({ write: true }, function truncateSyncSuccess() {
   deno.removeSync(filename);
 });
 
-testPerm({ write: true }, async function truncateSuccess() {
+testPerm({ read: true, write: true }, async function truncateSuccess() {
   const enc = new TextEncoder();
   const d = enc.encode("Hello");
   const filename = deno.makeTempDirSync() + "/test_truncate.txt";
@@ -50,10 +50,42 @@ testPerm({ write: true }, async function truncateSuccess() {
   await deno.remove(filename);
 });
 
+testPerm({ read: true, write: true }, function truncateSyncZeroFill() {
+  const enc = new TextEncoder();
+  const filename = deno.makeTempDirSync() + "/test_truncateSync.txt";
+  deno.writeFileSync(filename, enc.encode("Hello"));
+  deno.truncateSync(filename, 8);
+  assertEqual(readDataSync(filename), "Hello\0\0\0");
+  deno.truncateSync(filename, 2);
+  assertEqual(readDataSync(filename), "He");
+});
+
+testPerm({ write: true }, function truncateSyncNotFound() {
+  let err;
+  try {
+    deno.truncateSync("bad_filename", 2);
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.NotFound);
+  assertEqual(err.name, "NotFound");
+});
+
+testPerm({ write: true }, async function ftruncateBadResource() {
+  let err;
+  try {
+    await deno.ftruncate(123456, 2);
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.BadFileDescriptor);
+  assertEqual(err.name, "BadFileDescriptor");
+});
+
 testPerm({ write: false }, function truncateSyncPerm() {
   let err;
   try {
-    deno.mkdirSync("/test_truncateSyncPermission.txt");
+    deno.truncateSync("/test_truncateSyncPermission.txt");
   } catch (e) {
     err = e;
   }
@@ -64,7 +96,7 @@ testPerm({ write: false }, function truncateSyncPerm() {
 testPerm({ write: false }, async function truncatePerm() {
   let err;
   try {
-    await deno.mkdir("/test_truncatePermission.txt");
+    await deno.truncate("/test_truncatePermission.txt");
   } catch (e) {
     err = e;
   }
@@ -1,27 +1,45 @@
 // Copyright 2018 the Deno authors. All rights reserved. MIT license.
 // This test is executed as part of tools/test.py
 // But it can also be run manually: ./out/debug/deno js/unit_tests.ts
+import "./codec_test.ts";
 import "./compiler_test.ts";
 import "./console_test.ts";
+import "./console_size_test.ts";
+import "./disk_usage_test.ts";
 import "./fetch_test.ts";
 import "./os_test.ts";
 import "./files_test.ts";
 import "./read_file_test.ts";
+import "./read_lines_test.ts";
 import "./read_dir_test.ts";
 import "./write_file_test.ts";
 import "./copy_file_test.ts";
+import "./chmod_test.ts";
 import "./mkdir_test.ts";
 import "./make_temp_dir_test.ts";
+import "./make_temp_file_test.ts";
 import "./stat_test.ts";
 import "./rename_test.ts";
 import "./read_link_test.ts";
+import "./realpath_test.ts";
 import "./blob_test.ts";
 import "./timers_test.ts";
 import "./symlink_test.ts";
+import "./link_test.ts";
+import "./watch_fs_test.ts";
 import "./platform_test.ts";
 import "./text_encoding_test.ts";
 import "./net_test.ts";
 import "./trace_test.ts";
 import "./truncate_test.ts";
+import "./utime_test.ts";
+import "./wasm_test.ts";
+import "./hash_test.ts";
+import "./inspect_test.ts";
+import "./process_test.ts";
+import "./status_test.ts";
 import "./v8_source_maps_test.ts";
+import "./version_test.ts";
+import "./performance_test.ts";
+import "./format_error_test.ts";
 import "../website/app_test.js";
@@ -1,4 +1,5 @@
 // Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { flatbuffers } from "flatbuffers";
 import { TypedArray } from "./types";
 
 let logDebug = false;
@@ -94,6 +95,12 @@ export function hexdump(u8: Uint8Array): string {
     .join(" ");
 }
 
+// Converts a safe integer to the 64 bit integer flatbuffers uses for longs.
+// @internal
+export function toLong(n: number): flatbuffers.Long {
+  return flatbuffers.Long.create(n | 0, Math.floor(n / 0x100000000));
+}
+
 // @internal
 export function containsOnlyASCII(str: string): boolean {
   if (typeof str !== "string") {
@@ -0,0 +1,64 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import * as msg from "gen/msg_generated";
+import { flatbuffers } from "flatbuffers";
+import * as dispatch from "./dispatch";
+import { toLong } from "./util";
+
+/**
+ * Changes the access and modification times of a file system object
+ * synchronously. Times are either Dates or seconds since the Unix epoch,
+ * and are set to the microsecond.
+ *
+ *     import { utimeSync } from "deno";
+ *     utimeSync("myfile.txt", 1556495550, new Date());
+ */
+export function utimeSync(
+  filename: string,
+  atime: number | Date,
+  mtime: number | Date
+): void {
+  dispatch.sendSync(...req(filename, atime, mtime));
+}
+
+/**
+ * Changes the access and modification times of a file system object.
+ * Times are either Dates or seconds since the Unix epoch, and are set to the
+ * microsecond.
+ *
+ *     import { utime } from "deno";
+ *     await utime("myfile.txt", 1556495550, new Date());
+ */
+export async function utime(
+  filename: string,
+  atime: number | Date,
+  mtime: number | Date
+): Promise<void> {
+  await dispatch.sendAsync(...req(filename, atime, mtime));
+}
+
+// Splits a time into whole seconds and nanoseconds.
+function toSecNsec(time: number | Date): [number, number] {
+  const seconds = typeof time === "number" ? time : time.getTime() / 1000;
+  const sec = Math.floor(seconds);
+  const nsec = Math.min(Math.round((seconds - sec) * 1e9), 1e9 - 1);
+  return [sec, nsec];
+}
+
+function req(
+  filename: string,
+  atime: number | Date,
+  mtime: number | Date
+): [flatbuffers.Builder, msg.Any, flatbuffers.Offset] {
+  const [atimeSec, atimeNsec] = toSecNsec(atime);
+  const [mtimeSec, mtimeNsec] = toSecNsec(mtime);
+  const builder = new flatbuffers.Builder();
+  const filename_ = builder.createString(filename);
+  msg.Utime.startUtime(builder);
+  msg.Utime.addPath(builder, filename_);
+  msg.Utime.addAtimeSec(builder, toLong(atimeSec));
+  msg.Utime.addAtimeNsec(builder, atimeNsec);
+  msg.Utime.addMtimeSec(builder, toLong(mtimeSec));
+  msg.Utime.addMtimeNsec(builder, mtimeNsec);
+  const inner = msg.Utime.endUtime(builder);
+  return [builder, msg.Any.Utime, inner];
+}
@@ -0,0 +1,56 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { testPerm, assert, assertEqual } from "./test_util.ts";
+import * as deno from "deno";
+
+testPerm({ read: true, write: true }, function utimeSyncSuccess() {
+  const filename = deno.makeTempDirSync() + "/test.txt";
+  deno.writeFileSync(filename, new TextEncoder().encode("hello"));
+  const atime = 1000000000;
+  const mtime = new Date(1500000000 * 1000);
+  deno.utimeSync(filename, atime, mtime);
+  const fileInfo = deno.statSync(filename);
+  assertEqual(fileInfo.accessed, atime);
+  assertEqual(fileInfo.modified, 1500000000);
+});
+
+testPerm({ write: true }, function utimeSyncNotFound() {
+  let err;
+  try {
+    deno.utimeSync("/baddir/badfile.txt", 0, 0);
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.NotFound);
+  assertEqual(err.name, "NotFound");
+});
+
+testPerm({ write: false }, function utimeSyncPerm() {
+  let err;
+  try {
+    deno.utimeSync("/some_pretend_file.txt", 0, 0);
+  } catch (e) {
+    err = e;
+  }
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
+
+testPerm({ read: true, write: true }, async function utimeSuccess() {
+  const filename = deno.makeTempDirSync() + "/test.txt";
+  deno.writeFileSync(filename, new TextEncoder().encode("hello"));
+  await deno.utime(filename, 1234567890.5, 1234567890);
+  const fileInfo = deno.statSync(filename);
+  assertEqual(fileInfo.accessed, 1234567890);
+  assertEqual(fileInfo.modified, 1234567890);
+});
+
+testPerm({ write: true }, async function utimeNotFound() {
+  let err;
+  try {
+    await deno.utime("/baddir/badfile.txt", 0, 0);
+  } catch (e) {
+    err = e;
+  }
+  assert(!!err);
+  assertEqual(err.kind, deno.ErrorKind.NotFound);
+});
@@ -0,0 +1,26 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+
+interface Version {
+  deno: string;
+  v8: string;
+}
+
+/**
+ * The versions of deno and of the V8 it runs on, for bug reports and feature
+ * detection.
+ *
+ *     import { version } from "deno";
+ *     console.log(version.deno, version.v8);
+ */
+export const version: Version = {
+  deno: "",
+  v8: ""
+};
+
+// Filled in from the start message, after which version can't change.
+// @internal
+export function setVersions(denoVersion: string, v8Version: string): void {
+  version.deno = denoVersion;
+  version.v8 = v8Version;
+  Object.freeze(version);
+}
@@ -0,0 +1,16 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { test, assert } from "./test_util.ts";
+import * as deno from "deno";
+
+function isDottedVersion(version: string): boolean {
+  return /^\d+\.\d+\.\d+/.test(version);
+}
+
+test(function versionDotted() {
+  assert(isDottedVersion(deno.version.deno));
+  assert(isDottedVersion(deno.version.v8));
+});
+
+test(function versionFrozen() {
+  assert(Object.isFrozen(deno.version));
+});
@@ -0,0 +1,20 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import * as msg from "gen/msg_generated";
+import { flatbuffers } from "flatbuffers";
+import { sendSync } from "./dispatch";
+
+/**
+ * Validates and compiles the WebAssembly module in bytes. Throws a DenoError
+ * of kind InvalidWasm describing the problem if the module is invalid. No
+ * permissions are needed, since a module can only do what its imports allow.
+ *
+ *     import { compileWasm } from "deno";
+ *     compileWasm(bytes);
+ *     const m = new WebAssembly.Module(bytes);
+ */
+export function compileWasm(bytes: Uint8Array): void {
+  const builder = new flatbuffers.Builder();
+  msg.CompileWasm.startCompileWasm(builder);
+  const inner = msg.CompileWasm.endCompileWasm(builder);
+  sendSync(builder, msg.Any.CompileWasm, inner, bytes);
+}
@@ -0,0 +1,30 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { test, assert, assertEqual } from "./test_util.ts";
+import * as deno from "deno";
+
+// (module (func (export "add") (param i32 i32) (result i32)
+//   get_local 0 get_local 1 i32.add))
+// prettier-ignore
+const addWasm = new Uint8Array([
+  0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+  0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f,
+  0x03, 0x02, 0x01, 0x00,
+  0x07, 0x07, 0x01, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00,
+  0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b
+]);
+
+test(function compileWasmSuccess() {
+  deno.compileWasm(addWasm);
+});
+
+test(function compileWasmInvalid() {
+  let caughtError = false;
+  try {
+    deno.compileWasm(new Uint8Array([0x00, 0x61, 0x73, 0x6d, 0xff]));
+  } catch (e) {
+    caughtError = true;
+    assertEqual(e.kind, deno.ErrorKind.InvalidWasm);
+    assertEqual(e.name, "InvalidWasm");
+  }
+  assert(caughtError);
+});
@@ -0,0 +1,123 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import * as msg from "gen/msg_generated";
+import { flatbuffers } from "flatbuffers";
+import { assert } from "./util";
+import * as dispatch from "./dispatch";
+import { close } from "./files";
+
+/** A change to a watched path. */
+export interface FsEvent {
+  kind: "create" | "modify" | "remove";
+  path: string;
+}
+
+/** Paths being watched with watchFs(). */
+export class FsWatcher {
+  // The batches of events received that next() hasn't returned yet.
+  private readonly batches: FsEvent[][] = [];
+  private polling = false;
+  private done = false;
+  private error?: Error;
+  private wakeup?: () => void;
+
+  constructor(readonly rid: number) {}
+
+  /**
+   * Resolves to the changes since the last call, waiting for there to be
+   * some, or to null once the watcher has been closed. Changes seen together
+   * are returned together.
+   */
+  async next(): Promise<FsEvent[] | null> {
+    this.poll();
+    while (this.batches.length === 0 && !this.done) {
+      await new Promise(resolve => (this.wakeup = resolve));
+    }
+    if (this.batches.length > 0) {
+      return this.batches.shift()!;
+    }
+    if (this.error) {
+      throw this.error;
+    }
+    return null;
+  }
+
+  close(): void {
+    close(this.rid);
+  }
+
+  // Starts receiving the events, which are streamed as chunks until the
+  // watcher is closed.
+  private poll(): void {
+    if (this.polling) {
+      return;
+    }
+    this.polling = true;
+    const builder = new flatbuffers.Builder();
+    msg.FsEventsPoll.startFsEventsPoll(builder);
+    msg.FsEventsPoll.addRid(builder, this.rid);
+    const inner = msg.FsEventsPoll.endFsEventsPoll(builder);
+    dispatch
+      .sendAsyncChunked(builder, msg.Any.FsEventsPoll, inner, data => {
+        this.batches.push(decodeEvents(data));
+        this.wake();
+      })
+      .then(() => this.finish(), (err: Error) => this.finish(err));
+  }
+
+  private finish(err?: Error): void {
+    this.done = true;
+    this.error = err;
+    this.wake();
+  }
+
+  private wake(): void {
+    const wakeup = this.wakeup;
+    this.wakeup = undefined;
+    if (wakeup) {
+      wakeup();
+    }
+  }
+}
+
+function decodeEvents(data: Uint8Array): FsEvent[] {
+  const bb = new flatbuffers.ByteBuffer(data);
+  const res = msg.FsEventsPollRes.getRootAsFsEventsPollRes(bb);
+  const events: FsEvent[] = [];
+  for (let i = 0; i < res.eventsLength(); i++) {
+    const event = res.events(i)!;
+    events.push({
+      kind: event.kind() as FsEvent["kind"],
+      path: event.path()!
+    });
+  }
+  return events;
+}
+
+/**
+ * Watch paths for files and directories being created, modified and removed.
+ * Directories are watched recursively. A watched path that doesn't exist, or
+ * is removed, is watched for being created. Requires read permission.
+ *
+ *     import { watchFs } from "deno";
+ *     const watcher = watchFs("src");
+ *     let events;
+ *     while ((events = await watcher.next()) !== null) {
+ *       console.log(events);
+ *     }
+ */
+export function watchFs(paths: string | string[]): FsWatcher {
+  const builder = new flatbuffers.Builder();
+  const paths_ = (typeof paths === "string" ? [paths] : paths).map(path =>
+    builder.createString(path)
+  );
+  const pathsVector = msg.FsEvents.createPathsVector(builder, paths_);
+  msg.FsEvents.startFsEvents(builder);
+  msg.FsEvents.addPaths(builder, pathsVector);
+  const inner = msg.FsEvents.endFsEvents(builder);
+  const baseRes = dispatch.sendSync(builder, msg.Any.FsEvents, inner);
+  assert(baseRes != null);
+  assert(msg.Any.FsEventsRes === baseRes!.innerType());
+  const res = new msg.FsEventsRes();
+  assert(baseRes!.inner(res) != null);
+  return new FsWatcher(res.rid());
+}
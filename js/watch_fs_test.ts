@@ -0,0 +1,28 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+import { testPerm, assert, assertEqual } from "./test_util.ts";
+import * as deno from "deno";
+
+testPerm({ read: true, write: true }, async function watchFsCreate() {
+  const dir = deno.makeTempDirSync();
+  const watcher = deno.watchFs(dir);
+  const filename = dir + "/created.txt";
+  deno.writeFileSync(filename, new Uint8Array([1, 2, 3]));
+  const events = await watcher.next();
+  assert(events != null);
+  assertEqual(events![0], { kind: "create", path: filename });
+  watcher.close();
+  // Resolves to null once the watcher has been closed.
+  while ((await watcher.next()) !== null) {}
+});
+
+testPerm({ read: false }, function watchFsPerm() {
+  let err;
+  try {
+    deno.watchFs(".");
+  } catch (e) {
+    err = e;
+  }
+  assert(!!err);
+  assertEqual(err.kind, deno.ErrorKind.PermissionDenied);
+  assertEqual(err.name, "PermissionDenied");
+});
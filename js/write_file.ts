@@ -3,6 +3,18 @@ import * as msg from "gen/msg_generated";
 import { flatbuffers } from "flatbuffers";
 import * as dispatch from "./dispatch";
 
+export interface WriteFileOptions {
+  /** Create the file if it doesn't exist. Defaults to true. */
+  create?: boolean;
+  /** Add data to the end of the file instead of replacing its contents. */
+  append?: boolean;
+  /**
+   * Write to a temporary file and rename it into place, so the file is either
+   * replaced completely or not at all. Can't be combined with append.
+   */
+  atomic?: boolean;
+}
+
 /**
  * Write a new file, with given filename and data synchronously.
  *
@@ -15,9 +27,10 @@ import * as dispatch from "./dispatch";
 export function writeFileSync(
   filename: string,
   data: Uint8Array,
-  perm = 0o666
+  perm = 0o666,
+  options: WriteFileOptions = {}
 ): void {
-  dispatch.sendSync(...req(filename, data, perm));
+  dispatch.sendSync(...req(filename, data, perm, options));
 }
 
 /**
@@ -32,21 +45,26 @@ export function writeFileSync(
 export async function writeFile(
   filename: string,
   data: Uint8Array,
-  perm = 0o666
+  perm = 0o666,
+  options: WriteFileOptions = {}
 ): Promise<void> {
-  await dispatch.sendAsync(...req(filename, data, perm));
+  await dispatch.sendAsync(...req(filename, data, perm, options));
 }
 
 function req(
   filename: string,
   data: Uint8Array,
-  perm: number
+  perm: number,
+  options: WriteFileOptions
 ): [flatbuffers.Builder, msg.Any, flatbuffers.Offset, Uint8Array] {
   const builder = new flatbuffers.Builder();
   const filename_ = builder.createString(filename);
   msg.WriteFile.startWriteFile(builder);
   msg.WriteFile.addFilename(builder, filename_);
   msg.WriteFile.addPerm(builder, perm);
+  msg.WriteFile.addCreate(builder, options.create !== false);
+  msg.WriteFile.addAppend(builder, !!options.append);
+  msg.WriteFile.addAtomic(builder, !!options.atomic);
   const inner = msg.WriteFile.endWriteFile(builder);
   return [builder, msg.Any.WriteFile, inner, data];
 }
@@ -2,7 +2,7 @@
 import { testPerm, assert, assertEqual } from "./test_util.ts";
 import * as deno from "deno";
 
-testPerm({ write: true }, function writeFileSyncSuccess() {
+testPerm({ read: true, write: true }, function writeFileSyncSuccess() {
   const enc = new TextEncoder();
   const data = enc.encode("Hello");
   const filename = deno.makeTempDirSync() + "/test.txt";
@@ -45,7 +45,7 @@ testPerm({ write: false }, function writeFileSyncPerm() {
   assert(caughtError);
 });
 
-testPerm({ write: true }, async function writeFileSuccess() {
+testPerm({ read: true, write: true }, async function writeFileSuccess() {
   const enc = new TextEncoder();
   const data = enc.encode("Hello");
   const filename = deno.makeTempDirSync() + "/test.txt";
@@ -87,3 +87,39 @@ testPerm({ write: false }, async function writeFilePerm() {
   }
   assert(caughtError);
 });
+
+testPerm({ read: true, write: true }, function writeFileSyncAppend() {
+  const enc = new TextEncoder();
+  const dec = new TextDecoder("utf-8");
+  const filename = deno.makeTempDirSync() + "/test.txt";
+  deno.writeFileSync(filename, enc.encode("Hello"));
+  deno.writeFileSync(filename, enc.encode(" world"), 0o666, { append: true });
+  assertEqual(dec.decode(deno.readFileSync(filename)), "Hello world");
+});
+
+testPerm({ read: true, write: true }, async function writeFileAtomic() {
+  const enc = new TextEncoder();
+  const dec = new TextDecoder("utf-8");
+  const dir = deno.makeTempDirSync();
+  const filename = dir + "/test.txt";
+  deno.writeFileSync(filename, enc.encode("old"));
+  await deno.writeFile(filename, enc.encode("new"), 0o666, { atomic: true });
+  assertEqual(dec.decode(deno.readFileSync(filename)), "new");
+  const names = deno.readDirSync(dir).map(info => info.name);
+  assertEqual(names, ["test.txt"]);
+});
+
+testPerm({ write: true }, function writeFileSyncNoCreate() {
+  const enc = new TextEncoder();
+  const filename = deno.makeTempDirSync() + "/test.txt";
+  let caughtError = false;
+  try {
+    deno.writeFileSync(filename, enc.encode("Hello"), 0o666, {
+      create: false
+    });
+  } catch (e) {
+    caughtError = true;
+    assertEqual(e.kind, deno.ErrorKind.NotFound);
+  }
+  assert(caughtError);
+});
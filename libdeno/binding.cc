@@ -2,8 +2,15 @@
 #include <stdio.h>
 #include <stdlib.h>
 #include <string.h>
+#include <condition_variable>
+#include <deque>
 #include <iostream>
+#include <limits>
+#include <memory>
+#include <mutex>
 #include <string>
+#include <thread>
+#include <utility>
 
 #include "third_party/v8/include/libplatform/libplatform.h"
 #include "third_party/v8/include/v8.h"
@@ -146,7 +153,35 @@ void ExitOnPromiseRejectCallback(
   v8::HandleScope handle_scope(d->isolate);
   auto exception = promise_reject_message.GetValue();
   auto context = d->context.Get(d->isolate);
-  HandleException(context, exception);
+  if (!d->collect_rejections) {
+    HandleException(context, exception);
+    return;
+  }
+  // A handler may still be added later, so just keep track of the promise.
+  if (d->pending_rejections.IsEmpty()) {
+    d->pending_rejections.Reset(isolate, v8::Map::New(isolate));
+  }
+  auto promise = promise_reject_message.GetPromise();
+  auto pending_rejections = d->pending_rejections.Get(isolate);
+  switch (promise_reject_message.GetEvent()) {
+    case v8::kPromiseRejectWithNoHandler: {
+      v8::Local<v8::String> exception_str;
+      if (!exception->ToString(context).ToLocal(&exception_str)) {
+        exception_str = v8::String::NewFromUtf8(isolate, "<unknown>");
+      }
+      auto entry = v8::Array::New(isolate, 2);
+      CHECK(entry->Set(context, 0, exception_str).FromJust());
+      auto req_id_v = v8::Integer::New(isolate, d->responding_req_id);
+      CHECK(entry->Set(context, 1, req_id_v).FromJust());
+      CHECK(!pending_rejections->Set(context, promise, entry).IsEmpty());
+      break;
+    }
+    case v8::kPromiseHandlerAddedAfterReject:
+      CHECK(pending_rejections->Delete(context, promise).IsJust());
+      break;
+    default:
+      break;
+  }
 }
 
 void Print(const v8::FunctionCallbackInfo<v8::Value>& args) {
@@ -160,6 +195,10 @@ void Print(const v8::FunctionCallbackInfo<v8::Value>& args) {
   bool is_err =
       args.Length() >= 2 ? args[1]->BooleanValue(context).ToChecked() : false;
   const char* cstr = ToCString(str);
+  if (d->print_cb != nullptr) {
+    d->print_cb(d->user_data, cstr, is_err ? 1 : 0);
+    return;
+  }
   auto& stream = is_err ? std::cerr : std::cout;
   stream << cstr << std::endl;
 }
@@ -223,6 +262,9 @@ void Send(const v8::FunctionCallbackInfo<v8::Value>& args) {
   v8::EscapableHandleScope handle_scope(isolate);
 
   CHECK_EQ(d->currentArgs, nullptr);  // libdeno.send re-entry forbidden.
+  // Negative req_ids are reserved for responses which aren't for a send(),
+  // like the timer response. Don't let the counter wrap around into them.
+  CHECK_LT(d->next_req_id, std::numeric_limits<int32_t>::max());
   int32_t req_id = d->next_req_id++;
 
   v8::Local<v8::Value> control_v = args[0];
@@ -248,8 +290,9 @@ void Send(const v8::FunctionCallbackInfo<v8::Value>& args) {
   if (d->currentArgs == nullptr) {
     // This indicates that deno_repond() was called already.
   } else {
-    // Asynchronous.
+    // Asynchronous. JS gets the req_id to match it with the response.
     d->currentArgs = nullptr;
+    args.GetReturnValue().Set(v8::Integer::New(isolate, req_id));
     // If the data ArrayBuffer was given, we must maintain a strong reference
     // to it until deno_respond is called.
     if (!data_v.IsEmpty()) {
@@ -313,6 +356,127 @@ bool ExecuteV8StringSource(v8::Local<v8::Context> context,
   return true;
 }
 
+// Passes chunks from the thread that reads them from a deno_chunk_cb to V8's
+// streaming compiler, which calls GetMoreData() on the thread doing the
+// compilation and waits there for the next chunk. The whole source is kept
+// as well, because V8 needs it again once compilation is done.
+class ChunkStream : public v8::ScriptCompiler::ExternalSourceStream {
+ public:
+  ~ChunkStream() override {
+    for (auto& chunk : queue_) {
+      delete[] chunk.first;
+    }
+  }
+
+  // Reads chunks from cb until the end of the source, on the calling thread.
+  void ReadAll(deno_chunk_cb cb, void* cb_data) {
+    for (;;) {
+      const uint8_t* chunk = nullptr;
+      size_t len = cb(cb_data, &chunk);
+      if (len == DENO_CHUNK_ERROR) {
+        // Ends the stream. What was read so far must not be run.
+        failed_ = true;
+        len = 0;
+      }
+      Push(chunk, len);
+      if (len == 0) {
+        return;
+      }
+    }
+  }
+
+  size_t GetMoreData(const uint8_t** src) override {
+    std::unique_lock<std::mutex> lock(mutex_);
+    ready_.wait(lock, [this] { return !queue_.empty(); });
+    auto chunk = queue_.front();
+    queue_.pop_front();
+    // V8 takes ownership of the chunk and frees it with delete[].
+    *src = chunk.first;
+    return chunk.second;
+  }
+
+  // Only valid once ReadAll() has returned.
+  const std::string& source() const { return source_; }
+  bool failed() const { return failed_; }
+
+ private:
+  // An empty chunk ends the stream.
+  void Push(const uint8_t* chunk, size_t len) {
+    uint8_t* copy = nullptr;
+    if (len > 0) {
+      copy = new uint8_t[len];
+      memcpy(copy, chunk, len);
+      source_.append(reinterpret_cast<const char*>(chunk), len);
+    }
+    std::lock_guard<std::mutex> lock(mutex_);
+    queue_.push_back(std::make_pair(copy, len));
+    ready_.notify_one();
+  }
+
+  std::mutex mutex_;
+  std::condition_variable ready_;
+  std::deque<std::pair<uint8_t*, size_t>> queue_;
+  std::string source_;
+  bool failed_ = false;
+};
+
+bool ExecuteStreaming(v8::Local<v8::Context> context, const char* js_filename,
+                      deno_chunk_cb cb, void* cb_data) {
+  auto* isolate = context->GetIsolate();
+  v8::Isolate::Scope isolate_scope(isolate);
+  v8::HandleScope handle_scope(isolate);
+
+  v8::Context::Scope context_scope(context);
+
+  v8::TryCatch try_catch(isolate);
+
+  // StreamedSource owns the stream.
+  auto* stream = new ChunkStream();
+  v8::ScriptCompiler::StreamedSource streamed_source(
+      stream, v8::ScriptCompiler::StreamedSource::UTF8);
+  std::unique_ptr<v8::ScriptCompiler::ScriptStreamingTask> task(
+      v8::ScriptCompiler::StartStreamingScript(isolate, &streamed_source));
+  // The worker parses each chunk while this thread produces the next one.
+  // The task doesn't touch the isolate, so it doesn't need the lock.
+  std::thread worker([&task]() { task->Run(); });
+  stream->ReadAll(cb, cb_data);
+  worker.join();
+
+  if (stream->failed()) {
+    FromIsolate(isolate)->last_exception = "Reading the source failed.";
+    return false;
+  }
+
+  v8::Local<v8::String> source;
+  if (!v8::String::NewFromUtf8(isolate, stream->source().data(),
+                               v8::NewStringType::kNormal,
+                               static_cast<int>(stream->source().size()))
+           .ToLocal(&source)) {
+    FromIsolate(isolate)->last_exception = "Source is too long.";
+    return false;
+  }
+
+  v8::ScriptOrigin origin(v8_str(js_filename));
+  auto script = v8::ScriptCompiler::Compile(context, &streamed_source, source,
+                                            origin);
+
+  if (script.IsEmpty()) {
+    DCHECK(try_catch.HasCaught());
+    HandleException(context, try_catch.Exception());
+    return false;
+  }
+
+  auto result = script.ToLocalChecked()->Run(context);
+
+  if (result.IsEmpty()) {
+    DCHECK(try_catch.HasCaught());
+    HandleException(context, try_catch.Exception());
+    return false;
+  }
+
+  return true;
+}
+
 bool Execute(v8::Local<v8::Context> context, const char* js_filename,
              const char* js_source) {
   auto* isolate = context->GetIsolate();
@@ -390,6 +554,10 @@ void InitializeContext(v8::Isolate* isolate, v8::Local<v8::Context> context,
 
 void AddIsolate(Deno* d, v8::Isolate* isolate) {
   d->next_req_id = 0;
+  d->print_cb = nullptr;
+  d->gc_cb = nullptr;
+  d->collect_rejections = false;
+  d->responding_req_id = -1;
   d->isolate = isolate;
   // Leaving this code here because it will probably be useful later on, but
   // disabling it now as I haven't got tests for the desired behavior.
@@ -419,6 +587,23 @@ class UserDataScope {
   }
 };
 
+// Set by deno_init(). V8 measures idle deadlines with its clock.
+static v8::Platform* platform = nullptr;
+
+void GCEpilogue(v8::Isolate* isolate, v8::GCType type,
+                v8::GCCallbackFlags flags, void* data) {
+  Deno* d = static_cast<Deno*>(data);
+  d->gc_cb(d->user_data, type == v8::kGCTypeMarkSweepCompact ? 1 : 0);
+}
+
+void SetHeapLimit(v8::Isolate::CreateParams* params, deno_config config) {
+  if (config.heap_limit != 0) {
+    // V8 limits the old generation, in megabytes. The young generation comes
+    // on top of that.
+    params->constraints.set_max_old_space_size(config.heap_limit >> 20);
+  }
+}
+
 }  // namespace deno
 
 extern "C" {
@@ -427,6 +612,7 @@ void deno_init() {
   // v8::V8::InitializeICUDefaultLocation(argv[0]);
   // v8::V8::InitializeExternalStartupData(argv[0]);
   auto* p = v8::platform::CreateDefaultPlatform();
+  deno::platform = p;
   v8::V8::InitializePlatform(p);
   v8::V8::Initialize();
 }
@@ -450,11 +636,45 @@ int deno_execute(Deno* d, void* user_data, const char* js_filename,
   return deno::Execute(context, js_filename, js_source) ? 1 : 0;
 }
 
+int deno_execute_utf16(Deno* d, void* user_data, const char* js_filename,
+                       const uint16_t* js_source, int js_source_len) {
+  deno::UserDataScope user_data_scope(d, user_data);
+  auto* isolate = d->isolate;
+  v8::Locker locker(isolate);
+  v8::Isolate::Scope isolate_scope(isolate);
+  v8::HandleScope handle_scope(isolate);
+  auto context = d->context.Get(d->isolate);
+  // V8 stores two-byte strings natively, so no conversion is needed.
+  v8::Local<v8::String> source;
+  if (!v8::String::NewFromTwoByte(isolate, js_source,
+                                  v8::NewStringType::kNormal, js_source_len)
+           .ToLocal(&source)) {
+    d->last_exception = "Source is too long.";
+    return 0;
+  }
+  return deno::ExecuteV8StringSource(context, js_filename, source) ? 1 : 0;
+}
+
+int deno_execute_streaming(Deno* d, void* user_data, const char* js_filename,
+                           deno_chunk_cb cb, void* cb_data) {
+  deno::UserDataScope user_data_scope(d, user_data);
+  auto* isolate = d->isolate;
+  v8::Locker locker(isolate);
+  v8::Isolate::Scope isolate_scope(isolate);
+  v8::HandleScope handle_scope(isolate);
+  auto context = d->context.Get(d->isolate);
+  return deno::ExecuteStreaming(context, js_filename, cb, cb_data) ? 1 : 0;
+}
+
 int deno_respond(Deno* d, void* user_data, int32_t req_id, deno_buf buf) {
   if (d->currentArgs != nullptr) {
     // Synchronous response.
-    auto ab = deno::ImportBuf(d->isolate, buf);
-    d->currentArgs->GetReturnValue().Set(ab);
+    if (buf.data_len == 0) {
+      d->currentArgs->GetReturnValue().SetNull();
+    } else {
+      auto ab = deno::ImportBuf(d->isolate, buf);
+      d->currentArgs->GetReturnValue().Set(ab);
+    }
     d->currentArgs = nullptr;
     return 0;
   }
@@ -478,9 +698,20 @@ int deno_respond(Deno* d, void* user_data, int32_t req_id, deno_buf buf) {
     return 1;
   }
 
-  v8::Local<v8::Value> args[1];
-  args[0] = deno::ImportBuf(d->isolate, buf);
-  recv->Call(context->Global(), 1, args);
+  v8::Local<v8::Value> args[2];
+  // An op that completed with no data gets null rather than an empty
+  // ArrayBuffer.
+  if (buf.data_len == 0) {
+    args[0] = v8::Null(d->isolate);
+  } else {
+    args[0] = deno::ImportBuf(d->isolate, buf);
+  }
+  args[1] = v8::Integer::New(d->isolate, req_id);
+  // Microtasks, like the .then() callbacks of the op's promise, run before
+  // Call() returns, so rejections they cause are attributed to req_id.
+  d->responding_req_id = req_id;
+  recv->Call(context->Global(), 2, args);
+  d->responding_req_id = -1;
 
   if (try_catch.HasCaught()) {
     deno::HandleException(context, try_catch.Exception());
@@ -490,6 +721,17 @@ int deno_respond(Deno* d, void* user_data, int32_t req_id, deno_buf buf) {
   return 0;
 }
 
+void deno_reject_send(Deno* d, const char* msg) {
+  CHECK_NE(d->currentArgs, nullptr);
+  d->isolate->ThrowException(v8::Exception::RangeError(deno::v8_str(msg)));
+  d->currentArgs = nullptr;
+}
+
+Deno* deno_new(deno_recv_cb cb) {
+  deno_config config = {0, {nullptr, 0, nullptr, 0}};
+  return deno_new_with_config(cb, config);
+}
+
 void deno_delete(Deno* d) {
   d->isolate->Dispose();
   delete d;
@@ -497,4 +739,112 @@ void deno_delete(Deno* d) {
 
 void deno_terminate_execution(Deno* d) { d->isolate->TerminateExecution(); }
 
+void deno_get_heap_stats(Deno* d, deno_heap_stats* stats) {
+  v8::Locker locker(d->isolate);
+  v8::HeapStatistics hs;
+  d->isolate->GetHeapStatistics(&hs);
+  stats->total_heap_size = hs.total_heap_size();
+  stats->used_heap_size = hs.used_heap_size();
+  stats->heap_size_limit = hs.heap_size_limit();
+}
+
+int deno_compile_wasm(Deno* d, const uint8_t* bytes, size_t bytes_len) {
+  auto* isolate = d->isolate;
+  v8::Locker locker(isolate);
+  v8::Isolate::Scope isolate_scope(isolate);
+  v8::HandleScope handle_scope(isolate);
+  auto context = d->context.Get(d->isolate);
+  v8::Context::Scope context_scope(context);
+
+  v8::TryCatch try_catch(isolate);
+
+  // Go through the WebAssembly.Module constructor so the module is validated
+  // and compiled exactly as it would be from JS.
+  auto wasm = context->Global()->Get(context, deno::v8_str("WebAssembly"));
+  v8::Local<v8::Value> wasm_val;
+  v8::Local<v8::Value> module_val;
+  if (!wasm.ToLocal(&wasm_val) || !wasm_val->IsObject() ||
+      !wasm_val.As<v8::Object>()
+           ->Get(context, deno::v8_str("Module"))
+           .ToLocal(&module_val) ||
+      !module_val->IsFunction()) {
+    d->last_exception = "WebAssembly is not available.";
+    return 0;
+  }
+
+  auto ab = v8::ArrayBuffer::New(isolate, bytes_len);
+  memcpy(ab->GetContents().Data(), bytes, bytes_len);
+  v8::Local<v8::Value> args[1];
+  args[0] = ab;
+  auto module = module_val.As<v8::Function>()->NewInstance(context, 1, args);
+  if (module.IsEmpty()) {
+    CHECK(try_catch.HasCaught());
+    v8::String::Utf8Value exception_str(isolate, try_catch.Exception());
+    d->last_exception = deno::ToCString(exception_str);
+    return 0;
+  }
+  return 1;
+}
+
+int deno_idle_notification(Deno* d, void* user_data, double idle_seconds) {
+  deno::UserDataScope user_data_scope(d, user_data);
+  v8::Locker locker(d->isolate);
+  v8::Isolate::Scope isolate_scope(d->isolate);
+  double deadline =
+      deno::platform->MonotonicallyIncreasingTime() + idle_seconds;
+  return d->isolate->IdleNotificationDeadline(deadline) ? 1 : 0;
+}
+
+void deno_set_gc_cb(Deno* d, deno_gc_cb cb) {
+  v8::Locker locker(d->isolate);
+  if (d->gc_cb != nullptr) {
+    d->isolate->RemoveGCEpilogueCallback(deno::GCEpilogue, d);
+  }
+  d->gc_cb = cb;
+  if (cb != nullptr) {
+    d->isolate->AddGCEpilogueCallback(deno::GCEpilogue, d);
+  }
+}
+
+void deno_set_print_cb(Deno* d, deno_print_cb cb) { d->print_cb = cb; }
+
+void deno_collect_rejections(Deno* d) { d->collect_rejections = true; }
+
+int deno_take_rejection(Deno* d, int32_t* req_id) {
+  if (d->pending_rejections.IsEmpty()) {
+    return 0;
+  }
+  v8::Locker locker(d->isolate);
+  v8::Isolate::Scope isolate_scope(d->isolate);
+  v8::HandleScope handle_scope(d->isolate);
+  auto context = d->context.Get(d->isolate);
+  v8::Context::Scope context_scope(context);
+
+  auto pending_rejections = d->pending_rejections.Get(d->isolate);
+  if (pending_rejections->Size() == 0) {
+    return 0;
+  }
+  // Keys and values alternate, in the order the promises were rejected.
+  auto entries = pending_rejections->AsArray();
+  auto promise = entries->Get(context, 0).ToLocalChecked();
+  auto entry = entries->Get(context, 1).ToLocalChecked().As<v8::Array>();
+  CHECK(pending_rejections->Delete(context, promise).FromJust());
+  v8::String::Utf8Value exception_str(d->isolate,
+                                      entry->Get(context, 0).ToLocalChecked());
+  d->last_exception = ToCString(exception_str);
+  *req_id = entry->Get(context, 1)
+                .ToLocalChecked()
+                ->Int32Value(context)
+                .FromJust();
+  return 1;
+}
+
+void deno_set_stack_limit(Deno* d, size_t stack_size) {
+  v8::Locker locker(d->isolate);
+  // V8 wants the lowest address the stack may grow down to.
+  uintptr_t here = reinterpret_cast<uintptr_t>(&here);
+  CHECK_GT(here, stack_size);
+  d->isolate->SetStackLimit(here - stack_size);
+}
+
 }  // extern "C"
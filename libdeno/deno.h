@@ -20,17 +20,53 @@ typedef struct {
 struct deno_s;
 typedef struct deno_s Deno;
 
+// Sizes in bytes, see deno_get_heap_stats().
+typedef struct {
+  size_t total_heap_size;
+  size_t used_heap_size;
+  size_t heap_size_limit;
+} deno_heap_stats;
+
+// Options for deno_new_with_config(). A zeroed config means the defaults that
+// deno_new() uses.
+typedef struct {
+  // Maximum size of the V8 heap in bytes, rounded down to whole megabytes, or
+  // 0 for V8's default.
+  size_t heap_limit;
+  // A startup snapshot to create the isolate from instead of the one built
+  // into libdeno. It must come from the same build of libdeno and stay valid
+  // until the isolate is deleted. Ignored if libdeno loads the JS bundle from
+  // the file system instead of a snapshot.
+  deno_buf snapshot;
+} deno_config;
+
 // A callback to receive a message from a libdeno.send() javascript call.
 // control_buf is valid for only for the lifetime of this callback.
 // data_buf is valid until deno_respond() is called.
 typedef void (*deno_recv_cb)(void* user_data, int32_t req_id,
                              deno_buf control_buf, deno_buf data_buf);
 
+// A callback to receive the output of libdeno.print(). msg is valid only for
+// the lifetime of this callback. is_err is 1 when printing to stderr.
+typedef void (*deno_print_cb)(void* user_data, const char* msg, int is_err);
+
+// A callback called after each garbage collection. full is 1 for a
+// collection of the whole heap, and 0 for one of the young generation only.
+typedef void (*deno_gc_cb)(void* user_data, int full);
+
+// Supplies the next chunk of a script for deno_execute_streaming(). Sets
+// *chunk and returns its length, which must stay valid until the next call.
+// Returns 0 at the end of the script, or DENO_CHUNK_ERROR if the source can't
+// be read, which fails the execution. Called from another thread.
+typedef size_t (*deno_chunk_cb)(void* cb_data, const uint8_t** chunk);
+#define DENO_CHUNK_ERROR ((size_t)-1)
+
 void deno_init();
 const char* deno_v8_version();
 void deno_set_v8_flags(int* argc, char** argv);
 
 Deno* deno_new(deno_recv_cb cb);
+Deno* deno_new_with_config(deno_recv_cb cb, deno_config config);
 void deno_delete(Deno* d);
 
 // Returns false on error.
@@ -39,14 +75,29 @@ void deno_delete(Deno* d);
 int deno_execute(Deno* d, void* user_data, const char* js_filename,
                  const char* js_source);
 
+// Same as deno_execute, but js_source is a UTF-16 string of js_source_len code
+// units. It does not need to be null terminated.
+int deno_execute_utf16(Deno* d, void* user_data, const char* js_filename,
+                       const uint16_t* js_source, int js_source_len);
+
+// Same as deno_execute, but the UTF-8 source is read in chunks from cb, on
+// the calling thread, and compiled as it arrives, on a background thread.
+// Blocks until the script has run. Syntax errors are only reported once all
+// chunks have been read. If cb returns DENO_CHUNK_ERROR, nothing is run and 0
+// is returned.
+int deno_execute_streaming(Deno* d, void* user_data, const char* js_filename,
+                           deno_chunk_cb cb, void* cb_data);
+
 // deno_respond sends up to one message back for every deno_recv_cb made.
 //
 // If this is called during deno_recv_cb, the issuing libdeno.send() in
 // javascript will synchronously return the specified buf as an ArrayBuffer (or
-// null if buf is empty).
+// null if buf is empty). Otherwise libdeno.send() returns req_id.
 //
 // If this is called after deno_recv_cb has returned, the deno_respond
-// will call into the JS callback specified by libdeno.recv().
+// will call into the JS callback specified by libdeno.recv(), with the buf and
+// req_id. An empty buf is passed as null, without allocating an ArrayBuffer,
+// for ops that complete with no data.
 //
 // (Ideally, but not currently: After calling deno_respond(), the caller no
 // longer owns `buf` and must not use it; deno_respond() is responsible for
@@ -61,8 +112,50 @@ int deno_respond(Deno* d, void* user_data, int32_t req_id, deno_buf buf);
 
 const char* deno_last_exception(Deno* d);
 
+// Makes the libdeno.send() call that deno_recv_cb was called for throw a
+// RangeError with message msg, instead of getting a response. Only valid from
+// within deno_recv_cb, and instead of deno_respond().
+void deno_reject_send(Deno* d, const char* msg);
+
 void deno_terminate_execution(Deno* d);
 
+// Limits the stack JS may use to stack_size bytes below the caller's current
+// stack position. V8 only checks the limit, it doesn't allocate anything, so
+// the calling thread's stack must really be that big. JS must then only be
+// run on this thread, from about the same stack depth.
+void deno_set_stack_limit(Deno* d, size_t stack_size);
+
+// Fills in stats for the V8 heap of d. Cheap enough to call often.
+void deno_get_heap_stats(Deno* d, deno_heap_stats* stats);
+
+// Tells V8 that nothing will run on d for idle_seconds, so it can spend that
+// time on garbage collection. Returns 1 if V8 has no more garbage collection
+// work to do, or 0 if it stopped at the deadline.
+int deno_idle_notification(Deno* d, void* user_data, double idle_seconds);
+
+// Calls cb after each garbage collection of d's heap. Pass NULL to stop.
+void deno_set_gc_cb(Deno* d, deno_gc_cb cb);
+
+// Validates and compiles the WebAssembly module in bytes. Returns 1 on
+// success. On failure returns 0 and the CompileError message is available
+// from deno_last_exception(). May be called from within deno_recv_cb.
+int deno_compile_wasm(Deno* d, const uint8_t* bytes, size_t bytes_len);
+
+// Routes libdeno.print() to cb instead of stdout and stderr. Pass NULL to go
+// back to the default.
+void deno_set_print_cb(Deno* d, deno_print_cb cb);
+
+// Stops reporting promises that are rejected without a handler to the global
+// error handler, and keeps them instead, until deno_take_rejection() is
+// called. Promises that get a handler later are forgotten.
+void deno_collect_rejections(Deno* d);
+
+// Removes one collected rejection and puts its value, as a string, in
+// deno_last_exception(). If the promise was rejected while deno_respond() was
+// delivering a response, *req_id is set to that response's req_id, otherwise
+// to -1. Returns 0 if there are none left.
+int deno_take_rejection(Deno* d, int32_t* req_id);
+
 #ifdef __cplusplus
 }  // extern "C"
 #endif
@@ -14,7 +14,7 @@
 
 namespace deno {
 
-Deno* NewFromFileSystem(deno_recv_cb cb) {
+Deno* NewFromFileSystem(deno_recv_cb cb, deno_config config) {
   std::string exe_path;
   CHECK(deno::ExePath(&exe_path));
   std::string exe_dir = deno::Dirname(exe_path);  // Always ends with a slash.
@@ -34,6 +34,7 @@ Deno* NewFromFileSystem(deno_recv_cb cb) {
   v8::Isolate::CreateParams params;
   params.array_buffer_allocator =
       v8::ArrayBuffer::Allocator::NewDefaultAllocator();
+  SetHeapLimit(&params, config);
   v8::Isolate* isolate = v8::Isolate::New(params);
   AddIsolate(d, isolate);
 
@@ -55,5 +56,7 @@ Deno* NewFromFileSystem(deno_recv_cb cb) {
 }  // namespace deno
 
 extern "C" {
-Deno* deno_new(deno_recv_cb cb) { return deno::NewFromFileSystem(cb); }
+Deno* deno_new_with_config(deno_recv_cb cb, deno_config config) {
+  return deno::NewFromFileSystem(cb, config);
+}
 }
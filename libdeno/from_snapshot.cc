@@ -43,7 +43,7 @@ void DeserializeInternalFields(v8::Local<v8::Object> holder, int index,
   deserialized_data.push_back(embedder_field);
 }
 
-Deno* NewFromSnapshot(deno_recv_cb cb) {
+Deno* NewFromSnapshot(deno_recv_cb cb, deno_config config) {
   Deno* d = new Deno;
   d->currentArgs = nullptr;
   d->cb = cb;
@@ -52,12 +52,18 @@ Deno* NewFromSnapshot(deno_recv_cb cb) {
   params.array_buffer_allocator =
       v8::ArrayBuffer::Allocator::NewDefaultAllocator();
   params.external_references = external_references;
+  SetHeapLimit(&params, config);
 
-  CHECK_NE(&deno_snapshot_start, nullptr);
-  int snapshot_len =
-      static_cast<int>(&deno_snapshot_end - &deno_snapshot_start);
-  static v8::StartupData snapshot = {&deno_snapshot_start, snapshot_len};
-  params.snapshot_blob = &snapshot;
+  if (config.snapshot.data_ptr != nullptr) {
+    d->snapshot = {reinterpret_cast<const char*>(config.snapshot.data_ptr),
+                   static_cast<int>(config.snapshot.data_len)};
+  } else {
+    CHECK_NE(&deno_snapshot_start, nullptr);
+    int snapshot_len =
+        static_cast<int>(&deno_snapshot_end - &deno_snapshot_start);
+    d->snapshot = {&deno_snapshot_start, snapshot_len};
+  }
+  params.snapshot_blob = &d->snapshot;
 
   v8::Isolate* isolate = v8::Isolate::New(params);
   AddIsolate(d, isolate);
@@ -80,5 +86,7 @@ Deno* NewFromSnapshot(deno_recv_cb cb) {
 }  // namespace deno
 
 extern "C" {
-Deno* deno_new(deno_recv_cb cb) { return deno::NewFromSnapshot(cb); }
+Deno* deno_new_with_config(deno_recv_cb cb, deno_config config) {
+  return deno::NewFromSnapshot(cb, config);
+}
 }
@@ -3,6 +3,7 @@
 #define INTERNAL_H_
 
 #include <string>
+#include <utility>
 #include "deno.h"
 #include "third_party/v8/include/v8.h"
 
@@ -17,8 +18,22 @@ struct deno_s {
   v8::Persistent<v8::Context> context;
   v8::Persistent<v8::Map> async_data_map;
   deno_recv_cb cb;
+  deno_print_cb print_cb;
+  deno_gc_cb gc_cb;
+  // Set by deno_collect_rejections().
+  bool collect_rejections;
+  // Rejected promises without a handler, keyed on the promise itself, since
+  // identity hashes can collide. The value is an array of the rejection
+  // value, as a string, and the req_id that was being responded to when the
+  // promise was rejected.
+  v8::Persistent<v8::Map> pending_rejections;
+  // The req_id of the deno_respond() call in progress, or -1.
+  int32_t responding_req_id;
   int32_t next_req_id;
   void* user_data;
+  // V8 refers to the snapshot the isolate was created from for as long as the
+  // isolate is alive.
+  v8::StartupData snapshot;
 };
 }
 
@@ -37,7 +52,10 @@ static intptr_t external_references[] = {
     reinterpret_cast<intptr_t>(Send),
     reinterpret_cast<intptr_t>(SetGlobalErrorHandler), 0};
 
-Deno* NewFromSnapshot(void* user_data, deno_recv_cb cb);
+Deno* NewFromSnapshot(deno_recv_cb cb, deno_config config);
+
+// Applies config.heap_limit to params.
+void SetHeapLimit(v8::Isolate::CreateParams* params, deno_config config);
 
 void InitializeContext(v8::Isolate* isolate, v8::Local<v8::Context> context,
                        const char* js_filename, const std::string& js_source,
@@ -1,4 +1,7 @@
 // Copyright 2018 the Deno authors. All rights reserved. MIT license.
+#include <string.h>
+#include <string>
+
 #include "testing/gtest/include/gtest/gtest.h"
 
 #include "deno.h"
@@ -16,6 +19,121 @@ TEST(LibDenoTest, CanCallFunction) {
   deno_delete(d);
 }
 
+TEST(LibDenoTest, ExecuteUtf16) {
+  Deno* d = deno_new(nullptr);
+  // "'\u00e9'.length === 1 || x()" where the string literal is not escaped.
+  const uint16_t source[] = {'\'', 0xe9, '\'', '.', 'l', 'e', 'n', 'g', 't',
+                             'h', ' ', '=', '=', '=', ' ', '1', ' ', '|',
+                             '|', ' ', 'x', '(', ')'};
+  EXPECT_TRUE(deno_execute_utf16(d, nullptr, "a.js", source,
+                                 sizeof(source) / sizeof(source[0])));
+  deno_delete(d);
+}
+
+TEST(LibDenoTest, HeapStats) {
+  Deno* d = deno_new(nullptr);
+  deno_heap_stats stats;
+  deno_get_heap_stats(d, &stats);
+  EXPECT_GT(stats.used_heap_size, 0u);
+  EXPECT_LE(stats.used_heap_size, stats.total_heap_size);
+  EXPECT_LT(stats.total_heap_size, stats.heap_size_limit);
+  deno_delete(d);
+}
+
+TEST(LibDenoTest, HeapLimit) {
+  Deno* d = deno_new(nullptr);
+  deno_heap_stats default_stats;
+  deno_get_heap_stats(d, &default_stats);
+  deno_delete(d);
+
+  deno_config config = {64 << 20, {nullptr, 0, nullptr, 0}};
+  d = deno_new_with_config(nullptr, config);
+  deno_heap_stats stats;
+  deno_get_heap_stats(d, &stats);
+  EXPECT_GE(stats.heap_size_limit, config.heap_limit);
+  EXPECT_LT(stats.heap_size_limit, default_stats.heap_size_limit);
+  EXPECT_TRUE(deno_execute(d, nullptr, "a.js", "1 + 2"));
+  deno_delete(d);
+}
+
+TEST(LibDenoTest, CompileWasm) {
+  Deno* d = deno_new(nullptr);
+  // An empty module.
+  const uint8_t valid[] = {0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00};
+  EXPECT_TRUE(deno_compile_wasm(d, valid, sizeof(valid)));
+  const uint8_t invalid[] = {0x00, 0x61, 0x73, 0x6d, 0xff};
+  EXPECT_FALSE(deno_compile_wasm(d, invalid, sizeof(invalid)));
+  EXPECT_NE(std::string(deno_last_exception(d)).find("CompileError"),
+            std::string::npos);
+  deno_delete(d);
+}
+
+struct Chunks {
+  const char** chunks;  // Null terminated. An empty string is an error.
+  size_t next;
+};
+
+size_t NextChunk(void* cb_data, const uint8_t** chunk) {
+  auto* c = static_cast<Chunks*>(cb_data);
+  const char* s = c->chunks[c->next];
+  if (s == nullptr) return 0;
+  if (*s == '\0') return DENO_CHUNK_ERROR;
+  c->next++;
+  *chunk = reinterpret_cast<const uint8_t*>(s);
+  return strlen(s);
+}
+
+TEST(LibDenoTest, ExecuteStreaming) {
+  Deno* d = deno_new(nullptr);
+  const char* good[] = {"if (1 + ", "1 !== 2) ", "x();", nullptr};
+  Chunks good_chunks = {good, 0};
+  EXPECT_TRUE(
+      deno_execute_streaming(d, nullptr, "a.js", NextChunk, &good_chunks));
+  const char* bad[] = {"if (1 + ", ") {}", nullptr};
+  Chunks bad_chunks = {bad, 0};
+  EXPECT_FALSE(
+      deno_execute_streaming(d, nullptr, "a.js", NextChunk, &bad_chunks));
+  // The first chunk is valid on its own, but must not run.
+  const char* failing[] = {"throw Error('ran');", "", nullptr};
+  Chunks failing_chunks = {failing, 0};
+  EXPECT_FALSE(
+      deno_execute_streaming(d, nullptr, "a.js", NextChunk, &failing_chunks));
+  EXPECT_STREQ(deno_last_exception(d), "Reading the source failed.");
+  deno_delete(d);
+}
+
+TEST(LibDenoTest, CollectRejections) {
+  Deno* d = deno_new(nullptr);
+  deno_collect_rejections(d);
+  EXPECT_TRUE(deno_execute(d, nullptr, "a.js",
+                           "Promise.reject(new Error('one'));"
+                           "Promise.reject(2).catch(() => {});"));
+  int32_t req_id;
+  EXPECT_TRUE(deno_take_rejection(d, &req_id));
+  EXPECT_STREQ(deno_last_exception(d), "Error: one");
+  EXPECT_EQ(req_id, -1);
+  EXPECT_FALSE(deno_take_rejection(d, &req_id));
+  deno_delete(d);
+}
+
+TEST(LibDenoTest, CollectManyRejections) {
+  Deno* d = deno_new(nullptr);
+  deno_collect_rejections(d);
+  // Enough promises for some of their identity hashes to be the same.
+  EXPECT_TRUE(deno_execute(d, nullptr, "a.js",
+                           "for (let i = 0; i < 10000; i++) {"
+                           "  Promise.reject(i);"
+                           "}"));
+  int32_t req_id;
+  int count = 0;
+  while (deno_take_rejection(d, &req_id)) {
+    EXPECT_EQ(std::to_string(count), deno_last_exception(d));
+    count++;
+  }
+  EXPECT_EQ(count, 10000);
+  deno_delete(d);
+}
+
 TEST(LibDenoTest, ErrorsCorrectly) {
   Deno* d = deno_new(nullptr);
   EXPECT_FALSE(deno_execute(d, nullptr, "a.js", "throw Error()"));
@@ -52,6 +170,24 @@ void assert_null(deno_buf b) {
   EXPECT_EQ(b.data_len, 0u);
 }
 
+TEST(LibDenoTest, RejectionDuringRespond) {
+  Deno* d = deno_new(nullptr);
+  deno_collect_rejections(d);
+  EXPECT_TRUE(deno_execute(d, nullptr, "a.js",
+                           "libdeno.recv(() => {"
+                           "  Promise.resolve().then(() => {"
+                           "    throw new Error('then');"
+                           "  });"
+                           "});"));
+  EXPECT_EQ(deno_respond(d, nullptr, 42, strbuf("")), 0);
+  int32_t req_id;
+  EXPECT_TRUE(deno_take_rejection(d, &req_id));
+  EXPECT_STREQ(deno_last_exception(d), "Error: then");
+  EXPECT_EQ(req_id, 42);
+  EXPECT_FALSE(deno_take_rejection(d, &req_id));
+  deno_delete(d);
+}
+
 TEST(LibDenoTest, RecvReturnEmpty) {
   static int count = 0;
   Deno* d = deno_new([](auto _, int req_id, auto buf, auto data_buf) {
@@ -193,3 +329,25 @@ TEST(LibDenoTest, DataBuf) {
   EXPECT_EQ(data_buf_copy.data_ptr[1], 8);
   deno_delete(d);
 }
+
+TEST(LibDenoTest, GCCallback) {
+  static int count = 0;
+  static int user_data = 0;
+  Deno* d = deno_new(nullptr);
+  deno_set_gc_cb(d, [](auto data, int full) {
+    EXPECT_EQ(data, &user_data);
+    count++;
+  });
+  // Enough garbage to fill the young generation many times over.
+  EXPECT_TRUE(deno_execute(d, &user_data, "a.js",
+                           "for (let i = 0; i < 1e6; i++) new Array(100);"));
+  EXPECT_GT(count, 0);
+  deno_set_gc_cb(d, nullptr);
+  int seen = count;
+  EXPECT_TRUE(deno_execute(d, &user_data, "b.js",
+                           "for (let i = 0; i < 1e6; i++) new Array(100);"));
+  EXPECT_EQ(count, seen);
+  int done = deno_idle_notification(d, &user_data, 0.01);
+  EXPECT_TRUE(done == 0 || done == 1);
+  deno_delete(d);
+}
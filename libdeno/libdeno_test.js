@@ -28,10 +28,11 @@ global.TypedArraySnapshots = () => {
 global.RecvReturnEmpty = () => {
   const m1 = new Uint8Array("abc".split("").map(c => c.charCodeAt(0)));
   const m2 = m1.slice();
+  // Not responded to during send(), so these are async and return req_ids.
   const r1 = libdeno.send(m1);
-  assert(r1 == null);
+  assert(typeof r1 === "number");
   const r2 = libdeno.send(m2);
-  assert(r2 == null);
+  assert(r2 === r1 + 1);
 };
 
 global.RecvReturnBar = () => {
@@ -125,7 +126,7 @@ global.DataBuf = () => {
   // The second parameter of send should modified by the
   // privileged side.
   const r = libdeno.send(a, b);
-  assert(r == null);
+  assert(typeof r === "number");
   // b is different.
   assert(b[0] === 4);
   assert(b[1] === 2);
@@ -0,0 +1,366 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+// An op protocol for embedders that don't want to define flatbuffers for
+// their ops. Requests and responses are records, which a Codec turns into
+// bytes. The isolate's codec is chosen with Isolate::set_codec(), so the same
+// ops can speak JSON while debugging and RecordCodec's compact format
+// otherwise. Record ops are reached either through dispatch() below, or from
+// ops::dispatch with a msg::Record, which is what callRecordOp() in
+// js/codec.ts sends.
+use errors;
+use errors::{DenoResult, ErrorKind};
+use isolate::{Buf, Isolate, Op};
+
+use futures;
+use std::char;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::str;
+
+// A request or response: named string fields. Requests name their op in the
+// "op" field. Responses to failed ops have the fields "error" and "kind".
+pub type Record = BTreeMap<String, String>;
+
+pub trait Codec: Send + Sync {
+  // Passed to JS in StartRes, so that js/codec.ts can pick the same format.
+  fn name(&self) -> &str;
+  fn encode(&self, record: &Record) -> Buf;
+  // Fails with InvalidInput if bytes aren't a record in this format.
+  fn decode(&self, bytes: &[u8]) -> DenoResult<Record>;
+}
+
+// Handles a request decoded by dispatch(). See Isolate::register_record_op().
+pub type RecordOp =
+  fn(isolate: &mut Isolate, request: &Record) -> DenoResult<Record>;
+
+fn invalid(msg: &str) -> errors::DenoError {
+  errors::new(ErrorKind::InvalidInput, format!("Invalid record: {}", msg))
+}
+
+// Each field is the key and then the value, both as a little endian u32
+// length followed by that many bytes of UTF-8. This is the default.
+pub struct RecordCodec;
+
+impl Codec for RecordCodec {
+  fn name(&self) -> &str {
+    "record"
+  }
+
+  fn encode(&self, record: &Record) -> Buf {
+    let mut buf = Vec::new();
+    for (key, value) in record {
+      for s in &[key, value] {
+        let len = s.len() as u32;
+        for i in 0..4 {
+          buf.push((len >> (8 * i)) as u8);
+        }
+        buf.extend_from_slice(s.as_bytes());
+      }
+    }
+    buf.into_boxed_slice()
+  }
+
+  fn decode(&self, mut bytes: &[u8]) -> DenoResult<Record> {
+    let mut record = Record::new();
+    while !bytes.is_empty() {
+      let key = take_string(&mut bytes)?;
+      let value = take_string(&mut bytes)?;
+      record.insert(key, value);
+    }
+    Ok(record)
+  }
+}
+
+// Reads a length prefixed string off the front of bytes.
+fn take_string(bytes: &mut &[u8]) -> DenoResult<String> {
+  if bytes.len() < 4 {
+    return Err(invalid("truncated length"));
+  }
+  let len = (0..4).fold(0, |len, i| len | (bytes[i] as usize) << (8 * i));
+  let rest = &bytes[4..];
+  if rest.len() < len {
+    return Err(invalid("truncated string"));
+  }
+  let s = str::from_utf8(&rest[..len]).map_err(|_| invalid("not UTF-8"))?;
+  *bytes = &rest[len..];
+  Ok(s.to_string())
+}
+
+// A JSON object whose values are all strings, like {"op":"add","a":"1"}.
+pub struct JsonCodec;
+
+fn write_json_string(out: &mut String, s: &str) {
+  out.push('"');
+  for c in s.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+}
+
+// Parses the JSON accepted by JsonCodec::decode().
+struct JsonParser<'a> {
+  chars: str::Chars<'a>,
+  peeked: Option<char>,
+}
+
+impl<'a> JsonParser<'a> {
+  fn peek(&mut self) -> Option<char> {
+    if self.peeked.is_none() {
+      self.peeked = self.chars.next();
+    }
+    self.peeked
+  }
+
+  fn next(&mut self) -> Option<char> {
+    self.peek();
+    self.peeked.take()
+  }
+
+  fn skip_whitespace(&mut self) {
+    while let Some(c) = self.peek() {
+      if !c.is_whitespace() {
+        break;
+      }
+      self.next();
+    }
+  }
+
+  fn expect(&mut self, expected: char) -> DenoResult<()> {
+    self.skip_whitespace();
+    match self.next() {
+      Some(c) if c == expected => Ok(()),
+      _ => Err(invalid(&format!("expected '{}'", expected))),
+    }
+  }
+
+  fn hex4(&mut self) -> DenoResult<u32> {
+    let mut n = 0;
+    for _ in 0..4 {
+      let digit = self.next().and_then(|c| c.to_digit(16));
+      n = n * 16 + digit.ok_or_else(|| invalid("bad \\u escape"))?;
+    }
+    Ok(n)
+  }
+
+  fn string(&mut self) -> DenoResult<String> {
+    self.expect('"')?;
+    let mut s = String::new();
+    loop {
+      let c = match self.next() {
+        None => return Err(invalid("unterminated string")),
+        Some('"') => return Ok(s),
+        Some('\\') => match self.next() {
+          Some('"') => '"',
+          Some('\\') => '\\',
+          Some('/') => '/',
+          Some('b') => '\u{8}',
+          Some('f') => '\u{c}',
+          Some('n') => '\n',
+          Some('r') => '\r',
+          Some('t') => '\t',
+          Some('u') => {
+            let mut code = self.hex4()?;
+            if code >= 0xd800 && code < 0xdc00 {
+              // A surrogate pair.
+              if self.next() != Some('\\') || self.next() != Some('u') {
+                return Err(invalid("lone surrogate"));
+              }
+              let low = self.hex4()?;
+              if low < 0xdc00 || low >= 0xe000 {
+                return Err(invalid("lone surrogate"));
+              }
+              code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+            }
+            char::from_u32(code).ok_or_else(|| invalid("lone surrogate"))?
+          }
+          _ => return Err(invalid("bad escape")),
+        },
+        Some(c) => c,
+      };
+      s.push(c);
+    }
+  }
+
+  fn record(&mut self) -> DenoResult<Record> {
+    let mut record = Record::new();
+    self.expect('{')?;
+    self.skip_whitespace();
+    if self.peek() == Some('}') {
+      self.next();
+    } else {
+      loop {
+        let key = self.string()?;
+        self.expect(':')?;
+        self.skip_whitespace();
+        let value = self.string()?;
+        record.insert(key, value);
+        self.skip_whitespace();
+        match self.next() {
+          Some(',') => continue,
+          Some('}') => break,
+          _ => return Err(invalid("expected ',' or '}'")),
+        }
+      }
+    }
+    self.skip_whitespace();
+    match self.peek() {
+      None => Ok(record),
+      Some(_) => Err(invalid("trailing characters")),
+    }
+  }
+}
+
+impl Codec for JsonCodec {
+  fn name(&self) -> &str {
+    "json"
+  }
+
+  fn encode(&self, record: &Record) -> Buf {
+    let mut out = String::from("{");
+    for (i, (key, value)) in record.iter().enumerate() {
+      if i > 0 {
+        out.push(',');
+      }
+      write_json_string(&mut out, key);
+      out.push(':');
+      write_json_string(&mut out, value);
+    }
+    out.push('}');
+    out.into_bytes().into_boxed_slice()
+  }
+
+  fn decode(&self, bytes: &[u8]) -> DenoResult<Record> {
+    let s = str::from_utf8(bytes).map_err(|_| invalid("not UTF-8"))?;
+    let mut parser = JsonParser {
+      chars: s.chars(),
+      peeked: None,
+    };
+    parser.record()
+  }
+}
+
+// Decodes request with the isolate's codec, runs the record op it names and
+// returns the encoded response. Failures are encoded as records too.
+pub fn call(isolate: &mut Isolate, request: &[u8]) -> Buf {
+  let codec = isolate.state.codec();
+  let response = codec.decode(request).and_then(|request| {
+    let op = request
+      .get("op")
+      .and_then(|name| isolate.record_op(name))
+      .ok_or_else(|| invalid("no such op"))?;
+    op(isolate, &request)
+  });
+  let response = response.unwrap_or_else(|err| {
+    let mut record = Record::new();
+    record.insert("error".to_string(), err.to_string());
+    record.insert("kind".to_string(), format!("{:?}", err.kind()));
+    record
+  });
+  codec.encode(&response)
+}
+
+// A Dispatch for isolates whose ops are all RecordOps. The control buf is the
+// request, see call(). All record ops are sync.
+pub fn dispatch(
+  isolate: &mut Isolate,
+  control: &[u8],
+  data: &'static mut [u8],
+) -> (bool, Box<Op>) {
+  assert_eq!(data.len(), 0);
+  let buf = call(isolate, control);
+  (true, Box::new(futures::future::ok(buf)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use flags;
+
+  fn record(fields: &[(&str, &str)]) -> Record {
+    fields
+      .iter()
+      .map(|&(k, v)| (k.to_string(), v.to_string()))
+      .collect()
+  }
+
+  fn op_add(_isolate: &mut Isolate, request: &Record) -> DenoResult<Record> {
+    let arg = |name: &str| -> DenoResult<i64> {
+      request
+        .get(name)
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| invalid("expected a number"))
+    };
+    let sum = arg("a")? + arg("b")?;
+    Ok(record(&[("sum", &sum.to_string())]))
+  }
+
+  #[test]
+  fn test_round_trip() {
+    let fields = record(&[
+      ("op", "echo"),
+      ("empty", ""),
+      ("quotes", "say \"hi\"\\n"),
+      ("control", "\u{1}\t\n"),
+      ("unicode", "héllo 🦕"),
+    ]);
+    let codecs: Vec<Box<Codec>> =
+      vec![Box::new(RecordCodec), Box::new(JsonCodec)];
+    for codec in &codecs {
+      let bytes = codec.encode(&fields);
+      assert_eq!(codec.decode(&bytes).unwrap(), fields);
+      assert!(codec.decode(&bytes[..bytes.len() - 1]).is_err());
+    }
+    assert_eq!(
+      &*JsonCodec.encode(&record(&[("a", "1"), ("b", "x\"")])),
+      br#"{"a":"1","b":"x\""}"#
+    );
+    let json = r#" { "a" : "é🦕" , "b":"\/", "c": "\ud83e\udd95" } "#;
+    let parsed = JsonCodec.decode(json.as_bytes()).unwrap();
+    assert_eq!(parsed, record(&[("a", "é🦕"), ("b", "/"), ("c", "🦕")]));
+    let bad = ["{", "[]", r#"{"a":1}"#, r#"{"a":"b"} x"#, r#"{"a":"\ud83e"}"#];
+    for bad in &bad {
+      let err = JsonCodec.decode(bad.as_bytes()).unwrap_err();
+      assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+    let err = RecordCodec.decode(&[5, 0, 0, 0, b'a']).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+  }
+
+  #[test]
+  fn test_dispatch() {
+    let mut results = Vec::new();
+    let codecs: Vec<Box<Codec>> =
+      vec![Box::new(RecordCodec), Box::new(JsonCodec)];
+    for codec in codecs {
+      let mut isolate =
+        Isolate::from_flags(flags::DenoFlags::default(), vec![], dispatch);
+      isolate.set_codec(codec);
+      isolate.register_record_op("add", op_add);
+      let mut call = |request: &Record| {
+        let control = isolate.state.codec().encode(request);
+        let data = Box::leak(Vec::new().into_boxed_slice());
+        let (is_sync, op) = dispatch(&mut isolate, &control, data);
+        assert!(is_sync);
+        let buf = op.wait().unwrap();
+        isolate.state.codec().decode(&buf).unwrap()
+      };
+      let sum = call(&record(&[("op", "add"), ("a", "40"), ("b", "2")]));
+      let bad_arg = call(&record(&[("op", "add"), ("a", "x"), ("b", "2")]));
+      let no_op = call(&record(&[("op", "sub")]));
+      results.push((sum, bad_arg, no_op));
+    }
+    // Both codecs give the same answers.
+    assert_eq!(results[0], results[1]);
+    let (ref sum, ref bad_arg, ref no_op) = results[0];
+    assert_eq!(sum, &record(&[("sum", "42")]));
+    assert_eq!(bad_arg["kind"], "InvalidInput");
+    assert_eq!(no_op["kind"], "InvalidInput");
+  }
+}
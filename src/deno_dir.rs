@@ -1,5 +1,6 @@
 // Copyright 2018 the Deno authors. All rights reserved. MIT license.
 use dirs;
+use errors;
 use errors::DenoError;
 use errors::DenoResult;
 use errors::ErrorKind;
@@ -7,16 +8,115 @@ use fs as deno_fs;
 use http;
 use ring;
 use std;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::fmt::Write;
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 use std::result::Result;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::sync::Mutex;
 #[cfg(test)]
 use tempfile::TempDir;
 use url;
 use url::Url;
 
+// Tells module loading to give up. Clones share the same state.
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+  pub fn cancel(&self) {
+    self.0.store(true, Ordering::SeqCst);
+  }
+
+  pub fn reset(&self) {
+    self.0.store(false, Ordering::SeqCst);
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.0.load(Ordering::SeqCst)
+  }
+
+  // Fails with Cancelled once cancel() has been called.
+  pub fn check(&self) -> DenoResult<()> {
+    if self.is_cancelled() {
+      Err(errors::cancelled())
+    } else {
+      Ok(())
+    }
+  }
+}
+
+// Finds and loads the source of modules imported by JS. DenoDir, which reads
+// modules from disk and downloads remote ones, is the default. Embedders can
+// provide their own to serve modules from somewhere else, like a database or
+// an archive. Calls can come from any thread.
+pub trait ModuleLoader: Send + Sync {
+  // Returns the module name and filename of the module that module_specifier
+  // refers to when imported from containing_file.
+  fn resolve(
+    &self,
+    module_specifier: &str,
+    containing_file: &str,
+  ) -> DenoResult<(String, String)>;
+
+  // Returns the source, and compiled output if there is any, of a module
+  // returned by resolve().
+  fn load(
+    &self,
+    module_name: &str,
+    filename: &str,
+  ) -> DenoResult<CodeFetchOutput>;
+
+  // Like load(), but fails with Cancelled once cancel is cancelled. Loaders
+  // that can stop part way, say in the middle of a download, should poll
+  // cancel while they work; by default it's only checked before and after.
+  fn load_cancellable(
+    &self,
+    module_name: &str,
+    filename: &str,
+    cancel: &CancellationToken,
+  ) -> DenoResult<CodeFetchOutput> {
+    cancel.check()?;
+    let out = self.load(module_name, filename)?;
+    cancel.check()?;
+    Ok(out)
+  }
+
+  fn code_fetch(
+    &self,
+    module_specifier: &str,
+    containing_file: &str,
+  ) -> DenoResult<CodeFetchOutput> {
+    debug!(
+      "code_fetch. module_specifier {} containing_file {}",
+      module_specifier, containing_file
+    );
+
+    let (module_name, filename) =
+      self.resolve(module_specifier, containing_file)?;
+
+    self.load(&module_name, &filename).map_err(|err| {
+      if err.kind() == ErrorKind::NotFound {
+        // For NotFound, change the message to something better.
+        DenoError::from(std::io::Error::new(
+          std::io::ErrorKind::NotFound,
+          format!(
+            "Cannot resolve module \"{}\" from \"{}\"",
+            module_specifier, containing_file
+          ),
+        ))
+      } else {
+        err
+      }
+    })
+  }
+}
+
 pub struct DenoDir {
   // Example: /Users/rld/.deno/
   pub root: PathBuf,
@@ -30,6 +130,100 @@ pub struct DenoDir {
   pub deps: PathBuf,
   // If remote resources should be reloaded.
   reload: bool,
+  stats: CacheCounters,
+  memory_cache: Mutex<MemoryCache>,
+}
+
+// A snapshot of the module loading counters of a DenoDir. See
+// DenoDir::cache_stats().
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CacheStats {
+  // Successful calls to code_fetch().
+  pub modules_resolved: usize,
+  // Modules for which compiled output was found in the gen directory.
+  pub cache_hits: usize,
+  // Modules for which no compiled output was found.
+  pub cache_misses: usize,
+  // Source code downloaded over http.
+  pub bytes_from_network: usize,
+  // Remote source code and compiled output read from disk.
+  pub bytes_from_cache: usize,
+}
+
+// DenoDir is shared between threads (through IsolateState), so the counters
+// are atomic.
+#[derive(Default)]
+struct CacheCounters {
+  modules_resolved: AtomicUsize,
+  cache_hits: AtomicUsize,
+  cache_misses: AtomicUsize,
+  bytes_from_network: AtomicUsize,
+  bytes_from_cache: AtomicUsize,
+}
+
+// Loaded modules kept in memory, by module name, so that loading them again
+// doesn't touch the disk or the network. Pinned modules stay until the
+// DenoDir is dropped. The others are evicted, least recently loaded first,
+// once there are more than limit of them, which is 0 unless set with
+// DenoDir::set_memory_cache_limit().
+#[derive(Default)]
+struct MemoryCache {
+  limit: usize,
+  pinned: HashMap<String, CodeFetchOutput>,
+  // Most recently loaded last.
+  lru: VecDeque<CodeFetchOutput>,
+}
+
+impl MemoryCache {
+  fn get(&mut self, module_name: &str) -> Option<CodeFetchOutput> {
+    if let Some(out) = self.pinned.get(module_name) {
+      return Some(out.clone());
+    }
+    let i = self.lru.iter().position(|o| o.module_name == module_name)?;
+    let out = self.lru.remove(i).unwrap();
+    self.lru.push_back(out.clone());
+    Some(out)
+  }
+
+  fn insert(&mut self, out: CodeFetchOutput) {
+    if self.pinned.contains_key(&out.module_name) {
+      self.pinned.insert(out.module_name.clone(), out);
+      return;
+    }
+    self.lru.retain(|o| o.module_name != out.module_name);
+    self.lru.push_back(out);
+    self.evict();
+  }
+
+  fn pin(&mut self, out: CodeFetchOutput) {
+    self.lru.retain(|o| o.module_name != out.module_name);
+    self.pinned.insert(out.module_name.clone(), out);
+  }
+
+  fn evict(&mut self) {
+    while self.lru.len() > self.limit {
+      self.lru.pop_front();
+    }
+  }
+
+  // Adds output_code to the modules it was compiled from.
+  fn set_output_code(
+    &mut self,
+    filename: &str,
+    source_code: &str,
+    output_code: &str,
+  ) {
+    let outs = self.pinned.values_mut().chain(self.lru.iter_mut());
+    for out in outs {
+      if out.filename == filename && out.source_code == source_code {
+        out.maybe_output_code = Some(output_code.to_string());
+      }
+    }
+  }
+}
+
+fn incr(counter: &AtomicUsize, n: usize) {
+  counter.fetch_add(n, Ordering::SeqCst);
 }
 
 impl DenoDir {
@@ -55,9 +249,11 @@ impl DenoDir {
       gen,
       deps,
       reload,
+      stats: CacheCounters::default(),
+      memory_cache: Mutex::new(MemoryCache::default()),
     };
-    deno_fs::mkdir(deno_dir.gen.as_ref(), 0o755)?;
-    deno_fs::mkdir(deno_dir.deps.as_ref(), 0o755)?;
+    deno_fs::mkdir(deno_dir.gen.as_ref(), 0o755, true)?;
+    deno_fs::mkdir(deno_dir.deps.as_ref(), 0o755, true)?;
 
     debug!("root {}", deno_dir.root.display());
     debug!("gen {}", deno_dir.gen.display());
@@ -66,6 +262,43 @@ impl DenoDir {
     Ok(deno_dir)
   }
 
+  pub fn cache_stats(self: &DenoDir) -> CacheStats {
+    let s = &self.stats;
+    CacheStats {
+      modules_resolved: s.modules_resolved.load(Ordering::SeqCst),
+      cache_hits: s.cache_hits.load(Ordering::SeqCst),
+      cache_misses: s.cache_misses.load(Ordering::SeqCst),
+      bytes_from_network: s.bytes_from_network.load(Ordering::SeqCst),
+      bytes_from_cache: s.bytes_from_cache.load(Ordering::SeqCst),
+    }
+  }
+
+  // Keeps up to limit of the modules that were loaded most recently in
+  // memory, in addition to those pinned by preload(). 0, the default, only
+  // keeps pinned modules.
+  pub fn set_memory_cache_limit(self: &DenoDir, limit: usize) {
+    let mut memory_cache = self.memory_cache.lock().unwrap();
+    memory_cache.limit = limit;
+    memory_cache.evict();
+  }
+
+  // Resolves and loads each of specifiers, relative to the current directory,
+  // and pins them in memory so that loading them again is served from there
+  // however many other modules are loaded. Fails with the first module that
+  // can't be loaded.
+  //
+  // This only fetches, it doesn't compile: the TypeScript compiler runs in JS,
+  // not here. Compiled output is pinned along with a module if it is already
+  // in the gen directory, and otherwise once the compiler caches it with
+  // code_cache() the first time the module is used.
+  pub fn preload(self: &DenoDir, specifiers: &[String]) -> DenoResult<()> {
+    for specifier in specifiers {
+      let out = self.code_fetch(specifier, ".")?;
+      self.memory_cache.lock().unwrap().pin(out);
+    }
+    Ok(())
+  }
+
   // https://github.com/denoland/deno/blob/golang/deno_dir.go#L32-L35
   pub fn cache_path(
     self: &DenoDir,
@@ -97,6 +330,11 @@ impl DenoDir {
     // create the file in exclusive mode. A worry is what might happen is there
     // are two processes and one reads the cache file while the other is in the
     // midst of writing it.
+    self.memory_cache.lock().unwrap().set_output_code(
+      filename,
+      source_code,
+      output_code,
+    );
     if cache_path.exists() {
       Ok(())
     } else {
@@ -109,12 +347,14 @@ impl DenoDir {
     self: &DenoDir,
     module_name: &str,
     filename: &str,
+    cancel: &CancellationToken,
   ) -> DenoResult<String> {
     let p = Path::new(filename);
 
     let src = if self.reload || !p.exists() {
       println!("Downloading {}", module_name);
-      let source = http::fetch_sync_string(module_name)?;
+      let source = http::fetch_sync_string(module_name, cancel)?;
+      incr(&self.stats.bytes_from_network, source.len());
       match p.parent() {
         Some(ref parent) => fs::create_dir_all(parent),
         None => Ok(()),
@@ -123,6 +363,7 @@ impl DenoDir {
       source
     } else {
       let source = fs::read_to_string(&p)?;
+      incr(&self.stats.bytes_from_cache, source.len());
       source
     };
     Ok(src)
@@ -133,16 +374,18 @@ impl DenoDir {
     self: &DenoDir,
     module_name: &str,
     filename: &str,
+    cancel: &CancellationToken,
   ) -> DenoResult<CodeFetchOutput> {
     if module_name.starts_with(ASSET_PREFIX) {
       panic!("Asset resolution should be done in JS, not Rust.");
     }
     let is_module_remote = is_remote(module_name);
     let use_extension = |ext| {
+      cancel.check()?;
       let module_name = format!("{}{}", module_name, ext);
       let filename = format!("{}{}", filename, ext);
       let source_code = if is_module_remote {
-        self.fetch_remote_source(&module_name, &filename)?
+        self.fetch_remote_source(&module_name, &filename, cancel)?
       } else {
         assert_eq!(
           module_name, filename,
@@ -158,66 +401,68 @@ impl DenoDir {
       });
     };
     let default_attempt = use_extension("");
-    if default_attempt.is_ok() {
-      return default_attempt;
+    match default_attempt {
+      Err(ref err) if err.kind() != ErrorKind::Cancelled => (),
+      _ => return default_attempt,
     }
     debug!("Trying {}.ts...", module_name);
     let ts_attempt = use_extension(".ts");
-    if ts_attempt.is_ok() {
-      return ts_attempt;
+    match ts_attempt {
+      Err(ref err) if err.kind() != ErrorKind::Cancelled => (),
+      _ => return ts_attempt,
     }
     debug!("Trying {}.js...", module_name);
     use_extension(".js")
   }
 
-  pub fn code_fetch(
+  fn load_module(
     self: &DenoDir,
-    module_specifier: &str,
-    containing_file: &str,
-  ) -> Result<CodeFetchOutput, DenoError> {
-    debug!(
-      "code_fetch. module_specifier {} containing_file {}",
-      module_specifier, containing_file
-    );
-
-    let (module_name, filename) =
-      self.resolve_module(module_specifier, containing_file)?;
+    module_name: &str,
+    filename: &str,
+    cancel: &CancellationToken,
+  ) -> DenoResult<CodeFetchOutput> {
+    let cached = self.memory_cache.lock().unwrap().get(module_name);
+    if let Some(out) = cached {
+      incr(&self.stats.modules_resolved, 1);
+      Ok(out)
+    } else {
+      let out = self.load_module_from_disk(module_name, filename, cancel)?;
+      self.memory_cache.lock().unwrap().insert(out.clone());
+      Ok(out)
+    }
+  }
 
-    let result = self.get_source_code(module_name.as_str(), filename.as_str());
-    let out = match result {
-      Err(err) => {
-        if err.kind() == ErrorKind::NotFound {
-          // For NotFound, change the message to something better.
-          return Err(DenoError::from(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            format!(
-              "Cannot resolve module \"{}\" from \"{}\"",
-              module_specifier, containing_file
-            ),
-          )));
-        } else {
-          return Err(err);
-        }
-      }
-      Ok(out) => out,
-    };
+  fn load_module_from_disk(
+    self: &DenoDir,
+    module_name: &str,
+    filename: &str,
+    cancel: &CancellationToken,
+  ) -> DenoResult<CodeFetchOutput> {
+    let out = self.get_source_code(module_name, filename, cancel)?;
 
     let result =
       self.load_cache(out.filename.as_str(), out.source_code.as_str());
     match result {
       Err(err) => {
         if err.kind() == std::io::ErrorKind::NotFound {
+          incr(&self.stats.modules_resolved, 1);
+          incr(&self.stats.cache_misses, 1);
           Ok(out)
         } else {
           Err(err.into())
         }
       }
-      Ok(output_code) => Ok(CodeFetchOutput {
-        module_name: out.module_name,
-        filename: out.filename,
-        source_code: out.source_code,
-        maybe_output_code: Some(output_code),
-      }),
+      Ok(output_code) => {
+        incr(&self.stats.modules_resolved, 1);
+        incr(&self.stats.cache_hits, 1);
+        incr(&self.stats.bytes_from_cache, output_code.len());
+        Ok(CodeFetchOutput {
+          module_name: out.module_name,
+          filename: out.filename,
+          source_code: out.source_code,
+          maybe_output_code: Some(output_code),
+        })
+      }
     }
   }
 
@@ -292,6 +537,158 @@ impl DenoDir {
   }
 }
 
+impl ModuleLoader for DenoDir {
+  fn resolve(
+    &self,
+    module_specifier: &str,
+    containing_file: &str,
+  ) -> DenoResult<(String, String)> {
+    let r = self.resolve_module(module_specifier, containing_file)?;
+    Ok(r)
+  }
+
+  fn load(
+    &self,
+    module_name: &str,
+    filename: &str,
+  ) -> DenoResult<CodeFetchOutput> {
+    self.load_module(module_name, filename, &CancellationToken::default())
+  }
+
+  // Downloads are abandoned part way.
+  fn load_cancellable(
+    &self,
+    module_name: &str,
+    filename: &str,
+    cancel: &CancellationToken,
+  ) -> DenoResult<CodeFetchOutput> {
+    cancel.check()?;
+    self.load_module(module_name, filename, cancel)
+  }
+}
+
+// Fetches entry and, recursively, everything it statically imports, without
+// running or compiling anything. Returns the names of the modules in the order
+// they were found, starting with entry. Fails with the error of the first
+// import that can't be loaded, which names it. Dynamic imports and the
+// built-in "deno" module are not followed.
+pub fn module_graph(
+  loader: &ModuleLoader,
+  entry: &str,
+  containing_file: &str,
+) -> DenoResult<Vec<String>> {
+  let mut modules = Vec::new();
+  let mut seen = HashSet::new();
+  // (module_specifier, containing_file) pairs still to fetch.
+  let mut todo = vec![(entry.to_string(), containing_file.to_string())];
+  while let Some((specifier, containing_file)) = todo.pop() {
+    let out = loader.code_fetch(&specifier, &containing_file)?;
+    if !seen.insert(out.module_name.clone()) {
+      continue;
+    }
+    let imports = scan_imports(&out.module_name, &out.source_code);
+    // Reversed so that they are popped in source order.
+    for import in imports.into_iter().rev() {
+      if import != "deno" && !import.starts_with(ASSET_PREFIX) {
+        todo.push((import, out.module_name.clone()));
+      }
+    }
+    modules.push(out.module_name);
+  }
+  Ok(modules)
+}
+
+// The specifiers of the static imports of a module, as found by a textual
+// scan of its source. See scan_import_specifiers().
+pub fn scan_imports(module_name: &str, source_code: &str) -> Vec<String> {
+  // JSON modules don't import anything, and "from" is a common key.
+  if module_name.ends_with(".json") {
+    Vec::new()
+  } else {
+    scan_import_specifiers(source_code)
+  }
+}
+
+fn is_ident_char(c: char) -> bool {
+  c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+// Finds the specifiers of `import "x"` and `... from "x"` statements. This is
+// a textual scan, not a parser, so an import inside a comment or string is
+// found too.
+fn scan_import_specifiers(source: &str) -> Vec<String> {
+  let mut found = Vec::new();
+  for keyword in &["import", "from"] {
+    let mut start = 0;
+    while let Some(i) = source[start..].find(keyword) {
+      let i = start + i;
+      start = i + keyword.len();
+      let word_start = match source[..i].chars().next_back() {
+        Some(c) => !is_ident_char(c),
+        None => true,
+      };
+      let rest = &source[start..];
+      let after = rest.trim_left();
+      // `import("x")` is a dynamic import, and needs the whitespace.
+      if !word_start || (*keyword == "import" && after.len() == rest.len()) {
+        continue;
+      }
+      let quote = match after.chars().next() {
+        Some(q) if q == '"' || q == '\'' => q,
+        _ => continue,
+      };
+      if let Some(end) = after[1..].find(quote) {
+        found.push((i, after[1..end + 1].to_string()));
+      }
+    }
+  }
+  found.sort();
+  found.into_iter().map(|(_, specifier)| specifier).collect()
+}
+
+#[test]
+fn test_scan_import_specifiers() {
+  let source = r#"
+    import { a } from "./a.ts";
+    import * as b from './b';
+    import "./c.js";
+    export { d } from "http://example.com/d.ts";
+    const e = import("./dynamic.ts");
+    const reimported = 1;
+  "#;
+  assert_eq!(
+    scan_import_specifiers(source),
+    vec!["./a.ts", "./b", "./c.js", "http://example.com/d.ts"]
+  );
+}
+
+#[test]
+fn test_module_graph() {
+  let (temp_dir, deno_dir) = test_setup();
+  let dir = temp_dir.path().join("src");
+  fs::create_dir(&dir).unwrap();
+  let main = "import { b } from './b.ts';\nimport 'deno';";
+  fs::write(dir.join("main.ts"), main).unwrap();
+  // Cycles are fine.
+  let b = "export * from './c';\nimport './main.ts';";
+  fs::write(dir.join("b.ts"), b).unwrap();
+  fs::write(dir.join("c.ts"), "import './d.json';").unwrap();
+  fs::write(dir.join("d.json"), r#"{ "from": "./missing.ts" }"#).unwrap();
+  let dir_string = String::from(dir.to_str().unwrap()) + "/";
+
+  let modules = module_graph(&deno_dir, "./main.ts", &dir_string).unwrap();
+  let names: Vec<&str> = modules
+    .iter()
+    .map(|m| m.rsplit('/').next().unwrap())
+    .collect();
+  assert_eq!(names, vec!["main.ts", "b.ts", "c.ts", "d.json"]);
+
+  fs::write(dir.join("c.ts"), "import './missing.ts';").unwrap();
+  let err = module_graph(&deno_dir, "./main.ts", &dir_string).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::NotFound);
+  assert!(err.to_string().contains("./missing.ts"));
+}
+
 fn get_cache_filename(basedir: &Path, url: Url) -> PathBuf {
   let host = url.host_str().unwrap();
   let host_port = match url.port() {
@@ -320,7 +717,7 @@ fn test_get_cache_filename() {
   );
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct CodeFetchOutput {
   pub module_name: String,
   pub filename: String,
@@ -433,6 +830,85 @@ fn test_code_fetch() {
   //println!("code_fetch_output {:?}", code_fetch_output);
 }
 
+#[test]
+fn test_cache_stats() {
+  let (_temp_dir, deno_dir) = test_setup();
+  assert_eq!(deno_dir.cache_stats(), CacheStats::default());
+
+  let cwd = std::env::current_dir().unwrap();
+  let cwd_string = String::from(cwd.to_str().unwrap()) + "/";
+  let module_specifier = "./js/main.ts";
+  let containing_file = cwd_string.as_str();
+
+  // Nothing has been compiled yet.
+  let out = deno_dir.code_fetch(module_specifier, containing_file).unwrap();
+  assert!(out.maybe_output_code.is_none());
+  let stats = deno_dir.cache_stats();
+  assert_eq!(stats.modules_resolved, 1);
+  assert_eq!(stats.cache_misses, 1);
+  assert_eq!(stats.cache_hits, 0);
+
+  let output_code = "console.log('compiled')";
+  deno_dir
+    .code_cache(&out.filename, &out.source_code, output_code)
+    .unwrap();
+
+  let out = deno_dir.code_fetch(module_specifier, containing_file).unwrap();
+  assert!(out.maybe_output_code.is_some());
+  let stats = deno_dir.cache_stats();
+  assert_eq!(stats.modules_resolved, 2);
+  assert_eq!(stats.cache_misses, 1);
+  assert_eq!(stats.cache_hits, 1);
+  assert_eq!(stats.bytes_from_network, 0);
+  assert_eq!(stats.bytes_from_cache, output_code.len());
+}
+
+#[test]
+fn test_preload() {
+  let (temp_dir, deno_dir) = test_setup();
+  let module = |name: &str| {
+    let path = temp_dir.path().join(name);
+    fs::write(&path, format!("export const name = '{}';", name)).unwrap();
+    path.to_str().unwrap().to_string()
+  };
+  let hot = module("hot.ts");
+  let others: Vec<String> =
+    (0..4).map(|i| module(&format!("{}.ts", i))).collect();
+
+  deno_dir.set_memory_cache_limit(2);
+  deno_dir.preload(&[hot.clone()]).unwrap();
+  for other in &others {
+    deno_dir.code_fetch(other, ".").unwrap();
+  }
+
+  // Nothing is read from disk anymore for modules that are in memory: the
+  // pinned one and the two loaded last.
+  for path in others.iter().chain(Some(&hot)) {
+    fs::remove_file(path).unwrap();
+  }
+  let out = deno_dir.code_fetch(&hot, ".").unwrap();
+  assert_eq!(out.source_code, "export const name = 'hot.ts';");
+  assert!(deno_dir.code_fetch(&others[3], ".").is_ok());
+  assert!(deno_dir.code_fetch(&others[2], ".").is_ok());
+  let err = deno_dir.code_fetch(&others[1], ".").unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::NotFound);
+
+  // Compiled output cached afterwards is kept with the pinned module.
+  deno_dir
+    .code_cache(&out.filename, &out.source_code, "compiled")
+    .unwrap();
+  let out = deno_dir.code_fetch(&hot, ".").unwrap();
+  assert_eq!(out.maybe_output_code, Some("compiled".to_string()));
+
+  // Pinned modules don't count towards the limit.
+  deno_dir.set_memory_cache_limit(0);
+  assert!(deno_dir.code_fetch(&others[3], ".").is_err());
+  assert!(deno_dir.code_fetch(&hot, ".").is_ok());
+
+  let err = deno_dir.preload(&[others[0].clone()]).unwrap_err();
+  assert_eq!(err.kind(), ErrorKind::NotFound);
+}
+
 #[test]
 fn test_code_fetch_no_ext() {
   let (_temp_dir, deno_dir) = test_setup();
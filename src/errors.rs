@@ -2,6 +2,7 @@
 use hyper;
 pub use msg::ErrorKind;
 use std;
+use std::any::Any;
 use std::fmt;
 use std::io;
 use url;
@@ -122,9 +123,19 @@ impl std::error::Error for DenoError {
   }
 }
 
+// An io::Error carrying err, for implementations of traits like AsyncRead
+// that can only fail with io::Errors. DenoError::from() unwraps it again.
+pub fn to_io_error(err: DenoError) -> io::Error {
+  io::Error::new(io::ErrorKind::Other, err)
+}
+
 impl From<io::Error> for DenoError {
   #[inline]
   fn from(err: io::Error) -> DenoError {
+    if err.get_ref().map_or(false, |inner| inner.is::<DenoError>()) {
+      let inner = err.into_inner().unwrap();
+      return *inner.downcast::<DenoError>().unwrap();
+    }
     DenoError {
       repr: Repr::IoErr(err),
     }
@@ -162,3 +173,84 @@ pub fn permission_denied() -> DenoError {
     String::from("permission denied"),
   )
 }
+
+pub fn op_timed_out() -> DenoError {
+  new(ErrorKind::OpTimedOut, String::from("op timed out"))
+}
+
+pub fn loop_aborted(reason: String) -> DenoError {
+  new(ErrorKind::LoopAborted, reason)
+}
+
+pub fn unhandled_rejection(msg: String) -> DenoError {
+  new(ErrorKind::UnhandledRejection, msg)
+}
+
+pub fn uncaught_exception(msg: String) -> DenoError {
+  new(ErrorKind::UncaughtException, msg)
+}
+
+pub fn context_gone(req_id: i32) -> DenoError {
+  new(
+    ErrorKind::ContextGone,
+    format!("response to req_id {} after its context was reset", req_id),
+  )
+}
+
+pub fn execute_depth_exceeded() -> DenoError {
+  new(
+    ErrorKind::ExecuteDepthExceeded,
+    String::from(EXECUTE_DEPTH_EXCEEDED),
+  )
+}
+
+// What Isolate::execute() fails with when it would nest too deeply.
+pub const EXECUTE_DEPTH_EXCEEDED: &str = "maximum execute depth exceeded";
+
+pub fn not_a_terminal() -> DenoError {
+  new(ErrorKind::NotATerminal, String::from("not a terminal"))
+}
+
+// offset is where the first byte that isn't part of valid UTF-8 is.
+pub fn invalid_utf8(offset: usize) -> DenoError {
+  new(
+    ErrorKind::InvalidUtf8,
+    format!("invalid UTF-8 at byte offset {}", offset),
+  )
+}
+
+pub fn too_many_open_resources() -> DenoError {
+  new(
+    ErrorKind::TooManyOpenResources,
+    String::from("too many open resources"),
+  )
+}
+
+// What Isolate::restart() fails with while ops or IsolateHandles still hold
+// on to the isolate's state.
+pub fn isolate_in_use() -> DenoError {
+  new(
+    ErrorKind::Other,
+    String::from("isolate state is still in use by ops or handles"),
+  )
+}
+
+pub fn cancelled() -> DenoError {
+  new(ErrorKind::Cancelled, String::from("module loading cancelled"))
+}
+
+pub fn quota_exceeded() -> DenoError {
+  new(ErrorKind::QuotaExceeded, String::from("op byte quota exceeded"))
+}
+
+// Converts the payload of a caught panic into an error.
+pub fn op_panicked(payload: Box<Any + Send>) -> DenoError {
+  let msg = if let Some(s) = payload.downcast_ref::<&str>() {
+    s.to_string()
+  } else if let Some(s) = payload.downcast_ref::<String>() {
+    s.clone()
+  } else {
+    String::from("unknown panic")
+  };
+  new(ErrorKind::OpPanicked, format!("op panicked: {}", msg))
+}
@@ -2,10 +2,13 @@
 use libc::c_int;
 use libdeno;
 use log;
+use std::collections::HashMap;
 use std::ffi::CStr;
 use std::ffi::CString;
 use std::mem;
+use std::path::PathBuf;
 use std::process::exit;
+use std::time::Duration;
 use std::vec::Vec;
 use version;
 
@@ -15,6 +18,49 @@ macro_rules! svec {
     ($($x:expr),*) => (vec![$($x.to_string()),*]);
 }
 
+// What event_loop() does about promises that were rejected without a handler.
+// Like node's --unhandled-rejections.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnhandledRejectionMode {
+  // event_loop() returns an UnhandledRejection error.
+  Strict,
+  // Each rejection is printed to stderr, or the console sink.
+  Warn,
+  Ignore,
+}
+
+// What happens to the response of an async op that completes after the
+// context that dispatched it has been reset. Either way it isn't delivered to
+// the new context.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StaleResponsePolicy {
+  // The response is silently dropped.
+  Drop,
+  // The task completing the op gets a ContextGone error, which it logs.
+  Error,
+}
+
+impl Default for StaleResponsePolicy {
+  fn default() -> Self {
+    StaleResponsePolicy::Drop
+  }
+}
+
+// How the CLI prints an uncaught error before exiting.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ErrorFormat {
+  // The stack, after source maps, below the source line that threw.
+  Human,
+  // A JSON object on one line, for tools.
+  Json,
+}
+
+impl Default for ErrorFormat {
+  fn default() -> Self {
+    ErrorFormat::Human
+  }
+}
+
 #[derive(Debug, PartialEq, Default)]
 pub struct DenoFlags {
   pub help: bool,
@@ -22,10 +68,91 @@ pub struct DenoFlags {
   pub version: bool,
   pub reload: bool,
   pub recompile: bool,
+  pub allow_read: bool,
+  // If not empty, realpath only returns paths inside one of these
+  // directories, so symlinks can't be used to find files outside of them.
+  // copyFile() only copies from them.
+  pub read_roots: Vec<PathBuf>,
   pub allow_write: bool,
+  // If not empty, remove() refuses to recursively remove directories outside
+  // of these, which limits what a wrong path can wipe out. rename() and
+  // copyFile() refuse to write outside of them.
+  pub write_roots: Vec<PathBuf>,
   pub allow_net: bool,
   pub allow_env: bool,
+  pub allow_run: bool,
   pub deps_flag: bool,
+  // Maximum number of async ops running at once. Additional ops are queued.
+  // A limit of 0 is treated as 1.
+  pub max_concurrent_ops: Option<usize>,
+  // Maximum number of Isolate::execute() calls on the stack at once, which
+  // nest when ops execute JS. Going deeper fails rather than overflowing the
+  // native stack.
+  pub max_execute_depth: Option<usize>,
+  // When at least this many async ops are pending, running or queued, the
+  // Backpressure op tells JS to hold off on starting more.
+  pub op_high_water_mark: Option<usize>,
+  // Bytes of stack JS may use, instead of V8's default of about 1MB. The
+  // thread running the isolate must have a stack at least this big.
+  pub stack_size: Option<usize>,
+  // Bytes the V8 heap may grow to, rounded down to whole megabytes. Overrides
+  // DENO_HEAP_LIMIT, see IsolateConfig.
+  pub heap_limit: Option<usize>,
+  // Maximum bytes, control and data buffer together, that JS may pass to one
+  // libdeno.send(). Bigger requests aren't dispatched; send() throws a
+  // RangeError instead.
+  pub max_op_input_bytes: Option<usize>,
+  // Maximum number of resources that may be open in the isolate's resource
+  // table at once, counting the files and sockets it opened too. Ops that
+  // would open more fail with TooManyOpenResources until some are closed.
+  pub max_open_resources: Option<usize>,
+  // Maximum bytes, counting requests and responses, each op id may transfer.
+  // Once an op has used up its quota further calls fail with QuotaExceeded.
+  pub op_byte_quotas: HashMap<u32, u64>,
+  // Maximum number of async ops of each op id running at once, for op ids
+  // reported with Isolate::set_dispatch_op_id(). Additional ops of that id
+  // are queued until one completes. A limit of 0 is treated as 1.
+  pub op_concurrency_limits: HashMap<u32, usize>,
+  // Makes Math.random() in this isolate return the same sequence every run.
+  // Unlike V8's --random_seed this only affects one isolate.
+  pub random_seed: Option<u64>,
+  // If not set, each unhandled rejection is passed to the global error handler
+  // as soon as it happens, which the deno binary relies on to exit. If set,
+  // rejections are only looked at once the event loop is done, so a handler
+  // added later doesn't count as unhandled.
+  pub unhandled_rejection_mode: Option<UnhandledRejectionMode>,
+  // Makes event_loop() return an UncaughtException error as soon as an async
+  // callback throws or a promise is rejected without a handler, as test
+  // runners expect.
+  pub abort_on_uncaught: bool,
+  // Globals, like "libdeno", deleted before any script runs so untrusted code
+  // can't use them. The runtime keeps working, as it took its references to
+  // them while the snapshot was built. Globals that can't be deleted, like
+  // undefined, are left as they are, with a warning.
+  pub hidden_globals: Vec<String>,
+  // Records the order in which ops are dispatched and completed. See
+  // Isolate::op_ordering().
+  pub record_op_ordering: bool,
+  // Counts why the event loop woke up. See Isolate::wakeup_stats().
+  pub record_wakeups: bool,
+  // While the event loop waits for a timer and has nothing else to do, V8 is
+  // given the time until the timer fires to collect garbage in, so less of
+  // it happens while JS runs. Tasks posted to the isolate in that time wait
+  // until V8 is done. See Isolate::set_on_gc().
+  pub idle_gc: bool,
+  // Delivers the async op completions that are ready together in an order
+  // picked with this seed rather than in the order they completed in. Which
+  // completions are ready together still depends on thread scheduling, except
+  // for those buffered while the isolate is paused. See Isolate::pause().
+  pub deterministic_ops: Option<u64>,
+  // Async ops that haven't responded after this long are abandoned, and their
+  // promise is rejected with an OpTimedOut error. A safety net for ops that
+  // get stuck; see also ops::with_op_timeout().
+  pub op_response_timeout: Option<Duration>,
+  // See StaleResponsePolicy. Matters to embedders that call Isolate::reset()
+  // or restart() with ops in flight.
+  pub stale_response_policy: StaleResponsePolicy,
+  pub error_format: ErrorFormat,
 }
 
 pub fn process(flags: &DenoFlags) {
@@ -49,20 +176,26 @@ pub fn process(flags: &DenoFlags) {
 pub fn print_usage() {
   println!(
     "Usage: deno script.ts
+--allow-read       Allow file system read access.
 --allow-write      Allow file system write access.
 --allow-net        Allow network access.
 --allow-env        Allow environment access.
+--allow-run        Allow running subprocesses.
 --recompile        Force recompilation of TypeScript code.
 -v or --version    Print the version.
 -r or --reload     Reload cached remote resources.
 -D or --log-debug  Log debug output.
 -h or --help       Print this message.
 --v8-options       Print V8 command line options.
---deps             Print module dependencies."
+--deps             Print module dependencies.
+--error-format=json Print uncaught errors as JSON."
   );
 }
 
 // Parses flags for deno. This does not do v8_set_flags() - call that separately.
+// args must follow the CLI convention where args[0] is the program name (V8
+// never parses it as a flag). The returned args also start with the program
+// name. Embedders that only have flags should use set_flags_lib() instead.
 pub fn set_flags(args: Vec<String>) -> (DenoFlags, Vec<String>) {
   let args = v8_set_flags(args);
 
@@ -78,10 +211,14 @@ pub fn set_flags(args: Vec<String>) -> (DenoFlags, Vec<String>) {
         "--version" => flags.version = true,
         "--reload" => flags.reload = true,
         "--recompile" => flags.recompile = true,
+        "--allow-read" => flags.allow_read = true,
         "--allow-write" => flags.allow_write = true,
         "--allow-net" => flags.allow_net = true,
         "--allow-env" => flags.allow_env = true,
+        "--allow-run" => flags.allow_run = true,
         "--deps" => flags.deps_flag = true,
+        "--error-format=human" => flags.error_format = ErrorFormat::Human,
+        "--error-format=json" => flags.error_format = ErrorFormat::Json,
         "--" => break,
         _ => unimplemented!(),
       }
@@ -106,6 +243,18 @@ pub fn set_flags(args: Vec<String>) -> (DenoFlags, Vec<String>) {
   return (flags, rest);
 }
 
+// Like set_flags(), but for library embedders whose args do not start with a
+// program name. Every element of args is treated as a flag or an argument, and
+// the returned args do not have a program name prepended.
+pub fn set_flags_lib(args: Vec<String>) -> (DenoFlags, Vec<String>) {
+  let mut argv = vec![String::from("deno")];
+  argv.extend(args);
+  let (flags, mut rest) = set_flags(argv);
+  // The program name we added is always the first remaining argument.
+  rest.remove(0);
+  (flags, rest)
+}
+
 #[test]
 fn test_set_flags_1() {
   let (flags, rest) = set_flags(svec!["deno", "--version"]);
@@ -165,6 +314,73 @@ fn test_set_flags_4() {
   );
 }
 
+#[test]
+fn test_set_flags_5() {
+  let (flags, rest) =
+    set_flags(svec!["deno", "--allow-read", "script.ts", "--allow-net"]);
+  assert_eq!(rest, svec!["deno", "script.ts"]);
+  assert_eq!(
+    flags,
+    DenoFlags {
+      allow_read: true,
+      allow_net: true,
+      ..DenoFlags::default()
+    }
+  );
+}
+
+#[test]
+fn test_set_flags_6() {
+  let (flags, rest) = set_flags(svec!["deno", "--allow-run", "script.ts"]);
+  assert_eq!(rest, svec!["deno", "script.ts"]);
+  assert_eq!(
+    flags,
+    DenoFlags {
+      allow_run: true,
+      ..DenoFlags::default()
+    }
+  );
+}
+
+#[test]
+fn test_set_flags_7() {
+  let (flags, rest) =
+    set_flags(svec!["deno", "--error-format=json", "script.ts"]);
+  assert_eq!(rest, svec!["deno", "script.ts"]);
+  assert_eq!(
+    flags,
+    DenoFlags {
+      error_format: ErrorFormat::Json,
+      ..DenoFlags::default()
+    }
+  );
+}
+
+#[test]
+fn test_set_flags_lib() {
+  let (flags, rest) = set_flags_lib(svec!["--reload", "script.ts", "arg"]);
+  assert_eq!(rest, svec!["script.ts", "arg"]);
+  assert_eq!(
+    flags,
+    DenoFlags {
+      reload: true,
+      ..DenoFlags::default()
+    }
+  );
+
+  // Compare with the CLI convention, where the first element is kept as the
+  // program name.
+  let (flags, rest) = set_flags(svec!["deno", "--reload", "script.ts", "arg"]);
+  assert_eq!(rest, svec!["deno", "script.ts", "arg"]);
+  assert_eq!(
+    flags,
+    DenoFlags {
+      reload: true,
+      ..DenoFlags::default()
+    }
+  );
+}
+
 // Returns args passed to V8, followed by args passed to JS
 fn v8_set_flags_preprocess(args: Vec<String>) -> (Vec<String>, Vec<String>) {
   let mut rest = vec![];
@@ -7,27 +7,104 @@ use std::path::{Path, PathBuf};
 use rand;
 use rand::Rng;
 
+#[cfg(any(unix))]
+use libc;
 #[cfg(any(unix))]
 use std::os::unix::fs::DirBuilderExt;
 #[cfg(any(unix))]
+use std::os::unix::fs::OpenOptionsExt;
+#[cfg(any(unix))]
 use std::os::unix::fs::PermissionsExt;
 
+pub struct WriteFileOptions {
+  pub perm: u32,
+  // Create the file if it doesn't exist.
+  pub create: bool,
+  pub append: bool,
+  // Write to a temporary file next to the file and rename it into place, so
+  // that the file is either replaced completely or not at all.
+  pub atomic: bool,
+}
+
+// Bit 31 of perm means append.
 pub fn write_file(
   filename: &Path,
   data: &[u8],
   perm: u32,
 ) -> std::io::Result<()> {
-  let is_append = perm & (1 << 31) != 0;
-  let mut file = OpenOptions::new()
-    .read(false)
-    .write(true)
-    .append(is_append)
-    .truncate(!is_append)
-    .create(true)
-    .open(filename)?;
+  let options = WriteFileOptions {
+    perm,
+    create: true,
+    append: perm & (1 << 31) != 0,
+    atomic: false,
+  };
+  write_file_with_options(filename, data, &options)
+}
+
+pub fn write_file_with_options(
+  filename: &Path,
+  data: &[u8],
+  options: &WriteFileOptions,
+) -> std::io::Result<()> {
+  if !options.atomic {
+    let mut file = OpenOptions::new()
+      .read(false)
+      .write(true)
+      .append(options.append)
+      .truncate(!options.append)
+      .create(options.create)
+      .open(filename)?;
+    set_permissions(&mut file, options.perm)?;
+    return file.write_all(data);
+  }
 
-  set_permissions(&mut file, perm)?;
-  file.write_all(data)
+  if options.append {
+    return Err(std::io::Error::new(
+      ErrorKind::InvalidInput,
+      "atomic writes can't append",
+    ));
+  }
+  if !options.create {
+    // Fails with NotFound like a non-atomic write would.
+    std::fs::metadata(filename)?;
+  }
+  let (temp_path, mut file) = create_temp_file_next_to(filename)?;
+  let result = set_permissions(&mut file, options.perm)
+    .and_then(|()| file.write_all(data))
+    .and_then(|()| file.sync_all())
+    .and_then(|()| std::fs::rename(&temp_path, filename));
+  if result.is_err() {
+    // Don't leave the partial file behind. The original error is more useful
+    // than one from removing it.
+    let _ = std::fs::remove_file(&temp_path);
+  }
+  result
+}
+
+// Creates a new file with a random name in the same directory as filename,
+// so that it can be renamed over filename.
+fn create_temp_file_next_to(
+  filename: &Path,
+) -> std::io::Result<(PathBuf, File)> {
+  let name = filename
+    .file_name()
+    .map(|name| name.to_string_lossy().into_owned())
+    .unwrap_or_default();
+  let mut rng = rand::thread_rng();
+  loop {
+    let unique = rng.gen::<u32>();
+    let temp_path =
+      filename.with_file_name(format!(".{}.{:08x}.tmp", name, unique));
+    let r = OpenOptions::new()
+      .write(true)
+      .create_new(true)
+      .open(&temp_path);
+    match r {
+      Err(ref e) if e.kind() == ErrorKind::AlreadyExists => continue,
+      Ok(file) => return Ok((temp_path, file)),
+      Err(e) => return Err(e),
+    }
+  }
 }
 
 #[cfg(any(unix))]
@@ -41,11 +118,208 @@ fn set_permissions(_file: &mut File, _perm: u32) -> std::io::Result<()> {
   Ok(())
 }
 
+// Creates newname as a symlink to oldname. Windows has different kinds of
+// symlinks for files and directories; is_dir picks one, or if it is None the
+// kind is what oldname is. Other platforms ignore is_dir.
+#[cfg(any(unix))]
+pub fn symlink(
+  oldname: &Path,
+  newname: &Path,
+  _is_dir: Option<bool>,
+) -> std::io::Result<()> {
+  std::os::unix::fs::symlink(oldname, newname)
+}
+#[cfg(windows)]
+pub fn symlink(
+  oldname: &Path,
+  newname: &Path,
+  is_dir: Option<bool>,
+) -> std::io::Result<()> {
+  use std::os::windows::fs::{symlink_dir, symlink_file};
+  let is_dir = match is_dir {
+    Some(is_dir) => is_dir,
+    // A relative oldname is relative to the link, not the cwd.
+    None => newname
+      .parent()
+      .unwrap_or_else(|| Path::new(""))
+      .join(oldname)
+      .is_dir(),
+  };
+  if is_dir {
+    symlink_dir(oldname, newname)
+  } else {
+    symlink_file(oldname, newname)
+  }
+}
+
+// Sets the permission bits of path, following symlinks.
+#[cfg(any(unix))]
+pub fn chmod(path: &Path, mode: u32) -> std::io::Result<()> {
+  std::fs::set_permissions(path, PermissionsExt::from_mode(mode & 0o777))
+}
+#[cfg(not(any(unix)))]
+pub fn chmod(path: &Path, _mode: u32) -> std::io::Result<()> {
+  // There are no permission bits to set, but a missing file is an error.
+  std::fs::metadata(path)?;
+  warn!("chmod {} has no effect on this platform", path.display());
+  Ok(())
+}
+
+// Sets the access and modification times of path, following symlinks. Times
+// are seconds and nanoseconds since the Unix epoch, and are set to the
+// microsecond.
+#[cfg(any(unix))]
+pub fn utime(
+  path: &Path,
+  atime: (i64, u32),
+  mtime: (i64, u32),
+) -> std::io::Result<()> {
+  use std::ffi::CString;
+  use std::os::unix::ffi::OsStrExt;
+  let timeval = |(sec, nsec): (i64, u32)| libc::timeval {
+    tv_sec: sec as libc::time_t,
+    tv_usec: (nsec / 1000) as libc::suseconds_t,
+  };
+  let path = CString::new(path.as_os_str().as_bytes())?;
+  let times = [timeval(atime), timeval(mtime)];
+  if unsafe { libc::utimes(path.as_ptr(), times.as_ptr()) } != 0 {
+    return Err(std::io::Error::last_os_error());
+  }
+  Ok(())
+}
+#[cfg(windows)]
+pub fn utime(
+  path: &Path,
+  atime: (i64, u32),
+  mtime: (i64, u32),
+) -> std::io::Result<()> {
+  use std::os::windows::fs::OpenOptionsExt;
+  use std::os::windows::io::AsRawHandle;
+  #[repr(C)]
+  struct FileTime {
+    low: u32,
+    high: u32,
+  }
+  extern "system" {
+    fn SetFileTime(
+      file: *mut std::os::raw::c_void,
+      created: *const FileTime,
+      accessed: *const FileTime,
+      written: *const FileTime,
+    ) -> i32;
+  }
+  const FILE_WRITE_ATTRIBUTES: u32 = 0x100;
+  // Needed to open directories.
+  const FILE_FLAG_BACKUP_SEMANTICS: u32 = 0x0200_0000;
+  // FILETIMEs count 100ns intervals since 1601.
+  let filetime = |(sec, nsec): (i64, u32)| -> std::io::Result<FileTime> {
+    let ticks = sec
+      .checked_add(11_644_473_600)
+      .and_then(|sec| sec.checked_mul(10_000_000))
+      .and_then(|ticks| ticks.checked_add(i64::from(nsec / 100)))
+      .filter(|&ticks| ticks >= 0)
+      .ok_or_else(|| {
+        std::io::Error::new(ErrorKind::InvalidInput, "time out of range")
+      })?;
+    Ok(FileTime {
+      low: ticks as u32,
+      high: (ticks >> 32) as u32,
+    })
+  };
+  let (atime, mtime) = (filetime(atime)?, filetime(mtime)?);
+  let file = OpenOptions::new()
+    .access_mode(FILE_WRITE_ATTRIBUTES)
+    .custom_flags(FILE_FLAG_BACKUP_SEMANTICS)
+    .open(path)?;
+  let ok = unsafe {
+    SetFileTime(file.as_raw_handle(), std::ptr::null(), &atime, &mtime)
+  };
+  if ok == 0 {
+    return Err(std::io::Error::last_os_error());
+  }
+  Ok(())
+}
+
+// Returns the bytes available to the current user and the total bytes of the
+// file system that path is on.
+#[cfg(any(unix))]
+pub fn disk_usage(path: &Path) -> std::io::Result<(u64, u64)> {
+  use std::ffi::CString;
+  use std::os::unix::ffi::OsStrExt;
+  let path = CString::new(path.as_os_str().as_bytes())?;
+  let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+  if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+    return Err(std::io::Error::last_os_error());
+  }
+  let block_size = stat.f_frsize as u64;
+  Ok((stat.f_bavail as u64 * block_size, stat.f_blocks as u64 * block_size))
+}
+#[cfg(windows)]
+pub fn disk_usage(path: &Path) -> std::io::Result<(u64, u64)> {
+  use std::os::windows::ffi::OsStrExt;
+  extern "system" {
+    fn GetDiskFreeSpaceExW(
+      directory: *const u16,
+      available: *mut u64,
+      total: *mut u64,
+      free: *mut u64,
+    ) -> i32;
+  }
+  // GetDiskFreeSpaceExW() wants a directory.
+  let dir = if std::fs::metadata(path)?.is_dir() {
+    path
+  } else {
+    match path.parent() {
+      Some(parent) if parent != Path::new("") => parent,
+      _ => Path::new("."),
+    }
+  };
+  let dir: Vec<u16> = dir.as_os_str().encode_wide().chain(Some(0)).collect();
+  let (mut available, mut total, mut free) = (0, 0, 0);
+  let ok = unsafe {
+    GetDiskFreeSpaceExW(dir.as_ptr(), &mut available, &mut total, &mut free)
+  };
+  if ok == 0 {
+    return Err(std::io::Error::last_os_error());
+  }
+  Ok((available, total))
+}
+
 pub fn make_temp_dir(
   dir: Option<&Path>,
   prefix: Option<&str>,
   suffix: Option<&str>,
 ) -> std::io::Result<PathBuf> {
+  // TODO: on posix, set mode flags to 0o700.
+  make_temp(dir, prefix, suffix, |path| create_dir(path))
+}
+
+// Only the current user can read and write the file.
+pub fn make_temp_file(
+  dir: Option<&Path>,
+  prefix: Option<&str>,
+  suffix: Option<&str>,
+) -> std::io::Result<PathBuf> {
+  make_temp(dir, prefix, suffix, |path| {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    set_open_permission(&mut options, 0o600);
+    options.open(path).map(|_| ())
+  })
+}
+
+// Picks random names until create makes one that doesn't exist yet. create
+// must fail with AlreadyExists if the name is taken, so that two callers
+// can't both get the same path.
+fn make_temp<F>(
+  dir: Option<&Path>,
+  prefix: Option<&str>,
+  suffix: Option<&str>,
+  create: F,
+) -> std::io::Result<PathBuf>
+where
+  F: Fn(&Path) -> std::io::Result<()>,
+{
   let prefix_ = prefix.unwrap_or("");
   let suffix_ = suffix.unwrap_or("");
   let mut buf: PathBuf = match dir {
@@ -56,8 +330,7 @@ pub fn make_temp_dir(
   loop {
     let unique = rng.gen::<u32>();
     buf.set_file_name(format!("{}{:08x}{}", prefix_, unique, suffix_));
-    // TODO: on posix, set mode flags to 0o700.
-    let r = create_dir(buf.as_path());
+    let r = create(buf.as_path());
     match r {
       Err(ref e) if e.kind() == ErrorKind::AlreadyExists => continue,
       Ok(_) => return Ok(buf),
@@ -66,15 +339,14 @@ pub fn make_temp_dir(
   }
 }
 
-pub fn mkdir(path: &Path, perm: u32) -> std::io::Result<()> {
-  debug!("mkdir -p {}", path.display());
+// If recursive, like mkdir -p: missing parents are created and a directory
+// that exists already is not an error. A file in the way still is.
+pub fn mkdir(path: &Path, perm: u32, recursive: bool) -> std::io::Result<()> {
+  debug!("mkdir {} recursive {}", path.display(), recursive);
   let mut builder = DirBuilder::new();
-  builder.recursive(true);
+  builder.recursive(recursive);
   set_dir_permission(&mut builder, perm);
-  builder.create(path).or_else(|err| match err.kind() {
-    std::io::ErrorKind::AlreadyExists => Ok(()),
-    _ => Err(err),
-  })
+  builder.create(path)
 }
 
 #[cfg(any(unix))]
@@ -88,6 +360,17 @@ fn set_dir_permission(_builder: &mut DirBuilder, _perm: u32) {
   // NOOP on windows
 }
 
+// The permissions a file created through options gets, before the umask.
+#[cfg(any(unix))]
+pub fn set_open_permission(options: &mut OpenOptions, perm: u32) {
+  options.mode(perm & 0o777);
+}
+
+#[cfg(not(any(unix)))]
+pub fn set_open_permission(_options: &mut OpenOptions, _perm: u32) {
+  // NOOP on windows
+}
+
 pub fn normalize_path(path: &Path) -> String {
   let s = String::from(path.to_str().unwrap());
   if cfg!(windows) {
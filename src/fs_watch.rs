@@ -0,0 +1,569 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+// Watches paths for changes on a thread of their own. On Linux the changes are
+// reported by the kernel, with inotify. Elsewhere the paths are scanned
+// periodically, and changes are found by comparing each scan with the one
+// before, so several changes to a path between two scans are reported as one.
+use errors::DenoResult;
+use futures::sync::mpsc;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FsEventKind {
+  Create,
+  Modify,
+  Remove,
+}
+
+impl FsEventKind {
+  pub fn as_str(&self) -> &'static str {
+    match *self {
+      FsEventKind::Create => "create",
+      FsEventKind::Modify => "modify",
+      FsEventKind::Remove => "remove",
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FsEvent {
+  pub kind: FsEventKind,
+  pub path: PathBuf,
+}
+
+// The changes seen by a watcher, in batches of the ones seen together. Ends
+// once the watcher has stopped.
+pub type FsEvents = mpsc::UnboundedReceiver<Vec<FsEvent>>;
+
+// Watches with a thread until stopped or dropped. Directories are watched
+// recursively. Watched paths that don't exist, or have been removed, are
+// watched for being created, as long as their parent directory exists.
+pub struct FsWatcher {
+  events: Option<FsEvents>,
+  stopper: backend::Stopper,
+}
+
+impl FsWatcher {
+  // Changes made once this has returned are reported.
+  pub fn new(paths: Vec<PathBuf>) -> DenoResult<FsWatcher> {
+    let (tx, rx) = mpsc::unbounded();
+    let stopper = backend::spawn(paths, tx)?;
+    Ok(FsWatcher {
+      events: Some(rx),
+      stopper,
+    })
+  }
+
+  // Returns the changes, the first time it is called.
+  pub fn take_events(&mut self) -> Option<FsEvents> {
+    self.events.take()
+  }
+
+  // Ends the events, after any changes that have been seen already.
+  pub fn stop(&self) {
+    self.stopper.stop();
+  }
+}
+
+impl Drop for FsWatcher {
+  fn drop(&mut self) {
+    self.stop();
+  }
+}
+
+#[cfg(target_os = "linux")]
+mod backend {
+  use super::{FsEvent, FsEventKind};
+  use errors::DenoResult;
+  use futures::sync::mpsc::UnboundedSender;
+  use libc;
+  use std::collections::{HashMap, HashSet};
+  use std::ffi::{CString, OsStr, OsString};
+  use std::fs;
+  use std::io;
+  use std::mem;
+  use std::os::unix::ffi::OsStrExt;
+  use std::path::{Path, PathBuf};
+  use std::ptr;
+  use std::thread;
+
+  const MASK: u32 = libc::IN_CREATE
+    | libc::IN_DELETE
+    | libc::IN_MODIFY
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_DONT_FOLLOW;
+
+  // Wakes the watching thread up, through a pipe, to make it stop.
+  pub struct Stopper(libc::c_int);
+
+  impl Stopper {
+    pub fn stop(&self) {
+      // The thread may have stopped already and closed the other end, in
+      // which case this fails with EPIPE.
+      unsafe { libc::write(self.0, b"x".as_ptr() as *const libc::c_void, 1) };
+    }
+  }
+
+  impl Drop for Stopper {
+    fn drop(&mut self) {
+      unsafe { libc::close(self.0) };
+    }
+  }
+
+  pub fn spawn(
+    paths: Vec<PathBuf>,
+    tx: UnboundedSender<Vec<FsEvent>>,
+  ) -> DenoResult<Stopper> {
+    let flags = libc::IN_CLOEXEC | libc::IN_NONBLOCK;
+    let fd = unsafe { libc::inotify_init1(flags) };
+    if fd < 0 {
+      return Err(io::Error::last_os_error().into());
+    }
+    let mut inotify = Inotify {
+      fd,
+      watches: HashMap::new(),
+    };
+    let mut pipe = [0; 2];
+    if unsafe { libc::pipe2(pipe.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+      return Err(io::Error::last_os_error().into());
+    }
+    let stopper = Stopper(pipe[1]);
+    for path in &paths {
+      inotify.watch_root(path);
+    }
+    thread::spawn(move || {
+      inotify.run(pipe[0], &tx);
+      unsafe { libc::close(pipe[0]) };
+    });
+    Ok(stopper)
+  }
+
+  // A directory, or a watched file, that inotify reports on.
+  struct Watch {
+    path: PathBuf,
+    // Whether changes to anything in the directory, or to the file itself,
+    // are reported.
+    all: bool,
+    // Otherwise only these names in the directory are reported being created
+    // and removed. They are the watched paths the directory is the parent of.
+    names: HashSet<OsString>,
+  }
+
+  struct Inotify {
+    fd: libc::c_int,
+    watches: HashMap<libc::c_int, Watch>,
+  }
+
+  impl Drop for Inotify {
+    fn drop(&mut self) {
+      unsafe { libc::close(self.fd) };
+    }
+  }
+
+  impl Inotify {
+    fn run(
+      &mut self,
+      stop_fd: libc::c_int,
+      tx: &UnboundedSender<Vec<FsEvent>>,
+    ) {
+      let mut fds = [
+        libc::pollfd {
+          fd: self.fd,
+          events: libc::POLLIN,
+          revents: 0,
+        },
+        libc::pollfd {
+          fd: stop_fd,
+          events: libc::POLLIN,
+          revents: 0,
+        },
+      ];
+      loop {
+        if unsafe { libc::poll(fds.as_mut_ptr(), 2, -1) } < 0 {
+          if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+            continue;
+          }
+          return;
+        }
+        // Changes that were seen before stopping are still reported.
+        if fds[0].revents != 0 {
+          let events = self.read_events();
+          if !events.is_empty() && tx.unbounded_send(events).is_err() {
+            return;
+          }
+        }
+        if fds[1].revents != 0 {
+          return;
+        }
+      }
+    }
+
+    // Reads until there are no more events queued.
+    fn read_events(&mut self) -> Vec<FsEvent> {
+      let mut events = Vec::new();
+      let mut buf = [0u8; 4096];
+      loop {
+        let ptr = buf.as_mut_ptr() as *mut libc::c_void;
+        let n = unsafe { libc::read(self.fd, ptr, buf.len()) };
+        if n <= 0 {
+          break;
+        }
+        let n = n as usize;
+        let mut offset = 0;
+        while offset < n {
+          let event: libc::inotify_event = unsafe {
+            ptr::read_unaligned(buf[offset..].as_ptr() as *const _)
+          };
+          let start = offset + mem::size_of::<libc::inotify_event>();
+          offset = start + event.len as usize;
+          // The name is padded with NULs.
+          let name = &buf[start..offset];
+          let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+          let name = if len == 0 {
+            None
+          } else {
+            Some(OsStr::from_bytes(&name[..len]))
+          };
+          self.handle(event.wd, event.mask, name, &mut events);
+        }
+      }
+      // Overlapping watched paths report the same change twice.
+      events.dedup();
+      events
+    }
+
+    fn handle(
+      &mut self,
+      wd: libc::c_int,
+      mask: u32,
+      name: Option<&OsStr>,
+      events: &mut Vec<FsEvent>,
+    ) {
+      if mask & libc::IN_IGNORED != 0 {
+        self.watches.remove(&wd);
+        return;
+      }
+      let (path, is_root) = match self.watches.get(&wd) {
+        None => return,
+        // A watched file changed. Its parent reports it being removed.
+        Some(watch) if name.is_none() => {
+          if watch.all && mask & libc::IN_MODIFY != 0 {
+            events.push(event(FsEventKind::Modify, watch.path.clone()));
+          }
+          return;
+        }
+        Some(watch) => {
+          let name = name.unwrap();
+          let is_root = watch.names.contains(name);
+          // A watched file reports its own changes.
+          if !watch.all && !(is_root && mask & libc::IN_MODIFY == 0) {
+            return;
+          }
+          (watch.path.join(name), is_root)
+        }
+      };
+      let is_dir = mask & libc::IN_ISDIR != 0;
+      if mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+        events.push(event(FsEventKind::Create, path.clone()));
+        if is_dir || is_root {
+          // What it contains was there before it could be watched, so it is
+          // new too.
+          self.watch_tree(&path, Some(events));
+        }
+      } else if mask & (libc::IN_DELETE | libc::IN_MOVED_FROM) != 0 {
+        events.push(event(FsEventKind::Remove, path.clone()));
+        if mask & libc::IN_MOVED_FROM != 0 && is_dir {
+          // Still watched wherever it was moved to.
+          self.unwatch_tree(&path);
+        }
+      } else if mask & libc::IN_MODIFY != 0 && !is_dir {
+        events.push(event(FsEventKind::Modify, path));
+      }
+    }
+
+    fn watch_root(&mut self, path: &Path) {
+      if let (Some(parent), Some(name)) = (path.parent(), path.file_name()) {
+        if let Some(watch) = self.add_watch(parent) {
+          watch.names.insert(name.to_os_string());
+        }
+      }
+      self.watch_tree(path, None);
+    }
+
+    // Watches path, and if it is a directory everything in it. Symlinks are
+    // not followed. What is found in the directories is added to created.
+    fn watch_tree(
+      &mut self,
+      path: &Path,
+      mut created: Option<&mut Vec<FsEvent>>,
+    ) {
+      let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(_) => return,
+      };
+      match self.add_watch(path) {
+        Some(watch) => watch.all = true,
+        None => return,
+      }
+      if !metadata.is_dir() {
+        return;
+      }
+      let dir = match fs::read_dir(path) {
+        Ok(dir) => dir,
+        Err(_) => return,
+      };
+      for entry in dir.filter_map(|entry| entry.ok()) {
+        let child = entry.path();
+        if let Some(ref mut created) = created {
+          created.push(event(FsEventKind::Create, child.clone()));
+        }
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+          self.watch_tree(&child, created.as_mut().map(|c| &mut **c));
+        }
+      }
+    }
+
+    fn unwatch_tree(&mut self, path: &Path) {
+      let fd = self.fd;
+      self.watches.retain(|&wd, watch| {
+        if !watch.path.starts_with(path) {
+          return true;
+        }
+        watch.all = false;
+        if watch.names.is_empty() {
+          unsafe { libc::inotify_rm_watch(fd, wd) };
+          return false;
+        }
+        true
+      });
+    }
+
+    // Returns None if path can't be watched, for example because it has been
+    // removed in the meantime.
+    fn add_watch(&mut self, path: &Path) -> Option<&mut Watch> {
+      // A relative path with one component has an empty parent.
+      let target = if path.as_os_str().is_empty() {
+        Path::new(".")
+      } else {
+        path
+      };
+      let target = CString::new(target.as_os_str().as_bytes()).ok()?;
+      let wd =
+        unsafe { libc::inotify_add_watch(self.fd, target.as_ptr(), MASK) };
+      if wd < 0 {
+        return None;
+      }
+      // Watching the same directory again gives the same wd.
+      Some(self.watches.entry(wd).or_insert_with(|| Watch {
+        path: path.to_path_buf(),
+        all: false,
+        names: HashSet::new(),
+      }))
+    }
+  }
+
+  fn event(kind: FsEventKind, path: PathBuf) -> FsEvent {
+    FsEvent { kind, path }
+  }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod backend {
+  use super::{FsEvent, FsEventKind};
+  use errors::DenoResult;
+  use futures::sync::mpsc::UnboundedSender;
+  use std::collections::BTreeMap;
+  use std::fs;
+  use std::path::{Path, PathBuf};
+  use std::sync::atomic::{AtomicBool, Ordering};
+  use std::sync::Arc;
+  use std::thread;
+  use std::time::{Duration, SystemTime};
+
+  // How often the watched paths are scanned, in milliseconds.
+  const SCAN_INTERVAL_MS: u64 = 50;
+
+  pub struct Stopper(Arc<AtomicBool>);
+
+  impl Stopper {
+    pub fn stop(&self) {
+      self.0.store(true, Ordering::SeqCst);
+    }
+  }
+
+  // What a scan saw of a path: whether it is a directory, and for files when
+  // they were last modified and how long they are.
+  type Entry = (bool, Option<SystemTime>, u64);
+
+  pub fn spawn(
+    paths: Vec<PathBuf>,
+    tx: UnboundedSender<Vec<FsEvent>>,
+  ) -> DenoResult<Stopper> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_ = stop.clone();
+    let mut entries = scan(&paths);
+    thread::spawn(move || {
+      while !stop_.load(Ordering::SeqCst) {
+        thread::sleep(Duration::from_millis(SCAN_INTERVAL_MS));
+        let next = scan(&paths);
+        let events = diff(&entries, &next);
+        entries = next;
+        if !events.is_empty() && tx.unbounded_send(events).is_err() {
+          break;
+        }
+      }
+    });
+    Ok(Stopper(stop))
+  }
+
+  fn scan(paths: &[PathBuf]) -> BTreeMap<PathBuf, Entry> {
+    let mut entries = BTreeMap::new();
+    for path in paths {
+      scan_path(path, &mut entries);
+    }
+    entries
+  }
+
+  // Symlinks are not followed, so that links to a parent directory can't make
+  // a scan go on forever.
+  fn scan_path(path: &Path, entries: &mut BTreeMap<PathBuf, Entry>) {
+    // The path can be removed at any time during the scan, in which case it is
+    // left out.
+    let metadata = match fs::symlink_metadata(path) {
+      Ok(metadata) => metadata,
+      Err(_) => return,
+    };
+    if metadata.is_dir() {
+      entries.insert(path.to_path_buf(), (true, None, 0));
+      if let Ok(dir) = fs::read_dir(path) {
+        for entry in dir.filter_map(|entry| entry.ok()) {
+          scan_path(&entry.path(), entries);
+        }
+      }
+    } else {
+      let modified = metadata.modified().ok();
+      entries.insert(path.to_path_buf(), (false, modified, metadata.len()));
+    }
+  }
+
+  // Directories are only created and removed; a file changing inside one
+  // isn't a change to the directory itself.
+  fn diff(
+    before: &BTreeMap<PathBuf, Entry>,
+    after: &BTreeMap<PathBuf, Entry>,
+  ) -> Vec<FsEvent> {
+    let mut events = Vec::new();
+    {
+      let mut event = |kind, path: &PathBuf| {
+        events.push(FsEvent {
+          kind,
+          path: path.clone(),
+        })
+      };
+      for (path, entry) in after {
+        match before.get(path) {
+          None => event(FsEventKind::Create, path),
+          Some(old) if old.0 != entry.0 => {
+            // Replaced with a file by a directory, or the other way round.
+            event(FsEventKind::Remove, path);
+            event(FsEventKind::Create, path);
+          }
+          Some(old) if !entry.0 && old != entry => {
+            event(FsEventKind::Modify, path)
+          }
+          Some(_) => {}
+        }
+      }
+      for path in before.keys() {
+        if !after.contains_key(path) {
+          event(FsEventKind::Remove, path);
+        }
+      }
+    }
+    events.sort_by(|a, b| a.path.cmp(&b.path));
+    events
+  }
+
+  #[cfg(test)]
+  mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+      let mut before = BTreeMap::new();
+      before.insert(PathBuf::from("/a"), (true, None, 0));
+      before.insert(PathBuf::from("/a/kept"), (false, None, 1));
+      before.insert(PathBuf::from("/a/gone"), (false, None, 1));
+      before.insert(PathBuf::from("/a/changed"), (false, None, 1));
+      let mut after = before.clone();
+      after.remove(&PathBuf::from("/a/gone"));
+      after.insert(PathBuf::from("/a/changed"), (false, None, 2));
+      after.insert(PathBuf::from("/a/new"), (false, None, 0));
+      let events = diff(&before, &after);
+      let events: Vec<(FsEventKind, &str)> = events
+        .iter()
+        .map(|e| (e.kind, e.path.to_str().unwrap()))
+        .collect();
+      assert_eq!(
+        events,
+        vec![
+          (FsEventKind::Modify, "/a/changed"),
+          (FsEventKind::Remove, "/a/gone"),
+          (FsEventKind::Create, "/a/new"),
+        ]
+      );
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use futures::Stream;
+  use std::fs;
+  use std::path::Path;
+  use tempfile::TempDir;
+
+  // Waits for the watcher to report kind for path, skipping other events.
+  fn wait_for<I>(events: &mut I, kind: FsEventKind, path: &Path)
+  where
+    I: Iterator<Item = Result<Vec<FsEvent>, ()>>,
+  {
+    loop {
+      let events = events.next().expect("watcher stopped").unwrap();
+      if events.iter().any(|e| e.kind == kind && e.path == path) {
+        return;
+      }
+    }
+  }
+
+  #[test]
+  fn test_fs_watcher() {
+    let dir = TempDir::new().unwrap();
+    let watched = dir.path().join("watched");
+    fs::create_dir(&watched).unwrap();
+    let mut watcher = FsWatcher::new(vec![watched.clone()]).unwrap();
+    let mut events = watcher.take_events().unwrap().wait();
+    assert!(watcher.take_events().is_none());
+
+    let file = watched.join("subdir/file.txt");
+    fs::create_dir(watched.join("subdir")).unwrap();
+    fs::write(&file, b"hello").unwrap();
+    wait_for(&mut events, FsEventKind::Create, &file);
+    fs::write(&file, b"hello world").unwrap();
+    wait_for(&mut events, FsEventKind::Modify, &file);
+
+    // Removing the watched directory itself is a change too, and so is
+    // creating it again.
+    fs::remove_dir_all(&watched).unwrap();
+    wait_for(&mut events, FsEventKind::Remove, &watched);
+    fs::create_dir(&watched).unwrap();
+    wait_for(&mut events, FsEventKind::Create, &watched);
+    // It is watched again.
+    fs::write(watched.join("again.txt"), b"").unwrap();
+    wait_for(&mut events, FsEventKind::Create, &watched.join("again.txt"));
+
+    watcher.stop();
+    for _ in events {}
+  }
+}
@@ -1,5 +1,6 @@
 // Copyright 2018 the Deno authors. All rights reserved. MIT license.
 
+use deno_dir::CancellationToken;
 use errors;
 use errors::{DenoError, DenoResult};
 use tokio_util;
@@ -12,6 +13,9 @@ use hyper::client::Client;
 use hyper::client::HttpConnector;
 use hyper::Uri;
 use hyper_rustls;
+use std::time::Duration;
+use std::time::Instant;
+use tokio::timer::Interval;
 
 type Connector = hyper_rustls::HttpsConnector<HttpConnector>;
 
@@ -30,7 +34,12 @@ pub fn get_client() -> Client<Connector, hyper::Body> {
 
 // The CodeFetch message is used to load HTTP javascript resources and expects a
 // synchronous response, this utility method supports that.
-pub fn fetch_sync_string(module_name: &str) -> DenoResult<String> {
+// The download is dropped, and Cancelled returned, soon after cancel is
+// cancelled.
+pub fn fetch_sync_string(
+  module_name: &str,
+  cancel: &CancellationToken,
+) -> DenoResult<String> {
   let url = module_name.parse::<Uri>().unwrap();
   let client = get_client();
   let fetch_future = client
@@ -51,14 +60,29 @@ pub fn fetch_sync_string(module_name: &str) -> DenoResult<String> {
           .map_err(|err| DenoError::from(err)),
       )
     });
-  tokio_util::block_on(fetch_future)
+  let cancel = cancel.clone();
+  let cancelled = Interval::new(Instant::now(), Duration::from_millis(10))
+    .map_err(|err| errors::new(errors::ErrorKind::Other, err.to_string()))
+    .skip_while(move |_| Ok(!cancel.is_cancelled()))
+    .into_future()
+    .map_err(|(err, _rest)| err)
+    .and_then(|_| -> DenoResult<String> { Err(errors::cancelled()) });
+  tokio_util::block_on(
+    fetch_future
+      .select(cancelled)
+      .map(|(source, _next)| source)
+      .map_err(|(err, _next)| err),
+  )
 }
 
 #[test]
 fn test_fetch_sync_string() {
   // Relies on external http server. See tools/http_server.py
   tokio_util::init(|| {
-    let p = fetch_sync_string("http://127.0.0.1:4545/package.json").unwrap();
+    let p = fetch_sync_string(
+      "http://127.0.0.1:4545/package.json",
+      &CancellationToken::default(),
+    ).unwrap();
     println!("package.json len {}", p.len());
     assert!(p.len() > 1);
   });
@@ -4,23 +4,46 @@
 // TODO Currently this module uses Tokio, but it would be nice if they were
 // decoupled.
 
+use codec::{Codec, RecordCodec, RecordOp};
 use deno_dir;
-use errors::DenoError;
+use errors;
+use errors::{DenoError, DenoResult, ErrorKind};
 use flags;
+use isolate_config::IsolateConfig;
 use libdeno;
+use multi_loop::Wakeup;
+use permissions;
+use permissions::{Permission, PermissionPrompt, Permissions};
+use resources;
 
+#[cfg(feature = "tracing")]
+use futures::{Async, Poll};
 use futures::Future;
+use futures::Stream;
+use libc::c_char;
+use libc::c_int;
 use libc::c_void;
 use std;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::ffi::CStr;
 use std::ffi::CString;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
 use std::sync::mpsc;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::sync::RwLock;
 use std::time::Duration;
 use std::time::Instant;
 use tokio;
 use tokio_util;
+#[cfg(feature = "tracing")]
+use tracing;
 
 type DenoException<'a> = &'a str;
 
@@ -34,42 +57,690 @@ pub type Buf = Box<[u8]>;
 // which yields either a DenoError or a byte array.
 pub type Op = Future<Item = Buf, Error = DenoError> + Send;
 
+// The response of an op, in pieces. See chunked_response().
+pub type OpStream = Stream<Item = Buf, Error = DenoError> + Send;
+
 // Returns (is_sync, op)
 pub type Dispatch =
   fn(isolate: &mut Isolate, buf: &[u8], data_buf: &'static mut [u8])
     -> (bool, Box<Op>);
 
+// Handles an op registered with Isolate::register_op(). It gets the same
+// buffers as Dispatch, but whether the op is sync is decided by the Dispatch
+// function that looks it up.
+pub type OpHandler =
+  fn(isolate: &mut Isolate, control: &[u8], data: &'static mut [u8])
+    -> Box<Op>;
+
+// A group of related ops, like the ones of a subsystem, that are registered
+// together with Isolate::load_op_module(). Leaving a module out disables the
+// subsystem.
+pub trait OpModule {
+  // Registers the module's ops, with register_op() or register_record_op().
+  fn register(&self, isolate: &mut Isolate);
+}
+
+#[derive(Clone)]
+struct RegisteredOp {
+  name: Option<String>,
+  handler: OpHandler,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConsoleLevel {
+  // console.log() and console.info().
+  Log,
+  // console.error() and console.warn().
+  Error,
+}
+
+// Receives console output instead of stdout and stderr. See
+// Isolate::set_console_sink().
+pub type ConsoleSink = Box<Fn(ConsoleLevel, &str) + Send>;
+
+// What the callback set with Isolate::set_on_gc() is told about.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GcEvent {
+  // The event loop gave V8 deadline to collect garbage in, see
+  // flags.idle_gc. done is true if V8 had nothing left to collect.
+  Idle { deadline: Duration, done: bool },
+  // V8 collected garbage, in the whole heap if full and otherwise only in the
+  // young generation.
+  Collected { full: bool },
+}
+
+pub type GcCallback = Box<Fn(GcEvent) + Send>;
+
+// Timers due sooner than this aren't worth giving V8 idle time for.
+const IDLE_GC_MIN_WAIT_MS: u64 = 10;
+
+// A promise rejected without a handler, or an exception thrown by the
+// libdeno.recv() callback. See Isolate::take_rejections().
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rejection {
+  // The op whose response was being delivered when this happened, if any.
+  pub req_id: Option<i32>,
+  pub message: String,
+}
+
+impl fmt::Display for Rejection {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self.req_id {
+      Some(req_id) => {
+        write!(f, "{} (in response to req_id {})", self.message, req_id)
+      }
+      None => write!(f, "{}", self.message),
+    }
+  }
+}
+
+// What Isolate::poll_once() did.
+#[derive(Debug)]
+pub enum LoopStep {
+  // Handled a message or fired the timer.
+  Ran,
+  // Nothing to do until a message arrives or the timer is due.
+  Waiting,
+  // The loop is finished. This is what event_loop() would have returned.
+  Done(DenoResult<()>),
+}
+
+// Why the event loop woke up, for finding isolates that wake up more often
+// than they need to. Only counted if flags.record_wakeups is set.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WakeupStats {
+  // The global timer was due.
+  pub timer: u64,
+  // An async op completed or sent a chunk of its response.
+  pub op: u64,
+  // A task posted with IsolateHandle::post() arrived.
+  pub task: u64,
+  // There was nothing to do, like a response to a context that has been
+  // reset since the op was dispatched.
+  pub spurious: u64,
+}
+
+// An async op that has been dispatched but not yet responded to. See
+// Isolate::pending_ops_detail().
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingOpInfo {
+  pub req_id: i32,
+  // As reported by the dispatch function with Isolate::set_dispatch_op_id().
+  pub op_id: Option<u32>,
+  // Since the op was dispatched, according to the isolate's clock.
+  pub elapsed: Duration,
+}
+
+// The async ops of an op id that has a limit in flags.op_concurrency_limits.
+#[derive(Default)]
+struct OpQueue {
+  // The generation and req_id of each op that has been let through and hasn't
+  // completed yet.
+  running: HashSet<(usize, i32)>,
+  // Ops waiting for one of those to complete, in dispatch order.
+  queued: VecDeque<(i32, Box<Op>)>,
+}
+
+// Why Isolate::stop_event_loop() was called.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StopReason {
+  // event_loop() returns Ok.
+  Finished,
+  // event_loop() returns a LoopAborted error with this message.
+  Aborted(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+  // From JS to Rust, passed to libdeno.send().
+  Incoming,
+  // From Rust to JS, a response.
+  Outgoing,
+}
+
+// Is shown every buffer that crosses between JS and Rust, with the req_id it
+// belongs to. For each libdeno.send() it sees the control buffer and then, if
+// it isn't empty, the data buffer. See Isolate::set_inspector().
+pub type Inspector = Box<Fn(Direction, i32, &[u8]) + Send + Sync>;
+
+// Called with the name of each module the first time JS fetches it, and the
+// specifiers it imports, as written in its source. See
+// Isolate::set_on_module_load().
+pub type ModuleLoadCallback = Box<Fn(&str, &[String]) + Send + Sync>;
+
+// Gets a module specifier and the file importing it, and returns the
+// specifier to resolve instead, or None to leave it as it is. For import maps
+// and aliases. See Isolate::set_specifier_rewrite().
+pub type SpecifierRewrite =
+  Box<Fn(&str, &str) -> Option<String> + Send + Sync>;
+
+// Where the responses of ops come from and go back to once JS has them, for
+// embedders with their own memory management. Bufs are boxes that the global
+// allocator frees when they are dropped, so an allocator can't hand out other
+// memory, but it can pool and reuse buffers, or account for them. See
+// Isolate::set_buf_allocator().
+pub trait BufAllocator: Send + Sync {
+  // The ops of ops::dispatch get the buffers of their responses from here.
+  fn alloc(&self, len: usize) -> Buf;
+  // Gets back every response once libdeno has copied it, whichever allocator
+  // it came from.
+  fn free(&self, buf: Buf);
+}
+
+// The default BufAllocator.
+pub struct GlobalBufAllocator;
+
+impl BufAllocator for GlobalBufAllocator {
+  fn alloc(&self, len: usize) -> Buf {
+    vec![0; len].into_boxed_slice()
+  }
+
+  fn free(&self, buf: Buf) {
+    drop(buf);
+  }
+}
+
+// libdeno allocates the req_id of each libdeno.send() call, counting up from
+// 0. Negative req_ids are reserved for responses that aren't an answer to a
+// send(). JS tells the timer apart by its req_id.
+pub const TIMER_RESPONSE_ID: i32 = -1;
+
+// The chunks that chunked_response() delivers before the last one. They don't
+// complete a send(), so JS finds the request they belong to from their
+// contents.
+pub const CHUNK_RESPONSE_ID: i32 = -2;
+
+pub fn is_reserved_req_id(req_id: i32) -> bool {
+  req_id < 0
+}
+
+// The source of monotonic time used by ops. It can be replaced with
+// Isolate::set_clock(), for example to make tests deterministic.
+pub type Clock = fn() -> Instant;
+
+// Work that is sent from another thread, with IsolateHandle::post(), to be run
+// on the isolate's thread. Box<FnOnce> cannot be called directly, hence this
+// trait.
+pub trait IsolateTask: Send {
+  fn run(self: Box<Self>, isolate: &mut Isolate);
+}
+
+impl<F: FnOnce(&mut Isolate) + Send> IsolateTask for F {
+  fn run(self: Box<Self>, isolate: &mut Isolate) {
+    (*self)(isolate)
+  }
+}
+
+// What the event loop receives on rx.
+enum Message {
+  // An async op, dispatched by the context of the given generation, completed
+  // with a response for req_id.
+  OpDone(usize, i32, Buf),
+  // A chunk of a chunked_response(), from the context of the given
+  // generation, that doesn't complete the op yet.
+  OpChunk(usize, Buf),
+  Task(Box<IsolateTask>),
+  // The global timer fired. This is never sent on the channel, it is only
+  // used to buffer the timer while the event loop is paused.
+  Timeout,
+}
+
 pub struct Isolate {
   libdeno_isolate: *const libdeno::isolate,
+  id: usize,
+  config: IsolateConfig,
   dispatch: Dispatch,
-  rx: mpsc::Receiver<(i32, Buf)>,
+  // Ops added with register_op(), by op id.
+  ops: BTreeMap<u32, RegisteredOp>,
+  // Ops added with register_record_op(), by name.
+  record_ops: HashMap<String, RecordOp>,
+  console_sink: Option<ConsoleSink>,
+  on_gc: Option<GcCallback>,
+  rx: mpsc::Receiver<Message>,
   ntasks: i32,
+  // How many spawned ops of each context generation haven't completed.
+  ops_by_generation: HashMap<usize, usize>,
+  // V8 isolates replaced by reset() while ops they gave data buffers to were
+  // still in flight, by generation. Each is deleted once its last op is done.
+  retired_isolates: HashMap<usize, *const libdeno::isolate>,
+  // Set by the dispatch function through set_dispatch_op_id().
+  dispatch_op_id: Option<u32>,
+  // How many pre_dispatch() calls are on the stack. Dispatches nest when JS
+  // calls libdeno.send() from a recv callback, which itself runs inside
+  // respond(). Async responses must only be delivered at depth 0, so that a
+  // sync response is always the next thing JS sees after its send().
+  dispatch_depth: usize,
+  // How many execute() calls are on the stack. See flags.max_execute_depth.
+  execute_depth: usize,
+  // See pause().
+  paused: bool,
+  paused_queue: VecDeque<Message>,
+  // The state of the generator that shuffles completions for
+  // flags.deterministic_ops.
+  deterministic_rng: u64,
+  stop_reason: Option<StopReason>,
+  op_ordering: OpOrdering,
+  wakeup_stats: WakeupStats,
+  // Exceptions thrown by libdeno.recv() while responding or firing the timer,
+  // when rejections are collected.
+  recv_exceptions: Vec<Rejection>,
   pub timeout_due: Option<Instant>,
   pub state: Arc<IsolateState>,
 }
 
+// How many completed ops Isolate::op_ordering() remembers. Older ones are
+// forgotten, so that a long running isolate doesn't keep them all.
+const OP_ORDERING_CAPACITY: usize = 10_000;
+
+// Sequence numbers for Isolate::op_ordering().
+#[derive(Default)]
+struct OpOrdering {
+  dispatched: usize,
+  completed: usize,
+  // The dispatch sequence numbers of ops that haven't completed, by req_id.
+  in_flight: HashMap<i32, usize>,
+  // The last OP_ORDERING_CAPACITY completed ops, in completion order.
+  done: VecDeque<(i32, usize, usize)>,
+}
+
+impl OpOrdering {
+  fn dispatch(&mut self, req_id: i32) {
+    self.in_flight.insert(req_id, self.dispatched);
+    self.dispatched += 1;
+  }
+
+  fn complete(&mut self, req_id: i32) {
+    if let Some(dispatch_seq) = self.in_flight.remove(&req_id) {
+      if self.done.len() == OP_ORDERING_CAPACITY {
+        self.done.pop_front();
+      }
+      self.done.push_back((req_id, dispatch_seq, self.completed));
+      self.completed += 1;
+    }
+  }
+}
+
+// Sizes of the V8 heap, in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct HeapStats {
+  pub total_heap_size: usize,
+  pub used_heap_size: usize,
+  pub heap_size_limit: usize,
+}
+
+// A snapshot of the health of an isolate. See Isolate::status().
+#[derive(Clone, Debug, PartialEq)]
+pub struct Status {
+  // Time since the isolate was created, according to its clock.
+  pub uptime: Duration,
+  // Async ops that haven't completed, including queued ones.
+  pub pending_ops: usize,
+  pub heap: HeapStats,
+  // Bytes transferred by each op id, see Metrics.
+  pub op_bytes: BTreeMap<u32, u64>,
+}
+
+impl Status {
+  pub fn to_json(&self) -> String {
+    let op_bytes: Vec<String> = self
+      .op_bytes
+      .iter()
+      .map(|(op_id, n)| format!("\"{}\":{}", op_id, n))
+      .collect();
+    let heap = format!(
+      "{{\"total_heap_size\":{},\"used_heap_size\":{},\"heap_size_limit\":{}}}",
+      self.heap.total_heap_size,
+      self.heap.used_heap_size,
+      self.heap.heap_size_limit
+    );
+    let uptime_ms =
+      self.uptime.as_secs() * 1000 + u64::from(self.uptime.subsec_millis());
+    format!(
+      "{{\"uptime_ms\":{},\"pending_ops\":{},\"heap\":{},\"op_bytes\":{{{}}}}}",
+      uptime_ms,
+      self.pending_ops,
+      heap,
+      op_bytes.join(",")
+    )
+  }
+}
+
+// The values of an isolate's Metrics at some point. See
+// Isolate::export_metrics().
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MetricsSnapshot {
+  pub op_bytes: BTreeMap<u32, u64>,
+}
+
+// Counters about the work done by an isolate. Shared between threads.
+#[derive(Default)]
+pub struct Metrics {
+  // Bytes of requests and responses, by op id.
+  op_bytes: Mutex<HashMap<u32, u64>>,
+}
+
+impl Metrics {
+  pub fn op_bytes(&self, op_id: u32) -> u64 {
+    *self.op_bytes.lock().unwrap().get(&op_id).unwrap_or(&0)
+  }
+
+  pub fn add_op_bytes(&self, op_id: u32, n: u64) {
+    *self.op_bytes.lock().unwrap().entry(op_id).or_insert(0) += n;
+  }
+
+  // The totals of all op ids, sorted by op id.
+  pub fn all_op_bytes(&self) -> BTreeMap<u32, u64> {
+    let op_bytes = self.op_bytes.lock().unwrap();
+    op_bytes.iter().map(|(k, v)| (*k, *v)).collect()
+  }
+}
+
 // Isolate cannot be passed between threads but IsolateState can. So any state that
 // needs to be accessed outside the main V8 thread should be inside IsolateState.
 pub struct IsolateState {
   pub dir: deno_dir::DenoDir,
+  // Replaces dir for finding and loading modules. Compiled output is still
+  // cached in dir.
+  loader: Option<Box<deno_dir::ModuleLoader>>,
+  inspector: Option<Inspector>,
+  // Starts out as what the flags allow. See Isolate::grant_permission().
+  permissions: Mutex<Permissions>,
+  permission_prompt: Option<PermissionPrompt>,
+  on_module_load: Option<ModuleLoadCallback>,
+  specifier_rewrite: Option<SpecifierRewrite>,
+  // Encodes and decodes record op requests and responses. Behind a lock so
+  // that set_codec() works while ops hold the state.
+  codec: RwLock<Arc<Codec>>,
+  // Behind a lock for the same reason as codec.
+  buf_allocator: RwLock<Arc<BufAllocator>>,
+  // Modules that on_module_load has been called for.
+  loaded_modules: Mutex<HashSet<String>>,
   pub argv: Vec<String>,
   pub flags: flags::DenoFlags,
-  tx: Mutex<Option<mpsc::Sender<(i32, Buf)>>>,
+  pub clock: Clock,
+  // When the isolate was created, according to clock.
+  pub start_time: Instant,
+  // Named marks recorded by performance.mark() in JS.
+  pub perf_marks: Mutex<Vec<(String, Instant)>>,
+  pub metrics: Metrics,
+  // Resources handed out by ops of this isolate. Closed by reset().
+  pub resource_table: Mutex<resources::ResourceTable>,
+  tx: Mutex<Option<mpsc::Sender<Message>>>,
+  // Tasks that have been posted but not yet run. The event loop isn't idle
+  // while this is non-zero.
+  posted_tasks: AtomicUsize,
+  // Incremented by Isolate::reset(). req_ids are only unique within a
+  // context, so responses are tagged with the generation that dispatched them.
+  generation: AtomicUsize,
+  // Async ops that could not be spawned because max_concurrent_ops was
+  // reached. They are started, in order, as running ops complete.
+  pending_ops: Mutex<VecDeque<(i32, Box<Op>)>>,
+  // By op id, for the op ids in flags.op_concurrency_limits. Queued ops are
+  // discarded by reset(), like pending_ops.
+  op_queues: Mutex<HashMap<u32, OpQueue>>,
+  // Notified whenever a message is sent, for the MultiIsolateLoop that runs
+  // this isolate.
+  wakeup: Mutex<Option<Arc<Wakeup>>>,
+  // The req_ids that have been dispatched but not yet responded to, with the
+  // op id set by the dispatch function, if any, and when they were
+  // dispatched. Cleared by reset().
+  outstanding_ops: Mutex<BTreeMap<i32, (Option<u32>, Instant)>>,
+  // Cancelled by IsolateHandle::cancel_module_loading(). Cleared by reset()
+  // and by fetch_module_graph() when it gives up.
+  module_loading_cancel: deno_dir::CancellationToken,
+  // See Isolate::exit_code(). Cleared by reset().
+  exit_code: Mutex<i32>,
 }
 
 impl IsolateState {
+  pub fn now(&self) -> Instant {
+    (self.clock)()
+  }
+
+  pub fn codec(&self) -> Arc<Codec> {
+    self.codec.read().unwrap().clone()
+  }
+
+  pub fn buf_allocator(&self) -> Arc<BufAllocator> {
+    self.buf_allocator.read().unwrap().clone()
+  }
+
+  // Records the code the process should exit with once the event loop is
+  // done, without stopping it. See Isolate::exit_code().
+  pub fn set_exit_code(&self, code: i32) {
+    *self.exit_code.lock().unwrap() = code;
+  }
+
+  // The loader set with Isolate::set_module_loader(), or else dir.
+  pub fn module_loader(&self) -> &deno_dir::ModuleLoader {
+    match self.loader {
+      Some(ref loader) => &**loader,
+      None => &self.dir,
+    }
+  }
+
+  // Ok if perm is currently granted, or else if the permission prompt allows
+  // it for what. See permissions::check().
+  pub fn check_permission(
+    &self,
+    perm: Permission,
+    what: &str,
+  ) -> DenoResult<()> {
+    let permissions = *self.permissions.lock().unwrap();
+    let prompt = self.permission_prompt.as_ref();
+    permissions::check(&permissions, prompt, perm, what)
+  }
+
+  // Calls on_module_load, if it is set and hasn't been called for
+  // module_name yet. Called by op_code_fetch for every module it fetches.
+  pub fn module_loaded(&self, module_name: &str, source_code: &str) {
+    if let Some(ref on_module_load) = self.on_module_load {
+      let mut loaded_modules = self.loaded_modules.lock().unwrap();
+      if loaded_modules.insert(module_name.to_string()) {
+        let imports = deno_dir::scan_imports(module_name, source_code);
+        on_module_load(module_name, &imports);
+      }
+    }
+  }
+
+  // Adds the n bytes of a request for op_id to its total, unless that would
+  // go over the op's quota in flags.op_byte_quotas.
+  pub fn charge_op_bytes(&self, op_id: u32, n: u64) -> DenoResult<()> {
+    let mut op_bytes = self.metrics.op_bytes.lock().unwrap();
+    let total = op_bytes.entry(op_id).or_insert(0);
+    if let Some(&quota) = self.flags.op_byte_quotas.get(&op_id) {
+      if *total + n > quota {
+        return Err(errors::quota_exceeded());
+      }
+    }
+    *total += n;
+    Ok(())
+  }
+
+  // Thread safe. The response is sent even if the context of generation is
+  // gone, so that the event loop can account for the op, and dropped there.
+  // Under StaleResponsePolicy::Error that also returns a ContextGone error.
+  // A reset can still happen after this returns Ok.
+  fn send_to_js(
+    &self,
+    generation: usize,
+    req_id: i32,
+    buf: Buf,
+  ) -> DenoResult<()> {
+    let gone = generation != self.generation.load(Ordering::SeqCst);
+    self.send(Message::OpDone(generation, req_id, buf));
+    match self.flags.stale_response_policy {
+      flags::StaleResponsePolicy::Error if gone => {
+        Err(errors::context_gone(req_id))
+      }
+      _ => Ok(()),
+    }
+  }
+
+  // Thread safe. Stale chunks are dropped by the event loop, whatever the
+  // StaleResponsePolicy, since the op will report on its last chunk.
+  fn send_chunk(&self, generation: usize, buf: Buf) {
+    self.send(Message::OpChunk(generation, buf));
+  }
+
   // Thread safe.
-  fn send_to_js(&self, req_id: i32, buf: Buf) {
+  fn send(&self, msg: Message) {
     let mut g = self.tx.lock().unwrap();
     let maybe_tx = g.as_mut();
     assert!(maybe_tx.is_some(), "Expected tx to not be deleted.");
     let tx = maybe_tx.unwrap();
-    tx.send((req_id, buf)).expect("tx.send error");
+    tx.send(msg).expect("tx.send error");
+    if let Some(ref wakeup) = *self.wakeup.lock().unwrap() {
+      wakeup.notify();
+    }
+  }
+}
+
+// Makes an op out of a response that is too big to allocate as one Buf. Each
+// chunk but the last is sent to JS as soon as the next one is ready, and the
+// op completes with the last one. There is no backpressure: sent chunks wait
+// on the isolate's unbounded channel until the event loop delivers them, so
+// a stream that is faster than JS, or one that runs while the loop is paused,
+// can have all of its chunks in memory at once. The chunks must be responses
+// that JS can match to the request, and there must be at least one. Only for
+// async ops: the chunks of a sync op would arrive after it had returned.
+pub fn chunked_response(
+  state: Arc<IsolateState>,
+  chunks: Box<OpStream>,
+) -> Box<Op> {
+  let generation = state.generation.load(Ordering::SeqCst);
+  let op = chunks
+    .fold(None, move |prev: Option<Buf>, buf| {
+      if let Some(prev) = prev {
+        state.send_chunk(generation, prev);
+      }
+      Ok::<_, DenoError>(Some(buf))
+    }).map(|last| last.expect("chunked_response() got no chunks"));
+  Box::new(op)
+}
+
+// Like chunked_response(), for streams whose chunks can be far apart, such as
+// events. Each chunk is delivered as soon as it is ready, and once the stream
+// ends the op completes with what last() makes of the number of chunks.
+pub fn streaming_response<F>(
+  state: Arc<IsolateState>,
+  chunks: Box<OpStream>,
+  last: F,
+) -> Box<Op>
+where
+  F: FnOnce(u32) -> Buf + Send + 'static,
+{
+  let generation = state.generation.load(Ordering::SeqCst);
+  let op = chunks
+    .fold(0, move |n, buf| {
+      state.send_chunk(generation, buf);
+      Ok::<_, DenoError>(n + 1)
+    }).map(last);
+  Box::new(op)
+}
+
+// Resolves modules with the module loader, after applying the specifier
+// rewrite. This is what op_code_fetch and fetch_module_graph() load modules
+// with.
+impl deno_dir::ModuleLoader for IsolateState {
+  fn resolve(
+    &self,
+    module_specifier: &str,
+    containing_file: &str,
+  ) -> DenoResult<(String, String)> {
+    let rewritten = match self.specifier_rewrite {
+      Some(ref rewrite) => rewrite(module_specifier, containing_file),
+      None => None,
+    };
+    let module_specifier = match rewritten {
+      Some(ref specifier) => specifier.as_str(),
+      None => module_specifier,
+    };
+    self.module_loader().resolve(module_specifier, containing_file)
+  }
+
+  // Fails with Cancelled once module loading has been cancelled, including a
+  // load that was already in progress.
+  fn load(
+    &self,
+    module_name: &str,
+    filename: &str,
+  ) -> DenoResult<deno_dir::CodeFetchOutput> {
+    self.module_loader().load_cancellable(
+      module_name,
+      filename,
+      &self.module_loading_cancel,
+    )
+  }
+}
+
+// Isolate is not Send, but IsolateHandle is. It lets other threads schedule
+// work onto the isolate's thread.
+#[derive(Clone)]
+pub struct IsolateHandle {
+  state: Arc<IsolateState>,
+}
+
+impl IsolateHandle {
+  // Queues f to be run on the isolate's thread by the event loop. Tasks
+  // posted before or while event_loop() runs keep it from returning until
+  // they have run. A task posted after the loop has gone idle runs the next
+  // time event_loop() is called.
+  pub fn post<F>(&self, f: F)
+  where
+    F: FnOnce(&mut Isolate) + Send + 'static,
+  {
+    self.state.posted_tasks.fetch_add(1, Ordering::SeqCst);
+    self.state.send(Message::Task(Box::new(f)));
+  }
+
+  // Makes the modules being loaded by fetch_module_graph() or for JS fail
+  // with Cancelled instead of loading the rest of the module graph. A
+  // download in progress is dropped. Loading modules keeps failing until
+  // fetch_module_graph() returns or Isolate::reset().
+  pub fn cancel_module_loading(&self) {
+    self.state.module_loading_cancel.cancel();
   }
 }
 
 static DENO_INIT: std::sync::Once = std::sync::ONCE_INIT;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IsolateLifecycle {
+  Created,
+  Destroyed,
+}
+
+// Told about every isolate of the process when it is created and when it is
+// dropped, with its Isolate::id(). It is called on the thread of the isolate,
+// so it must be quick. See on_isolate_lifecycle().
+pub type LifecycleCallback = Box<LifecycleFn>;
+type LifecycleFn = Fn(IsolateLifecycle, usize) + Send + Sync;
+
+// Isolate ids count up from 1 and aren't reused.
+static NEXT_ISOLATE_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+lazy_static! {
+  // In an Arc so that it can be called without holding the lock, which would
+  // deadlock a callback that sets a new callback.
+  static ref LIFECYCLE_CALLBACK: RwLock<Option<Arc<LifecycleFn>>> =
+    RwLock::new(None);
+}
+
+// Sets the callback for isolates being created and destroyed, for all
+// isolates of the process, replacing any callback set before. None stops
+// calling it. Isolates that already exist are not reported.
+pub fn on_isolate_lifecycle(callback: Option<LifecycleCallback>) {
+  *LIFECYCLE_CALLBACK.write().unwrap() = callback.map(Arc::from);
+}
+
+fn isolate_lifecycle(event: IsolateLifecycle, id: usize) {
+  let callback = LIFECYCLE_CALLBACK.read().unwrap().clone();
+  if let Some(callback) = callback {
+    callback(event, id);
+  }
+}
+
 impl Isolate {
   pub fn new(argv: Vec<String>, dispatch: Dispatch) -> Isolate {
     DENO_INIT.call_once(|| {
@@ -77,240 +748,3865 @@ impl Isolate {
     });
 
     let (flags, argv_rest) = flags::set_flags(argv);
-    let libdeno_isolate = unsafe { libdeno::deno_new(pre_dispatch) };
+    Isolate::from_flags(flags, argv_rest, dispatch)
+  }
+
+  // Like new(), but takes already parsed flags. Note that V8 flags must have
+  // been set by the caller (see flags::v8_set_flags) before calling this.
+  pub fn from_flags(
+    flags: flags::DenoFlags,
+    argv_rest: Vec<String>,
+    dispatch: Dispatch,
+  ) -> Isolate {
+    let config = IsolateConfig::from_flags(&flags);
+    Isolate::from_config(config, flags, argv_rest, dispatch)
+  }
+
+  // Like from_flags(), but with V8 set up as in config rather than as the
+  // flags and the environment say.
+  pub fn from_config(
+    config: IsolateConfig,
+    flags: flags::DenoFlags,
+    argv_rest: Vec<String>,
+    dispatch: Dispatch,
+  ) -> Isolate {
+    let dir = deno_dir::DenoDir::new(flags.reload, None).unwrap();
+    Isolate::from_dir(dir, config, flags, argv_rest, dispatch)
+  }
+
+  // Like from_flags(), but creates the isolate from snapshot instead of the
+  // built-in one. Many isolates can be made from the same snapshot without
+  // copying it; see IsolateConfig::snapshot.
+  pub fn new_with_snapshot(
+    snapshot: Arc<[u8]>,
+    flags: flags::DenoFlags,
+    argv_rest: Vec<String>,
+    dispatch: Dispatch,
+  ) -> Isolate {
+    let mut config = IsolateConfig::from_flags(&flags);
+    config.snapshot = Some(snapshot);
+    Isolate::from_config(config, flags, argv_rest, dispatch)
+  }
+
+  fn from_dir(
+    dir: deno_dir::DenoDir,
+    config: IsolateConfig,
+    flags: flags::DenoFlags,
+    argv_rest: Vec<String>,
+    dispatch: Dispatch,
+  ) -> Isolate {
+    DENO_INIT.call_once(|| {
+      unsafe { libdeno::deno_init() };
+    });
+
+    if !config.v8_flags.is_empty() {
+      let mut args = vec![String::from("deno")];
+      args.extend(config.v8_flags.iter().cloned());
+      let unknown = flags::v8_set_flags(args);
+      for arg in &unknown[1..] {
+        warn!("V8 doesn't know the flag {}", arg);
+      }
+    }
+    let libdeno_isolate = new_libdeno_isolate(&config, &flags);
     // This channel handles sending async messages back to the runtime.
-    let (tx, rx) = mpsc::channel::<(i32, Buf)>();
+    let (tx, rx) = mpsc::channel::<Message>();
+    let resource_table =
+      resources::ResourceTable::new(flags.max_open_resources);
 
-    Isolate {
+    let mut isolate = Isolate {
       libdeno_isolate,
+      id: NEXT_ISOLATE_ID.fetch_add(1, Ordering::SeqCst) + 1,
+      config,
       dispatch,
+      ops: BTreeMap::new(),
+      record_ops: HashMap::new(),
+      console_sink: None,
+      on_gc: None,
       rx,
       ntasks: 0,
+      ops_by_generation: HashMap::new(),
+      retired_isolates: HashMap::new(),
+      dispatch_op_id: None,
+      dispatch_depth: 0,
+      execute_depth: 0,
+      paused: false,
+      paused_queue: VecDeque::new(),
+      deterministic_rng: flags.deterministic_ops.unwrap_or(0),
+      stop_reason: None,
+      op_ordering: OpOrdering::default(),
+      wakeup_stats: WakeupStats::default(),
+      recv_exceptions: Vec::new(),
       timeout_due: None,
       state: Arc::new(IsolateState {
-        dir: deno_dir::DenoDir::new(flags.reload, None).unwrap(),
+        dir,
+        loader: None,
+        inspector: None,
+        permissions: Mutex::new(Permissions::from_flags(&flags)),
+        permission_prompt: None,
+        on_module_load: None,
+        specifier_rewrite: None,
+        codec: RwLock::new(Arc::new(RecordCodec)),
+        buf_allocator: RwLock::new(Arc::new(GlobalBufAllocator)),
+        loaded_modules: Mutex::new(HashSet::new()),
         argv: argv_rest,
         flags,
+        clock: Instant::now,
+        start_time: Instant::now(),
+        perf_marks: Mutex::new(Vec::new()),
+        metrics: Metrics::default(),
+        resource_table: Mutex::new(resource_table),
         tx: Mutex::new(Some(tx)),
+        posted_tasks: AtomicUsize::new(0),
+        generation: AtomicUsize::new(0),
+        pending_ops: Mutex::new(VecDeque::new()),
+        op_queues: Mutex::new(HashMap::new()),
+        wakeup: Mutex::new(None),
+        outstanding_ops: Mutex::new(BTreeMap::new()),
+        module_loading_cancel: deno_dir::CancellationToken::default(),
+        exit_code: Mutex::new(0),
       }),
-    }
+    };
+    isolate.install_random_seed();
+    isolate.hide_globals();
+    isolate_lifecycle(IsolateLifecycle::Created, isolate.id);
+    isolate
   }
 
-  pub fn as_void_ptr(&mut self) -> *mut c_void {
-    self as *mut _ as *mut c_void
+  // Unique among the isolates of the process. A restart() makes a new id.
+  pub fn id(&self) -> usize {
+    self.id
   }
 
-  pub fn from_void_ptr<'a>(ptr: *mut c_void) -> &'a mut Isolate {
-    let ptr = ptr as *mut _;
-    unsafe { &mut *ptr }
+  // Replaces Math.random() with a generator seeded from flags.random_seed, if
+  // that is set. Runs before any other script in the context.
+  fn install_random_seed(&mut self) {
+    let seed = match self.state.flags.random_seed {
+      Some(seed) => seed,
+      None => return,
+    };
+    let source = seeded_random_js(seed);
+    self
+      .execute("random_seed.js", &source)
+      .expect("failed to install Math.random()");
   }
 
-  pub fn execute(
-    &mut self,
-    js_filename: &str,
-    js_source: &str,
-  ) -> Result<(), DenoException> {
-    let filename = CString::new(js_filename).unwrap();
-    let source = CString::new(js_source).unwrap();
-    let r = unsafe {
-      libdeno::deno_execute(
-        self.libdeno_isolate,
-        self.as_void_ptr(),
-        filename.as_ptr(),
-        source.as_ptr(),
-      )
-    };
-    if r == 0 {
-      let ptr = unsafe { libdeno::deno_last_exception(self.libdeno_isolate) };
-      let cstr = unsafe { CStr::from_ptr(ptr) };
-      return Err(cstr.to_str().unwrap());
+  // Deletes flags.hidden_globals from the global object. Globals that can't
+  // be deleted, like undefined, are left as they are, with a warning.
+  fn hide_globals(&mut self) {
+    if self.state.flags.hidden_globals.is_empty() {
+      return;
+    }
+    let names: Vec<String> = self
+      .state
+      .flags
+      .hidden_globals
+      .iter()
+      .map(|name| js_string(name))
+      .collect();
+    let source = format!(
+      "{{
+        const failed = [{}].filter(name => !delete this[name]);
+        if (failed.length > 0) {{
+          throw Error(`Cannot hide globals ${{failed.join(\", \")}}`);
+        }}
+      }}",
+      names.join(", ")
+    );
+    if let Err(msg) = self.execute("hidden_globals.js", &source) {
+      warn!("{}", msg);
     }
-    Ok(())
   }
 
-  pub fn respond(&mut self, req_id: i32, buf: Buf) {
-    // TODO(zero-copy) Use Buf::leak(buf) to leak the heap allocated buf. And
-    // don't do the memcpy in ImportBuf() (in libdeno/binding.cc)
-    unsafe {
-      libdeno::deno_respond(
-        self.libdeno_isolate,
-        self.as_void_ptr(),
-        req_id,
-        buf.into(),
-      )
-    }
+  // Returns when the earliest pending timer is due, or None if there are no
+  // timers. The JS side only reports its soonest timer to us (see
+  // setGlobalTimeout in js/timers.ts), so this covers the whole timer queue.
+  // Embedders can use this to decide how long to park the thread.
+  pub fn next_deadline(&self) -> Option<Instant> {
+    self.timeout_due
   }
 
-  fn complete_op(&mut self, req_id: i32, buf: Buf) {
-    // Receiving a message on rx exactly corresponds to an async task
-    // completing.
-    self.ntasks_decrement();
-    // Call into JS with the buf.
-    self.respond(req_id, buf);
+  // Replaces the clock and resets start_time. Must be called before any op is
+  // dispatched.
+  pub fn set_clock(&mut self, clock: Clock) {
+    let state = Arc::get_mut(&mut self.state)
+      .expect("set_clock() called while ops are in flight");
+    state.clock = clock;
+    state.start_time = clock();
   }
 
-  fn timeout(&mut self) {
-    let dummy_buf = libdeno::deno_buf {
-      alloc_ptr: 0 as *mut u8,
-      alloc_len: 0,
-      data_ptr: 0 as *mut u8,
-      data_len: 0,
-    };
-    unsafe {
-      libdeno::deno_respond(
-        self.libdeno_isolate,
-        self.as_void_ptr(),
-        -1,
-        dummy_buf,
-      )
-    }
+  // Resolves and loads modules with loader rather than from the DenoDir. Must
+  // be called before any op is dispatched.
+  pub fn set_module_loader(&mut self, loader: Box<deno_dir::ModuleLoader>) {
+    let state = Arc::get_mut(&mut self.state)
+      .expect("set_module_loader() called while ops are in flight");
+    state.loader = Some(loader);
   }
 
-  // TODO Use Park abstraction? Note at time of writing Tokio default runtime
-  // does not have new_with_park().
-  pub fn event_loop(&mut self) {
-    // Main thread event loop.
-    while !self.is_idle() {
-      // Ideally, mpsc::Receiver would have a receive method that takes a optional
-      // timeout. But it doesn't so we need all this duplicate code.
-      match self.timeout_due {
-        Some(due) => {
-          // Subtracting two Instants causes a panic if the resulting duration
-          // would become negative. Avoid this.
-          let now = Instant::now();
-          let timeout = if due > now {
-            due - now
-          } else {
-            Duration::new(0, 0)
-          };
-          // TODO: use recv_deadline() instead of recv_timeout() when this
-          // feature becomes stable/available.
-          match self.rx.recv_timeout(timeout) {
-            Ok((req_id, buf)) => self.complete_op(req_id, buf),
-            Err(mpsc::RecvTimeoutError::Timeout) => self.timeout(),
-            Err(e) => panic!("mpsc::Receiver::recv_timeout() failed: {:?}", e),
-          }
-        }
-        None => match self.rx.recv() {
-          Ok((req_id, buf)) => self.complete_op(req_id, buf),
-          Err(e) => panic!("mpsc::Receiver::recv() failed: {:?}", e),
-        },
-      };
-    }
+  // Calls inspector with the raw bytes of every op request and response, to
+  // debug the messages exchanged with JS. Must be called before any op is
+  // dispatched.
+  pub fn set_inspector(&mut self, inspector: Inspector) {
+    let state = Arc::get_mut(&mut self.state)
+      .expect("set_inspector() called while ops are in flight");
+    state.inspector = Some(inspector);
   }
 
-  fn ntasks_increment(&mut self) {
-    assert!(self.ntasks >= 0);
-    self.ntasks = self.ntasks + 1;
+  // Grants perm to ops dispatched from now on, as if it had been allowed by
+  // the flags. Ops that are already in flight have been checked.
+  pub fn grant_permission(&mut self, perm: Permission) {
+    self.state.permissions.lock().unwrap().set(perm, true);
   }
 
-  fn ntasks_decrement(&mut self) {
-    self.ntasks = self.ntasks - 1;
-    assert!(self.ntasks >= 0);
+  // Denies perm to ops dispatched from now on, unless the permission prompt
+  // allows them.
+  pub fn revoke_permission(&mut self, perm: Permission) {
+    self.state.permissions.lock().unwrap().set(perm, false);
   }
 
-  fn is_idle(&self) -> bool {
-    self.ntasks == 0 && self.timeout_due.is_none()
+  pub fn permissions(&self) -> Permissions {
+    *self.state.permissions.lock().unwrap()
   }
-}
 
-impl Drop for Isolate {
-  fn drop(&mut self) {
-    unsafe { libdeno::deno_delete(self.libdeno_isolate) }
+  // Asks prompt about permissions that aren't granted, instead of denying
+  // them. Must be called before any op is dispatched.
+  pub fn set_permission_prompt(&mut self, prompt: PermissionPrompt) {
+    let state = Arc::get_mut(&mut self.state)
+      .expect("set_permission_prompt() called while ops are in flight");
+    state.permission_prompt = Some(prompt);
   }
-}
 
-/// Converts Rust Buf to libdeno deno_buf.
-impl From<Buf> for libdeno::deno_buf {
-  fn from(x: Buf) -> libdeno::deno_buf {
-    let len = x.len();
-    let ptr = Box::into_raw(x);
-    libdeno::deno_buf {
-      alloc_ptr: 0 as *mut u8,
-      alloc_len: 0,
-      data_ptr: ptr as *mut u8,
-      data_len: len,
-    }
+  // Calls on_module_load once for every module that is loaded, to observe
+  // the module graph as it is built. Must be called before any op is
+  // dispatched.
+  pub fn set_on_module_load(&mut self, on_module_load: ModuleLoadCallback) {
+    let state = Arc::get_mut(&mut self.state)
+      .expect("set_on_module_load() called while ops are in flight");
+    state.on_module_load = Some(on_module_load);
   }
-}
 
-// Dereferences the C pointer into the Rust Isolate object.
-extern "C" fn pre_dispatch(
-  user_data: *mut c_void,
-  req_id: i32,
-  control_buf: libdeno::deno_buf,
+  // Passes the specifiers of all imports through rewrite before they are
+  // resolved. Must be called before any op is dispatched.
+  pub fn set_specifier_rewrite(&mut self, rewrite: SpecifierRewrite) {
+    let state = Arc::get_mut(&mut self.state)
+      .expect("set_specifier_rewrite() called while ops are in flight");
+    state.specifier_rewrite = Some(rewrite);
+  }
+
+  // Replaces the codec of record ops, RecordCodec by default. JS learns the
+  // codec's name at startup, so changing it afterwards only suits embedders
+  // that use codec::dispatch.
+  pub fn set_codec(&mut self, codec: Box<Codec>) {
+    *self.state.codec.write().unwrap() = Arc::from(codec);
+  }
+
+  // Replaces where op responses are allocated and freed, GlobalBufAllocator
+  // by default. Responses already allocated are still freed by the new one.
+  pub fn set_buf_allocator(&mut self, allocator: Box<BufAllocator>) {
+    *self.state.buf_allocator.write().unwrap() = Arc::from(allocator);
+  }
+
+  // Notifies wakeup whenever a message for the event loop arrives, in addition
+  // to sending it on rx. None stops notifying.
+  pub fn set_wakeup(&self, wakeup: Option<Arc<Wakeup>>) {
+    *self.state.wakeup.lock().unwrap() = wakeup;
+  }
+
+  // The completed ops as (req_id, dispatch sequence number, completion
+  // sequence number), in dispatch order. An op completes when its response
+  // is delivered to JS. Only recorded if flags.record_op_ordering is set, and
+  // only for the last OP_ORDERING_CAPACITY ops to complete.
+  pub fn op_ordering(&self) -> Vec<(i32, usize, usize)> {
+    let mut ordering: Vec<_> = self.op_ordering.done.iter().cloned().collect();
+    ordering.sort_by_key(|&(_, dispatch_seq, _)| dispatch_seq);
+    ordering
+  }
+
+  pub fn wakeup_stats(&self) -> WakeupStats {
+    self.wakeup_stats
+  }
+
+  // Called by the dispatch function to say which op the message it is
+  // handling is for. pending_ops_detail() reports it and
+  // flags.op_concurrency_limits are looked up by it, so async ops of an
+  // unreported id are never queued. ops::dispatch reports the msg::Any type,
+  // the same id it charges to flags.op_byte_quotas.
+  pub fn set_dispatch_op_id(&mut self, op_id: u32) {
+    self.dispatch_op_id = Some(op_id);
+  }
+
+  // The ops that JS is waiting on, including ones that max_concurrent_ops
+  // has queued, in req_id order. A sync op is only waited on while it is
+  // being dispatched.
+  pub fn pending_ops_detail(&self) -> Vec<PendingOpInfo> {
+    let now = self.state.now();
+    let outstanding_ops = self.state.outstanding_ops.lock().unwrap();
+    outstanding_ops
+      .iter()
+      .map(|(&req_id, &(op_id, dispatched_at))| PendingOpInfo {
+        req_id,
+        op_id,
+        elapsed: now.duration_since(dispatched_at),
+      }).collect()
+  }
+
+  // How V8 was set up for this isolate.
+  pub fn config(&self) -> &IsolateConfig {
+    &self.config
+  }
+
+  pub fn heap_stats(&self) -> HeapStats {
+    let mut stats = libdeno::deno_heap_stats::default();
+    unsafe { libdeno::deno_get_heap_stats(self.libdeno_isolate, &mut stats) };
+    HeapStats {
+      total_heap_size: stats.total_heap_size,
+      used_heap_size: stats.used_heap_size,
+      heap_size_limit: stats.heap_size_limit,
+    }
+  }
+
+  // The code the process should exit with when event_loop() returns, as last
+  // set by JS with setExitCode(), or 0. Unlike exit(), setting it doesn't stop
+  // the isolate.
+  pub fn exit_code(&self) -> i32 {
+    *self.state.exit_code.lock().unwrap()
+  }
+
+  // Everything a health check needs in one call. Nothing here blocks for
+  // long, so it's fine to call frequently.
+  pub fn status(&self) -> Status {
+    Status {
+      uptime: self.state.now().duration_since(self.state.start_time),
+      pending_ops: self.pending_op_count(),
+      heap: self.heap_stats(),
+      op_bytes: self.state.metrics.all_op_bytes(),
+    }
+  }
+
+  // True if flags.op_high_water_mark is set and reached. Scripts that start
+  // many async ops can check this and wait before starting more.
+  pub fn is_backpressured(&self) -> bool {
+    match self.state.flags.op_high_water_mark {
+      Some(mark) => self.pending_op_count() >= mark,
+      None => false,
+    }
+  }
+
+  // Async ops that haven't completed, including queued ones.
+  fn pending_op_count(&self) -> usize {
+    let queued = self.state.pending_ops.lock().unwrap().len();
+    let op_queues = self.state.op_queues.lock().unwrap();
+    let op_queued: usize = op_queues.values().map(|q| q.queued.len()).sum();
+    self.ntasks as usize + queued + op_queued
+  }
+
+  pub fn export_metrics(&self) -> MetricsSnapshot {
+    MetricsSnapshot {
+      op_bytes: self.state.metrics.all_op_bytes(),
+    }
+  }
+
+  // Adds the counters of snapshot to this isolate's, so that totals keep
+  // accumulating when an isolate is replaced by a new one.
+  pub fn seed_metrics(&mut self, snapshot: &MetricsSnapshot) {
+    for (op_id, n) in &snapshot.op_bytes {
+      self.state.metrics.add_op_bytes(*op_id, *n);
+    }
+  }
+
+  // Fetches entry, a module specifier relative to the current directory, and
+  // the modules it imports, without running or compiling any of them.
+  // Returns the names of the modules. The imports are found by scanning the
+  // source text, see deno_dir::scan_imports(), not by the TypeScript
+  // compiler, so this is only a best guess at what running entry would load,
+  // and type errors are not found by it. If it's cancelled with
+  // IsolateHandle::cancel_module_loading() the cancellation is cleared
+  // before it returns, so the next fetch_module_graph() starts afresh.
+  pub fn fetch_module_graph(
+    &mut self,
+    entry: &str,
+  ) -> DenoResult<Vec<String>> {
+    let cwd = std::env::current_dir()?;
+    let cwd = format!("{}/", cwd.display());
+    let result = deno_dir::module_graph(&*self.state, entry, &cwd);
+    self.state.module_loading_cancel.reset();
+    result
+  }
+
+  // Returns the marks recorded by performance.mark(), in the order they were
+  // made.
+  pub fn performance_marks(&self) -> Vec<(String, Instant)> {
+    self.state.perf_marks.lock().unwrap().clone()
+  }
+
+  // Replaces this isolate with a new one, as if it had been created with the
+  // same arguments, but reuses the DenoDir so that its caches, and cache_stats,
+  // stay warm. Registered ops, the console sink, on_gc, the clock, the module
+  // loader, the inspector, the permissions, the permission prompt,
+  // on_module_load, the specifier rewrite, the codec and the buf allocator
+  // are kept too.
+  // There must be no ops in flight and no IsolateHandles left, otherwise
+  // this fails and gives the isolate back, untouched.
+  pub fn restart(mut self) -> Result<Isolate, (Isolate, DenoError)> {
+    if Arc::strong_count(&self.state) != 1 {
+      return Err((self, errors::isolate_in_use()));
+    }
+    let dispatch = self.dispatch;
+    let ops = std::mem::replace(&mut self.ops, BTreeMap::new());
+    let record_ops = std::mem::replace(&mut self.record_ops, HashMap::new());
+    let console_sink = self.console_sink.take();
+    let on_gc = self.on_gc.take();
+    let config = self.config.clone();
+    let state = self.state.clone();
+    // Deletes the V8 isolate and drops self's reference to the state. Nothing
+    // else can have taken one since it was checked, without self.
+    drop(self);
+    let state = match Arc::try_unwrap(state) {
+      Ok(state) => state,
+      Err(_) => unreachable!("isolate state still in use"),
+    };
+    let clock = state.clock;
+    let loader = state.loader;
+    let inspector = state.inspector;
+    let permissions = state.permissions.into_inner().unwrap();
+    let permission_prompt = state.permission_prompt;
+    let on_module_load = state.on_module_load;
+    let specifier_rewrite = state.specifier_rewrite;
+    let codec = state.codec.into_inner().unwrap();
+    let buf_allocator = state.buf_allocator.into_inner().unwrap();
+
+    let mut isolate =
+      Isolate::from_dir(state.dir, config, state.flags, state.argv, dispatch);
+    isolate.ops = ops;
+    isolate.record_ops = record_ops;
+    *isolate.state.permissions.lock().unwrap() = permissions;
+    if let Some(loader) = loader {
+      isolate.set_module_loader(loader);
+    }
+    if let Some(inspector) = inspector {
+      isolate.set_inspector(inspector);
+    }
+    if let Some(prompt) = permission_prompt {
+      isolate.set_permission_prompt(prompt);
+    }
+    if let Some(on_module_load) = on_module_load {
+      isolate.set_on_module_load(on_module_load);
+    }
+    if let Some(rewrite) = specifier_rewrite {
+      isolate.set_specifier_rewrite(rewrite);
+    }
+    if let Some(sink) = console_sink {
+      isolate.set_console_sink(sink);
+    }
+    if let Some(on_gc) = on_gc {
+      isolate.set_on_gc(on_gc);
+    }
+    *isolate.state.codec.write().unwrap() = codec;
+    *isolate.state.buf_allocator.write().unwrap() = buf_allocator;
+    isolate.set_clock(clock);
+    Ok(isolate)
+  }
+
+  // Replaces the JS context with a fresh one, created from the snapshot as in
+  // new(). All JS state is lost. Responses to async ops dispatched by the old
+  // context are dropped when they arrive, rather than being delivered to the
+  // new context, which reuses req_ids starting from 0. See also
+  // flags::StaleResponsePolicy. Queued ops that have not started are
+  // discarded. Ops still in flight may still use their data buffers, which
+  // live in the old V8 heap, so the old V8 isolate is only deleted once they
+  // have all completed.
+  pub fn reset(&mut self) {
+    let old_generation = self.state.generation.fetch_add(1, Ordering::SeqCst);
+    self.state.pending_ops.lock().unwrap().clear();
+    // Ops of the old generation that are still in flight give up their
+    // slots too, so they don't hold back the new context's ops.
+    self.state.op_queues.lock().unwrap().clear();
+    self.state.outstanding_ops.lock().unwrap().clear();
+    self.op_ordering.in_flight.clear();
+    self.recv_exceptions.clear();
+    // The new context loads its modules again.
+    self.state.loaded_modules.lock().unwrap().clear();
+    self.state.module_loading_cancel.reset();
+    self.state.set_exit_code(0);
+    self.state.resource_table.lock().unwrap().clear();
+    self.timeout_due = None;
+    if self.ops_by_generation.contains_key(&old_generation) {
+      self.retired_isolates.insert(old_generation, self.libdeno_isolate);
+    } else {
+      unsafe { libdeno::deno_delete(self.libdeno_isolate) };
+    }
+    self.libdeno_isolate =
+      new_libdeno_isolate(&self.config, &self.state.flags);
+    if self.console_sink.is_some() {
+      unsafe { libdeno::deno_set_print_cb(self.libdeno_isolate, print_cb) };
+    }
+    if self.on_gc.is_some() {
+      unsafe { libdeno::deno_set_gc_cb(self.libdeno_isolate, gc_cb) };
+    }
+    self.install_random_seed();
+    self.hide_globals();
+  }
+
+  // Sends console output to sink rather than stdout and stderr, for example
+  // to capture it when stdout is used for something else.
+  pub fn set_console_sink(&mut self, sink: ConsoleSink) {
+    self.console_sink = Some(sink);
+    unsafe { libdeno::deno_set_print_cb(self.libdeno_isolate, print_cb) };
+  }
+
+  // Calls on_gc after each garbage collection, and each time the event loop
+  // gives V8 idle time, see flags.idle_gc. on_gc must not call back into the
+  // isolate.
+  pub fn set_on_gc(&mut self, on_gc: GcCallback) {
+    self.on_gc = Some(on_gc);
+    unsafe { libdeno::deno_set_gc_cb(self.libdeno_isolate, gc_cb) };
+  }
+
+  // Makes event_loop() return as soon as the current message has been
+  // handled, even if ops are still pending. Meant for dispatch functions that
+  // hit an unrecoverable error; unlike Exit this isn't visible to JS. Ops left
+  // running are not cancelled, and their responses are delivered if the event
+  // loop is run again.
+  pub fn stop_event_loop(&mut self, reason: StopReason) {
+    self.stop_reason = Some(reason);
+  }
+
+  // While paused, the event loop keeps running ops and posted tasks, but op
+  // completions and timers are buffered rather than delivered to JS. If
+  // nothing is left that could make progress, event_loop() returns.
+  pub fn pause(&mut self) {
+    self.paused = true;
+  }
+
+  // Delivers everything that was buffered while paused, in the order it
+  // happened, and goes back to delivering as normal.
+  pub fn resume(&mut self) {
+    self.paused = false;
+    while let Some(msg) = self.paused_queue.pop_front() {
+      self.handle_message(msg);
+    }
+  }
+
+  // Registers handler for op_id, replacing any handler registered before.
+  // It's up to the Dispatch function to look up registered ops. ops::dispatch
+  // uses the msg::Any type of a message as its op id and prefers a registered
+  // handler over the builtin one.
+  pub fn register_op(
+    &mut self,
+    op_id: u32,
+    name: Option<&str>,
+    handler: OpHandler,
+  ) {
+    let name = name.map(String::from);
+    self.ops.insert(op_id, RegisteredOp { name, handler });
+  }
+
+  // Registers all the ops of module.
+  pub fn load_op_module<M: OpModule>(&mut self, module: M) {
+    module.register(self);
+  }
+
+  pub fn registered_op(&self, op_id: u32) -> Option<OpHandler> {
+    self.ops.get(&op_id).map(|op| op.handler)
+  }
+
+  // Returns the ids of all registered ops, in ascending order.
+  pub fn registered_ops(&self) -> Vec<u32> {
+    self.ops.keys().cloned().collect()
+  }
+
+  // Returns the name given when op_id was registered, if any.
+  pub fn registered_op_name(&self, op_id: u32) -> Option<&str> {
+    self.ops.get(&op_id).and_then(|op| op.name.as_ref().map(|s| s.as_str()))
+  }
+
+  // Registers op under name for codec::dispatch, replacing any op registered
+  // under that name before.
+  pub fn register_record_op(&mut self, name: &str, op: RecordOp) {
+    self.record_ops.insert(name.to_string(), op);
+  }
+
+  // Replaces the dispatch function given when the isolate was created. It
+  // only takes effect for ops dispatched from now on: ops already in flight
+  // respond as usual, and a dispatch function that replaces itself finishes
+  // handling the op it was called for. restart() keeps the replacement.
+  pub fn set_dispatch(&mut self, dispatch: Dispatch) {
+    self.dispatch = dispatch;
+  }
+
+  pub fn record_op(&self, name: &str) -> Option<RecordOp> {
+    self.record_ops.get(name).cloned()
+  }
+
+  pub fn handle(&self) -> IsolateHandle {
+    IsolateHandle {
+      state: self.state.clone(),
+    }
+  }
+
+  pub fn as_void_ptr(&mut self) -> *mut c_void {
+    self as *mut _ as *mut c_void
+  }
+
+  pub fn from_void_ptr<'a>(ptr: *mut c_void) -> &'a mut Isolate {
+    let ptr = ptr as *mut _;
+    unsafe { &mut *ptr }
+  }
+
+  pub fn execute(
+    &mut self,
+    js_filename: &str,
+    js_source: &str,
+  ) -> Result<(), DenoException> {
+    match self.deno_execute(js_filename, js_source) {
+      None => Err(errors::EXECUTE_DEPTH_EXCEEDED),
+      Some(0) => Err(self.last_exception()),
+      Some(_) => Ok(()),
+    }
+  }
+
+  // For ops that run JS while handling a dispatch, which nests it in the
+  // execute() that sent the op. Fails with ExecuteDepthExceeded rather than
+  // executing if flags.max_execute_depth executes are already running, and
+  // with UncaughtException if js_source throws.
+  pub fn execute_nested(
+    &mut self,
+    js_filename: &str,
+    js_source: &str,
+  ) -> DenoResult<()> {
+    match self.deno_execute(js_filename, js_source) {
+      None => Err(errors::execute_depth_exceeded()),
+      Some(0) => {
+        let msg = self.last_exception().to_string();
+        Err(errors::uncaught_exception(msg))
+      }
+      Some(_) => Ok(()),
+    }
+  }
+
+  // Calls libdeno::deno_execute() through depth_guarded().
+  fn deno_execute(
+    &mut self,
+    js_filename: &str,
+    js_source: &str,
+  ) -> Option<c_int> {
+    let filename = CString::new(js_filename).unwrap();
+    let source = CString::new(js_source).unwrap();
+    self.depth_guarded(|isolate| unsafe {
+      libdeno::deno_execute(
+        isolate.libdeno_isolate,
+        isolate.as_void_ptr(),
+        filename.as_ptr(),
+        source.as_ptr(),
+      )
+    })
+  }
+
+  // Runs execute, which calls into libdeno to run JS, as one of the executes
+  // that flags.max_execute_depth limits, and returns what it returned. If
+  // that many are already running, returns None without calling it.
+  fn depth_guarded<F>(&mut self, execute: F) -> Option<c_int>
+  where
+    F: FnOnce(&mut Isolate) -> c_int,
+  {
+    if let Some(max) = self.state.flags.max_execute_depth {
+      if self.execute_depth >= max {
+        return None;
+      }
+    }
+    self.execute_depth += 1;
+    let r = execute(self);
+    self.execute_depth -= 1;
+    Some(r)
+  }
+
+  // Like execute(), but takes UTF-16 source, which is passed to V8 without
+  // converting it to UTF-8 first. The filename is still UTF-8.
+  pub fn execute_utf16(
+    &mut self,
+    js_filename: &str,
+    js_source: &[u16],
+  ) -> Result<(), DenoException> {
+    let filename = CString::new(js_filename).unwrap();
+    let r = self.depth_guarded(|isolate| unsafe {
+      libdeno::deno_execute_utf16(
+        isolate.libdeno_isolate,
+        isolate.as_void_ptr(),
+        filename.as_ptr(),
+        js_source.as_ptr(),
+        js_source.len() as c_int,
+      )
+    });
+    match r {
+      None => Err(errors::EXECUTE_DEPTH_EXCEEDED),
+      Some(0) => Err(self.last_exception()),
+      Some(_) => Ok(()),
+    }
+  }
+
+  // Validates and compiles a WebAssembly module. Nothing is kept; this only
+  // reports whether JS would be able to instantiate it.
+  pub fn compile_wasm(&mut self, bytes: &[u8]) -> Result<(), DenoException> {
+    let r = unsafe {
+      libdeno::deno_compile_wasm(
+        self.libdeno_isolate,
+        bytes.as_ptr(),
+        bytes.len(),
+      )
+    };
+    if r == 0 {
+      return Err(self.last_exception());
+    }
+    Ok(())
+  }
+
+  // Like execute(), but the UTF-8 source is given in chunks, which V8 parses
+  // on a background thread as they are produced. Useful for large scripts that
+  // are downloaded or decompressed incrementally. chunks is consumed on this
+  // thread, while V8 parses the chunks before. A chunk may end in the middle
+  // of a token or character. If chunks panics, nothing is run and the panic is
+  // resumed.
+  pub fn execute_streaming<I>(
+    &mut self,
+    js_filename: &str,
+    chunks: I,
+  ) -> Result<(), DenoException>
+  where
+    I: Iterator<Item = Vec<u8>>,
+  {
+    let filename = CString::new(js_filename).unwrap();
+    let mut reader = ChunkReader {
+      chunks,
+      current: Vec::new(),
+      panic: None,
+    };
+    let r = {
+      let reader = &mut reader;
+      self.depth_guarded(|isolate| unsafe {
+        libdeno::deno_execute_streaming(
+          isolate.libdeno_isolate,
+          isolate.as_void_ptr(),
+          filename.as_ptr(),
+          next_chunk::<I>,
+          reader as *mut _ as *mut c_void,
+        )
+      })
+    };
+    if let Some(payload) = reader.panic.take() {
+      panic::resume_unwind(payload);
+    }
+    match r {
+      None => Err(errors::EXECUTE_DEPTH_EXCEEDED),
+      Some(0) => Err(self.last_exception()),
+      Some(_) => Ok(()),
+    }
+  }
+
+  // The message is owned by libdeno, which replaces it on the next exception,
+  // so it borrows from self.
+  fn last_exception(&self) -> DenoException {
+    let ptr = unsafe { libdeno::deno_last_exception(self.libdeno_isolate) };
+    let cstr = unsafe { CStr::from_ptr(ptr) };
+    cstr.to_str().unwrap()
+  }
+
+  // Each dispatched req_id must be responded to at most once. Responding to a
+  // req_id that isn't pending (it was already completed, or never dispatched)
+  // would corrupt the promise table on the JS side. This is a bug in the
+  // caller, so it asserts in debug builds and returns an error otherwise.
+  pub fn respond(&mut self, req_id: i32, buf: Buf) -> DenoResult<()> {
+    let pending = {
+      let mut outstanding_ops = self.state.outstanding_ops.lock().unwrap();
+      outstanding_ops.remove(&req_id).is_some()
+    };
+    if !pending {
+      let msg = format!("respond() called for req_id {} not pending", req_id);
+      debug_assert!(false, "{}", msg);
+      return Err(errors::new(ErrorKind::Other, msg));
+    }
+    self.op_ordering.complete(req_id);
+    if let Some(ref inspector) = self.state.inspector {
+      inspector(Direction::Outgoing, req_id, &buf);
+    }
+    let r = self.deno_respond(req_id, buf);
+    // Rejections caused by .then() callbacks are attributed to req_id by
+    // libdeno. An exception thrown by recv itself is only reported here.
+    if r != 0 {
+      self.recv_exception(Some(req_id));
+    }
+    Ok(())
+  }
+
+  // Records the exception that libdeno.recv() just threw, if rejections are
+  // being collected. Otherwise it has already gone to the global error
+  // handler.
+  fn recv_exception(&mut self, req_id: Option<i32>) {
+    if collects_rejections(&self.state.flags) {
+      let message = self.last_exception().to_string();
+      self.recv_exceptions.push(Rejection { req_id, message });
+    }
+  }
+
+  // Queues op if op_id is at its limit in flags.op_concurrency_limits, to be
+  // started by complete_op() once an op of that id finishes. Otherwise
+  // passes it on to spawn_or_queue().
+  fn admit_op(&mut self, req_id: i32, op_id: Option<u32>, op: Box<Op>) {
+    let limit = {
+      let limits = &self.state.flags.op_concurrency_limits;
+      op_id.and_then(|op_id| limits.get(&op_id).map(|&l| (op_id, l)))
+    };
+    if let Some((op_id, limit)) = limit {
+      let generation = self.state.generation.load(Ordering::SeqCst);
+      let mut op_queues = self.state.op_queues.lock().unwrap();
+      let queue = op_queues.entry(op_id).or_insert_with(OpQueue::default);
+      if queue.running.len() >= limit.max(1) {
+        queue.queued.push_back((req_id, op));
+        return;
+      }
+      queue.running.insert((generation, req_id));
+    }
+    self.spawn_or_queue(req_id, op);
+  }
+
+  // Frees the slot of the op that completed, if its op id has a limit, and
+  // returns the next queued op of that id, which now holds the slot.
+  fn release_op_slot(
+    &self,
+    generation: usize,
+    req_id: i32,
+  ) -> Option<(i32, Box<Op>)> {
+    let current = self.state.generation.load(Ordering::SeqCst);
+    let mut op_queues = self.state.op_queues.lock().unwrap();
+    for queue in op_queues.values_mut() {
+      if queue.running.remove(&(generation, req_id)) {
+        let next = queue.queued.pop_front();
+        if let Some((next_req_id, _)) = next {
+          queue.running.insert((current, next_req_id));
+        }
+        return next;
+      }
+    }
+    None
+  }
+
+  // Spawns an async op on the tokio runtime. If max_concurrent_ops has been
+  // reached the op is queued instead and started by spawn_pending_ops() once
+  // a running op finishes.
+  fn spawn_or_queue(&mut self, req_id: i32, op: Box<Op>) {
+    if self.at_concurrency_limit() {
+      let mut pending_ops = self.state.pending_ops.lock().unwrap();
+      pending_ops.push_back((req_id, op));
+    } else {
+      self.spawn_op(req_id, op);
+    }
+  }
+
+  fn at_concurrency_limit(&self) -> bool {
+    match self.state.flags.max_concurrent_ops {
+      // A limit of 0 would never let anything run.
+      Some(max) => self.ntasks as usize >= max.max(1),
+      None => false,
+    }
+  }
+
+  // Spawns queued ops until max_concurrent_ops is reached. Only called by
+  // the event loop, so that delivering a completion, which may happen
+  // outside of the tokio runtime, like in resume(), never spawns anything.
+  fn spawn_pending_ops(&mut self) {
+    while !self.at_concurrency_limit() {
+      let maybe_next = self.state.pending_ops.lock().unwrap().pop_front();
+      match maybe_next {
+        Some((req_id, op)) => self.spawn_op(req_id, op),
+        None => break,
+      }
+    }
+  }
+
+  fn spawn_op(&mut self, req_id: i32, op: Box<Op>) {
+    let state = self.state.clone();
+    let generation = state.generation.load(Ordering::SeqCst);
+
+    // TODO Ideally Tokio would could tell us how many tasks are executing, but
+    // it cannot currently. Therefore we track top-level promises/tasks
+    // manually.
+    self.ntasks_increment();
+    *self.ops_by_generation.entry(generation).or_insert(0) += 1;
+
+    // Ops that complete with no data send an empty Buf, which isn't
+    // allocated, and JS gets null for it rather than an ArrayBuffer.
+    let task = op
+      .and_then(move |buf| state.send_to_js(generation, req_id, buf))
+      .map_err(|err| error!("{}", err));
+    tokio::spawn(task);
+  }
+
+  fn complete_op(&mut self, generation: usize, req_id: i32, buf: Buf) {
+    assert_eq!(
+      self.dispatch_depth, 0,
+      "async response delivered during a dispatch"
+    );
+    // Receiving a message on rx exactly corresponds to an async task
+    // completing.
+    self.ntasks_decrement();
+    self.finish_generation_op(generation);
+    // A slot has been freed. If the op's id has a limit of its own, the next
+    // op of that id can have it. The event loop spawns it, with any other
+    // queued ops.
+    let maybe_next = self.release_op_slot(generation, req_id);
+    if let Some(next) = maybe_next {
+      self.state.pending_ops.lock().unwrap().push_back(next);
+    }
+    if generation != self.state.generation.load(Ordering::SeqCst) {
+      debug!("dropping response for req_id {} from a reset context", req_id);
+      return;
+    }
+    // Call into JS with the buf.
+    if let Err(err) = self.respond(req_id, buf) {
+      error!("{}", err);
+    }
+  }
+
+  // Counts an op of generation as completed, and deletes the V8 isolate that
+  // reset() kept for that generation's ops once they are all done.
+  fn finish_generation_op(&mut self, generation: usize) {
+    let done = match self.ops_by_generation.get_mut(&generation) {
+      Some(n) => {
+        *n -= 1;
+        *n == 0
+      }
+      None => false,
+    };
+    if done {
+      self.ops_by_generation.remove(&generation);
+      if let Some(old) = self.retired_isolates.remove(&generation) {
+        unsafe { libdeno::deno_delete(old) };
+      }
+    }
+  }
+
+  fn deliver_chunk(&mut self, generation: usize, buf: Buf) {
+    assert_eq!(
+      self.dispatch_depth, 0,
+      "async response delivered during a dispatch"
+    );
+    if generation != self.state.generation.load(Ordering::SeqCst) {
+      debug!("dropping response chunk from a reset context");
+      return;
+    }
+    if let Some(ref inspector) = self.state.inspector {
+      inspector(Direction::Outgoing, CHUNK_RESPONSE_ID, &buf);
+    }
+    let r = self.deno_respond(CHUNK_RESPONSE_ID, buf);
+    if r != 0 {
+      self.recv_exception(None);
+    }
+  }
+
+  // Passes buf to libdeno::deno_respond() and gives it back to the buf
+  // allocator afterwards.
+  fn deno_respond(&mut self, req_id: i32, buf: Buf) -> c_int {
+    // TODO(zero-copy) Use Buf::leak(buf) to leak the heap allocated buf. And
+    // don't do the memcpy in ImportBuf() (in libdeno/binding.cc)
+    let buf: libdeno::deno_buf = buf.into();
+    let (data_ptr, data_len) = (buf.data_ptr, buf.data_len);
+    let r = unsafe {
+      libdeno::deno_respond(
+        self.libdeno_isolate,
+        self.as_void_ptr(),
+        req_id,
+        buf,
+      )
+    };
+    // ImportBuf() has copied it.
+    let buf = unsafe { reclaim_buf(data_ptr, data_len) };
+    self.state.buf_allocator().free(buf);
+    r
+  }
+
+  // Handles a message that woke up the event loop.
+  fn wake(&mut self, msg: Message) {
+    if self.state.flags.record_wakeups {
+      let current = self.state.generation.load(Ordering::SeqCst);
+      let stats = &mut self.wakeup_stats;
+      match msg {
+        Message::OpDone(generation, ..) | Message::OpChunk(generation, _)
+          if generation != current =>
+        {
+          stats.spurious += 1
+        }
+        Message::OpDone(..) | Message::OpChunk(..) => stats.op += 1,
+        Message::Task(_) => stats.task += 1,
+        Message::Timeout => stats.timer += 1,
+      }
+    }
+    self.handle_message(msg);
+  }
+
+  fn handle_message(&mut self, msg: Message) {
+    match msg {
+      Message::OpDone(..) | Message::OpChunk(..) | Message::Timeout
+        if self.paused =>
+      {
+        if let Message::Timeout = msg {
+          // Otherwise the loop would keep waking up for the same timer.
+          self.timeout_due = None;
+        }
+        self.paused_queue.push_back(msg);
+      }
+      Message::OpDone(generation, req_id, buf) => {
+        if self.state.flags.deterministic_ops.is_some() {
+          self.deliver_deterministic((generation, req_id, buf));
+        } else {
+          self.complete_op(generation, req_id, buf)
+        }
+      }
+      Message::OpChunk(generation, buf) => self.deliver_chunk(generation, buf),
+      Message::Task(task) => {
+        task.run(self);
+        self.state.posted_tasks.fetch_sub(1, Ordering::SeqCst);
+      }
+      Message::Timeout => self.timeout(),
+    }
+  }
+
+  // Delivers first and the other completions that are ready with it in a
+  // seeded shuffle of req_id order. Completions aren't held back for ops that
+  // are still running, since those might only complete once JS has seen one
+  // of these, so which completions make up a batch still depends on timing.
+  // Only a batch whose members are fixed, like the completions buffered while
+  // paused, is always delivered in the same order.
+  fn deliver_deterministic(&mut self, first: (usize, i32, Buf)) {
+    let mut batch = vec![first];
+    // When resume() is draining the paused queue, the buffered completions
+    // come before anything newer.
+    loop {
+      match self.paused_queue.pop_front() {
+        Some(Message::OpDone(generation, req_id, buf)) => {
+          batch.push((generation, req_id, buf))
+        }
+        Some(msg) => {
+          self.paused_queue.push_front(msg);
+          break;
+        }
+        None => break,
+      }
+    }
+    // The first message that isn't a completion is handled after the batch.
+    let mut next = None;
+    while self.paused_queue.is_empty() {
+      match self.rx.try_recv() {
+        Ok(Message::OpDone(generation, req_id, buf)) => {
+          batch.push((generation, req_id, buf))
+        }
+        Ok(msg) => {
+          next = Some(msg);
+          break;
+        }
+        Err(_) => break,
+      }
+    }
+    batch.sort_by_key(|&(generation, req_id, _)| (generation, req_id));
+    // Fisher-Yates.
+    for i in (1..batch.len()).rev() {
+      let r = splitmix64(&mut self.deterministic_rng);
+      batch.swap(i, (r % (i as u64 + 1)) as usize);
+    }
+    for (generation, req_id, buf) in batch {
+      self.complete_op(generation, req_id, buf);
+    }
+    if let Some(msg) = next {
+      self.handle_message(msg);
+    }
+  }
+
+  // Tells JS that the global timer has fired, so it can run its timers.
+  fn timeout(&mut self) {
+    let dummy_buf = libdeno::deno_buf {
+      alloc_ptr: 0 as *mut u8,
+      alloc_len: 0,
+      data_ptr: 0 as *mut u8,
+      data_len: 0,
+    };
+    let r = unsafe {
+      libdeno::deno_respond(
+        self.libdeno_isolate,
+        self.as_void_ptr(),
+        TIMER_RESPONSE_ID,
+        dummy_buf,
+      )
+    };
+    if r != 0 {
+      self.recv_exception(None);
+    }
+  }
+
+  // TODO Use Park abstraction? Note at time of writing Tokio default runtime
+  // does not have new_with_park().
+  pub fn event_loop(&mut self) -> DenoResult<()> {
+    // Main thread event loop.
+    loop {
+      self.spawn_pending_ops();
+      if let Some(result) = self.loop_result() {
+        return result;
+      }
+      // Ideally, mpsc::Receiver would have a receive method that takes a optional
+      // timeout. But it doesn't so we need all this duplicate code.
+      match self.timeout_due {
+        Some(due) => {
+          if self.state.flags.idle_gc {
+            self.idle_gc(due);
+          }
+          // Subtracting two Instants causes a panic if the resulting duration
+          // would become negative. Avoid this.
+          let now = Instant::now();
+          let timeout = if due > now {
+            due - now
+          } else {
+            Duration::new(0, 0)
+          };
+          // TODO: use recv_deadline() instead of recv_timeout() when this
+          // feature becomes stable/available.
+          match self.rx.recv_timeout(timeout) {
+            Ok(msg) => self.wake(msg),
+            Err(mpsc::RecvTimeoutError::Timeout) => self.wake(Message::Timeout),
+            Err(e) => panic!("mpsc::Receiver::recv_timeout() failed: {:?}", e),
+          }
+        }
+        None => match self.rx.recv() {
+          Ok(msg) => self.wake(msg),
+          Err(e) => panic!("mpsc::Receiver::recv() failed: {:?}", e),
+        },
+      };
+    }
+  }
+
+  // Gives V8 until due to collect garbage in, unless something other than
+  // the timer could need the thread before then.
+  fn idle_gc(&mut self, due: Instant) {
+    let now = Instant::now();
+    if due < now + Duration::from_millis(IDLE_GC_MIN_WAIT_MS)
+      || self.ntasks != 0
+      || !self.state.pending_ops.lock().unwrap().is_empty()
+      || self.state.posted_tasks.load(Ordering::SeqCst) != 0
+    {
+      return;
+    }
+    let deadline = due - now;
+    let seconds =
+      deadline.as_secs() as f64 + f64::from(deadline.subsec_nanos()) * 1e-9;
+    let done = unsafe {
+      libdeno::deno_idle_notification(
+        self.libdeno_isolate,
+        self.as_void_ptr(),
+        seconds,
+      )
+    } != 0;
+    if let Some(ref on_gc) = self.on_gc {
+      on_gc(GcEvent::Idle { deadline, done });
+    }
+  }
+
+  // Handles one message, or the timer if it is due, without blocking. This
+  // lets the loop be driven by something other than event_loop(), like a
+  // MultiIsolateLoop.
+  pub fn poll_once(&mut self) -> LoopStep {
+    self.spawn_pending_ops();
+    if let Some(result) = self.loop_result() {
+      return LoopStep::Done(result);
+    }
+    match self.rx.try_recv() {
+      Ok(msg) => self.wake(msg),
+      Err(mpsc::TryRecvError::Empty) => match self.timeout_due {
+        Some(due) if due <= Instant::now() => self.wake(Message::Timeout),
+        _ => return LoopStep::Waiting,
+      },
+      Err(e) => panic!("mpsc::Receiver::try_recv() failed: {:?}", e),
+    }
+    LoopStep::Ran
+  }
+
+  // What event_loop() returns, if it should return now.
+  fn loop_result(&mut self) -> Option<DenoResult<()>> {
+    if let Some(reason) = self.stop_reason.take() {
+      return Some(match reason {
+        StopReason::Finished => Ok(()),
+        StopReason::Aborted(msg) => Err(errors::loop_aborted(msg)),
+      });
+    }
+    if self.state.flags.abort_on_uncaught {
+      if let Some(first) = self.take_rejections().first() {
+        let msg = format!("Uncaught exception: {}", first);
+        return Some(Err(errors::uncaught_exception(msg)));
+      }
+    }
+    if self.is_idle() || self.is_stalled() {
+      return Some(self.check_rejections());
+    }
+    None
+  }
+
+  // Blocks until a message for the event loop arrives, like a task posted
+  // with IsolateHandle::post(), and handles it. For keeping an idle isolate
+  // around to run tasks; see IsolateThread.
+  pub fn wait_for_message(&mut self) {
+    match self.rx.recv() {
+      Ok(msg) => self.wake(msg),
+      Err(e) => panic!("mpsc::Receiver::recv() failed: {:?}", e),
+    }
+  }
+
+  // Removes and returns the rejections collected so far. They are only
+  // collected when flags.unhandled_rejection_mode or flags.abort_on_uncaught
+  // is set; event_loop() then handles them according to those flags.
+  pub fn take_rejections(&mut self) -> Vec<Rejection> {
+    let mut rejections = std::mem::replace(&mut self.recv_exceptions, vec![]);
+    loop {
+      let mut req_id = -1;
+      let taken = unsafe {
+        libdeno::deno_take_rejection(self.libdeno_isolate, &mut req_id)
+      };
+      if taken == 0 {
+        break;
+      }
+      rejections.push(Rejection {
+        req_id: if req_id == -1 { None } else { Some(req_id) },
+        message: self.last_exception().to_string(),
+      });
+    }
+    rejections
+  }
+
+  // Applies flags.unhandled_rejection_mode to the rejections collected so far.
+  fn check_rejections(&mut self) -> DenoResult<()> {
+    let mode = match self.state.flags.unhandled_rejection_mode {
+      Some(mode) => mode,
+      None => return Ok(()),
+    };
+    let rejections = self.take_rejections();
+    if rejections.is_empty() {
+      return Ok(());
+    }
+    match mode {
+      flags::UnhandledRejectionMode::Strict => {
+        let msg = format!("Unhandled promise rejection: {}", rejections[0]);
+        Err(errors::unhandled_rejection(msg))
+      }
+      flags::UnhandledRejectionMode::Warn => {
+        for rejection in rejections {
+          let msg = format!("Unhandled promise rejection: {}", rejection);
+          match self.console_sink {
+            Some(ref sink) => sink(ConsoleLevel::Error, &msg),
+            None => eprintln!("{}", msg),
+          }
+        }
+        Ok(())
+      }
+      flags::UnhandledRejectionMode::Ignore => Ok(()),
+    }
+  }
+
+  fn ntasks_increment(&mut self) {
+    assert!(self.ntasks >= 0);
+    self.ntasks = self.ntasks + 1;
+  }
+
+  fn ntasks_decrement(&mut self) {
+    self.ntasks = self.ntasks - 1;
+    assert!(self.ntasks >= 0);
+  }
+
+  // True if the loop is paused and only buffered completions are left, so
+  // waiting for messages would block until resume() is called.
+  fn is_stalled(&self) -> bool {
+    if !self.paused {
+      return false;
+    }
+    let buffered_ops = self
+      .paused_queue
+      .iter()
+      .filter(|msg| match **msg {
+        Message::OpDone(..) => true,
+        _ => false,
+      }).count();
+    self.ntasks as usize == buffered_ops
+      && self.timeout_due.is_none()
+      && self.state.posted_tasks.load(Ordering::SeqCst) == 0
+  }
+
+  // Ops queued by max_concurrent_ops haven't been spawned, so they aren't
+  // counted in ntasks, but they are still work left to do.
+  fn is_idle(&self) -> bool {
+    self.ntasks == 0
+      && self.state.pending_ops.lock().unwrap().is_empty()
+      && self.timeout_due.is_none()
+      && self.state.posted_tasks.load(Ordering::SeqCst) == 0
+  }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+  *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+  let mut z = *state;
+  z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+  z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+  z ^ (z >> 31)
+}
+
+// Quotes s as a JS string literal.
+fn js_string(s: &str) -> String {
+  let mut literal = String::from("\"");
+  for c in s.chars() {
+    match c {
+      '"' => literal.push_str("\\\""),
+      '\\' => literal.push_str("\\\\"),
+      // Line terminators and other control characters, which can't appear in
+      // a literal as they are.
+      '\u{0}'...'\u{1f}' | '\u{7f}' | '\u{2028}' | '\u{2029}' => {
+        literal.push_str(&format!("\\u{:04x}", c as u32))
+      }
+      c => literal.push(c),
+    }
+  }
+  literal.push('"');
+  literal
+}
+
+// Returns JS that replaces Math.random() with xorshift128. JS only has 32 bit
+// integer operations, so the seed is expanded into four 32 bit words here.
+fn seeded_random_js(seed: u64) -> String {
+  let mut state = seed;
+  let a = splitmix64(&mut state);
+  let b = splitmix64(&mut state);
+  let words = [a as u32, (a >> 32) as u32, b as u32, (b >> 32) as u32];
+  format!(
+    r#"(function(x, y, z, w) {{
+      Math.random = function random() {{
+        let t = x ^ (x << 11);
+        x = y;
+        y = z;
+        z = w;
+        w = (w ^ (w >>> 19) ^ t ^ (t >>> 8)) >>> 0;
+        return w / 4294967296;
+      }};
+    }})({}, {}, {}, {});"#,
+    words[0], words[1], words[2], words[3]
+  )
+}
+
+fn new_libdeno_isolate(
+  config: &IsolateConfig,
+  flags: &flags::DenoFlags,
+) -> *const libdeno::isolate {
+  // The isolate keeps config, and so the snapshot, until it is deleted.
+  let snapshot: &[u8] = config.snapshot.as_ref().map(|s| &**s).unwrap_or(&[]);
+  let deno_config = libdeno::deno_config {
+    heap_limit: config.heap_limit.unwrap_or(0),
+    // Only read by libdeno; an empty snapshot means the built-in one.
+    snapshot: libdeno::deno_buf {
+      alloc_ptr: 0 as *mut u8,
+      alloc_len: 0,
+      data_ptr: if snapshot.is_empty() {
+        0 as *mut u8
+      } else {
+        snapshot.as_ptr() as *mut u8
+      },
+      data_len: snapshot.len(),
+    },
+  };
+  let libdeno_isolate =
+    unsafe { libdeno::deno_new_with_config(pre_dispatch, deno_config) };
+  if let Some(stack_size) = config.stack_size {
+    unsafe { libdeno::deno_set_stack_limit(libdeno_isolate, stack_size) };
+  }
+  if collects_rejections(flags) {
+    unsafe { libdeno::deno_collect_rejections(libdeno_isolate) };
+  }
+  libdeno_isolate
+}
+
+fn collects_rejections(flags: &flags::DenoFlags) -> bool {
+  flags.unhandled_rejection_mode.is_some() || flags.abort_on_uncaught
+}
+
+impl Drop for Isolate {
+  fn drop(&mut self) {
+    unsafe { libdeno::deno_delete(self.libdeno_isolate) }
+    for (_, old) in self.retired_isolates.drain() {
+      unsafe { libdeno::deno_delete(old) }
+    }
+    isolate_lifecycle(IsolateLifecycle::Destroyed, self.id);
+    // The count is for the whole process, so another isolate may be in the
+    // middle of a response.
+    let n = outstanding_bufs();
+    if n != 0 {
+      warn!("{} bufs passed to libdeno have not been freed", n);
+    }
+  }
+}
+
+// The number of bufs made by From<Buf>, on any thread, that haven't been
+// given to reclaim_buf() yet. Only counted in debug builds, to catch leaks at
+// the FFI boundary; it is always 0 in release builds.
+static OUTSTANDING_BUFS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+pub fn outstanding_bufs() -> usize {
+  OUTSTANDING_BUFS.load(Ordering::SeqCst)
+}
+
+fn count_buf(allocated: bool) {
+  if cfg!(debug_assertions) {
+    if allocated {
+      OUTSTANDING_BUFS.fetch_add(1, Ordering::SeqCst);
+    } else {
+      OUTSTANDING_BUFS.fetch_sub(1, Ordering::SeqCst);
+    }
+  }
+}
+
+// Takes back the data of a deno_buf made by From<Buf>, once libdeno is done
+// with it.
+unsafe fn reclaim_buf(data_ptr: *mut u8, data_len: usize) -> Buf {
+  let slice = std::slice::from_raw_parts_mut(data_ptr, data_len);
+  count_buf(false);
+  Box::from_raw(slice as *mut [u8])
+}
+
+// Frees the data of a deno_buf made by From<Buf>.
+#[cfg(test)]
+unsafe fn free_buf(data_ptr: *mut u8, data_len: usize) {
+  drop(reclaim_buf(data_ptr, data_len));
+}
+
+/// Converts Rust Buf to libdeno deno_buf. The data is leaked; it must be
+/// taken back with reclaim_buf().
+impl From<Buf> for libdeno::deno_buf {
+  fn from(x: Buf) -> libdeno::deno_buf {
+    let len = x.len();
+    let ptr = Box::into_raw(x);
+    count_buf(true);
+    libdeno::deno_buf {
+      alloc_ptr: 0 as *mut u8,
+      alloc_len: 0,
+      data_ptr: ptr as *mut u8,
+      data_len: len,
+    }
+  }
+}
+
+struct ChunkReader<I> {
+  chunks: I,
+  // The chunk libdeno is reading. Kept alive until the next call.
+  current: Vec<u8>,
+  // A panic can't unwind into libdeno, so it is kept here instead.
+  panic: Option<Box<Any + Send>>,
+}
+
+// A libdeno::DenoChunkCb. cb_data is a ChunkReader<I>.
+extern "C" fn next_chunk<I: Iterator<Item = Vec<u8>>>(
+  cb_data: *mut c_void,
+  chunk: *mut *const u8,
+) -> usize {
+  let reader = unsafe { &mut *(cb_data as *mut ChunkReader<I>) };
+  let chunks = &mut reader.chunks;
+  // An empty chunk would mean the end, so skip them.
+  let next = panic::catch_unwind(AssertUnwindSafe(|| {
+    chunks.by_ref().find(|next| !next.is_empty())
+  }));
+  match next {
+    Ok(Some(next)) => {
+      reader.current = next;
+      unsafe { *chunk = reader.current.as_ptr() };
+      reader.current.len()
+    }
+    Ok(None) => 0,
+    Err(payload) => {
+      reader.panic = Some(payload);
+      libdeno::DENO_CHUNK_ERROR
+    }
+  }
+}
+
+extern "C" fn print_cb(
+  user_data: *mut c_void,
+  msg: *const c_char,
+  is_err: c_int,
+) {
+  let msg = unsafe { CStr::from_ptr(msg) }.to_string_lossy();
+  let level = if is_err != 0 {
+    ConsoleLevel::Error
+  } else {
+    ConsoleLevel::Log
+  };
+  // The callback is only installed together with a sink, but print() could be
+  // called outside of execute() and respond(), when there's no user_data.
+  if !user_data.is_null() {
+    let isolate = Isolate::from_void_ptr(user_data);
+    if let Some(ref sink) = isolate.console_sink {
+      return sink(level, &*msg);
+    }
+  }
+  match level {
+    ConsoleLevel::Log => println!("{}", msg),
+    ConsoleLevel::Error => eprintln!("{}", msg),
+  }
+}
+
+extern "C" fn gc_cb(user_data: *mut c_void, full: c_int) {
+  // V8 also collects garbage outside of execute() and respond(), when there's
+  // no user_data.
+  if user_data.is_null() {
+    return;
+  }
+  let isolate = Isolate::from_void_ptr(user_data);
+  if let Some(ref on_gc) = isolate.on_gc {
+    on_gc(GcEvent::Collected { full: full != 0 });
+  }
+}
+
+// Dereferences the C pointer into the Rust Isolate object.
+extern "C" fn pre_dispatch(
+  user_data: *mut c_void,
+  req_id: i32,
+  control_buf: libdeno::deno_buf,
+  data_buf: libdeno::deno_buf,
+) {
+  let isolate = Isolate::from_void_ptr(user_data);
+  let depth = isolate.dispatch_depth;
+  let mut handed_off = false;
+  // A panic must not unwind into libdeno, wherever it happens: in the
+  // dispatch function, or while the op is admitted or responded to.
+  let r = panic::catch_unwind(AssertUnwindSafe(|| {
+    dispatch_from_js(isolate, req_id, control_buf, data_buf, &mut handed_off)
+  }));
+  let payload = match r {
+    Ok(()) => return,
+    Err(payload) => payload,
+  };
+  let err = errors::op_panicked(payload);
+  if handed_off {
+    // The op may be in flight or responded to already, so the send can't be
+    // rejected anymore.
+    error!("req_id {} {}", req_id, err);
+    std::process::abort();
+  }
+  isolate.dispatch_depth = depth;
+  isolate.dispatch_op_id = None;
+  reject_send(isolate, req_id, &err);
+}
+
+// Makes the libdeno.send() of req_id throw err, as a RangeError, instead of
+// returning a response.
+fn reject_send(isolate: &mut Isolate, req_id: i32, err: &DenoError) {
+  if let Ok(mut outstanding_ops) = isolate.state.outstanding_ops.lock() {
+    outstanding_ops.remove(&req_id);
+  }
+  isolate.op_ordering.complete(req_id);
+  let msg = CString::new(err.to_string())
+    .unwrap_or_else(|_| CString::new("op failed").unwrap());
+  unsafe { libdeno::deno_reject_send(isolate.libdeno_isolate, msg.as_ptr()) };
+}
+
+// The body of pre_dispatch(). handed_off is set once the op has been passed
+// on to be responded to or run.
+fn dispatch_from_js(
+  isolate: &mut Isolate,
+  req_id: i32,
+  control_buf: libdeno::deno_buf,
   data_buf: libdeno::deno_buf,
+  handed_off: &mut bool,
 ) {
+  if let Some(max) = isolate.state.flags.max_op_input_bytes {
+    if control_buf.data_len + data_buf.data_len > max {
+      let msg = CString::new(format!(
+        "op input of {} bytes exceeds max_op_input_bytes",
+        control_buf.data_len + data_buf.data_len
+      )).unwrap();
+      unsafe {
+        libdeno::deno_reject_send(isolate.libdeno_isolate, msg.as_ptr())
+      };
+      return;
+    }
+  }
+
   // control_buf is only valid for the lifetime of this call, thus is
   // interpretted as a slice.
   let control_slice = unsafe {
     std::slice::from_raw_parts(control_buf.data_ptr, control_buf.data_len)
   };
 
-  // data_buf is valid for the lifetime of the promise, thus a mutable buf with
-  // static lifetime.
-  let data_slice = unsafe {
-    std::slice::from_raw_parts_mut::<'static>(
-      data_buf.data_ptr,
-      data_buf.data_len,
-    )
-  };
+  // data_buf is valid for the lifetime of the promise, thus a mutable buf with
+  // static lifetime.
+  let data_slice = unsafe {
+    std::slice::from_raw_parts_mut::<'static>(
+      data_buf.data_ptr,
+      data_buf.data_len,
+    )
+  };
+
+  let dispatch = isolate.dispatch;
+  debug_assert!(!is_reserved_req_id(req_id));
+  let now = isolate.state.now();
+  let mut outstanding_ops = isolate.state.outstanding_ops.lock().unwrap();
+  outstanding_ops.insert(req_id, (None, now));
+  drop(outstanding_ops);
+  if isolate.state.flags.record_op_ordering {
+    isolate.op_ordering.dispatch(req_id);
+  }
+
+  if let Some(ref inspector) = isolate.state.inspector {
+    inspector(Direction::Incoming, req_id, control_slice);
+    if data_slice.len() > 0 {
+      inspector(Direction::Incoming, req_id, data_slice);
+    }
+  }
+
+  // The span is entered while the dispatch function runs, so that it can
+  // record which op is being executed.
+  #[cfg(feature = "tracing")]
+  let span = tracing::span!(
+    tracing::Level::DEBUG,
+    "op_dispatch",
+    req_id = req_id,
+    op_id = tracing::field::Empty
+  );
+  #[cfg(feature = "tracing")]
+  let dispatched_at = Instant::now();
+
+  isolate.dispatch_depth += 1;
+  let depth = isolate.dispatch_depth;
+  isolate.dispatch_op_id = None;
+
+  let (is_sync, op) = {
+    #[cfg(feature = "tracing")]
+    let _guard = span.enter();
+    dispatch(isolate, control_slice, data_slice)
+  };
+  // Anything nested in dispatch() must have returned. If not, responses may
+  // have been delivered out of order, and unwinding wouldn't fix that.
+  if isolate.dispatch_depth != depth {
+    error!(
+      "dispatch depth is {} after req_id {}, expected {}",
+      isolate.dispatch_depth, req_id, depth
+    );
+    std::process::abort();
+  }
+  let op_id = isolate.dispatch_op_id.take();
+
+  if is_sync {
+    // Execute op synchronously. ops::dispatch() already turns errors into
+    // error responses, so this only fails for other dispatch functions, or if
+    // the op panicked. Either way send() throws.
+    let r = tokio_util::block_on(op);
+    #[cfg(feature = "tracing")]
+    trace_op_completed(&span, dispatched_at);
+    *handed_off = true;
+    let buf = match r {
+      Ok(buf) => buf,
+      Err(err) => {
+        reject_send(isolate, req_id, &err);
+        isolate.dispatch_depth -= 1;
+        return;
+      }
+    };
+    if buf.len() != 0 {
+      // Set the synchronous response, the value returned from isolate.send().
+      isolate.respond(req_id, buf).unwrap();
+    } else {
+      // An empty sync response makes libdeno.send() return null. It is
+      // passed on without respond(), which would show it to the inspector,
+      // so that libdeno can tell it apart from an async op.
+      isolate.state.outstanding_ops.lock().unwrap().remove(&req_id);
+      isolate.op_ordering.complete(req_id);
+      isolate.deno_respond(req_id, buf);
+    }
+  } else {
+    // Execute op asynchronously.
+    // The span is moved into the op so that it follows it across
+    // tokio::spawn().
+    #[cfg(feature = "tracing")]
+    let op: Box<Op> = Box::new(Instrumented {
+      op,
+      span,
+      dispatched_at,
+    });
+    let mut outstanding_ops = isolate.state.outstanding_ops.lock().unwrap();
+    if let Some(entry) = outstanding_ops.get_mut(&req_id) {
+      entry.0 = op_id;
+    }
+    drop(outstanding_ops);
+    *handed_off = true;
+    isolate.admit_op(req_id, op_id, op);
+  }
+  isolate.dispatch_depth -= 1;
+}
+
+// Enters span every time op is polled, like tracing-futures'
+// Future::instrument(), so that whatever the op logs on the thread it is
+// polled on is recorded in the span. Emits op_completed when it's done.
+#[cfg(feature = "tracing")]
+struct Instrumented {
+  op: Box<Op>,
+  span: tracing::Span,
+  dispatched_at: Instant,
+}
+
+#[cfg(feature = "tracing")]
+impl Future for Instrumented {
+  type Item = Buf;
+  type Error = DenoError;
+
+  fn poll(&mut self) -> Poll<Buf, DenoError> {
+    let poll = {
+      let _guard = self.span.enter();
+      self.op.poll()
+    };
+    match poll {
+      Ok(Async::NotReady) => (),
+      _ => trace_op_completed(&self.span, self.dispatched_at),
+    }
+    poll
+  }
+}
+
+#[cfg(feature = "tracing")]
+fn trace_op_completed(span: &tracing::Span, dispatched_at: Instant) {
+  let elapsed = dispatched_at.elapsed();
+  let elapsed_us =
+    elapsed.as_secs() * 1_000_000 + elapsed.subsec_micros() as u64;
+  span.in_scope(|| {
+    tracing::event!(
+      tracing::Level::DEBUG,
+      elapsed_us = elapsed_us,
+      "op_completed"
+    );
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use deno_dir::ModuleLoader;
+  use futures;
+  use std::sync::atomic::{AtomicBool, ATOMIC_BOOL_INIT, ATOMIC_USIZE_INIT};
+
+  #[test]
+  fn test_dispatch_sync() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_sync);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "y.js",
+          r#"
+          const m = new Uint8Array([4, 5, 6]);
+          let n = libdeno.send(m);
+          if (!(n.byteLength === 3 &&
+                n[0] === 1 &&
+                n[1] === 2 &&
+                n[2] === 3)) {
+            throw Error("assert error");
+          }
+        "#,
+        ).expect("execute error");
+      isolate.event_loop().unwrap();
+    });
+  }
+
+  #[test]
+  fn test_isolate_lifecycle() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_ = events.clone();
+    on_isolate_lifecycle(Some(Box::new(move |event, id| {
+      events_.lock().unwrap().push((event, id));
+    })));
+    let a = Isolate::from_flags(Default::default(), vec![], dispatch_async);
+    let b = Isolate::from_flags(Default::default(), vec![], dispatch_async);
+    let (a_id, b_id) = (a.id(), b.id());
+    assert_ne!(a_id, b_id);
+    drop(b);
+    drop(a);
+    on_isolate_lifecycle(None);
+
+    // Other tests create isolates at the same time.
+    let events: Vec<_> = events
+      .lock()
+      .unwrap()
+      .iter()
+      .cloned()
+      .filter(|&(_, id)| id == a_id || id == b_id)
+      .collect();
+    assert_eq!(
+      events,
+      vec![
+        (IsolateLifecycle::Created, a_id),
+        (IsolateLifecycle::Created, b_id),
+        (IsolateLifecycle::Destroyed, b_id),
+        (IsolateLifecycle::Destroyed, a_id),
+      ]
+    );
+
+    // The callback may create isolates itself, or replace the callback.
+    on_isolate_lifecycle(Some(Box::new(|event, _| {
+      if event == IsolateLifecycle::Created {
+        on_isolate_lifecycle(None);
+        Isolate::from_flags(Default::default(), vec![], dispatch_async);
+      }
+    })));
+    Isolate::from_flags(Default::default(), vec![], dispatch_async);
+    assert!(LIFECYCLE_CALLBACK.read().unwrap().is_none());
+  }
+
+  #[test]
+  fn test_outstanding_bufs() {
+    let buf: Buf = Box::new([1, 2, 3]);
+    let buf: libdeno::deno_buf = buf.into();
+    let expected = if cfg!(debug_assertions) { 1 } else { 0 };
+    assert_eq!(outstanding_bufs(), expected);
+    unsafe { free_buf(buf.data_ptr, buf.data_len) };
+    assert_eq!(outstanding_bufs(), 0);
+
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch_async);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "y.js",
+          r#"
+          let n = 0;
+          libdeno.recv(() => { n++; });
+          for (let i = 0; i < 10; i++) {
+            libdeno.send(new Uint8Array([i]));
+          }
+        "#,
+        ).expect("execute error");
+      isolate.event_loop().unwrap();
+      isolate
+        .execute("z.js", "if (n !== 10) throw Error(n);")
+        .expect("execute error");
+    });
+    // Every response was freed once JS had it.
+    assert_eq!(outstanding_bufs(), 0);
+  }
+
+  // Records a non-zero first byte as the exit code, and responds async.
+  fn dispatch_exit_code(
+    isolate: &mut Isolate,
+    control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    if control[0] != 0 {
+      isolate.state.set_exit_code(i32::from(control[0]));
+    }
+    let buf: Buf = Box::new([]);
+    (false, Box::new(futures::future::ok(buf)))
+  }
+
+  #[test]
+  fn test_exit_code() {
+    let mut isolate =
+      Isolate::from_flags(Default::default(), vec![], dispatch_exit_code);
+    assert_eq!(isolate.exit_code(), 0);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "a.js",
+          r#"
+          let responses = 0;
+          libdeno.recv(() => {
+            // Keeps working after the exit code is set.
+            if (++responses < 3) libdeno.send(new Uint8Array([0]));
+          });
+          libdeno.send(new Uint8Array([3]));
+        "#,
+        ).expect("execute error");
+      isolate.event_loop().unwrap();
+      isolate
+        .execute("b.js", "if (responses !== 3) throw Error(responses);")
+        .expect("execute error");
+    });
+    assert_eq!(isolate.exit_code(), 3);
+    isolate.reset();
+    assert_eq!(isolate.exit_code(), 0);
+  }
+
+  #[test]
+  fn test_async_empty_response() {
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch_async_empty);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "y.js",
+          r#"
+          const promises = new Map();
+          libdeno.recv((msg, reqId) => {
+            promises.get(reqId)(msg);
+            promises.delete(reqId);
+          });
+          const reqId = libdeno.send(new Uint8Array([1]));
+          if (typeof reqId !== "number") throw Error("got " + reqId);
+          result = undefined;
+          new Promise(resolve => promises.set(reqId, resolve)).then(msg => {
+            result = msg;
+          });
+        "#,
+        ).expect("execute error");
+      isolate.event_loop().unwrap();
+    });
+    // The promise resolved with null, not an empty ArrayBuffer.
+    isolate
+      .execute("check.js", "if (result !== null) throw Error(result);")
+      .expect("execute error");
+    assert_eq!(outstanding_bufs(), 0);
+  }
+
+  #[cfg(feature = "tracing")]
+  #[test]
+  fn test_dispatch_emits_spans() {
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    // Records the name of every span that is created.
+    struct CaptureSpans {
+      next_id: AtomicUsize,
+      names: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl Subscriber for CaptureSpans {
+      fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+      }
+      fn new_span(&self, attrs: &Attributes) -> Id {
+        self.names.lock().unwrap().push(attrs.metadata().name());
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        Id::from_u64(id as u64)
+      }
+      fn record(&self, _span: &Id, _values: &Record) {}
+      fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+      fn event(&self, _event: &Event) {}
+      fn enter(&self, _span: &Id) {}
+      fn exit(&self, _span: &Id) {}
+    }
+
+    let names = Arc::new(Mutex::new(Vec::new()));
+    let subscriber = CaptureSpans {
+      next_id: AtomicUsize::new(1),
+      names: names.clone(),
+    };
+
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_sync);
+    tracing::subscriber::with_default(subscriber, || {
+      tokio_util::init(|| {
+        isolate
+          .execute(
+            "y.js",
+            r#"
+            libdeno.send(new Uint8Array([4, 5, 6]));
+            libdeno.send(new Uint8Array([4, 5, 6]));
+          "#,
+          ).expect("execute error");
+        isolate.event_loop().unwrap();
+      });
+    });
+
+    let names = names.lock().unwrap();
+    let n = names.iter().filter(|name| **name == "op_dispatch").count();
+    assert_eq!(n, 2);
+  }
+
+  fn dispatch_first(
+    isolate: &mut Isolate,
+    _control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    // Swapping while dispatching leaves this op to this function.
+    isolate.set_dispatch(dispatch_second);
+    let buf = vec![1u8].into_boxed_slice();
+    (true, Box::new(futures::future::ok(buf)))
+  }
+
+  fn dispatch_second(
+    _isolate: &mut Isolate,
+    _control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    let buf = vec![2u8].into_boxed_slice();
+    (true, Box::new(futures::future::ok(buf)))
+  }
+
+  #[test]
+  fn test_set_dispatch() {
+    let mut isolate =
+      Isolate::from_flags(Default::default(), vec![], dispatch_first);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "a.js",
+          r#"
+          const a = libdeno.send(new Uint8Array([0]));
+          const b = libdeno.send(new Uint8Array([0]));
+          if (a[0] !== 1 || b[0] !== 2) {
+            throw Error("assert error");
+          }
+        "#,
+        ).expect("execute error");
+      isolate.set_dispatch(dispatch_first);
+      isolate
+        .execute(
+          "b.js",
+          r#"
+          if (libdeno.send(new Uint8Array([0]))[0] !== 1) {
+            throw Error("assert error");
+          }
+        "#,
+        ).expect("execute error");
+      isolate.event_loop().unwrap();
+    });
+  }
+
+  static DISPATCHED: AtomicUsize = ATOMIC_USIZE_INIT;
+
+  fn dispatch_counted(
+    isolate: &mut Isolate,
+    control: &[u8],
+    data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    DISPATCHED.fetch_add(1, Ordering::SeqCst);
+    dispatch_sync(isolate, control, data)
+  }
+
+  #[test]
+  fn test_max_op_input_bytes() {
+    let flags = flags::DenoFlags {
+      max_op_input_bytes: Some(8),
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch_counted);
+    isolate
+      .execute(
+        "limit.js",
+        r#"
+        function rejected(f) {
+          try {
+            f();
+          } catch (e) {
+            return e instanceof RangeError;
+          }
+          return false;
+        }
+        const control = new Uint8Array([4, 5, 6]);
+        if (!rejected(() => libdeno.send(new Uint8Array(9)))) {
+          throw Error("big control accepted");
+        }
+        if (!rejected(() => libdeno.send(control, new Uint8Array(6)))) {
+          throw Error("big data accepted");
+        }
+        // Exactly at the limit is fine.
+        const r = libdeno.send(new Uint8Array([4, 5, 6, 0, 0, 0, 0, 0]));
+        if (r[0] !== 1) throw Error("no response");
+      "#,
+      ).expect("execute error");
+    // Only the request within the limit got to the dispatch function.
+    assert_eq!(DISPATCHED.load(Ordering::SeqCst), 1);
+    assert!(isolate.state.outstanding_ops.lock().unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_max_concurrent_ops() {
+    let flags = flags::DenoFlags {
+      max_concurrent_ops: Some(1),
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch_async_slow);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "y.js",
+          r#"
+          let n = 0;
+          libdeno.recv(() => { n++; });
+          libdeno.send(new Uint8Array([1]));
+          libdeno.send(new Uint8Array([2]));
+          libdeno.send(new Uint8Array([3]));
+        "#,
+        ).expect("execute error");
+      isolate.event_loop().unwrap();
+      isolate
+        .execute("z.js", "if (n !== 3) throw Error('expected 3 responses');")
+        .expect("execute error");
+    });
+    // With a limit of 1 the ops must have run one after another.
+    assert_eq!(MAX_RUNNING.load(Ordering::SeqCst), 1);
+  }
+
+  #[test]
+  fn test_idle_with_queued_ops() {
+    let flags = flags::DenoFlags {
+      max_concurrent_ops: Some(1),
+      ..Default::default()
+    };
+    let mut isolate =
+      Isolate::from_flags(flags, vec![], dispatch_echo_delayed);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "y.js",
+          r#"
+          let order = [];
+          libdeno.recv((buf) => { order.push(new Uint8Array(buf)[0]); });
+          for (let i = 1; i <= 5; i++) {
+            libdeno.send(new Uint8Array([i]));
+          }
+        "#,
+        ).expect("execute error");
+      // One op is running, the rest are waiting for it.
+      assert_eq!(isolate.ntasks, 1);
+      assert_eq!(isolate.state.pending_ops.lock().unwrap().len(), 4);
+      assert!(!isolate.is_idle());
+      isolate.event_loop().unwrap();
+      assert!(isolate.is_idle());
+      isolate
+        .execute(
+          "z.js",
+          "if (order.join() !== '1,2,3,4,5') throw Error(order.join());",
+        ).expect("execute error");
+    });
+  }
+
+  #[test]
+  fn test_max_concurrent_ops_zero() {
+    let flags = flags::DenoFlags {
+      max_concurrent_ops: Some(0),
+      ..Default::default()
+    };
+    let mut isolate =
+      Isolate::from_flags(flags, vec![], dispatch_echo_delayed);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "y.js",
+          r#"
+          let order = [];
+          libdeno.recv((buf) => { order.push(new Uint8Array(buf)[0]); });
+          for (let i = 1; i <= 3; i++) {
+            libdeno.send(new Uint8Array([i]));
+          }
+        "#,
+        ).expect("execute error");
+      // Treated as a limit of 1.
+      assert_eq!(isolate.ntasks, 1);
+      isolate.event_loop().unwrap();
+      isolate
+        .execute(
+          "z.js",
+          "if (order.join() !== '1,2,3') throw Error(order.join());",
+        ).expect("execute error");
+    });
+  }
+
+  #[test]
+  fn test_op_concurrency_limits() {
+    static LIMITED_RUNNING: AtomicUsize = ATOMIC_USIZE_INIT;
+    static LIMITED_MAX_RUNNING: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    // Echoes the op id, the first byte of control. Ops with id 1 are slow and
+    // count how many of them run at once.
+    fn dispatch_counted_op(
+      isolate: &mut Isolate,
+      control: &[u8],
+      _data: &'static mut [u8],
+    ) -> (bool, Box<Op>) {
+      let op_id = control[0] as u32;
+      isolate.set_dispatch_op_id(op_id);
+      let buf: Buf = control.to_vec().into_boxed_slice();
+      let op = futures::future::lazy(move || -> Result<Buf, DenoError> {
+        if op_id == 1 {
+          let running = LIMITED_RUNNING.fetch_add(1, Ordering::SeqCst) + 1;
+          // There is no fetch_max, so retry until running is stored or a
+          // larger count is.
+          let mut max = LIMITED_MAX_RUNNING.load(Ordering::SeqCst);
+          while running > max {
+            let prev = LIMITED_MAX_RUNNING.compare_and_swap(
+              max,
+              running,
+              Ordering::SeqCst,
+            );
+            if prev == max {
+              break;
+            }
+            max = prev;
+          }
+          std::thread::sleep(Duration::from_millis(20));
+          LIMITED_RUNNING.fetch_sub(1, Ordering::SeqCst);
+        }
+        Ok(buf)
+      });
+      (false, Box::new(op))
+    }
+
+    let mut op_concurrency_limits = HashMap::new();
+    op_concurrency_limits.insert(1, 2);
+    let flags = flags::DenoFlags {
+      op_concurrency_limits,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch_counted_op);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "y.js",
+          r#"
+          let order = [];
+          libdeno.recv((buf) => { order.push(new Uint8Array(buf)[0]); });
+          for (let i = 0; i < 5; i++) {
+            libdeno.send(new Uint8Array([1]));
+          }
+          libdeno.send(new Uint8Array([2]));
+        "#,
+        ).expect("execute error");
+      // Two ops with id 1 are running and three are waiting for them. The op
+      // with id 2 isn't limited.
+      assert_eq!(isolate.ntasks, 3);
+      assert_eq!(isolate.pending_op_count(), 6);
+      isolate.event_loop().unwrap();
+      assert!(isolate.is_idle());
+      isolate
+        .execute(
+          "z.js",
+          r#"
+          if (order.length !== 6 || order.filter(id => id === 1).length !== 5) {
+            throw Error(order.join());
+          }
+        "#,
+        ).expect("execute error");
+
+      // reset() frees the slots of ops that are still in flight.
+      isolate
+        .execute(
+          "r.js",
+          r#"
+          libdeno.send(new Uint8Array([1]));
+          libdeno.send(new Uint8Array([1]));
+        "#,
+        ).expect("execute error");
+      isolate.reset();
+      assert!(isolate.state.op_queues.lock().unwrap().is_empty());
+      isolate.event_loop().unwrap();
+    });
+    assert!(LIMITED_MAX_RUNNING.load(Ordering::SeqCst) <= 2);
+    assert!(isolate.state.op_queues.lock().unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_status() {
+    let flags = flags::DenoFlags {
+      max_concurrent_ops: Some(1),
+      ..Default::default()
+    };
+    let mut isolate =
+      Isolate::from_flags(flags, vec![], dispatch_echo_delayed);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "y.js",
+          r#"
+          libdeno.recv(() => {});
+          libdeno.send(new Uint8Array([1]));
+          libdeno.send(new Uint8Array([2]));
+          libdeno.send(new Uint8Array([3]));
+        "#,
+        ).expect("execute error");
+      std::thread::sleep(Duration::from_millis(1));
+      let status = isolate.status();
+      // One running, two queued.
+      assert_eq!(status.pending_ops, 3);
+      assert!(status.uptime >= Duration::from_millis(1));
+      assert!(status.heap.used_heap_size > 0);
+      assert!(status.to_json().contains("\"pending_ops\":3,"));
+
+      isolate.event_loop().unwrap();
+      assert_eq!(isolate.status().pending_ops, 0);
+    });
+  }
+
+  fn run_one_async_op() -> Isolate {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_async);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "y.js",
+          r#"
+          libdeno.recv(() => {});
+          libdeno.send(new Uint8Array([1]));
+        "#,
+        ).expect("execute error");
+      isolate.event_loop().unwrap();
+    });
+    isolate
+  }
+
+  #[cfg(debug_assertions)]
+  #[test]
+  #[should_panic(expected = "not pending")]
+  fn test_double_respond_asserts() {
+    let mut isolate = run_one_async_op();
+    // req_id 0 has already been completed by the event loop.
+    let _ = isolate.respond(0, Box::new([]));
+  }
+
+  #[cfg(not(debug_assertions))]
+  #[test]
+  fn test_double_respond_errors() {
+    let mut isolate = run_one_async_op();
+    // req_id 0 has already been completed by the event loop.
+    assert!(isolate.respond(0, Box::new([])).is_err());
+  }
+
+  static RUNNING: AtomicUsize = ATOMIC_USIZE_INIT;
+  static MAX_RUNNING: AtomicUsize = ATOMIC_USIZE_INIT;
+
+  fn dispatch_async_slow(
+    _isolate: &mut Isolate,
+    _control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    let op = futures::future::lazy(|| -> Result<Buf, DenoError> {
+      let running = RUNNING.fetch_add(1, Ordering::SeqCst) + 1;
+      // There is no fetch_max, so retry until running is stored or a larger
+      // count is.
+      let mut max = MAX_RUNNING.load(Ordering::SeqCst);
+      while running > max {
+        let prev =
+          MAX_RUNNING.compare_and_swap(max, running, Ordering::SeqCst);
+        if prev == max {
+          break;
+        }
+        max = prev;
+      }
+      std::thread::sleep(Duration::from_millis(10));
+      RUNNING.fetch_sub(1, Ordering::SeqCst);
+      let buf: Buf = Box::new([]);
+      Ok(buf)
+    });
+    (false, Box::new(op))
+  }
+
+  #[test]
+  fn test_execute_utf16() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_sync);
+    let source: Vec<u16> =
+      "if ('héllo wörld ✓'.length !== 13) throw Error('bad length');"
+        .encode_utf16()
+        .collect();
+    isolate
+      .execute_utf16("utf16.js", &source)
+      .expect("execute error");
+
+    let source: Vec<u16> = "throw Error('größe');".encode_utf16().collect();
+    let err = isolate.execute_utf16("utf16.js", &source).unwrap_err();
+    assert!(err.contains("größe"));
+  }
+
+  #[test]
+  fn test_post_task() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_sync);
+    let handle = isolate.handle();
+    let loop_thread = std::thread::current().id();
+    let poster = std::thread::spawn(move || {
+      handle.post(move |isolate: &mut Isolate| {
+        assert_eq!(std::thread::current().id(), loop_thread);
+        isolate
+          .execute("post.js", "posted = true;")
+          .expect("execute error");
+      });
+    });
+    poster.join().unwrap();
+    assert!(!isolate.is_idle());
+    tokio_util::init(|| {
+      isolate.event_loop().unwrap();
+    });
+    isolate
+      .execute("check.js", "if (!posted) throw Error('task did not run');")
+      .expect("execute error");
+  }
+
+  #[test]
+  fn test_reset_drops_stale_responses() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_echo_delayed);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "old.js",
+          r#"
+          libdeno.recv(() => { throw Error("old context got a response"); });
+          libdeno.send(new Uint8Array([1]));
+        "#,
+        ).expect("execute error");
+      isolate.reset();
+      // The new context starts again at req_id 0, so the response to the old
+      // op would be delivered here if it weren't dropped.
+      isolate
+        .execute(
+          "new.js",
+          r#"
+          received = [];
+          libdeno.recv((msg) => { received.push(msg[0]); });
+          libdeno.send(new Uint8Array([2]));
+        "#,
+        ).expect("execute error");
+      isolate.event_loop().unwrap();
+    });
+    isolate
+      .execute(
+        "check.js",
+        r#"
+        if (received.length !== 1 || received[0] !== 2) {
+          throw Error("unexpected responses: " + received);
+        }
+      "#,
+      ).expect("execute error");
+  }
+
+  // Writes to the data buffer after a while, like an op reading into it.
+  fn dispatch_fill_delayed(
+    _isolate: &mut Isolate,
+    _control: &[u8],
+    data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    let op = futures::future::lazy(move || -> Result<Buf, DenoError> {
+      std::thread::sleep(Duration::from_millis(10));
+      for byte in data.iter_mut() {
+        *byte = 9;
+      }
+      Ok(Box::new([]))
+    });
+    (false, Box::new(op))
+  }
+
+  #[test]
+  fn test_reset_keeps_heap_for_ops_in_flight() {
+    let mut isolate =
+      Isolate::from_flags(Default::default(), vec![], dispatch_fill_delayed);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "old.js",
+          "libdeno.send(new Uint8Array([0]), new Uint8Array(1024));",
+        ).expect("execute error");
+      isolate.reset();
+      // The op still has its data buffer, in the old V8 heap.
+      assert_eq!(isolate.retired_isolates.len(), 1);
+      isolate.event_loop().unwrap();
+    });
+    assert!(isolate.retired_isolates.is_empty());
+    assert!(isolate.ops_by_generation.is_empty());
+    // Without ops in flight, the old isolate is deleted right away.
+    isolate.reset();
+    assert!(isolate.retired_isolates.is_empty());
+  }
+
+  #[test]
+  fn test_reset_closes_resources() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_sync);
+    let rid = isolate
+      .state
+      .resource_table
+      .lock()
+      .unwrap()
+      .add(Box::new(String::from("resource")))
+      .unwrap();
+    isolate.reset();
+    let mut resource_table = isolate.state.resource_table.lock().unwrap();
+    assert!(resource_table.get_mut::<String>(rid).is_err());
+  }
+
+  #[test]
+  fn test_stale_response_policy() {
+    for &policy in &[
+      flags::StaleResponsePolicy::Drop,
+      flags::StaleResponsePolicy::Error,
+    ] {
+      let flags = flags::DenoFlags {
+        stale_response_policy: policy,
+        ..Default::default()
+      };
+      let mut isolate =
+        Isolate::from_flags(flags, vec![], dispatch_echo_delayed);
+      tokio_util::init(|| {
+        isolate
+          .execute(
+            "old.js",
+            r#"
+            libdeno.recv(() => { throw Error("old context got a response"); });
+            libdeno.send(new Uint8Array([1]));
+          "#,
+          ).expect("execute error");
+        isolate.reset();
+        isolate
+          .execute(
+            "new.js",
+            r#"
+            received = [];
+            libdeno.recv((msg) => { received.push(msg[0]); });
+            libdeno.send(new Uint8Array([2]));
+          "#,
+          ).expect("execute error");
+        // The old op completes while the new context is running.
+        isolate.event_loop().unwrap();
+      });
+      isolate
+        .execute(
+          "check.js",
+          "if (received.length !== 1) throw Error(received);",
+        ).expect("execute error");
+
+      // What the task that completed the old op was told. Generation 0 is
+      // the context from before reset().
+      isolate.ntasks_increment();
+      let buf: Buf = vec![1].into_boxed_slice();
+      let result = isolate.state.send_to_js(0, 0, buf);
+      match policy {
+        flags::StaleResponsePolicy::Drop => assert!(result.is_ok()),
+        flags::StaleResponsePolicy::Error => {
+          assert_eq!(result.unwrap_err().kind(), ErrorKind::ContextGone)
+        }
+      }
+      // The response is dropped, and the op accounted for.
+      isolate.wait_for_message();
+      assert_eq!(isolate.ntasks, 0);
+      isolate
+        .execute(
+          "check.js",
+          "if (received.length !== 1) throw Error(received);",
+        ).expect("execute error");
+    }
+  }
+
+  #[test]
+  fn test_pause_resume() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_echo_delayed);
+    isolate.pause();
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "pause.js",
+          r#"
+          received = [];
+          libdeno.recv((msg) => { received.push(msg[0]); });
+          libdeno.send(new Uint8Array([1]));
+          libdeno.send(new Uint8Array([2]));
+          libdeno.send(new Uint8Array([3]));
+        "#,
+        ).expect("execute error");
+      // Returns once all three completions have been buffered.
+      isolate.event_loop().unwrap();
+    });
+    isolate
+      .execute(
+        "check_paused.js",
+        "if (received.length !== 0) throw Error('delivered while paused');",
+      ).expect("execute error");
+
+    let buffered: Vec<String> = isolate
+      .paused_queue
+      .iter()
+      .map(|msg| match *msg {
+        Message::OpDone(_, _, ref buf) => buf[0].to_string(),
+        _ => unreachable!(),
+      }).collect();
+    assert_eq!(buffered.len(), 3);
+
+    isolate.resume();
+    assert!(isolate.is_idle());
+    let check = format!(
+      "if (received.join() !== '{}') throw Error('got ' + received);",
+      buffered.join(",")
+    );
+    isolate
+      .execute("check_resumed.js", &check)
+      .expect("execute error");
+  }
+
+  #[test]
+  fn test_resume_with_queued_ops() {
+    let flags = flags::DenoFlags {
+      max_concurrent_ops: Some(1),
+      ..Default::default()
+    };
+    let mut isolate =
+      Isolate::from_flags(flags, vec![], dispatch_echo_delayed);
+    isolate.pause();
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "pause.js",
+          r#"
+          received = [];
+          libdeno.recv((msg) => { received.push(msg[0]); });
+          for (let i = 1; i <= 3; i++) {
+            libdeno.send(new Uint8Array([i]));
+          }
+        "#,
+        ).expect("execute error");
+      // Returns once the running op's completion has been buffered.
+      isolate.event_loop().unwrap();
+    });
+    assert_eq!(isolate.state.pending_ops.lock().unwrap().len(), 2);
+
+    // Outside of the runtime, so resume() must leave the queued ops to the
+    // event loop.
+    isolate.resume();
+    assert_eq!(isolate.ntasks, 0);
+    assert_eq!(isolate.state.pending_ops.lock().unwrap().len(), 2);
+    assert!(!isolate.is_idle());
+    tokio_util::init(|| {
+      isolate.event_loop().unwrap();
+    });
+    isolate
+      .execute(
+        "check.js",
+        "if (received.join() !== '1,2,3') throw Error('got ' + received);",
+      ).expect("execute error");
+  }
+
+  #[test]
+  fn test_timer_response() {
+    assert!(is_reserved_req_id(TIMER_RESPONSE_ID));
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_timer);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "timer.js",
+          r#"
+          timerFired = 0;
+          libdeno.recv((msg, reqId) => {
+            if (msg !== null) throw Error("expected null msg");
+            if (reqId !== -1) throw Error("expected timer req_id");
+            timerFired++;
+            // Clear the timer.
+            libdeno.send(new Uint8Array([0]));
+          });
+          // Set the timer.
+          libdeno.send(new Uint8Array([1]));
+        "#,
+        ).expect("execute error");
+      isolate.event_loop().unwrap();
+    });
+    isolate
+      .execute("check.js", "if (timerFired !== 1) throw Error(timerFired);")
+      .expect("execute error");
+    // The timer response is not tracked like an op response.
+    assert!(isolate.state.outstanding_ops.lock().unwrap().is_empty());
+  }
+
+  // 1 sets the timer, 0 clears it, and 2 is an async op.
+  fn dispatch_wakeups(
+    isolate: &mut Isolate,
+    control: &[u8],
+    data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    match control[0] {
+      2 => dispatch_echo_delayed(isolate, control, data),
+      _ => dispatch_timer(isolate, control, data),
+    }
+  }
+
+  #[test]
+  fn test_wakeup_stats() {
+    let flags = flags::DenoFlags {
+      record_wakeups: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch_wakeups);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "wakeups.js",
+          r#"
+          responses = 0;
+          libdeno.recv((msg, reqId) => {
+            if (reqId === -1) {
+              // Clear the timer.
+              libdeno.send(new Uint8Array([0]));
+            } else {
+              responses++;
+            }
+          });
+          for (let i = 0; i < 3; i++) {
+            libdeno.send(new Uint8Array([2]));
+          }
+          // Set the timer.
+          libdeno.send(new Uint8Array([1]));
+        "#,
+        ).expect("execute error");
+      isolate.event_loop().unwrap();
+      assert_eq!(
+        isolate.wakeup_stats(),
+        WakeupStats {
+          timer: 1,
+          op: 3,
+          ..Default::default()
+        }
+      );
+
+      isolate.handle().post(|_| {});
+      isolate.event_loop().unwrap();
+      assert_eq!(isolate.wakeup_stats().task, 1);
+
+      // The response arrives after the context that wanted it is gone.
+      isolate
+        .execute("stale.js", "libdeno.send(new Uint8Array([2]));")
+        .expect("execute error");
+      isolate.reset();
+      isolate.event_loop().unwrap();
+      assert_eq!(
+        isolate.wakeup_stats(),
+        WakeupStats {
+          timer: 1,
+          op: 3,
+          task: 1,
+          spurious: 1,
+        }
+      );
+    });
+
+    // Nothing is counted unless asked for.
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch_async);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "a.js",
+          "libdeno.recv(() => {}); libdeno.send(new Uint8Array([1]));",
+        ).expect("execute error");
+      isolate.event_loop().unwrap();
+    });
+    assert_eq!(isolate.wakeup_stats(), WakeupStats::default());
+  }
+
+  #[test]
+  fn test_stack_size() {
+    const RECURSE: &str = r#"
+      function f(n) { return n === 0 ? 0 : 1 + f(n - 1); }
+      f(200000);
+    "#;
+    // Raising V8's limit is only safe if the thread's stack is that big.
+    let child = std::thread::Builder::new()
+      .stack_size(256 << 20)
+      .spawn(|| {
+        let argv = vec![String::from("./deno"), String::from("hello.js")];
+        let mut isolate = Isolate::new(argv, dispatch_sync);
+        // RangeError: Maximum call stack size exceeded
+        assert!(isolate.execute("default.js", RECURSE).is_err());
+
+        let flags = flags::DenoFlags {
+          stack_size: Some(128 << 20),
+          ..flags::DenoFlags::default()
+        };
+        let mut isolate = Isolate::from_flags(flags, vec![], dispatch_sync);
+        isolate.execute("big.js", RECURSE).expect("execute error");
+      }).unwrap();
+    child.join().unwrap();
+  }
+
+  #[test]
+  fn test_idle_gc() {
+    fn run(idle_gc: bool) -> Vec<GcEvent> {
+      let flags = flags::DenoFlags {
+        idle_gc,
+        ..Default::default()
+      };
+      let mut isolate = Isolate::from_flags(flags, vec![], dispatch_slow_timer);
+      let events = Arc::new(Mutex::new(Vec::new()));
+      let events_ = events.clone();
+      isolate.set_on_gc(Box::new(move |event| {
+        events_.lock().unwrap().push(event);
+      }));
+      tokio_util::init(|| {
+        isolate
+          .execute(
+            "idle.js",
+            r#"
+            // Garbage, some of which is left for the idle time.
+            for (let i = 0; i < 1e5; i++) new Array(100);
+            libdeno.recv(() => {
+              // Clear the timer.
+              libdeno.send(new Uint8Array([0]));
+            });
+            // Set the timer.
+            libdeno.send(new Uint8Array([1]));
+          "#,
+          ).expect("execute error");
+        isolate.event_loop().unwrap();
+      });
+      let events = events.lock().unwrap().clone();
+      events
+    }
+
+    let events = run(true);
+    // The script made enough garbage for V8 to collect while it ran.
+    assert!(events.iter().any(|e| match *e {
+      GcEvent::Collected { .. } => true,
+      _ => false,
+    }));
+    let idle: Vec<Duration> = events
+      .iter()
+      .filter_map(|e| match *e {
+        GcEvent::Idle { deadline, .. } => Some(deadline),
+        _ => None,
+      }).collect();
+    assert!(!idle.is_empty());
+    for deadline in idle {
+      assert!(deadline >= Duration::from_millis(IDLE_GC_MIN_WAIT_MS));
+      assert!(deadline <= Duration::from_millis(200));
+    }
+
+    let events = run(false);
+    assert!(events.iter().all(|e| match *e {
+      GcEvent::Idle { .. } => false,
+      _ => true,
+    }));
+  }
+
+  #[test]
+  fn test_console_sink() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_sync);
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let output_ = output.clone();
+    isolate.set_console_sink(Box::new(move |level, msg| {
+      output_.lock().unwrap().push((level, msg.to_string()));
+    }));
+    isolate
+      .execute(
+        "console.js",
+        r#"
+        console.log("hello", 42);
+        console.error("oops");
+      "#,
+      ).expect("execute error");
+    let output = output.lock().unwrap();
+    assert_eq!(
+      *output,
+      vec![
+        (ConsoleLevel::Log, String::from("hello 42")),
+        (ConsoleLevel::Error, String::from("oops")),
+      ]
+    );
+  }
+
+  fn random_sequence(seed: u64) -> String {
+    let flags = flags::DenoFlags {
+      random_seed: Some(seed),
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch_sync);
+    let output = Arc::new(Mutex::new(String::new()));
+    let output_ = output.clone();
+    isolate.set_console_sink(Box::new(move |_level, msg| {
+      output_.lock().unwrap().push_str(msg);
+    }));
+    isolate
+      .execute(
+        "random.js",
+        r#"
+        const a = [Math.random(), Math.random(), Math.random()];
+        if (!a.every(x => x >= 0 && x < 1)) throw Error(a.join());
+        console.log(a.join());
+      "#,
+      ).expect("execute error");
+    let output = output.lock().unwrap();
+    output.clone()
+  }
+
+  #[test]
+  fn test_random_seed() {
+    assert_eq!(random_sequence(1), random_sequence(1));
+    assert_ne!(random_sequence(1), random_sequence(2));
+    assert_ne!(random_sequence(0), random_sequence(1));
+  }
+
+  #[test]
+  fn test_execute_streaming() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_sync);
+    let source = "const größe = [1, 2, 3].map(x => x * 2).join();";
+    // Split every 3 bytes, which cuts "größe" in the middle of a character.
+    let chunks: Vec<Vec<u8>> =
+      source.as_bytes().chunks(3).map(|c| c.to_vec()).collect();
+    assert!(chunks.len() > 1);
+    isolate
+      .execute_streaming("streamed.js", chunks.into_iter())
+      .expect("execute error");
+    isolate
+      .execute("check.js", "if (größe !== '2,4,6') throw Error(größe);")
+      .expect("execute error");
+
+    let chunks = vec![b"let x = (1 +".to_vec(), b";".to_vec()];
+    let err = isolate
+      .execute_streaming("bad.js", chunks.into_iter())
+      .unwrap_err();
+    assert!(err.contains("SyntaxError"));
+  }
+
+  fn dispatch_panic(
+    _isolate: &mut Isolate,
+    _control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    panic!("dispatch failed")
+  }
+
+  #[test]
+  fn test_dispatch_panic() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_panic);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "panic.js",
+          r#"
+          let err;
+          try {
+            libdeno.send(new Uint8Array([1]));
+          } catch (e) {
+            err = e;
+          }
+          if (!(err instanceof RangeError)) throw Error("got " + err);
+          if (err.message !== "op panicked: dispatch failed") {
+            throw Error(err.message);
+          }
+        "#,
+        ).expect("execute error");
+    });
+    assert_eq!(isolate.dispatch_depth, 0);
+    assert!(isolate.state.outstanding_ops.lock().unwrap().is_empty());
+  }
+
+  #[test]
+  fn test_execute_streaming_panic() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_sync);
+    let chunks = vec![b"ran = true;".to_vec()]
+      .into_iter()
+      .chain((0..1).map(|_| -> Vec<u8> { panic!("chunk failed") }));
+    let r = panic::catch_unwind(AssertUnwindSafe(|| {
+      isolate.execute_streaming("panic.js", chunks)
+    }));
+    let payload = r.unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"chunk failed"));
+    // The chunk read before the panic wasn't run.
+    isolate
+      .execute("check.js", "if (typeof ran !== 'undefined') throw Error();")
+      .expect("execute error");
+  }
+
+  // Runs a script with an unhandled and a handled rejection in the given mode.
+  // Returns what event_loop() returned and the console output.
+  fn run_rejections(
+    mode: flags::UnhandledRejectionMode,
+  ) -> (DenoResult<()>, Vec<String>) {
+    let flags = flags::DenoFlags {
+      unhandled_rejection_mode: Some(mode),
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch_sync);
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let output_ = output.clone();
+    isolate.set_console_sink(Box::new(move |_level, msg| {
+      output_.lock().unwrap().push(msg.to_string());
+    }));
+    isolate
+      .execute(
+        "rejections.js",
+        r#"
+        Promise.reject(new Error("boom"));
+        const handled = Promise.reject(new Error("handled"));
+        handled.catch(() => {});
+      "#,
+      ).expect("execute error");
+    let r = isolate.event_loop();
+    let output = output.lock().unwrap().clone();
+    (r, output)
+  }
+
+  #[test]
+  fn test_unhandled_rejection_strict() {
+    let (r, output) = run_rejections(flags::UnhandledRejectionMode::Strict);
+    let err = r.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnhandledRejection);
+    assert!(err.to_string().contains("boom"));
+    assert!(output.is_empty());
+  }
+
+  #[test]
+  fn test_unhandled_rejection_warn() {
+    let (r, output) = run_rejections(flags::UnhandledRejectionMode::Warn);
+    assert!(r.is_ok());
+    assert_eq!(
+      output,
+      vec![String::from("Unhandled promise rejection: Error: boom")]
+    );
+  }
+
+  #[test]
+  fn test_unhandled_rejection_ignore() {
+    let (r, output) = run_rejections(flags::UnhandledRejectionMode::Ignore);
+    assert!(r.is_ok());
+    assert!(output.is_empty());
+  }
+
+  #[test]
+  fn test_rejection_req_id() {
+    let flags = flags::DenoFlags {
+      unhandled_rejection_mode: Some(flags::UnhandledRejectionMode::Warn),
+      ..Default::default()
+    };
+    let mut isolate =
+      Isolate::from_flags(flags, vec![], dispatch_echo_delayed);
+    // Which req_id each message, identified by its first byte, was sent with.
+    let req_ids = Arc::new(Mutex::new(HashMap::new()));
+    let req_ids_ = req_ids.clone();
+    isolate.set_inspector(Box::new(move |direction, req_id, buf| {
+      if direction == Direction::Incoming && !buf.is_empty() {
+        req_ids_.lock().unwrap().insert(buf[0], req_id);
+      }
+    }));
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let output_ = output.clone();
+    isolate.set_console_sink(Box::new(move |_level, msg| {
+      output_.lock().unwrap().push(msg.to_string());
+    }));
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "rejection_req_id.js",
+          r#"
+          libdeno.recv((buf) => {
+            const id = new Uint8Array(buf)[0];
+            if (id === 3) throw new Error("recv " + id);
+            Promise.resolve().then(() => {
+              if (id === 2) throw new Error("then " + id);
+            });
+          });
+          libdeno.send(new Uint8Array([1]));
+          libdeno.send(new Uint8Array([2]));
+          libdeno.send(new Uint8Array([3]));
+        "#,
+        ).expect("execute error");
+      isolate.event_loop().unwrap();
+    });
+
+    let req_ids = req_ids.lock().unwrap();
+    let mut output = output.lock().unwrap().clone();
+    output.sort();
+    assert_eq!(output.len(), 2);
+    let recv_suffix = format!("(in response to req_id {})", req_ids[&3]);
+    assert!(output[0].contains("Error: recv 3"));
+    assert!(output[0].ends_with(&recv_suffix));
+    let then_suffix = format!(" (in response to req_id {})", req_ids[&2]);
+    assert_eq!(
+      output[1],
+      String::from("Unhandled promise rejection: Error: then 2") + &then_suffix
+    );
+  }
+
+  #[test]
+  fn test_abort_on_uncaught() {
+    let flags = flags::DenoFlags {
+      abort_on_uncaught: true,
+      ..Default::default()
+    };
+    let mut isolate =
+      Isolate::from_flags(flags, vec![], dispatch_echo_delayed);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "abort_on_uncaught.js",
+          r#"
+          let received = 0;
+          libdeno.recv(() => {
+            received++;
+            throw new Error("deferred");
+          });
+          libdeno.send(new Uint8Array([1]));
+          libdeno.send(new Uint8Array([2]));
+        "#,
+        ).expect("execute error");
+      let err = isolate.event_loop().unwrap_err();
+      assert_eq!(err.kind(), ErrorKind::UncaughtException);
+      assert!(err.to_string().contains("Error: deferred"));
+      // The loop stopped at the first throw.
+      isolate
+        .execute("check.js", "if (received !== 1) throw Error(received);")
+        .expect("execute error");
+    });
+  }
 
-  let isolate = Isolate::from_void_ptr(user_data);
-  let dispatch = isolate.dispatch;
-  let (is_sync, op) = dispatch(isolate, control_slice, data_slice);
+  // Where libdeno embeds the snapshot it is built with, see
+  // libdeno/from_snapshot.cc.
+  extern "C" {
+    static deno_snapshot_start: u8;
+    static deno_snapshot_end: u8;
+  }
 
-  if is_sync {
-    // Execute op synchronously.
-    let buf = tokio_util::block_on(op).unwrap();
-    if buf.len() != 0 {
-      // Set the synchronous response, the value returned from isolate.send().
-      isolate.respond(req_id, buf);
+  #[test]
+  fn test_shared_snapshot() {
+    let snapshot: Arc<[u8]> = unsafe {
+      let start = &deno_snapshot_start as *const u8;
+      let end = &deno_snapshot_end as *const u8;
+      let len = end as usize - start as usize;
+      Arc::from(std::slice::from_raw_parts(start, len))
+    };
+    let mut isolates: Vec<Isolate> = (0..3)
+      .map(|_| {
+        Isolate::new_with_snapshot(
+          snapshot.clone(),
+          Default::default(),
+          vec![],
+          dispatch_sync,
+        )
+      }).collect();
+    assert_eq!(Arc::strong_count(&snapshot), 4);
+    // Defined by the snapshotted runtime, see js/globals.ts and js/main.ts.
+    let script = r#"
+      if (typeof TextEncoder !== "function") throw Error("no TextEncoder");
+      if (typeof denoMain !== "function") throw Error("no denoMain");
+    "#;
+    for isolate in &mut isolates {
+      isolate.execute("check.js", script).expect("execute error");
     }
-  } else {
-    // Execute op asynchronously.
-    let state = isolate.state.clone();
+    // The new context is created from the same snapshot.
+    isolates[0].reset();
+    isolates[0].execute("check.js", script).expect("execute error");
+    drop(isolates);
+    assert_eq!(Arc::strong_count(&snapshot), 1);
+  }
 
-    // TODO Ideally Tokio would could tell us how many tasks are executing, but
-    // it cannot currently. Therefore we track top-level promises/tasks
-    // manually.
-    isolate.ntasks_increment();
+  #[test]
+  fn test_hidden_globals() {
+    let flags = flags::DenoFlags {
+      hidden_globals: vec![String::from("libdeno")],
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch_sync);
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let output_ = output.clone();
+    isolate.set_console_sink(Box::new(move |_level, msg| {
+      output_.lock().unwrap().push(msg.to_string());
+    }));
+    let script = r#"
+      if (typeof libdeno !== "undefined") throw Error("libdeno is visible");
+      // console uses libdeno.print() internally.
+      console.log("still works");
+    "#;
+    isolate.execute("sandboxed.js", script).expect("execute error");
+    // The new context is sandboxed too.
+    isolate.reset();
+    isolate.execute("sandboxed.js", script).expect("execute error");
+    assert_eq!(
+      *output.lock().unwrap(),
+      vec![String::from("still works"), String::from("still works")]
+    );
 
-    let task = op
-      .and_then(move |buf| {
-        state.send_to_js(req_id, buf);
-        Ok(())
-      }).map_err(|_| ());
-    tokio::spawn(task);
+    // Names that can't be hidden don't stop the others from being hidden, and
+    // any name is quoted properly.
+    let flags = flags::DenoFlags {
+      hidden_globals: vec![
+        String::from("undefined"),
+        String::from("a\"b\\c\n\u{2028}"),
+        String::from("libdeno"),
+      ],
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch_sync);
+    isolate
+      .execute(
+        "sandboxed.js",
+        r#"if (typeof libdeno !== "undefined") throw Error("visible");"#,
+      ).expect("execute error");
   }
-}
 
-#[cfg(test)]
-mod tests {
-  use super::*;
-  use futures;
+  #[test]
+  fn test_js_string() {
+    assert_eq!(js_string("libdeno"), r#""libdeno""#);
+    assert_eq!(js_string("a\"b\\c"), r#""a\"b\\c""#);
+    assert_eq!(js_string("\n\u{2028}é"), r#""\u000a\u2028é""#);
+  }
+
+  // Responds with the control buffer after control[0] * 10 milliseconds.
+  fn dispatch_sleep(
+    _isolate: &mut Isolate,
+    control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    let buf: Buf = control.to_vec().into_boxed_slice();
+    let delay = Duration::from_millis(10 * control[0] as u64);
+    let op = futures::future::lazy(move || -> Result<Buf, DenoError> {
+      std::thread::sleep(delay);
+      Ok(buf)
+    });
+    (false, Box::new(op))
+  }
 
   #[test]
-  fn test_dispatch_sync() {
+  fn test_op_ordering() {
+    let flags = flags::DenoFlags {
+      record_op_ordering: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch_sleep);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "ordering.js",
+          r#"
+          libdeno.recv(() => {});
+          libdeno.send(new Uint8Array([10]));
+          libdeno.send(new Uint8Array([1]));
+        "#,
+        ).expect("execute error");
+      isolate.event_loop().unwrap();
+    });
+    let ordering = isolate.op_ordering();
+    assert_eq!(ordering.len(), 2);
+    // The first op dispatched completed second.
+    assert_eq!((ordering[0].1, ordering[0].2), (0, 1));
+    assert_eq!((ordering[1].1, ordering[1].2), (1, 0));
+    assert!(ordering[0].0 != ordering[1].0);
+  }
+
+  #[test]
+  fn test_op_ordering_capacity() {
+    let mut ordering = OpOrdering::default();
+    for req_id in 0..(OP_ORDERING_CAPACITY as i32 + 5) {
+      ordering.dispatch(req_id);
+      ordering.complete(req_id);
+    }
+    assert_eq!(ordering.done.len(), OP_ORDERING_CAPACITY);
+    // The oldest were dropped.
+    assert_eq!(ordering.done.front(), Some(&(5, 5, 5)));
+  }
+
+  thread_local! {
+    // (deepest execute_depth seen, errors) of dispatch_recurse.
+    static RECURSE_RESULTS: std::cell::RefCell<(usize, Vec<ErrorKind>)> =
+      std::cell::RefCell::new((0, Vec::new()));
+  }
+
+  // Executes JS that sends another op to this function, forever.
+  fn dispatch_recurse(
+    isolate: &mut Isolate,
+    _control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    let depth = isolate.execute_depth;
+    RECURSE_RESULTS.with(|results| {
+      let mut results = results.borrow_mut();
+      results.0 = std::cmp::max(results.0, depth);
+    });
+    let source = "libdeno.send(new Uint8Array([0]));";
+    if let Err(err) = isolate.execute_nested("recurse.js", source) {
+      RECURSE_RESULTS.with(|results| results.borrow_mut().1.push(err.kind()));
+    }
+    let buf: Buf = Box::new([]);
+    (true, Box::new(futures::future::ok(buf)))
+  }
+
+  #[test]
+  fn test_max_execute_depth() {
+    let flags = flags::DenoFlags {
+      max_execute_depth: Some(3),
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch_recurse);
+    isolate
+      .execute("start.js", "libdeno.send(new Uint8Array([0]));")
+      .expect("execute error");
+    let (deepest, errors) =
+      RECURSE_RESULTS.with(|results| results.borrow().clone());
+    assert_eq!(deepest, 3);
+    assert_eq!(errors, vec![ErrorKind::ExecuteDepthExceeded]);
+    assert_eq!(isolate.execute_depth, 0);
+    // Only nested executes are refused.
+    isolate.execute("again.js", "1").expect("execute error");
+  }
+
+  #[test]
+  fn test_pending_ops_detail() {
+    fn dispatch_sleep_op(
+      isolate: &mut Isolate,
+      control: &[u8],
+      data: &'static mut [u8],
+    ) -> (bool, Box<Op>) {
+      isolate.set_dispatch_op_id(control[0] as u32);
+      dispatch_sleep(isolate, control, data)
+    }
+
+    let flags = flags::DenoFlags::default();
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch_sleep_op);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "pending.js",
+          r#"
+          libdeno.recv(() => {});
+          libdeno.send(new Uint8Array([20]));
+          libdeno.send(new Uint8Array([30]));
+        "#,
+        ).expect("execute error");
+      std::thread::sleep(Duration::from_millis(10));
+      let detail = isolate.pending_ops_detail();
+      assert_eq!(detail.len(), 2);
+      assert!(detail[0].req_id < detail[1].req_id);
+      assert_eq!(detail[0].op_id, Some(20));
+      assert_eq!(detail[1].op_id, Some(30));
+      assert!(detail.iter().all(|op| op.elapsed > Duration::from_millis(0)));
+      isolate.event_loop().unwrap();
+    });
+    assert!(isolate.pending_ops_detail().is_empty());
+  }
+
+  #[test]
+  fn test_deterministic_ops() {
+    // Returns the completion sequence number of each op, in dispatch order.
+    fn run(seed: u64, delays: &[u8]) -> Vec<usize> {
+      let flags = flags::DenoFlags {
+        record_op_ordering: true,
+        deterministic_ops: Some(seed),
+        ..Default::default()
+      };
+      let mut isolate = Isolate::from_flags(flags, vec![], dispatch_sleep);
+      let mut script = String::from("libdeno.recv(() => {});");
+      for delay in delays {
+        script += &format!("libdeno.send(new Uint8Array([{}]));", delay);
+      }
+      isolate.pause();
+      tokio_util::init(|| {
+        isolate.execute("workload.js", &script).expect("execute error");
+        // Returns once every op has completed and been buffered, so that
+        // resume() delivers them as one batch.
+        isolate.event_loop().unwrap();
+        isolate.resume();
+        isolate.event_loop().unwrap();
+      });
+      let ordering = isolate.op_ordering();
+      ordering.iter().map(|&(_, _, completion)| completion).collect()
+    }
+
+    let first = run(42, &[0, 1, 2, 3, 4, 5]);
+    assert_eq!(run(42, &[0, 1, 2, 3, 4, 5]), first);
+    // The order the ops completed in doesn't matter, only the seed and what
+    // was dispatched.
+    assert_eq!(run(42, &[5, 4, 3, 2, 1, 0]), first);
+    let mut completions = first.clone();
+    completions.sort();
+    assert_eq!(completions, vec![0, 1, 2, 3, 4, 5]);
+  }
+
+  #[test]
+  fn test_deterministic_ops_interdependent() {
+    static SIGNALLED: AtomicBool = ATOMIC_BOOL_INIT;
+
+    // Op 0 completes once op 1 has been dispatched. Op 2 completes at once.
+    fn dispatch_signal(
+      _isolate: &mut Isolate,
+      control: &[u8],
+      _data: &'static mut [u8],
+    ) -> (bool, Box<Op>) {
+      let buf: Buf = control.to_vec().into_boxed_slice();
+      let kind = control[0];
+      let op = futures::future::lazy(move || -> Result<Buf, DenoError> {
+        if kind == 1 {
+          SIGNALLED.store(true, Ordering::SeqCst);
+        }
+        while kind == 0 && !SIGNALLED.load(Ordering::SeqCst) {
+          std::thread::sleep(Duration::from_millis(1));
+        }
+        Ok(buf)
+      });
+      (false, Box::new(op))
+    }
+
+    let flags = flags::DenoFlags {
+      record_op_ordering: true,
+      deterministic_ops: Some(42),
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch_signal);
+    tokio_util::init(|| {
+      // Op 1 is only dispatched once op 2 has been delivered, while op 0 is
+      // still running.
+      isolate
+        .execute(
+          "interdependent.js",
+          r#"
+          libdeno.recv(ui8 => {
+            if (ui8[0] === 2) {
+              libdeno.send(new Uint8Array([1]));
+            }
+          });
+          libdeno.send(new Uint8Array([0]));
+          libdeno.send(new Uint8Array([2]));
+        "#,
+        ).expect("execute error");
+      isolate.event_loop().unwrap();
+    });
+    assert_eq!(isolate.op_ordering().len(), 3);
+  }
+
+  #[test]
+  fn test_fetch_module_graph() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_sync);
+    // Assuming cwd is the deno repo root.
+    let modules = isolate
+      .fetch_module_graph("./tests/003_relative_import.ts")
+      .unwrap();
+    assert!(modules[0].ends_with("/tests/003_relative_import.ts"));
+    assert!(modules[1].ends_with("/tests/subdir/print_hello.ts"));
+    assert_eq!(modules.len(), 2);
+
+    let err = isolate
+      .fetch_module_graph("./tests/error_004_missing_module.ts")
+      .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotFound);
+    assert!(err.to_string().contains("\"bad-module.ts\""));
+  }
+
+  #[test]
+  fn test_cancel_module_loading() {
+    // Serves m0 to m9, each importing the next. While block_m3 is set,
+    // loading m3 only finishes when it's cancelled.
+    struct BlockingLoader {
+      loads: Arc<AtomicUsize>,
+      block_m3: Arc<AtomicBool>,
+    }
+
+    impl deno_dir::ModuleLoader for BlockingLoader {
+      fn resolve(
+        &self,
+        module_specifier: &str,
+        _containing_file: &str,
+      ) -> DenoResult<(String, String)> {
+        Ok((module_specifier.to_string(), module_specifier.to_string()))
+      }
+
+      fn load(
+        &self,
+        module_name: &str,
+        filename: &str,
+      ) -> DenoResult<deno_dir::CodeFetchOutput> {
+        self.load_cancellable(
+          module_name,
+          filename,
+          &deno_dir::CancellationToken::default(),
+        )
+      }
+
+      fn load_cancellable(
+        &self,
+        module_name: &str,
+        filename: &str,
+        cancel: &deno_dir::CancellationToken,
+      ) -> DenoResult<deno_dir::CodeFetchOutput> {
+        cancel.check()?;
+        self.loads.fetch_add(1, Ordering::SeqCst);
+        let i: usize = module_name[1..].parse().unwrap();
+        if i == 3 && self.block_m3.load(Ordering::SeqCst) {
+          while !cancel.is_cancelled() {
+            std::thread::sleep(Duration::from_millis(1));
+          }
+          cancel.check()?;
+        }
+        let source_code = if i < 9 {
+          format!("import \"m{}\";", i + 1)
+        } else {
+          String::new()
+        };
+        Ok(deno_dir::CodeFetchOutput {
+          module_name: module_name.to_string(),
+          filename: filename.to_string(),
+          source_code,
+          maybe_output_code: None,
+        })
+      }
+    }
+
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch_sync);
+    let loads = Arc::new(AtomicUsize::new(0));
+    let block_m3 = Arc::new(AtomicBool::new(true));
+    isolate.set_module_loader(Box::new(BlockingLoader {
+      loads: loads.clone(),
+      block_m3: block_m3.clone(),
+    }));
+    let handle = isolate.handle();
+    let loads_ = loads.clone();
+    let canceller = std::thread::spawn(move || {
+      while loads_.load(Ordering::SeqCst) < 4 {
+        std::thread::sleep(Duration::from_millis(1));
+      }
+      handle.cancel_module_loading();
+    });
+    let err = isolate.fetch_module_graph("m0").unwrap_err();
+    canceller.join().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Cancelled);
+    // m4 to m9 were never loaded.
+    assert_eq!(loads.load(Ordering::SeqCst), 4);
+
+    // The cancellation was cleared by fetch_module_graph().
+    block_m3.store(false, Ordering::SeqCst);
+    loads.store(0, Ordering::SeqCst);
+    assert_eq!(isolate.fetch_module_graph("m0").unwrap().len(), 10);
+    assert_eq!(loads.load(Ordering::SeqCst), 10);
+  }
+
+  #[test]
+  fn test_inspector() {
     let argv = vec![String::from("./deno"), String::from("hello.js")];
     let mut isolate = Isolate::new(argv, dispatch_sync);
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let events_ = events.clone();
+    isolate.set_inspector(Box::new(move |direction, _req_id, buf| {
+      events_.lock().unwrap().push((direction, buf.len()));
+    }));
+    isolate
+      .execute("y.js", "libdeno.send(new Uint8Array([4, 5, 6]));")
+      .expect("execute error");
+    assert_eq!(
+      *events.lock().unwrap(),
+      vec![(Direction::Incoming, 3), (Direction::Outgoing, 3)]
+    );
+  }
+
+  #[test]
+  fn test_sync_op_in_async_completion() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_mixed);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "nested.js",
+          r#"
+          log = [];
+          libdeno.recv((msg) => {
+            log.push("async " + msg[0]);
+            const res = libdeno.send(new Uint8Array([7]));
+            log.push("sync " + res[0]);
+            log.push("async done");
+          });
+          libdeno.send(new Uint8Array([0]));
+        "#,
+        ).expect("execute error");
+      isolate.event_loop().unwrap();
+    });
+    assert_eq!(isolate.dispatch_depth, 0);
+    isolate
+      .execute(
+        "check.js",
+        r#"
+        if (log.join() !== "async 9,sync 7,async done") {
+          throw Error("wrong order: " + log);
+        }
+      "#,
+      ).expect("execute error");
+  }
+
+  // Async ops that never complete. [2] also aborts the event loop.
+  fn dispatch_abort(
+    isolate: &mut Isolate,
+    control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    if control[0] == 2 {
+      isolate.stop_event_loop(StopReason::Aborted(String::from("fatal")));
+    }
+    (false, Box::new(futures::future::empty()))
+  }
+
+  #[test]
+  fn test_stop_event_loop() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_abort);
     tokio_util::init(|| {
       isolate
         .execute(
           "y.js",
           r#"
-          const m = new Uint8Array([4, 5, 6]);
-          let n = libdeno.send(m);
-          if (!(n.byteLength === 3 &&
-                n[0] === 1 &&
-                n[1] === 2 &&
-                n[2] === 3)) {
-            throw Error("assert error");
+          libdeno.recv(() => {});
+          libdeno.send(new Uint8Array([1]));
+          libdeno.send(new Uint8Array([2]));
+        "#,
+        ).expect("execute error");
+      let err = isolate.event_loop().unwrap_err();
+      assert_eq!(err.kind(), ErrorKind::LoopAborted);
+      assert_eq!(isolate.status().pending_ops, 2);
+
+      isolate.stop_event_loop(StopReason::Finished);
+      assert!(isolate.event_loop().is_ok());
+      assert_eq!(isolate.status().pending_ops, 2);
+    });
+  }
+
+  // [1] starts an op that never completes, [2] synchronously returns whether
+  // the isolate is backpressured.
+  fn dispatch_backpressure(
+    isolate: &mut Isolate,
+    control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    if control[0] == 2 {
+      let buf: Buf = Box::new([isolate.is_backpressured() as u8]);
+      return (true, Box::new(futures::future::ok(buf)));
+    }
+    (false, Box::new(futures::future::empty()))
+  }
+
+  #[test]
+  fn test_backpressure() {
+    let flags = flags::DenoFlags {
+      op_high_water_mark: Some(3),
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch_backpressure);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "y.js",
+          r#"
+          function backpressured() {
+            return new Uint8Array(libdeno.send(new Uint8Array([2])))[0] === 1;
+          }
+          libdeno.recv(() => {});
+          const seen = [];
+          for (let i = 0; i < 4; i++) {
+            seen.push(backpressured());
+            libdeno.send(new Uint8Array([1]));
+          }
+          seen.push(backpressured());
+          if (seen.join() !== "false,false,false,true,true") {
+            throw Error(seen.join());
+          }
+        "#,
+        ).expect("execute error");
+    });
+  }
+
+  // control[0] == 0 is an async op responding with [9]. Anything else is a
+  // sync op which echos control.
+  fn dispatch_mixed(
+    isolate: &mut Isolate,
+    control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    // The sync op is sent from the async op's completion, which is delivered
+    // by the event loop, not from inside another dispatch.
+    assert_eq!(isolate.dispatch_depth, 1);
+    let is_sync = control[0] != 0;
+    let buf: Buf = if is_sync {
+      control.to_vec().into_boxed_slice()
+    } else {
+      Box::new([9])
+    };
+    (is_sync, Box::new(futures::future::ok(buf)))
+  }
+
+  #[test]
+  fn test_restart_in_use() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let isolate = Isolate::new(argv, dispatch_sync);
+    let handle = isolate.handle();
+    let (mut isolate, err) = isolate.restart().err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::Other);
+    // The isolate still works, and restarts once the handle is gone.
+    isolate.execute("a.js", "1 + 1").expect("execute error");
+    drop(handle);
+    isolate.restart().ok().expect("restart error");
+  }
+
+  #[test]
+  fn test_restart() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_sync);
+    isolate.register_op(1, Some("noop"), noop_op);
+    isolate.execute("a.js", "before = 1;").expect("execute error");
+
+    // Assuming cwd is the deno repo root.
+    let cwd = std::env::current_dir().unwrap();
+    let cwd_string = String::from(cwd.to_str().unwrap()) + "/";
+    isolate
+      .state
+      .dir
+      .code_fetch("./js/main.ts", &cwd_string)
+      .expect("code_fetch error");
+    let root = isolate.state.dir.root.clone();
+    let stats = isolate.state.dir.cache_stats();
+    assert_eq!(stats.modules_resolved, 1);
+
+    let mut isolate = isolate.restart().ok().expect("restart error");
+    // The same DenoDir, not just the same path.
+    assert_eq!(isolate.state.dir.root, root);
+    assert_eq!(isolate.state.dir.cache_stats(), stats);
+    assert_eq!(
+      isolate.state.argv,
+      vec![String::from("./deno"), String::from("hello.js")]
+    );
+    assert_eq!(isolate.registered_ops(), vec![1]);
+    // But the JS state is gone.
+    isolate
+      .execute("b.js", "if (typeof before !== 'undefined') throw Error();")
+      .expect("execute error");
+  }
+
+  fn noop_op(
+    _isolate: &mut Isolate,
+    _control: &[u8],
+    _data: &'static mut [u8],
+  ) -> Box<Op> {
+    let buf: Buf = Box::new([]);
+    Box::new(futures::future::ok(buf))
+  }
+
+  #[test]
+  fn test_registered_ops() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_sync);
+    assert!(isolate.registered_ops().is_empty());
+    isolate.register_op(7, Some("seven"), noop_op);
+    isolate.register_op(3, None, noop_op);
+    isolate.register_op(42, Some("answer"), noop_op);
+    // Registering an id again replaces it.
+    isolate.register_op(7, Some("sept"), noop_op);
+    assert_eq!(isolate.registered_ops(), vec![3, 7, 42]);
+    assert_eq!(isolate.registered_op_name(7), Some("sept"));
+    assert_eq!(isolate.registered_op_name(3), None);
+    assert!(isolate.registered_op(42).is_some());
+    assert!(isolate.registered_op(1).is_none());
+  }
+
+  #[test]
+  fn test_load_op_module() {
+    struct TinyModule;
+
+    impl OpModule for TinyModule {
+      fn register(&self, isolate: &mut Isolate) {
+        isolate.register_op(100, Some("tiny_a"), noop_op);
+        isolate.register_op(101, Some("tiny_b"), noop_op);
+      }
+    }
+
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_sync);
+    isolate.load_op_module(TinyModule);
+    assert_eq!(isolate.registered_ops(), vec![100, 101]);
+    assert_eq!(isolate.registered_op_name(100), Some("tiny_a"));
+    assert_eq!(isolate.registered_op_name(101), Some("tiny_b"));
+  }
+
+  #[test]
+  fn test_sync_op_panic() {
+    let argv = vec![String::from("./deno"), String::from("hello.js")];
+    let mut isolate = Isolate::new(argv, dispatch_sync_future_panic);
+    tokio_util::init(|| {
+      // The panic must not unwind through libdeno. send() throws it instead.
+      isolate
+        .execute(
+          "panic.js",
+          r#"
+          let err;
+          try {
+            libdeno.send(new Uint8Array([]));
+          } catch (e) {
+            err = e;
+          }
+          if (!(err instanceof RangeError)) throw Error("got " + err);
+          if (err.message !== "op panicked: sync op panicked") {
+            throw Error(err.message);
           }
         "#,
         ).expect("execute error");
-      isolate.event_loop();
     });
+    assert!(isolate.state.outstanding_ops.lock().unwrap().is_empty());
+  }
+
+  fn dispatch_sync_future_panic(
+    _isolate: &mut Isolate,
+    _control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    let op = futures::future::lazy(|| -> Result<Buf, DenoError> {
+      panic!("sync op panicked");
+    });
+    (true, Box::new(op))
+  }
+
+  // control[0] == 1 makes the global timer fire right away, 0 clears it.
+  fn dispatch_timer(
+    isolate: &mut Isolate,
+    control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    isolate.timeout_due = if control[0] == 1 {
+      Some(Instant::now())
+    } else {
+      None
+    };
+    let buf: Buf = Box::new([]);
+    (true, Box::new(futures::future::ok(buf)))
+  }
+
+  // control[0] == 1 makes the global timer fire in 200ms, 0 clears it.
+  fn dispatch_slow_timer(
+    isolate: &mut Isolate,
+    control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    isolate.timeout_due = if control[0] == 1 {
+      Some(Instant::now() + Duration::from_millis(200))
+    } else {
+      None
+    };
+    let buf: Buf = Box::new([]);
+    (true, Box::new(futures::future::ok(buf)))
+  }
+
+  // Echos the control buf back after a delay.
+  fn dispatch_echo_delayed(
+    _isolate: &mut Isolate,
+    control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    let buf: Buf = control.to_vec().into_boxed_slice();
+    let op = futures::future::lazy(move || -> Result<Buf, DenoError> {
+      std::thread::sleep(Duration::from_millis(10));
+      Ok(buf)
+    });
+    (false, Box::new(op))
+  }
+
+  fn dispatch_async(
+    _isolate: &mut Isolate,
+    _control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    let buf: Buf = Box::new([1]);
+    (false, Box::new(futures::future::ok(buf)))
+  }
+
+  fn dispatch_async_empty(
+    _isolate: &mut Isolate,
+    _control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    let buf: Buf = Box::new([]);
+    (false, Box::new(futures::future::ok(buf)))
   }
 
   fn dispatch_sync(
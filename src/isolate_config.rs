@@ -0,0 +1,132 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+use flags::DenoFlags;
+
+use std::env;
+use std::sync::Arc;
+
+// How V8 is set up for an isolate, in one place. Made from the flags and the
+// environment by from_flags(), or filled in by hand and passed to
+// Isolate::from_config().
+#[derive(Clone, Default)]
+pub struct IsolateConfig {
+  // Bytes the V8 heap may grow to, instead of V8's default. See
+  // DenoFlags::heap_limit.
+  pub heap_limit: Option<usize>,
+  // See DenoFlags::stack_size.
+  pub stack_size: Option<usize>,
+  // Passed to V8 before the isolate is created, like "--expose-gc". V8 flags
+  // are global to the process, so they stay set for isolates created later.
+  pub v8_flags: Vec<String>,
+  // A startup snapshot to create the isolate from instead of the built-in
+  // one. See deno_config in libdeno/deno.h. Isolates made from clones of a
+  // config share the snapshot, which is kept until the last of them is
+  // dropped.
+  pub snapshot: Option<Arc<[u8]>>,
+}
+
+impl IsolateConfig {
+  pub fn from_flags(flags: &DenoFlags) -> Self {
+    Self::from_flags_and_env(flags, |name| env::var(name).ok())
+  }
+
+  // Like from_flags(), but looks up environment variables with get_env.
+  // DENO_HEAP_LIMIT is the heap limit in megabytes, used if flags has none.
+  // DENO_V8_FLAGS are V8 flags separated by whitespace.
+  pub fn from_flags_and_env<F>(flags: &DenoFlags, get_env: F) -> Self
+  where
+    F: Fn(&str) -> Option<String>,
+  {
+    let heap_limit = flags.heap_limit.or_else(|| {
+      let mb = get_env("DENO_HEAP_LIMIT")?;
+      match mb.trim().parse::<usize>().map(|n| n.checked_mul(1 << 20)) {
+        Ok(Some(bytes)) => Some(bytes),
+        Ok(None) => {
+          warn!("ignoring DENO_HEAP_LIMIT={}, too large", mb);
+          None
+        }
+        Err(_) => {
+          warn!("ignoring DENO_HEAP_LIMIT={}, not a number", mb);
+          None
+        }
+      }
+    });
+    let v8_flags = get_env("DENO_V8_FLAGS")
+      .map(|s| s.split_whitespace().map(String::from).collect())
+      .unwrap_or_default();
+    IsolateConfig {
+      heap_limit,
+      stack_size: flags.stack_size,
+      v8_flags,
+      snapshot: None,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use isolate::Isolate;
+  use ops;
+
+  #[test]
+  fn test_from_flags_and_env() {
+    let env = |name: &str| match name {
+      "DENO_HEAP_LIMIT" => Some("64".to_string()),
+      "DENO_V8_FLAGS" => Some(" --expose-gc  --harmony ".to_string()),
+      _ => None,
+    };
+    let flags = DenoFlags {
+      stack_size: Some(2 << 20),
+      ..Default::default()
+    };
+    let config = IsolateConfig::from_flags_and_env(&flags, env);
+    assert_eq!(config.heap_limit, Some(64 << 20));
+    assert_eq!(config.stack_size, Some(2 << 20));
+    assert_eq!(config.v8_flags, vec!["--expose-gc", "--harmony"]);
+    assert!(config.snapshot.is_none());
+
+    // The flags win over the environment.
+    let flags = DenoFlags {
+      heap_limit: Some(32 << 20),
+      ..Default::default()
+    };
+    let config = IsolateConfig::from_flags_and_env(&flags, env);
+    assert_eq!(config.heap_limit, Some(32 << 20));
+
+    let config = IsolateConfig::from_flags_and_env(&flags, |_| None);
+    assert!(config.v8_flags.is_empty());
+    let bad = |_: &str| Some("lots".to_string());
+    let config = IsolateConfig::from_flags_and_env(&Default::default(), bad);
+    assert_eq!(config.heap_limit, None);
+    // Megabytes that don't fit in a usize once converted to bytes.
+    let huge = |_: &str| Some((usize::max_value() >> 10).to_string());
+    let config = IsolateConfig::from_flags_and_env(&Default::default(), huge);
+    assert_eq!(config.heap_limit, None);
+  }
+
+  #[test]
+  fn test_heap_limit() {
+    let flags = DenoFlags::default();
+    let isolate = Isolate::from_flags(flags, vec![], ops::dispatch);
+    let default_limit = isolate.heap_stats().heap_size_limit;
+    drop(isolate);
+
+    let config = IsolateConfig {
+      heap_limit: Some(64 << 20),
+      ..Default::default()
+    };
+    let flags = DenoFlags::default();
+    let mut isolate =
+      Isolate::from_config(config, flags, vec![], ops::dispatch);
+    assert_eq!(isolate.config().heap_limit, Some(64 << 20));
+    // The young generation comes on top of the limit.
+    let limit = isolate.heap_stats().heap_size_limit;
+    assert!(limit >= 64 << 20);
+    assert!(limit < default_limit);
+    isolate.execute("a.js", "1 + 2").expect("execute error");
+
+    // Kept by restart().
+    let isolate = isolate.restart().ok().expect("restart error");
+    assert_eq!(isolate.heap_stats().heap_size_limit, limit);
+  }
+}
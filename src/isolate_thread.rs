@@ -0,0 +1,140 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+use isolate::Isolate;
+use isolate::IsolateHandle;
+use isolate::MetricsSnapshot;
+use isolate::StopReason;
+use tokio_util;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+// Runs an isolate on a thread of its own. Isolate can't leave the thread it
+// was created on, but IsolateThread can be passed around freely. Requests are
+// posted to the isolate's event loop as tasks, so they run in between its
+// other work, and the caller waits for the result.
+pub struct IsolateThread {
+  handle: IsolateHandle,
+  shutting_down: Arc<AtomicBool>,
+  thread: Option<thread::JoinHandle<()>>,
+}
+
+impl IsolateThread {
+  // Starts a thread and calls create on it to make the isolate.
+  pub fn spawn<F>(create: F) -> IsolateThread
+  where
+    F: FnOnce() -> Isolate + Send + 'static,
+  {
+    let shutting_down = Arc::new(AtomicBool::new(false));
+    let shutting_down_ = shutting_down.clone();
+    let (handle_tx, handle_rx) = mpsc::channel();
+    let thread = thread::spawn(move || {
+      let mut isolate = create();
+      handle_tx.send(isolate.handle()).unwrap();
+      tokio_util::init(|| loop {
+        if let Err(err) = isolate.event_loop() {
+          error!("{}", err);
+        }
+        if shutting_down_.load(Ordering::SeqCst) {
+          break;
+        }
+        // Idle until the next request.
+        isolate.wait_for_message();
+      });
+    });
+    let handle = handle_rx
+      .recv()
+      .expect("isolate thread exited before creating the isolate");
+    IsolateThread {
+      handle,
+      shutting_down,
+      thread: Some(thread),
+    }
+  }
+
+  // Runs js_source on the isolate and waits for it to finish. Globals it
+  // defines are seen by later calls.
+  pub fn execute(
+    &self,
+    js_filename: &str,
+    js_source: &str,
+  ) -> Result<(), String> {
+    let js_filename = js_filename.to_string();
+    let js_source = js_source.to_string();
+    self.call(move |isolate| {
+      isolate
+        .execute(&js_filename, &js_source)
+        .map_err(|err| err.to_string())
+    })
+  }
+
+  pub fn metrics(&self) -> MetricsSnapshot {
+    self.call(|isolate| isolate.export_metrics())
+  }
+
+  // Runs f on the isolate thread and returns what it returned.
+  pub fn call<F, R>(&self, f: F) -> R
+  where
+    F: FnOnce(&mut Isolate) -> R + Send + 'static,
+    R: Send + 'static,
+  {
+    let (tx, rx) = mpsc::channel();
+    self.handle.post(move |isolate| {
+      tx.send(f(isolate)).unwrap();
+    });
+    rx.recv().expect("isolate thread exited")
+  }
+
+  // Stops the event loop, even if ops are still pending, drops the isolate
+  // and waits for the thread to exit. Dropping the IsolateThread does the
+  // same.
+  pub fn shutdown(mut self) {
+    self.stop();
+  }
+
+  fn stop(&mut self) {
+    if let Some(thread) = self.thread.take() {
+      self.shutting_down.store(true, Ordering::SeqCst);
+      self
+        .handle
+        .post(|isolate| isolate.stop_event_loop(StopReason::Finished));
+      thread.join().expect("isolate thread panicked");
+    }
+  }
+}
+
+impl Drop for IsolateThread {
+  fn drop(&mut self) {
+    self.stop();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use ops;
+
+  #[test]
+  fn test_isolate_thread() {
+    let isolate_thread = IsolateThread::spawn(|| {
+      let argv = vec![String::from("./deno")];
+      Isolate::new(argv, ops::dispatch)
+    });
+    isolate_thread
+      .execute("a.js", "var answer = 42;")
+      .expect("execute error");
+    isolate_thread
+      .execute("b.js", "if (answer !== 42) throw Error('not visible');")
+      .expect("execute error");
+    let err = isolate_thread
+      .execute("c.js", "throw Error('boom');")
+      .unwrap_err();
+    assert!(err.contains("boom"));
+    // Requests run on the isolate's thread, not this one.
+    let caller = thread::current().id();
+    assert!(isolate_thread.call(|_| thread::current().id()) != caller);
+    assert!(isolate_thread.metrics().op_bytes.is_empty());
+    isolate_thread.shutdown();
+  }
+}
@@ -19,6 +19,20 @@ pub struct deno_buf {
   pub data_len: usize,
 }
 
+#[repr(C)]
+pub struct deno_config {
+  pub heap_limit: usize,
+  pub snapshot: deno_buf,
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct deno_heap_stats {
+  pub total_heap_size: usize,
+  pub used_heap_size: usize,
+  pub heap_size_limit: usize,
+}
+
 type DenoRecvCb = unsafe extern "C" fn(
   user_data: *mut c_void,
   req_id: i32,
@@ -26,23 +40,71 @@ type DenoRecvCb = unsafe extern "C" fn(
   data_buf: deno_buf,
 );
 
+type DenoPrintCb = unsafe extern "C" fn(
+  user_data: *mut c_void,
+  msg: *const c_char,
+  is_err: c_int,
+);
+
+type DenoGcCb = unsafe extern "C" fn(user_data: *mut c_void, full: c_int);
+
+pub type DenoChunkCb =
+  unsafe extern "C" fn(cb_data: *mut c_void, chunk: *mut *const u8) -> usize;
+
+pub const DENO_CHUNK_ERROR: usize = !0;
+
 extern "C" {
   pub fn deno_init();
   pub fn deno_v8_version() -> *const c_char;
   pub fn deno_set_v8_flags(argc: *mut c_int, argv: *mut *mut c_char);
   pub fn deno_new(cb: DenoRecvCb) -> *const isolate;
+  pub fn deno_new_with_config(
+    cb: DenoRecvCb,
+    config: deno_config,
+  ) -> *const isolate;
   pub fn deno_delete(i: *const isolate);
+  pub fn deno_set_stack_limit(i: *const isolate, stack_size: usize);
+  pub fn deno_set_print_cb(i: *const isolate, cb: DenoPrintCb);
+  pub fn deno_collect_rejections(i: *const isolate);
+  pub fn deno_take_rejection(i: *const isolate, req_id: *mut i32) -> c_int;
   pub fn deno_last_exception(i: *const isolate) -> *const c_char;
+  pub fn deno_reject_send(i: *const isolate, msg: *const c_char);
+  pub fn deno_get_heap_stats(i: *const isolate, stats: *mut deno_heap_stats);
+  pub fn deno_idle_notification(
+    i: *const isolate,
+    user_data: *mut c_void,
+    idle_seconds: f64,
+  ) -> c_int;
+  pub fn deno_set_gc_cb(i: *const isolate, cb: DenoGcCb);
+  pub fn deno_compile_wasm(
+    i: *const isolate,
+    bytes: *const u8,
+    bytes_len: usize,
+  ) -> c_int;
   pub fn deno_respond(
     i: *const isolate,
     user_data: *mut c_void,
     req_id: i32,
     buf: deno_buf,
-  );
+  ) -> c_int;
   pub fn deno_execute(
     i: *const isolate,
     user_data: *mut c_void,
     js_filename: *const c_char,
     js_source: *const c_char,
   ) -> c_int;
+  pub fn deno_execute_streaming(
+    i: *const isolate,
+    user_data: *mut c_void,
+    js_filename: *const c_char,
+    cb: DenoChunkCb,
+    cb_data: *mut c_void,
+  ) -> c_int;
+  pub fn deno_execute_utf16(
+    i: *const isolate,
+    user_data: *mut c_void,
+    js_filename: *const c_char,
+    js_source: *const u16,
+    js_source_len: c_int,
+  ) -> c_int;
 }
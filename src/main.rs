@@ -12,6 +12,8 @@ extern crate tokio_executor;
 extern crate tokio_fs;
 extern crate tokio_io;
 extern crate tokio_threadpool;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 extern crate url;
 #[macro_use]
 extern crate lazy_static;
@@ -22,14 +24,20 @@ extern crate hyper_rustls;
 extern crate remove_dir_all;
 extern crate ring;
 
+mod codec;
 mod deno_dir;
 mod errors;
 mod flags;
 mod fs;
+mod fs_watch;
 mod http;
 mod isolate;
+mod isolate_config;
+mod isolate_thread;
 mod libdeno;
+mod multi_loop;
 pub mod ops;
+mod permissions;
 mod resources;
 mod tokio_util;
 mod version;
@@ -69,6 +77,7 @@ fn main() {
   log::set_logger(&LOGGER).unwrap();
   let args = env::args().collect();
   let mut isolate = isolate::Isolate::new(args, ops::dispatch);
+  isolate.load_op_module(ops::SystemInfoOps);
   flags::process(&isolate.state.flags);
   tokio_util::init(|| {
     isolate
@@ -77,6 +86,13 @@ fn main() {
         error!("{}", err);
         std::process::exit(1);
       });
-    isolate.event_loop();
+    isolate.event_loop().unwrap_or_else(|err| {
+      error!("{}", err);
+      std::process::exit(1);
+    });
+    let code = isolate.exit_code();
+    if code != 0 {
+      std::process::exit(code);
+    }
   });
 }
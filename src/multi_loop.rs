@@ -0,0 +1,195 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+use errors::DenoResult;
+use isolate::Isolate;
+use isolate::LoopStep;
+
+use std::sync::Arc;
+use std::sync::{Condvar, Mutex};
+use std::time::Instant;
+
+// Wakes the thread running a MultiIsolateLoop when one of its isolates gets a
+// message. See Isolate::set_wakeup().
+#[derive(Default)]
+pub struct Wakeup {
+  notified: Mutex<bool>,
+  condvar: Condvar,
+}
+
+impl Wakeup {
+  pub fn notify(&self) {
+    *self.notified.lock().unwrap() = true;
+    self.condvar.notify_one();
+  }
+
+  // Blocks until notify() is called or deadline passes. Returns right away if
+  // notify() has been called since the last wait().
+  pub fn wait(&self, deadline: Option<Instant>) {
+    let mut notified = self.notified.lock().unwrap();
+    while !*notified {
+      notified = match deadline {
+        None => self.condvar.wait(notified).unwrap(),
+        Some(deadline) => {
+          let now = Instant::now();
+          if deadline <= now {
+            break;
+          }
+          self.condvar.wait_timeout(notified, deadline - now).unwrap().0
+        }
+      };
+    }
+    *notified = false;
+  }
+}
+
+// Runs the event loops of several isolates on one thread. Each turn, every
+// isolate that has something to do handles one message, in the order they
+// were added, so a busy isolate can't starve the others. When none of them
+// has, the thread sleeps until a message arrives for any of them or the
+// soonest of their timers is due.
+pub struct MultiIsolateLoop {
+  isolates: Vec<Isolate>,
+  wakeup: Arc<Wakeup>,
+}
+
+impl MultiIsolateLoop {
+  pub fn new() -> Self {
+    MultiIsolateLoop {
+      isolates: Vec::new(),
+      wakeup: Arc::new(Wakeup::default()),
+    }
+  }
+
+  // Returns the index of the isolate, for isolate() and the results of run().
+  pub fn add(&mut self, isolate: Isolate) -> usize {
+    isolate.set_wakeup(Some(self.wakeup.clone()));
+    self.isolates.push(isolate);
+    self.isolates.len() - 1
+  }
+
+  pub fn isolate(&mut self, index: usize) -> &mut Isolate {
+    &mut self.isolates[index]
+  }
+
+  // Runs until the event loops of all isolates are done. Returns what
+  // event_loop() would have returned for each of them, by index.
+  pub fn run(&mut self) -> Vec<DenoResult<()>> {
+    let mut results: Vec<Option<DenoResult<()>>> =
+      self.isolates.iter().map(|_| None).collect();
+    loop {
+      let mut ran = false;
+      for (isolate, result) in self.isolates.iter_mut().zip(results.iter_mut())
+      {
+        if result.is_some() {
+          continue;
+        }
+        match isolate.poll_once() {
+          LoopStep::Ran => ran = true,
+          LoopStep::Waiting => {}
+          LoopStep::Done(r) => *result = Some(r),
+        }
+      }
+      if results.iter().all(|result| result.is_some()) {
+        break;
+      }
+      if !ran {
+        let deadline = self
+          .isolates
+          .iter()
+          .zip(results.iter())
+          .filter(|(_, result)| result.is_none())
+          .filter_map(|(isolate, _)| isolate.next_deadline())
+          .min();
+        self.wakeup.wait(deadline);
+      }
+    }
+    results.into_iter().map(|result| result.unwrap()).collect()
+  }
+
+  // Gives the isolates back, so they can run their own event loops again.
+  pub fn into_isolates(self) -> Vec<Isolate> {
+    for isolate in &self.isolates {
+      isolate.set_wakeup(None);
+    }
+    self.isolates
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use errors::DenoError;
+  use flags;
+  use futures;
+  use isolate::{Buf, Direction, Op};
+  use std::thread;
+  use std::time::Duration;
+  use tokio_util;
+
+  fn dispatch_echo_delayed(
+    _isolate: &mut Isolate,
+    control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    let buf: Buf = control.to_vec().into_boxed_slice();
+    let op = futures::future::lazy(move || -> Result<Buf, DenoError> {
+      thread::sleep(Duration::from_millis(10));
+      Ok(buf)
+    });
+    (false, Box::new(op))
+  }
+
+  #[test]
+  fn test_multi_isolate_loop() {
+    // Which isolate each response was delivered to, in order.
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let mut multi_loop = MultiIsolateLoop::new();
+    for i in 0..2 {
+      let flags = flags::DenoFlags::default();
+      let mut isolate =
+        Isolate::from_flags(flags, vec![], dispatch_echo_delayed);
+      let log_ = log.clone();
+      isolate.set_inspector(Box::new(move |direction, _req_id, _buf| {
+        if direction == Direction::Outgoing {
+          log_.lock().unwrap().push(i);
+        }
+      }));
+      multi_loop.add(isolate);
+    }
+    tokio_util::init(|| {
+      for i in 0..2 {
+        multi_loop
+          .isolate(i)
+          .execute(
+            "chain.js",
+            r#"
+            // Each op is sent when the previous one completes.
+            let n = 0;
+            libdeno.recv(() => {
+              if (++n < 3) libdeno.send(new Uint8Array([n]));
+            });
+            libdeno.send(new Uint8Array([n]));
+          "#,
+          ).expect("execute error");
+      }
+      let results = multi_loop.run();
+      assert_eq!(results.len(), 2);
+      assert!(results.iter().all(|result| result.is_ok()));
+    });
+
+    let log = log.lock().unwrap();
+    assert_eq!(log.len(), 6);
+    assert_eq!(log.iter().filter(|&&i| i == 0).count(), 3);
+    // Neither isolate had to wait for the other to finish.
+    let last_of = |i| log.iter().rposition(|&j| j == i).unwrap();
+    let first_of = |i| log.iter().position(|&j| j == i).unwrap();
+    assert!(first_of(1) < last_of(0));
+    assert!(first_of(0) < last_of(1));
+
+    let isolates = multi_loop.into_isolates();
+    for mut isolate in isolates {
+      isolate
+        .execute("check.js", "if (n !== 3) throw Error(n);")
+        .expect("execute error");
+    }
+  }
+}
@@ -1,17 +1,26 @@
 // Copyright 2018 the Deno authors. All rights reserved. MIT license.
 
+use codec;
+use deno_dir::ModuleLoader;
 use errors;
 use errors::permission_denied;
 use errors::{DenoError, DenoResult, ErrorKind};
+use flags;
 use fs as deno_fs;
+use fs_watch::{FsEvent, FsWatcher};
+use isolate;
 use isolate::Buf;
 use isolate::Isolate;
 use isolate::IsolateState;
 use isolate::Op;
+use isolate::OpModule;
+use isolate::OpStream;
 use msg;
+use permissions::Permission;
 use resources;
 use resources::Resource;
 use tokio_util;
+use version;
 
 use flatbuffers::FlatBufferBuilder;
 use futures;
@@ -20,23 +29,37 @@ use futures::Poll;
 use hyper;
 use hyper::rt::{Future, Stream};
 use hyper::Client;
+#[cfg(any(unix))]
+use libc;
 use remove_dir_all::remove_dir_all;
+use ring::digest;
 use std;
+use std::ffi::OsString;
 use std::fs;
+use std::io::{BufRead, BufReader, Read, SeekFrom};
 use std::net::{Shutdown, SocketAddr};
 #[cfg(any(unix))]
 use std::os::unix::fs::PermissionsExt;
+#[cfg(any(unix))]
+use std::os::unix::process::ExitStatusExt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::path::Path;
 use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
 use std::str::FromStr;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::UNIX_EPOCH;
 use std::time::{Duration, Instant};
 use tokio;
+use tokio::executor::DefaultExecutor;
 use tokio::net::TcpListener;
 use tokio::net::TcpStream;
+use tokio::timer::Delay;
 use tokio_io;
 use tokio_threadpool;
+#[cfg(feature = "tracing")]
+use tracing;
 
 type OpResult = DenoResult<Buf>;
 
@@ -51,6 +74,31 @@ fn empty_buf() -> Buf {
   Box::new([])
 }
 
+// Paths are sent to JS as strings, which can't hold a path that isn't valid
+// Unicode.
+fn path_to_str(path: &Path) -> DenoResult<&str> {
+  path.to_str().ok_or_else(|| {
+    errors::new(
+      ErrorKind::InvalidData,
+      format!("path is not valid Unicode: {}", path.display()),
+    )
+  })
+}
+
+// Races op against a timer, so that an op which never completes (for example
+// a read from a socket that never gets data) can't keep the event loop alive
+// forever. If dur elapses first the op is dropped and the result is an
+// OpTimedOut error.
+pub fn with_op_timeout(dur: Duration, op: Box<Op>) -> Box<Op> {
+  let timeout = Delay::new(Instant::now() + dur)
+    .then(|_| -> DenoResult<Buf> { Err(errors::op_timed_out()) });
+  Box::new(
+    op.select(timeout)
+      .map(|(buf, _next)| buf)
+      .map_err(|(err, _next)| err),
+  )
+}
+
 /// Processes raw messages from JavaScript.
 /// This functions invoked every time libdeno.send() is called.
 /// control corresponds to the first argument of libdeno.send().
@@ -65,12 +113,42 @@ pub fn dispatch(
   let inner_type = base.inner_type();
   let cmd_id = base.cmd_id();
 
-  let op: Box<Op> = if inner_type == msg::Any::SetTimeout {
+  #[cfg(feature = "tracing")]
+  tracing::Span::current().record("op_id", &msg::enum_name_any(inner_type));
+
+  let op_id = inner_type as u32;
+  isolate.set_dispatch_op_id(op_id);
+  let state = isolate.state.clone();
+  let request_bytes = (control.len() + data.len()) as u64;
+
+  let op: Box<Op> = if let Err(err) =
+    state.charge_op_bytes(op_id, request_bytes)
+  {
+    odd_future(err)
+  } else if let Some(handler) = isolate.registered_op(op_id) {
+    let r = catch_unwind(AssertUnwindSafe(|| handler(isolate, control, data)));
+    op_or_panic(r)
+  } else if inner_type == msg::Any::SetTimeout {
     // SetTimeout is an exceptional op: the global timeout field is part of the
     // Isolate state (not the IsolateState state) and it must be updated on the
     // main thread.
     assert_eq!(is_sync, true);
     op_set_timeout(isolate, &base, data)
+  } else if inner_type == msg::Any::CompileWasm {
+    // Compiling needs the V8 isolate, so like SetTimeout this runs on the main
+    // thread.
+    assert_eq!(is_sync, true);
+    op_compile_wasm(isolate, &base, data)
+  } else if inner_type == msg::Any::IsolateStatus {
+    // Needs ntasks and the V8 heap, which only the main thread can see.
+    op_isolate_status(isolate, &base, data)
+  } else if inner_type == msg::Any::Backpressure {
+    op_backpressure(isolate, &base, data)
+  } else if inner_type == msg::Any::Record {
+    // Record ops get the Isolate, so they run on the main thread too.
+    assert_eq!(is_sync, true);
+    let r = catch_unwind(AssertUnwindSafe(|| op_record(isolate, &base, data)));
+    op_or_panic(r)
   } else {
     // Handle regular ops.
     let op_creator: OpCreator = match inner_type {
@@ -78,8 +156,12 @@ pub fn dispatch(
       msg::Any::CodeFetch => op_code_fetch,
       msg::Any::CodeCache => op_code_cache,
       msg::Any::Environ => op_env,
+      msg::Any::GetEnv => op_get_env,
+      msg::Any::Seek => op_seek,
+      msg::Any::Fsync => op_fsync,
       msg::Any::FetchReq => op_fetch_req,
       msg::Any::MakeTempDir => op_make_temp_dir,
+      msg::Any::MakeTempFile => op_make_temp_file,
       msg::Any::Mkdir => op_mkdir,
       msg::Any::Open => op_open,
       msg::Any::Read => op_read,
@@ -88,35 +170,64 @@ pub fn dispatch(
       msg::Any::Shutdown => op_shutdown,
       msg::Any::Remove => op_remove,
       msg::Any::ReadFile => op_read_file,
+      msg::Any::ReadTextFile => op_read_text_file,
+      msg::Any::ReadFiles => op_read_files,
+      msg::Any::OpenLines => op_open_lines,
+      msg::Any::ReadLine => op_read_line,
+      msg::Any::FsEvents => op_fs_events,
+      msg::Any::FsEventsPoll => op_fs_events_poll,
       msg::Any::ReadDir => op_read_dir,
       msg::Any::Rename => op_rename,
       msg::Any::Readlink => op_read_link,
+      msg::Any::Realpath => op_realpath,
+      msg::Any::Hash => op_hash,
       msg::Any::Symlink => op_symlink,
+      msg::Any::Link => op_link,
       msg::Any::SetEnv => op_set_env,
       msg::Any::Stat => op_stat,
       msg::Any::Truncate => op_truncate,
+      msg::Any::Chmod => op_chmod,
+      msg::Any::Utime => op_utime,
+      msg::Any::DiskUsage => op_disk_usage,
+      msg::Any::ConsoleSize => op_console_size,
       msg::Any::WriteFile => op_write_file,
       msg::Any::Exit => op_exit,
+      msg::Any::SetExitCode => op_set_exit_code,
       msg::Any::CopyFile => op_copy_file,
       msg::Any::Listen => op_listen,
       msg::Any::Accept => op_accept,
       msg::Any::Dial => op_dial,
+      msg::Any::PerfNow => op_perf_now,
+      msg::Any::PerfMark => op_perf_mark,
+      msg::Any::Run => op_run,
+      msg::Any::RunStatus => op_run_status,
       _ => panic!(format!(
         "Unhandled message {}",
         msg::enum_name_any(inner_type)
       )),
     };
-    op_creator(isolate.state.clone(), &base, data)
+    // Many ops do their work, or at least check their arguments, before
+    // returning a future, so panics must be caught here too.
+    let r = catch_unwind(AssertUnwindSafe(|| {
+      op_creator(state.clone(), &base, data)
+    }));
+    op_or_panic(r)
+  };
+  let op = match state.flags.op_response_timeout {
+    Some(dur) if !is_sync => with_op_timeout(dur, op),
+    _ => op,
   };
 
+  let err_state = state.clone();
   let boxed_op = Box::new(
-    op.or_else(move |err: DenoError| -> DenoResult<Buf> {
+    catch_panics(op).or_else(move |err: DenoError| -> DenoResult<Buf> {
       debug!("op err {}", err);
       // No matter whether we got an Err or Ok, we want a serialized message to
       // send back. So transform the DenoError into a deno_buf.
       let builder = &mut FlatBufferBuilder::new();
       let errmsg_offset = builder.create_string(&format!("{}", err));
       Ok(serialize_response(
+        &err_state,
         cmd_id,
         builder,
         msg::BaseArgs {
@@ -125,23 +236,12 @@ pub fn dispatch(
           ..Default::default()
         },
       ))
-    }).and_then(move |buf: Buf| -> DenoResult<Buf> {
-      // Handle empty responses. For sync responses we just want
-      // to send null. For async we want to send a small message
-      // with the cmd_id.
-      let buf = if is_sync || buf.len() > 0 {
-        buf
-      } else {
-        let builder = &mut FlatBufferBuilder::new();
-        serialize_response(
-          cmd_id,
-          builder,
-          msg::BaseArgs {
-            ..Default::default()
-          },
-        )
-      };
-      Ok(buf)
+    }).map(move |buf: Buf| {
+      // Responses are counted after the fact; they can't be rejected anymore.
+      // Empty responses are passed on as they are: JS gets null, from send()
+      // if the op is sync and otherwise with the req_id of the op.
+      state.metrics.add_op_bytes(op_id, buf.len() as u64);
+      buf
     }),
   );
 
@@ -162,6 +262,17 @@ fn op_exit(
   std::process::exit(inner.code())
 }
 
+fn op_set_exit_code(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_set_exit_code().unwrap();
+  state.set_exit_code(inner.code());
+  ok_future(empty_buf())
+}
+
 fn op_start(
   state: Arc<IsolateState>,
   base: &msg::Base,
@@ -176,6 +287,9 @@ fn op_start(
   let cwd_path = std::env::current_dir().unwrap();
   let cwd_off =
     builder.create_string(deno_fs::normalize_path(cwd_path.as_ref()).as_ref());
+  let deno_version_off = builder.create_string(version::DENO_VERSION);
+  let v8_version_off = builder.create_string(&version::v8_version());
+  let codec_off = builder.create_string(state.codec().name());
 
   let inner = msg::StartRes::create(
     &mut builder,
@@ -184,11 +298,20 @@ fn op_start(
       argv: Some(argv_off),
       debug_flag: state.flags.log_debug,
       recompile_flag: state.flags.recompile,
+      error_format: match state.flags.error_format {
+        flags::ErrorFormat::Human => msg::ErrorFormat::Human,
+        flags::ErrorFormat::Json => msg::ErrorFormat::Json,
+      },
+      color_flag: use_color(),
+      deno_version: Some(deno_version_off),
+      v8_version: Some(v8_version_off),
+      codec: Some(codec_off),
       ..Default::default()
     },
   );
 
   ok_future(serialize_response(
+    &state,
     base.cmd_id(),
     &mut builder,
     msg::BaseArgs {
@@ -199,7 +322,26 @@ fn op_start(
   ))
 }
 
+// Colors are only written to a terminal, and never if NO_COLOR is set. See
+// https://no-color.org.
+fn use_color() -> bool {
+  std::env::var_os("NO_COLOR").is_none() && stdout_is_tty()
+}
+
+#[cfg(any(unix))]
+fn stdout_is_tty() -> bool {
+  unsafe { libc::isatty(1) != 0 }
+}
+
+#[cfg(not(any(unix)))]
+fn stdout_is_tty() -> bool {
+  false
+}
+
+// The response is copied out of builder into a buffer from the isolate's
+// BufAllocator.
 fn serialize_response(
+  state: &IsolateState,
   cmd_id: u32,
   builder: &mut FlatBufferBuilder,
   mut args: msg::BaseArgs,
@@ -209,8 +351,26 @@ fn serialize_response(
   msg::finish_base_buffer(builder, base);
   let data = builder.finished_data();
   // println!("serialize_response {:x?}", data);
-  let vec = data.to_vec();
-  vec.into_boxed_slice()
+  let mut buf = state.buf_allocator().alloc(data.len());
+  buf.copy_from_slice(data);
+  buf
+}
+
+// Turns a panic inside op into an OpPanicked error, which is reported to JS
+// like any other error.
+fn catch_panics(op: Box<Op>) -> Box<Op> {
+  Box::new(AssertUnwindSafe(op).catch_unwind().then(|r| match r {
+    Ok(result) => result,
+    Err(payload) => Err(errors::op_panicked(payload)),
+  }))
+}
+
+// Handles the result of catch_unwind() around creating an op.
+fn op_or_panic(r: std::thread::Result<Box<Op>>) -> Box<Op> {
+  match r {
+    Ok(op) => op,
+    Err(payload) => odd_future(errors::op_panicked(payload)),
+  }
 }
 
 fn ok_future(buf: Buf) -> Box<Op> {
@@ -238,7 +398,8 @@ fn op_code_fetch(
 
   Box::new(futures::future::result(|| -> OpResult {
     let builder = &mut FlatBufferBuilder::new();
-    let out = state.dir.code_fetch(module_specifier, containing_file)?;
+    let out = state.code_fetch(module_specifier, containing_file)?;
+    state.module_loaded(&out.module_name, &out.source_code);
     let mut msg_args = msg::CodeFetchResArgs {
       module_name: Some(builder.create_string(&out.module_name)),
       filename: Some(builder.create_string(&out.filename)),
@@ -253,6 +414,7 @@ fn op_code_fetch(
     };
     let inner = msg::CodeFetchRes::create(builder, &msg_args);
     Ok(serialize_response(
+      &state,
       cmd_id,
       builder,
       msg::BaseArgs {
@@ -297,6 +459,145 @@ fn op_set_timeout(
   ok_future(empty_buf())
 }
 
+fn op_record(
+  isolate: &mut Isolate,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert!(base.inner_as_record().is_some());
+  let response = codec::call(isolate, data);
+  let builder = &mut FlatBufferBuilder::new();
+  let data_off = builder.create_vector(&response);
+  let inner = msg::RecordRes::create(
+    builder,
+    &msg::RecordResArgs {
+      data: Some(data_off),
+    },
+  );
+  ok_future(serialize_response(
+    &isolate.state,
+    base.cmd_id(),
+    builder,
+    msg::BaseArgs {
+      inner_type: msg::Any::RecordRes,
+      inner: Some(inner.as_union_value()),
+      ..Default::default()
+    },
+  ))
+}
+
+// Compiling a module gives it no capabilities. Whatever it imports still has
+// to come from JS, which is subject to the usual permission checks, so no
+// permission is needed here.
+fn op_compile_wasm(
+  isolate: &mut Isolate,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert!(base.inner_as_compile_wasm().is_some());
+  match isolate.compile_wasm(data) {
+    Ok(()) => ok_future(empty_buf()),
+    Err(msg) => odd_future(errors::new(
+      ErrorKind::InvalidWasm,
+      format!("Invalid WebAssembly module: {}", msg),
+    )),
+  }
+}
+
+fn op_isolate_status(
+  isolate: &mut Isolate,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let cmd_id = base.cmd_id();
+  let builder = &mut FlatBufferBuilder::new();
+  let json = builder.create_string(&isolate.status().to_json());
+  let inner = msg::IsolateStatusRes::create(
+    builder,
+    &msg::IsolateStatusResArgs { json: Some(json) },
+  );
+  ok_future(serialize_response(
+    &isolate.state,
+    cmd_id,
+    builder,
+    msg::BaseArgs {
+      inner: Some(inner.as_union_value()),
+      inner_type: msg::Any::IsolateStatusRes,
+      ..Default::default()
+    },
+  ))
+}
+
+fn op_backpressure(
+  isolate: &mut Isolate,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let cmd_id = base.cmd_id();
+  let builder = &mut FlatBufferBuilder::new();
+  let inner = msg::BackpressureRes::create(
+    builder,
+    &msg::BackpressureResArgs {
+      active: isolate.is_backpressured(),
+    },
+  );
+  ok_future(serialize_response(
+    &isolate.state,
+    cmd_id,
+    builder,
+    msg::BaseArgs {
+      inner: Some(inner.as_union_value()),
+      inner_type: msg::Any::BackpressureRes,
+      ..Default::default()
+    },
+  ))
+}
+
+fn perf_now_res(cmd_id: u32, state: &IsolateState, t: Instant) -> Buf {
+  let d = t.duration_since(state.start_time);
+  let now = d.as_secs() as f64 * 1e3 + d.subsec_nanos() as f64 / 1e6;
+  let builder = &mut FlatBufferBuilder::new();
+  let inner =
+    msg::PerfNowRes::create(builder, &msg::PerfNowResArgs { now });
+  serialize_response(
+    state,
+    cmd_id,
+    builder,
+    msg::BaseArgs {
+      inner: Some(inner.as_union_value()),
+      inner_type: msg::Any::PerfNowRes,
+      ..Default::default()
+    },
+  )
+}
+
+fn op_perf_now(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let now = state.now();
+  ok_future(perf_now_res(base.cmd_id(), &state, now))
+}
+
+// Records a named mark and responds with its timestamp, so that the JS side
+// can compute measures between marks.
+fn op_perf_mark(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_perf_mark().unwrap();
+  let name = String::from(inner.name().unwrap());
+  let now = state.now();
+  state.perf_marks.lock().unwrap().push((name, now));
+  ok_future(perf_now_res(base.cmd_id(), &state, now))
+}
+
 fn op_set_env(
   state: Arc<IsolateState>,
   base: &msg::Base,
@@ -307,14 +608,166 @@ fn op_set_env(
   let key = inner.key().unwrap();
   let value = inner.value().unwrap();
 
-  if !state.flags.allow_env {
-    return odd_future(permission_denied());
+  if let Err(err) = state.check_permission(Permission::Env, key) {
+    return odd_future(err);
   }
 
   std::env::set_var(key, value);
   ok_future(empty_buf())
 }
 
+fn op_get_env(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_get_env().unwrap();
+  let cmd_id = base.cmd_id();
+  let key = inner.key().unwrap();
+
+  if let Err(err) = state.check_permission(Permission::Env, key) {
+    return odd_future(err);
+  }
+
+  let builder = &mut FlatBufferBuilder::new();
+  let value = std::env::var(key)
+    .ok()
+    .map(|value| builder.create_string(&value));
+  let inner = msg::GetEnvRes::create(
+    builder,
+    &msg::GetEnvResArgs {
+      value,
+      ..Default::default()
+    },
+  );
+  ok_future(serialize_response(
+    &state,
+    cmd_id,
+    builder,
+    msg::BaseArgs {
+      inner: Some(inner.as_union_value()),
+      inner_type: msg::Any::GetEnvRes,
+      ..Default::default()
+    },
+  ))
+}
+
+#[cfg(any(unix))]
+fn hostname() -> String {
+  let mut buf = [0u8; 256];
+  let r = unsafe {
+    libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len())
+  };
+  let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+  if r != 0 || len == 0 {
+    return "localhost".to_string();
+  }
+  String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[cfg(not(any(unix)))]
+fn hostname() -> String {
+  std::env::var("COMPUTERNAME").unwrap_or_else(|_| "localhost".to_string())
+}
+
+#[cfg(any(unix))]
+fn os_release() -> String {
+  let mut name: libc::utsname = unsafe { std::mem::zeroed() };
+  if unsafe { libc::uname(&mut name) } != 0 {
+    return "unknown".to_string();
+  }
+  let release = unsafe { std::ffi::CStr::from_ptr(name.release.as_ptr()) };
+  release.to_string_lossy().into_owned()
+}
+
+#[cfg(not(any(unix)))]
+fn os_release() -> String {
+  "unknown".to_string()
+}
+
+// The ops that describe the machine, deno.hostname() and deno.osRelease().
+// They aren't builtin: the embedder loads them with Isolate::load_op_module().
+pub struct SystemInfoOps;
+
+impl OpModule for SystemInfoOps {
+  fn register(&self, isolate: &mut Isolate) {
+    let hostname = msg::Any::Hostname as u32;
+    let os_release = msg::Any::OsRelease as u32;
+    isolate.register_op(hostname, Some("Hostname"), op_hostname);
+    isolate.register_op(os_release, Some("OsRelease"), op_os_release);
+  }
+}
+
+// Names the machine on a network, so it needs the env permission.
+fn op_hostname(
+  isolate: &mut Isolate,
+  control: &[u8],
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let base = msg::get_root_as_base(control);
+  let state = isolate.state.clone();
+  let cmd_id = base.cmd_id();
+
+  if let Err(err) = state.check_permission(Permission::Env, "") {
+    return odd_future(err);
+  }
+
+  let builder = &mut FlatBufferBuilder::new();
+  let hostname = builder.create_string(&hostname());
+  let inner = msg::HostnameRes::create(
+    builder,
+    &msg::HostnameResArgs {
+      hostname: Some(hostname),
+    },
+  );
+  ok_future(serialize_response(
+    &state,
+    cmd_id,
+    builder,
+    msg::BaseArgs {
+      inner: Some(inner.as_union_value()),
+      inner_type: msg::Any::HostnameRes,
+      ..Default::default()
+    },
+  ))
+}
+
+fn op_os_release(
+  isolate: &mut Isolate,
+  control: &[u8],
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let base = msg::get_root_as_base(control);
+  let state = isolate.state.clone();
+  let cmd_id = base.cmd_id();
+
+  if let Err(err) = state.check_permission(Permission::Env, "") {
+    return odd_future(err);
+  }
+
+  let builder = &mut FlatBufferBuilder::new();
+  let release = builder.create_string(&os_release());
+  let inner = msg::OsReleaseRes::create(
+    builder,
+    &msg::OsReleaseResArgs {
+      release: Some(release),
+    },
+  );
+  ok_future(serialize_response(
+    &state,
+    cmd_id,
+    builder,
+    msg::BaseArgs {
+      inner: Some(inner.as_union_value()),
+      inner_type: msg::Any::OsReleaseRes,
+      ..Default::default()
+    },
+  ))
+}
+
 fn op_env(
   state: Arc<IsolateState>,
   base: &msg::Base,
@@ -323,8 +776,8 @@ fn op_env(
   assert_eq!(data.len(), 0);
   let cmd_id = base.cmd_id();
 
-  if !state.flags.allow_env {
-    return odd_future(permission_denied());
+  if let Err(err) = state.check_permission(Permission::Env, "") {
+    return odd_future(err);
   }
 
   let builder = &mut FlatBufferBuilder::new();
@@ -351,6 +804,7 @@ fn op_env(
     },
   );
   ok_future(serialize_response(
+    &state,
     cmd_id,
     builder,
     msg::BaseArgs {
@@ -372,8 +826,8 @@ fn op_fetch_req(
   let id = inner.id();
   let url = inner.url().unwrap();
 
-  if !state.flags.allow_net {
-    return odd_future(permission_denied());
+  if let Err(err) = state.check_permission(Permission::Net, url) {
+    return odd_future(err);
   }
 
   let url = url.parse::<hyper::Uri>().unwrap();
@@ -431,6 +885,7 @@ fn op_fetch_req(
       );
 
       Ok(serialize_response(
+        &state,
         cmd_id,
         builder,
         msg::BaseArgs {
@@ -486,8 +941,9 @@ fn op_make_temp_dir(
   let inner = base.inner_as_make_temp_dir().unwrap();
   let cmd_id = base.cmd_id();
 
-  if !state.flags.allow_write {
-    return odd_future(permission_denied());
+  let what = inner.dir().unwrap_or("");
+  if let Err(err) = state.check_permission(Permission::Write, what) {
+    return odd_future(err);
   }
 
   let dir = inner.dir().map(PathBuf::from);
@@ -505,7 +961,7 @@ fn op_make_temp_dir(
       suffix.as_ref().map(|x| &**x),
     )?;
     let builder = &mut FlatBufferBuilder::new();
-    let path_off = builder.create_string(path.to_str().unwrap());
+    let path_off = builder.create_string(path_to_str(&path)?);
     let inner = msg::MakeTempDirRes::create(
       builder,
       &msg::MakeTempDirResArgs {
@@ -514,6 +970,7 @@ fn op_make_temp_dir(
       },
     );
     Ok(serialize_response(
+      &state,
       cmd_id,
       builder,
       msg::BaseArgs {
@@ -525,6 +982,52 @@ fn op_make_temp_dir(
   })
 }
 
+fn op_make_temp_file(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_make_temp_file().unwrap();
+  let cmd_id = base.cmd_id();
+
+  let what = inner.dir().unwrap_or("");
+  if let Err(err) = state.check_permission(Permission::Write, what) {
+    return odd_future(err);
+  }
+
+  let dir = inner.dir().map(PathBuf::from);
+  let prefix = inner.prefix().map(String::from);
+  let suffix = inner.suffix().map(String::from);
+
+  blocking!(base.sync(), || -> OpResult {
+    let path = deno_fs::make_temp_file(
+      dir.as_ref().map(|x| &**x),
+      prefix.as_ref().map(|x| &**x),
+      suffix.as_ref().map(|x| &**x),
+    )?;
+    let builder = &mut FlatBufferBuilder::new();
+    let path_off = builder.create_string(path_to_str(&path)?);
+    let inner = msg::MakeTempFileRes::create(
+      builder,
+      &msg::MakeTempFileResArgs {
+        path: Some(path_off),
+        ..Default::default()
+      },
+    );
+    Ok(serialize_response(
+      &state,
+      cmd_id,
+      builder,
+      msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::MakeTempFileRes,
+        ..Default::default()
+      },
+    ))
+  })
+}
+
 fn op_mkdir(
   state: Arc<IsolateState>,
   base: &msg::Base,
@@ -533,57 +1036,194 @@ fn op_mkdir(
   assert_eq!(data.len(), 0);
   let inner = base.inner_as_mkdir().unwrap();
   let mode = inner.mode();
+  let recursive = inner.recursive();
   let path = String::from(inner.path().unwrap());
 
-  if !state.flags.allow_write {
-    return odd_future(permission_denied());
+  if let Err(err) = state.check_permission(Permission::Write, &path) {
+    return odd_future(err);
   }
 
   blocking!(base.sync(), || {
     debug!("op_mkdir {}", path);
-    deno_fs::mkdir(Path::new(&path), mode)?;
+    deno_fs::mkdir(Path::new(&path), mode, recursive)?;
     Ok(empty_buf())
   })
 }
 
-fn op_open(
-  _state: Arc<IsolateState>,
+fn op_console_size(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_console_size().unwrap();
+  let cmd_id = base.cmd_id();
+  let (columns, rows) = match resources::console_size(inner.rid()) {
+    Ok(size) => size,
+    Err(err) => return odd_future(err),
+  };
+  let builder = &mut FlatBufferBuilder::new();
+  let inner = msg::ConsoleSizeRes::create(
+    builder,
+    &msg::ConsoleSizeResArgs { columns, rows },
+  );
+  ok_future(serialize_response(
+    &state,
+    cmd_id,
+    builder,
+    msg::BaseArgs {
+      inner: Some(inner.as_union_value()),
+      inner_type: msg::Any::ConsoleSizeRes,
+      ..Default::default()
+    },
+  ))
+}
+
+fn op_chmod(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_chmod().unwrap();
+  let mode = inner.mode();
+  let what = inner.path().unwrap();
+  if let Err(err) = state.check_permission(Permission::Write, what) {
+    return odd_future(err);
+  }
+  let path = PathBuf::from(inner.path().unwrap());
+  blocking!(base.sync(), || {
+    debug!("op_chmod {} {:o}", path.display(), mode);
+    deno_fs::chmod(&path, mode)?;
+    Ok(empty_buf())
+  })
+}
+
+fn op_utime(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_utime().unwrap();
+  let what = inner.path().unwrap();
+  if let Err(err) = state.check_permission(Permission::Write, what) {
+    return odd_future(err);
+  }
+  let atime = (inner.atime_sec(), inner.atime_nsec());
+  let mtime = (inner.mtime_sec(), inner.mtime_nsec());
+  if atime.1 >= 1_000_000_000 || mtime.1 >= 1_000_000_000 {
+    return odd_future(errors::new(
+      ErrorKind::InvalidInput,
+      "Nanoseconds must be less than a second".to_string(),
+    ));
+  }
+  let path = PathBuf::from(what);
+  blocking!(base.sync(), || {
+    debug!("op_utime {} {:?} {:?}", path.display(), atime, mtime);
+    deno_fs::utime(&path, atime, mtime)?;
+    Ok(empty_buf())
+  })
+}
+
+fn op_disk_usage(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_disk_usage().unwrap();
+  let cmd_id = base.cmd_id();
+  let path = inner.path().unwrap();
+  if let Err(err) = state.check_permission(Permission::Read, path) {
+    return odd_future(err);
+  }
+  debug!("op_disk_usage {}", path);
+  let (available, total) = match deno_fs::disk_usage(Path::new(path)) {
+    Ok(usage) => usage,
+    Err(err) => return odd_future(err.into()),
+  };
+  let builder = &mut FlatBufferBuilder::new();
+  let inner = msg::DiskUsageRes::create(
+    builder,
+    &msg::DiskUsageResArgs { available, total },
+  );
+  ok_future(serialize_response(
+    &state,
+    cmd_id,
+    builder,
+    msg::BaseArgs {
+      inner: Some(inner.as_union_value()),
+      inner_type: msg::Any::DiskUsageRes,
+      ..Default::default()
+    },
+  ))
+}
+
+fn op_open(
+  state: Arc<IsolateState>,
   base: &msg::Base,
   data: &'static mut [u8],
 ) -> Box<Op> {
   assert_eq!(data.len(), 0);
   let cmd_id = base.cmd_id();
   let inner = base.inner_as_open().unwrap();
-  let filename = PathBuf::from(inner.filename().unwrap());
-  // TODO let perm = inner.perm();
+  let filename_ = inner.filename().unwrap();
+  let filename = PathBuf::from(filename_);
+  let writes = inner.write()
+    || inner.append()
+    || inner.truncate()
+    || inner.create()
+    || inner.create_new();
+  if inner.read() {
+    if let Err(err) = state.check_permission(Permission::Read, filename_) {
+      return odd_future(err);
+    }
+  }
+  if writes {
+    if let Err(err) = state.check_permission(Permission::Write, filename_) {
+      return odd_future(err);
+    }
+  }
 
-  let op = tokio::fs::File::open(filename)
-    .map_err(|err| DenoError::from(err))
-    .and_then(move |fs_file| -> OpResult {
-      let resource = resources::add_fs_file(fs_file);
-      let builder = &mut FlatBufferBuilder::new();
-      let inner = msg::OpenRes::create(
-        builder,
-        &msg::OpenResArgs {
-          rid: resource.rid,
-          ..Default::default()
-        },
-      );
-      Ok(serialize_response(
-        cmd_id,
-        builder,
-        msg::BaseArgs {
-          inner: Some(inner.as_union_value()),
-          inner_type: msg::Any::OpenRes,
-          ..Default::default()
-        },
-      ))
-    });
-  Box::new(op)
+  let mut options = fs::OpenOptions::new();
+  options
+    .read(inner.read())
+    .write(inner.write())
+    .append(inner.append())
+    .truncate(inner.truncate())
+    .create(inner.create())
+    .create_new(inner.create_new());
+  deno_fs::set_open_permission(&mut options, inner.perm());
+
+  blocking!(base.sync(), || {
+    debug!("op_open {}", filename.display());
+    let file = tokio::fs::File::from_std(options.open(&filename)?);
+    let table = state.resource_table.lock().unwrap();
+    let resource = resources::add_fs_file(&table, file)?;
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::OpenRes::create(
+      builder,
+      &msg::OpenResArgs {
+        rid: resource.rid,
+        ..Default::default()
+      },
+    );
+    Ok(serialize_response(
+      &state,
+      cmd_id,
+      builder,
+      msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::OpenRes,
+        ..Default::default()
+      },
+    ))
+  })
 }
 
 fn op_close(
-  _state: Arc<IsolateState>,
+  state: Arc<IsolateState>,
   base: &msg::Base,
   data: &'static mut [u8],
 ) -> Box<Op> {
@@ -591,7 +1231,12 @@ fn op_close(
   let inner = base.inner_as_close().unwrap();
   let rid = inner.rid();
   match resources::lookup(rid) {
-    None => odd_future(errors::bad_resource()),
+    // Not a global resource, maybe one of the isolate's own, like a
+    // LineReader.
+    None => match state.resource_table.lock().unwrap().close(rid) {
+      Ok(()) => ok_future(empty_buf()),
+      Err(err) => odd_future(err),
+    },
     Some(mut resource) => {
       resource.close();
       ok_future(empty_buf())
@@ -629,7 +1274,7 @@ fn op_shutdown(
 }
 
 fn op_read(
-  _state: Arc<IsolateState>,
+  state: Arc<IsolateState>,
   base: &msg::Base,
   data: &'static mut [u8],
 ) -> Box<Op> {
@@ -653,6 +1298,7 @@ fn op_read(
             },
           );
           Ok(serialize_response(
+            &state,
             cmd_id,
             builder,
             msg::BaseArgs {
@@ -668,7 +1314,7 @@ fn op_read(
 }
 
 fn op_write(
-  _state: Arc<IsolateState>,
+  state: Arc<IsolateState>,
   base: &msg::Base,
   data: &'static mut [u8],
 ) -> Box<Op> {
@@ -692,6 +1338,7 @@ fn op_write(
             },
           );
           Ok(serialize_response(
+            &state,
             cmd_id,
             builder,
             msg::BaseArgs {
@@ -715,20 +1362,25 @@ fn op_remove(
   let inner = base.inner_as_remove().unwrap();
   let path = PathBuf::from(inner.path().unwrap());
   let recursive = inner.recursive();
-  if !state.flags.allow_write {
-    return odd_future(permission_denied());
+  let what = inner.path().unwrap();
+  if let Err(err) = state.check_permission(Permission::Write, what) {
+    return odd_future(err);
   }
   blocking!(base.sync(), || {
     debug!("op_remove {}", path.display());
-    let metadata = fs::metadata(&path)?;
-    if metadata.is_file() {
+    // A symlink is removed itself, never what it points to.
+    let metadata = fs::symlink_metadata(&path)?;
+    if !metadata.is_dir() {
       fs::remove_file(&path)?;
-    } else {
-      if recursive {
-        remove_dir_all(&path)?;
-      } else {
-        fs::remove_dir(&path)?;
+    } else if recursive {
+      let real_path = fs::canonicalize(&path)?;
+      if !is_in_roots(&state.flags.write_roots, &real_path) {
+        return Err(permission_denied());
       }
+      remove_dir_all(&path)?;
+    } else {
+      // Fails if the directory isn't empty.
+      fs::remove_dir(&path)?;
     }
     Ok(empty_buf())
   })
@@ -736,15 +1388,31 @@ fn op_remove(
 
 // Prototype https://github.com/denoland/deno/blob/golang/os.go#L171-L184
 fn op_read_file(
-  _config: Arc<IsolateState>,
+  state: Arc<IsolateState>,
   base: &msg::Base,
   data: &'static mut [u8],
 ) -> Box<Op> {
   assert_eq!(data.len(), 0);
   let inner = base.inner_as_read_file().unwrap();
   let cmd_id = base.cmd_id();
+  let what = inner.filename().unwrap();
+  if let Err(err) = state.check_permission(Permission::Read, what) {
+    return odd_future(err);
+  }
   let filename = PathBuf::from(inner.filename().unwrap());
   debug!("op_read_file {}", filename.display());
+  let chunk_size = inner.chunk_size() as usize;
+  if chunk_size > 0 {
+    if base.sync() {
+      return odd_future(errors::new(
+        ErrorKind::InvalidInput,
+        "Chunked reads must be async".to_string(),
+      ));
+    }
+    let chunks =
+      read_file_chunks(state.clone(), cmd_id, filename, chunk_size);
+    return isolate::chunked_response(state, chunks);
+  }
   blocking!(base.sync(), || {
     let vec = fs::read(&filename)?;
     // Build the response message. memcpy data into inner.
@@ -759,6 +1427,7 @@ fn op_read_file(
       },
     );
     Ok(serialize_response(
+      &state,
       cmd_id,
       builder,
       msg::BaseArgs {
@@ -770,389 +1439,3967 @@ fn op_read_file(
   })
 }
 
-fn op_copy_file(
+// Reads filename on the blocking pool, chunk_size bytes at a time, into
+// ResponseChunks for isolate::chunked_response(). The file is opened when the
+// first chunk is polled.
+fn read_file_chunks(
+  state: Arc<IsolateState>,
+  cmd_id: u32,
+  filename: PathBuf,
+  chunk_size: usize,
+) -> Box<OpStream> {
+  let mut file: Option<fs::File> = None;
+  let mut seq = 0;
+  let mut done = false;
+  Box::new(futures::stream::poll_fn(
+    move || -> Poll<Option<Buf>, DenoError> {
+      use futures::Async::*;
+      if done {
+        return Ok(Ready(None));
+      }
+      let r = tokio_threadpool::blocking(|| -> DenoResult<Vec<u8>> {
+        if file.is_none() {
+          file = Some(fs::File::open(&filename)?);
+        }
+        let mut data = Vec::with_capacity(chunk_size);
+        let file = file.as_mut().unwrap();
+        file.take(chunk_size as u64).read_to_end(&mut data)?;
+        Ok(data)
+      });
+      let data = match r {
+        Ok(Ready(result)) => result?,
+        Ok(NotReady) => return Ok(NotReady),
+        Err(_) => panic!("blocking error"),
+      };
+      // Running out of data is reported with an empty chunk that has end set.
+      done = data.is_empty();
+      let builder = &mut FlatBufferBuilder::new();
+      let data_off = if done {
+        None
+      } else {
+        Some(builder.create_vector(data.as_slice()))
+      };
+      let inner = msg::ResponseChunk::create(
+        builder,
+        &msg::ResponseChunkArgs {
+          seq,
+          end: done,
+          data: data_off,
+        },
+      );
+      seq += 1;
+      Ok(Ready(Some(serialize_response(
+        &state,
+        cmd_id,
+        builder,
+        msg::BaseArgs {
+          inner: Some(inner.as_union_value()),
+          inner_type: msg::Any::ResponseChunk,
+          ..Default::default()
+        },
+      ))))
+    },
+  ))
+}
+
+// An open file that is read a line at a time with op_read_line. It lives in
+// the isolate's resource table, behind an Arc so that lines are read without
+// holding the table's lock.
+type LineReader = Arc<Mutex<BufReader<fs::File>>>;
+
+fn op_open_lines(
   state: Arc<IsolateState>,
   base: &msg::Base,
   data: &'static mut [u8],
 ) -> Box<Op> {
   assert_eq!(data.len(), 0);
-  let inner = base.inner_as_copy_file().unwrap();
-  let from = PathBuf::from(inner.from().unwrap());
-  let to = PathBuf::from(inner.to().unwrap());
-
-  if !state.flags.allow_write {
-    return odd_future(permission_denied());
+  let inner = base.inner_as_open_lines().unwrap();
+  let cmd_id = base.cmd_id();
+  let what = inner.filename().unwrap();
+  if let Err(err) = state.check_permission(Permission::Read, what) {
+    return odd_future(err);
   }
-
-  debug!("op_copy_file {} {}", from.display(), to.display());
+  let filename = PathBuf::from(inner.filename().unwrap());
+  debug!("op_open_lines {}", filename.display());
   blocking!(base.sync(), || {
-    fs::copy(&from, &to)?;
-    Ok(empty_buf())
+    let file = fs::File::open(&filename)?;
+    let reader: LineReader = Arc::new(Mutex::new(BufReader::new(file)));
+    let rid = state.resource_table.lock().unwrap().add(Box::new(reader))?;
+    let builder = &mut FlatBufferBuilder::new();
+    let inner =
+      msg::OpenLinesRes::create(builder, &msg::OpenLinesResArgs { rid });
+    Ok(serialize_response(
+      &state,
+      cmd_id,
+      builder,
+      msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::OpenLinesRes,
+        ..Default::default()
+      },
+    ))
   })
 }
 
-macro_rules! to_seconds {
-  ($time:expr) => {{
-    // Unwrap is safe here as if the file is before the unix epoch
-    // something is very wrong.
-    $time
-      .and_then(|t| Ok(t.duration_since(UNIX_EPOCH).unwrap().as_secs()))
-      .unwrap_or(0)
-  }};
-}
-
-#[cfg(any(unix))]
-fn get_mode(perm: fs::Permissions) -> u32 {
-  perm.mode()
+// Responds with the next line of an op_open_lines() file, without its line
+// terminator, or with no line at the end of the file. A last line without a
+// terminator is still a line. Lines can be of any length.
+fn op_read_line(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_read_line().unwrap();
+  let cmd_id = base.cmd_id();
+  let reader = {
+    let mut table = state.resource_table.lock().unwrap();
+    match table.get_mut::<LineReader>(inner.rid()) {
+      Ok(reader) => reader.clone(),
+      Err(err) => return odd_future(err),
+    }
+  };
+  blocking!(base.sync(), || {
+    let mut line = Vec::new();
+    let n = reader.lock().unwrap().read_until(b'\n', &mut line)?;
+    if line.ends_with(b"\n") {
+      line.pop();
+      if line.ends_with(b"\r") {
+        line.pop();
+      }
+    }
+    let builder = &mut FlatBufferBuilder::new();
+    let line_off = if n == 0 {
+      None
+    } else {
+      Some(builder.create_vector(line.as_slice()))
+    };
+    let inner = msg::ReadLineRes::create(
+      builder,
+      &msg::ReadLineResArgs { line: line_off },
+    );
+    Ok(serialize_response(
+      &state,
+      cmd_id,
+      builder,
+      msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::ReadLineRes,
+        ..Default::default()
+      },
+    ))
+  })
 }
 
-#[cfg(not(any(unix)))]
-fn get_mode(_perm: fs::Permissions) -> u32 {
-  0
+// Reads a file on the blocking pool. The outer Result is always Ok, so that
+// one failed read doesn't fail the whole batch in op_read_files().
+fn read_file_result(
+  state: &IsolateState,
+  filename: PathBuf,
+) -> Box<Future<Item = DenoResult<Vec<u8>>, Error = DenoError> + Send> {
+  let what = filename.to_string_lossy();
+  if let Err(err) = state.check_permission(Permission::Read, &what) {
+    return Box::new(futures::future::ok(Err(err)));
+  }
+  Box::new(poll_fn(move || -> Poll<DenoResult<Vec<u8>>, DenoError> {
+    use futures::Async::*;
+    match tokio_threadpool::blocking(|| fs::read(&filename)) {
+      Ok(Ready(result)) => Ok(Ready(result.map_err(DenoError::from))),
+      Ok(NotReady) => Ok(NotReady),
+      Err(_) => panic!("blocking error"),
+    }
+  }))
 }
 
-fn op_stat(
-  _config: Arc<IsolateState>,
+fn op_read_text_file(
+  state: Arc<IsolateState>,
   base: &msg::Base,
   data: &'static mut [u8],
 ) -> Box<Op> {
   assert_eq!(data.len(), 0);
-  let inner = base.inner_as_stat().unwrap();
+  let inner = base.inner_as_read_text_file().unwrap();
   let cmd_id = base.cmd_id();
-  let filename = PathBuf::from(inner.filename().unwrap());
-  let lstat = inner.lstat();
-
+  let what = inner.filename().unwrap();
+  if let Err(err) = state.check_permission(Permission::Read, what) {
+    return odd_future(err);
+  }
+  let filename = PathBuf::from(what);
+  debug!("op_read_text_file {}", filename.display());
   blocking!(base.sync(), || {
+    let vec = fs::read(&filename)?;
+    let text = String::from_utf8(vec)
+      .map_err(|err| errors::invalid_utf8(err.utf8_error().valid_up_to()))?;
     let builder = &mut FlatBufferBuilder::new();
-    debug!("op_stat {} {}", filename.display(), lstat);
-    let metadata = if lstat {
-      fs::symlink_metadata(&filename)?
-    } else {
-      fs::metadata(&filename)?
-    };
-
-    let inner = msg::StatRes::create(
+    let text_off = builder.create_string(&text);
+    let inner = msg::ReadTextFileRes::create(
       builder,
-      &msg::StatResArgs {
-        is_file: metadata.is_file(),
-        is_symlink: metadata.file_type().is_symlink(),
-        len: metadata.len(),
-        modified: to_seconds!(metadata.modified()),
-        accessed: to_seconds!(metadata.accessed()),
-        created: to_seconds!(metadata.created()),
-        mode: get_mode(metadata.permissions()),
-        has_mode: cfg!(target_family = "unix"),
-        ..Default::default()
+      &msg::ReadTextFileResArgs {
+        text: Some(text_off),
       },
     );
-
     Ok(serialize_response(
+      &state,
       cmd_id,
       builder,
       msg::BaseArgs {
         inner: Some(inner.as_union_value()),
-        inner_type: msg::Any::StatRes,
+        inner_type: msg::Any::ReadTextFileRes,
         ..Default::default()
       },
     ))
   })
 }
 
-fn op_read_dir(
-  _state: Arc<IsolateState>,
+// Like op_read_file, but for many files at once. The files are read
+// concurrently and the result of each read is reported separately.
+fn op_read_files(
+  state: Arc<IsolateState>,
   base: &msg::Base,
   data: &'static mut [u8],
 ) -> Box<Op> {
   assert_eq!(data.len(), 0);
-  let inner = base.inner_as_read_dir().unwrap();
+  let inner = base.inner_as_read_files().unwrap();
   let cmd_id = base.cmd_id();
-  let path = String::from(inner.path().unwrap());
+  let filenames = inner.filenames().unwrap();
+  let reads: Vec<_> = (0..filenames.len())
+    .map(|i| {
+      let filename = PathBuf::from(filenames.get(i));
+      debug!("op_read_files {}", filename.display());
+      // join_all() polls its futures one after another on a single task, and
+      // a blocking read would hold up the rest, so each gets a task of its
+      // own.
+      futures::sync::oneshot::spawn(
+        read_file_result(&state, filename),
+        &DefaultExecutor::current(),
+      )
+    }).collect();
 
-  blocking!(base.sync(), || -> OpResult {
-    debug!("op_read_dir {}", path);
+  let op = futures::future::join_all(reads).and_then(move |results| {
     let builder = &mut FlatBufferBuilder::new();
-    let entries: Vec<_> = fs::read_dir(Path::new(&path))?
-      .map(|entry| {
-        let entry = entry.unwrap();
-        let metadata = entry.metadata().unwrap();
-        let file_type = metadata.file_type();
-        let name = builder.create_string(entry.file_name().to_str().unwrap());
-        let path = builder.create_string(entry.path().to_str().unwrap());
-
-        msg::StatRes::create(
-          builder,
-          &msg::StatResArgs {
-            is_file: file_type.is_file(),
-            is_symlink: file_type.is_symlink(),
-            len: metadata.len(),
-            modified: to_seconds!(metadata.modified()),
-            accessed: to_seconds!(metadata.accessed()),
-            created: to_seconds!(metadata.created()),
-            name: Some(name),
-            path: Some(path),
+    let results: Vec<_> = results
+      .into_iter()
+      .map(|result| {
+        let args = match result {
+          Ok(vec) => msg::ReadFilesResultArgs {
+            data: Some(builder.create_vector(vec.as_slice())),
+            ..Default::default()
+          },
+          Err(err) => msg::ReadFilesResultArgs {
+            error_kind: err.kind(),
+            error: Some(builder.create_string(&format!("{}", err))),
             ..Default::default()
           },
-        )
+        };
+        msg::ReadFilesResult::create(builder, &args)
       }).collect();
-
-    let entries = builder.create_vector(&entries);
-    let inner = msg::ReadDirRes::create(
+    let results = builder.create_vector(&results);
+    let inner = msg::ReadFilesRes::create(
       builder,
-      &msg::ReadDirResArgs {
-        entries: Some(entries),
+      &msg::ReadFilesResArgs {
+        results: Some(results),
         ..Default::default()
       },
     );
     Ok(serialize_response(
+      &state,
       cmd_id,
       builder,
       msg::BaseArgs {
         inner: Some(inner.as_union_value()),
-        inner_type: msg::Any::ReadDirRes,
+        inner_type: msg::Any::ReadFilesRes,
         ..Default::default()
       },
     ))
-  })
+  });
+  Box::new(op)
 }
 
-fn op_write_file(
+fn op_copy_file(
   state: Arc<IsolateState>,
   base: &msg::Base,
   data: &'static mut [u8],
 ) -> Box<Op> {
-  let inner = base.inner_as_write_file().unwrap();
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_copy_file().unwrap();
+  let from = PathBuf::from(inner.from().unwrap());
+  let to = PathBuf::from(inner.to().unwrap());
 
-  if !state.flags.allow_write {
-    return odd_future(permission_denied());
+  let what = inner.from().unwrap();
+  if let Err(err) = state.check_permission(Permission::Read, what) {
+    return odd_future(err);
+  }
+  let what = inner.to().unwrap();
+  if let Err(err) = state.check_permission(Permission::Write, what) {
+    return odd_future(err);
   }
 
-  let filename = String::from(inner.filename().unwrap());
-  let perm = inner.perm();
-
-  blocking!(base.sync(), || -> OpResult {
-    debug!("op_write_file {} {}", filename, data.len());
-    deno_fs::write_file(Path::new(&filename), data, perm)?;
+  debug!("op_copy_file {} {}", from.display(), to.display());
+  blocking!(base.sync(), || {
+    // fs::copy() reports a missing source as InvalidInput on unix, see
+    // https://github.com/rust-lang/rust/issues/54800
+    fs::metadata(&from)?;
+    if !is_in_roots(&state.flags.read_roots, &fs::canonicalize(&from)?)
+      || !is_in_roots(&state.flags.write_roots, &resolve_new_path(&to)?)
+    {
+      return Err(permission_denied());
+    }
+    // Copies the mode too, and uses copy_file_range() etc. where available.
+    fs::copy(&from, &to)?;
     Ok(empty_buf())
   })
 }
 
-fn op_rename(
-  state: Arc<IsolateState>,
-  base: &msg::Base,
-  data: &'static mut [u8],
-) -> Box<Op> {
-  assert_eq!(data.len(), 0);
-  if !state.flags.allow_write {
-    return odd_future(permission_denied());
-  }
-  let inner = base.inner_as_rename().unwrap();
-  let oldpath = PathBuf::from(inner.oldpath().unwrap());
-  let newpath = PathBuf::from(inner.newpath().unwrap());
-  blocking!(base.sync(), || -> OpResult {
-    debug!("op_rename {} {}", oldpath.display(), newpath.display());
-    fs::rename(&oldpath, &newpath)?;
-    Ok(empty_buf())
-  })
+macro_rules! to_seconds {
+  ($time:expr) => {{
+    // Unwrap is safe here as if the file is before the unix epoch
+    // something is very wrong.
+    $time
+      .and_then(|t| Ok(t.duration_since(UNIX_EPOCH).unwrap().as_secs()))
+      .unwrap_or(0)
+  }};
 }
 
-fn op_symlink(
-  state: Arc<IsolateState>,
-  base: &msg::Base,
-  data: &'static mut [u8],
-) -> Box<Op> {
-  assert_eq!(data.len(), 0);
-  if !state.flags.allow_write {
-    return odd_future(permission_denied());
-  }
-  // TODO Use type for Windows.
-  if cfg!(windows) {
-    return odd_future(errors::new(
-      ErrorKind::Other,
-      "Not implemented".to_string(),
-    ));
-  }
+#[cfg(any(unix))]
+fn get_mode(perm: fs::Permissions) -> u32 {
+  perm.mode()
+}
 
-  let inner = base.inner_as_symlink().unwrap();
-  let oldname = PathBuf::from(inner.oldname().unwrap());
-  let newname = PathBuf::from(inner.newname().unwrap());
-  blocking!(base.sync(), || -> OpResult {
-    debug!("op_symlink {} {}", oldname.display(), newname.display());
-    #[cfg(any(unix))]
-    std::os::unix::fs::symlink(&oldname, &newname)?;
-    Ok(empty_buf())
-  })
+#[cfg(not(any(unix)))]
+fn get_mode(_perm: fs::Permissions) -> u32 {
+  0
 }
 
-fn op_read_link(
-  _state: Arc<IsolateState>,
+fn op_stat(
+  state: Arc<IsolateState>,
   base: &msg::Base,
   data: &'static mut [u8],
 ) -> Box<Op> {
   assert_eq!(data.len(), 0);
-  let inner = base.inner_as_readlink().unwrap();
+  let inner = base.inner_as_stat().unwrap();
   let cmd_id = base.cmd_id();
-  let name = PathBuf::from(inner.name().unwrap());
+  let filename = PathBuf::from(inner.filename().unwrap());
+  let lstat = inner.lstat();
 
-  blocking!(base.sync(), || -> OpResult {
-    debug!("op_read_link {}", name.display());
-    let path = fs::read_link(&name)?;
+  let what = inner.filename().unwrap();
+  if let Err(err) = state.check_permission(Permission::Read, what) {
+    return odd_future(err);
+  }
+
+  blocking!(base.sync(), || {
     let builder = &mut FlatBufferBuilder::new();
-    let path_off = builder.create_string(path.to_str().unwrap());
-    let inner = msg::ReadlinkRes::create(
+    debug!("op_stat {} {}", filename.display(), lstat);
+    let metadata = if lstat {
+      fs::symlink_metadata(&filename)?
+    } else {
+      fs::metadata(&filename)?
+    };
+
+    let inner = msg::StatRes::create(
       builder,
-      &msg::ReadlinkResArgs {
-        path: Some(path_off),
+      &msg::StatResArgs {
+        is_file: metadata.is_file(),
+        is_symlink: metadata.file_type().is_symlink(),
+        len: metadata.len(),
+        modified: to_seconds!(metadata.modified()),
+        accessed: to_seconds!(metadata.accessed()),
+        created: to_seconds!(metadata.created()),
+        mode: get_mode(metadata.permissions()),
+        has_mode: cfg!(target_family = "unix"),
         ..Default::default()
       },
     );
+
     Ok(serialize_response(
+      &state,
       cmd_id,
       builder,
       msg::BaseArgs {
         inner: Some(inner.as_union_value()),
-        inner_type: msg::Any::ReadlinkRes,
+        inner_type: msg::Any::StatRes,
         ..Default::default()
       },
     ))
   })
 }
 
-fn op_truncate(
+fn op_fs_events(
   state: Arc<IsolateState>,
   base: &msg::Base,
   data: &'static mut [u8],
 ) -> Box<Op> {
   assert_eq!(data.len(), 0);
-
-  if !state.flags.allow_write {
-    return odd_future(permission_denied());
+  let inner = base.inner_as_fs_events().unwrap();
+  let cmd_id = base.cmd_id();
+  let mut paths = Vec::new();
+  if let Some(names) = inner.paths() {
+    for i in 0..names.len() {
+      let what = names.get(i);
+      if let Err(err) = state.check_permission(Permission::Read, what) {
+        return odd_future(err);
+      }
+      paths.push(PathBuf::from(what));
+    }
   }
-
-  let inner = base.inner_as_truncate().unwrap();
-  let filename = String::from(inner.name().unwrap());
-  let len = inner.len();
-  blocking!(base.sync(), || {
-    debug!("op_truncate {} {}", filename, len);
-    let f = fs::OpenOptions::new().write(true).open(&filename)?;
-    f.set_len(len as u64)?;
-    Ok(empty_buf())
-  })
+  debug!("op_fs_events {:?}", paths);
+  let watcher = match FsWatcher::new(paths) {
+    Ok(watcher) => watcher,
+    Err(err) => return odd_future(err),
+  };
+  let added = state.resource_table.lock().unwrap().add(Box::new(watcher));
+  let rid = match added {
+    Ok(rid) => rid,
+    Err(err) => return odd_future(err),
+  };
+  let builder = &mut FlatBufferBuilder::new();
+  let inner = msg::FsEventsRes::create(builder, &msg::FsEventsResArgs { rid });
+  ok_future(serialize_response(
+    &state,
+    cmd_id,
+    builder,
+    msg::BaseArgs {
+      inner: Some(inner.as_union_value()),
+      inner_type: msg::Any::FsEventsRes,
+      ..Default::default()
+    },
+  ))
 }
 
-fn op_listen(
+// Streams the changes seen by an op_fs_events watch, as ResponseChunks. The op
+// completes once the watch has been closed. A watch can only be polled once.
+fn op_fs_events_poll(
   state: Arc<IsolateState>,
   base: &msg::Base,
   data: &'static mut [u8],
 ) -> Box<Op> {
   assert_eq!(data.len(), 0);
-  if !state.flags.allow_net {
-    return odd_future(permission_denied());
-  }
-
+  let inner = base.inner_as_fs_events_poll().unwrap();
   let cmd_id = base.cmd_id();
-  let inner = base.inner_as_listen().unwrap();
-  let network = inner.network().unwrap();
-  assert_eq!(network, "tcp");
-  let address = inner.address().unwrap();
-
-  Box::new(futures::future::result((move || {
-    // TODO properly parse addr
-    let addr = SocketAddr::from_str(address).unwrap();
-
-    let listener = TcpListener::bind(&addr)?;
-    let resource = resources::add_tcp_listener(listener);
+  if base.sync() {
+    return odd_future(errors::new(
+      ErrorKind::InvalidInput,
+      "FsEventsPoll must be async".to_string(),
+    ));
+  }
+  let events = {
+    let mut table = state.resource_table.lock().unwrap();
+    match table.get_mut::<FsWatcher>(inner.rid()) {
+      Ok(watcher) => watcher.take_events(),
+      Err(err) => return odd_future(err),
+    }
+  };
+  let events = match events {
+    Some(events) => events,
+    None => {
+      return odd_future(errors::new(
+        ErrorKind::Other,
+        "The watch is already being polled".to_string(),
+      ))
+    }
+  };
+  let mut seq = 0;
+  let chunk_state = state.clone();
+  let chunks = events
+    .map_err(|()| -> DenoError { unreachable!() })
+    .map(move |events| {
+      let buf = fs_events_chunk(&chunk_state, cmd_id, seq, Some(&events[..]));
+      seq += 1;
+      buf
+    });
+  let last_state = state.clone();
+  isolate::streaming_response(state, Box::new(chunks), move |n| {
+    fs_events_chunk(&last_state, cmd_id, n, None)
+  })
+}
 
+// A ResponseChunk of op_fs_events_poll. Its data is an FsEventsPollRes with
+// events, or is unset for the last chunk.
+fn fs_events_chunk(
+  state: &IsolateState,
+  cmd_id: u32,
+  seq: u32,
+  events: Option<&[FsEvent]>,
+) -> Buf {
+  let data = events.map(|events| {
     let builder = &mut FlatBufferBuilder::new();
-    let inner = msg::ListenRes::create(
+    let mut offsets = Vec::new();
+    for event in events {
+      let kind = builder.create_string(event.kind.as_str());
+      let path = builder.create_string(&event.path.to_string_lossy());
+      offsets.push(msg::FsEvent::create(
+        builder,
+        &msg::FsEventArgs {
+          kind: Some(kind),
+          path: Some(path),
+        },
+      ));
+    }
+    let events = builder.create_vector(&offsets);
+    let res = msg::FsEventsPollRes::create(
       builder,
-      &msg::ListenResArgs {
-        rid: resource.rid,
-        ..Default::default()
+      &msg::FsEventsPollResArgs {
+        events: Some(events),
       },
     );
-    Ok(serialize_response(
-      cmd_id,
-      builder,
-      msg::BaseArgs {
-        inner: Some(inner.as_union_value()),
-        inner_type: msg::Any::ListenRes,
-        ..Default::default()
-      },
-    ))
-  })()))
-}
-
-fn new_conn(cmd_id: u32, tcp_stream: TcpStream) -> OpResult {
-  let tcp_stream_resource = resources::add_tcp_stream(tcp_stream);
-  // TODO forward socket_addr to client.
-
+    builder.finish(res, None);
+    builder.finished_data().to_vec()
+  });
   let builder = &mut FlatBufferBuilder::new();
-  let inner = msg::NewConn::create(
+  let data = data.map(|data| builder.create_vector(&data));
+  let inner = msg::ResponseChunk::create(
     builder,
-    &msg::NewConnArgs {
-      rid: tcp_stream_resource.rid,
-      ..Default::default()
+    &msg::ResponseChunkArgs {
+      seq,
+      end: data.is_none(),
+      data,
     },
   );
-  Ok(serialize_response(
+  serialize_response(
+    state,
     cmd_id,
     builder,
     msg::BaseArgs {
       inner: Some(inner.as_union_value()),
-      inner_type: msg::Any::NewConn,
+      inner_type: msg::Any::ResponseChunk,
       ..Default::default()
     },
-  ))
+  )
 }
 
-fn op_accept(
+fn op_read_dir(
   state: Arc<IsolateState>,
   base: &msg::Base,
   data: &'static mut [u8],
 ) -> Box<Op> {
   assert_eq!(data.len(), 0);
-  if !state.flags.allow_net {
-    return odd_future(permission_denied());
+  let inner = base.inner_as_read_dir().unwrap();
+  let cmd_id = base.cmd_id();
+  let path = String::from(inner.path().unwrap());
+
+  if let Err(err) = state.check_permission(Permission::Read, &path) {
+    return odd_future(err);
   }
 
-  let cmd_id = base.cmd_id();
-  let inner = base.inner_as_accept().unwrap();
-  let server_rid = inner.rid();
+  blocking!(base.sync(), || -> OpResult {
+    debug!("op_read_dir {}", path);
+    let builder = &mut FlatBufferBuilder::new();
+    // Entries that disappear or can't be stat'ed while listing fail the whole
+    // op, rather than being skipped.
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(Path::new(&path))? {
+      let entry = entry?;
+      let metadata = entry.metadata()?;
+      let file_type = metadata.file_type();
+      let file_name = entry.file_name();
+      let name = builder.create_string(path_to_str(Path::new(&file_name))?);
+      let path = builder.create_string(path_to_str(&entry.path())?);
 
-  match resources::lookup(server_rid) {
-    None => odd_future(errors::bad_resource()),
-    Some(server_resource) => {
-      let op = tokio_util::accept(server_resource)
-        .map_err(|err| DenoError::from(err))
-        .and_then(move |(tcp_stream, _socket_addr)| {
-          new_conn(cmd_id, tcp_stream)
-        });
-      Box::new(op)
+      entries.push(msg::StatRes::create(
+        builder,
+        &msg::StatResArgs {
+          is_file: file_type.is_file(),
+          is_symlink: file_type.is_symlink(),
+          len: metadata.len(),
+          modified: to_seconds!(metadata.modified()),
+          accessed: to_seconds!(metadata.accessed()),
+          created: to_seconds!(metadata.created()),
+          name: Some(name),
+          path: Some(path),
+          ..Default::default()
+        },
+      ));
     }
-  }
+
+    let entries = builder.create_vector(&entries);
+    let inner = msg::ReadDirRes::create(
+      builder,
+      &msg::ReadDirResArgs {
+        entries: Some(entries),
+        ..Default::default()
+      },
+    );
+    Ok(serialize_response(
+      &state,
+      cmd_id,
+      builder,
+      msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::ReadDirRes,
+        ..Default::default()
+      },
+    ))
+  })
 }
 
-fn op_dial(
+fn op_write_file(
   state: Arc<IsolateState>,
   base: &msg::Base,
   data: &'static mut [u8],
 ) -> Box<Op> {
-  assert_eq!(data.len(), 0);
-  if !state.flags.allow_net {
-    return odd_future(permission_denied());
+  let inner = base.inner_as_write_file().unwrap();
+  let filename = String::from(inner.filename().unwrap());
+
+  if let Err(err) = state.check_permission(Permission::Write, &filename) {
+    return odd_future(err);
   }
 
-  let cmd_id = base.cmd_id();
-  let inner = base.inner_as_dial().unwrap();
-  let network = inner.network().unwrap();
-  assert_eq!(network, "tcp");
-  let address = inner.address().unwrap();
+  let perm = inner.perm();
+  let options = deno_fs::WriteFileOptions {
+    perm,
+    create: inner.create(),
+    append: inner.append() || perm & (1 << 31) != 0,
+    atomic: inner.atomic(),
+  };
 
-  // TODO properly parse addr
-  let addr = SocketAddr::from_str(address).unwrap();
+  blocking!(base.sync(), || -> OpResult {
+    debug!("op_write_file {} {}", filename, data.len());
+    deno_fs::write_file_with_options(Path::new(&filename), data, &options)?;
+    Ok(empty_buf())
+  })
+}
 
-  let op = TcpStream::connect(&addr)
-    .map_err(|err| err.into())
-    .and_then(move |tcp_stream| new_conn(cmd_id, tcp_stream));
-  Box::new(op)
+fn op_rename(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_rename().unwrap();
+  // oldpath is removed, so it is written to as well as read.
+  let what = inner.oldpath().unwrap();
+  if let Err(err) = state.check_permission(Permission::Read, what) {
+    return odd_future(err);
+  }
+  if let Err(err) = state.check_permission(Permission::Write, what) {
+    return odd_future(err);
+  }
+  let what = inner.newpath().unwrap();
+  if let Err(err) = state.check_permission(Permission::Write, what) {
+    return odd_future(err);
+  }
+  let oldpath = PathBuf::from(inner.oldpath().unwrap());
+  let newpath = PathBuf::from(inner.newpath().unwrap());
+  blocking!(base.sync(), || -> OpResult {
+    debug!("op_rename {} {}", oldpath.display(), newpath.display());
+    // A missing oldpath is NotFound, whatever newpath is.
+    fs::symlink_metadata(&oldpath)?;
+    if !state.flags.write_roots.is_empty() {
+      for path in &[&oldpath, &newpath] {
+        let real_path = resolve_new_path(path)?;
+        if !is_in_roots(&state.flags.write_roots, &real_path) {
+          return Err(permission_denied());
+        }
+      }
+    }
+    match fs::rename(&oldpath, &newpath) {
+      Err(ref err) if err.raw_os_error() == Some(EXDEV) => {
+        move_by_copy(&oldpath, &newpath)?
+      }
+      result => result?,
+    }
+    Ok(empty_buf())
+  })
+}
+
+// The error rename() fails with when from and to are on different file
+// systems.
+#[cfg(unix)]
+const EXDEV: i32 = libc::EXDEV;
+#[cfg(windows)]
+const EXDEV: i32 = 17; // ERROR_NOT_SAME_DEVICE
+
+// Where path will be once it is created: its parent with symlinks resolved.
+// path itself needn't exist, and is not followed if it is a symlink.
+fn resolve_new_path(path: &Path) -> DenoResult<PathBuf> {
+  match (path.parent(), path.file_name()) {
+    (Some(parent), Some(name)) => {
+      let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+      } else {
+        parent
+      };
+      Ok(fs::canonicalize(parent)?.join(name))
+    }
+    _ => Ok(fs::canonicalize(path)?),
+  }
+}
+
+// Moves from to to where rename() can't. from is copied next to to, and the
+// copy is renamed into place, so that a failed move doesn't leave a partial
+// copy at to.
+fn move_by_copy(from: &Path, to: &Path) -> DenoResult<()> {
+  let mut tmp_name = OsString::from(".");
+  match to.file_name() {
+    Some(name) => tmp_name.push(name),
+    None => {
+      let msg = format!("Can't move to {}", to.display());
+      return Err(errors::new(ErrorKind::InvalidInput, msg));
+    }
+  }
+  tmp_name.push(format!(".{}.moving", std::process::id()));
+  let tmp = to.with_file_name(tmp_name);
+  let copied = copy_recursive(from, &tmp)
+    .and_then(|()| fs::rename(&tmp, to).map_err(DenoError::from));
+  if let Err(err) = copied {
+    let _ = remove_path(&tmp);
+    return Err(err);
+  }
+  remove_path(from)
+}
+
+fn remove_path(path: &Path) -> DenoResult<()> {
+  if fs::symlink_metadata(path)?.is_dir() {
+    remove_dir_all(path)?;
+  } else {
+    fs::remove_file(path)?;
+  }
+  Ok(())
+}
+
+// Copies symlinks as symlinks, rather than what they point to.
+fn copy_recursive(from: &Path, to: &Path) -> DenoResult<()> {
+  let metadata = fs::symlink_metadata(from)?;
+  let file_type = metadata.file_type();
+  if file_type.is_dir() {
+    fs::create_dir(to)?;
+    for entry in fs::read_dir(from)? {
+      let entry = entry?;
+      copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+    }
+    fs::set_permissions(to, metadata.permissions())?;
+  } else if file_type.is_symlink() {
+    #[cfg(any(unix))]
+    std::os::unix::fs::symlink(fs::read_link(from)?, to)?;
+    #[cfg(not(unix))]
+    return Err(errors::new(
+      ErrorKind::Other,
+      "Not implemented".to_string(),
+    ));
+  } else {
+    fs::copy(from, to)?;
+  }
+  Ok(())
+}
+
+fn op_symlink(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_symlink().unwrap();
+  let what = inner.oldname().unwrap();
+  if let Err(err) = state.check_permission(Permission::Read, what) {
+    return odd_future(err);
+  }
+  let what = inner.newname().unwrap();
+  if let Err(err) = state.check_permission(Permission::Write, what) {
+    return odd_future(err);
+  }
+  let is_dir = match inner.kind() {
+    Some("dir") => Some(true),
+    Some("file") => Some(false),
+    None => None,
+    Some(kind) => {
+      return odd_future(errors::new(
+        ErrorKind::InvalidInput,
+        format!("Invalid symlink type: {}", kind),
+      ))
+    }
+  };
+
+  let oldname = PathBuf::from(inner.oldname().unwrap());
+  let newname = PathBuf::from(inner.newname().unwrap());
+  blocking!(base.sync(), || -> OpResult {
+    debug!("op_symlink {} {}", oldname.display(), newname.display());
+    deno_fs::symlink(&oldname, &newname, is_dir)?;
+    Ok(empty_buf())
+  })
+}
+
+fn op_link(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_link().unwrap();
+  let what = inner.oldname().unwrap();
+  if let Err(err) = state.check_permission(Permission::Read, what) {
+    return odd_future(err);
+  }
+  let what = inner.newname().unwrap();
+  if let Err(err) = state.check_permission(Permission::Write, what) {
+    return odd_future(err);
+  }
+  let oldname = PathBuf::from(inner.oldname().unwrap());
+  let newname = PathBuf::from(inner.newname().unwrap());
+  blocking!(base.sync(), || -> OpResult {
+    debug!("op_link {} {}", oldname.display(), newname.display());
+    fs::hard_link(&oldname, &newname)?;
+    Ok(empty_buf())
+  })
+}
+
+fn op_read_link(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_readlink().unwrap();
+  let what = inner.name().unwrap();
+  if let Err(err) = state.check_permission(Permission::Read, what) {
+    return odd_future(err);
+  }
+  let cmd_id = base.cmd_id();
+  let name = PathBuf::from(inner.name().unwrap());
+
+  blocking!(base.sync(), || -> OpResult {
+    debug!("op_read_link {}", name.display());
+    let path = fs::read_link(&name)?;
+    let builder = &mut FlatBufferBuilder::new();
+    let path_off = builder.create_string(path_to_str(&path)?);
+    let inner = msg::ReadlinkRes::create(
+      builder,
+      &msg::ReadlinkResArgs {
+        path: Some(path_off),
+        ..Default::default()
+      },
+    );
+    Ok(serialize_response(
+      &state,
+      cmd_id,
+      builder,
+      msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::ReadlinkRes,
+        ..Default::default()
+      },
+    ))
+  })
+}
+
+// True if path, which must be canonical, is inside one of roots, or if there
+// are no roots.
+fn is_in_roots(roots: &[PathBuf], path: &Path) -> bool {
+  roots.is_empty() || roots.iter().any(|root| {
+    // Roots may contain symlinks themselves.
+    match fs::canonicalize(root) {
+      Ok(root) => path.starts_with(root),
+      Err(_) => false,
+    }
+  })
+}
+
+fn op_realpath(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_realpath().unwrap();
+  let what = inner.path().unwrap();
+  if let Err(err) = state.check_permission(Permission::Read, what) {
+    return odd_future(err);
+  }
+  let cmd_id = base.cmd_id();
+  let path = PathBuf::from(inner.path().unwrap());
+
+  blocking!(base.sync(), || -> OpResult {
+    debug!("op_realpath {}", path.display());
+    let real_path = fs::canonicalize(&path)?;
+    if !is_in_roots(&state.flags.read_roots, &real_path) {
+      return Err(permission_denied());
+    }
+    let builder = &mut FlatBufferBuilder::new();
+    let path_off = builder.create_string(path_to_str(&real_path)?);
+    let inner = msg::RealpathRes::create(
+      builder,
+      &msg::RealpathResArgs {
+        path: Some(path_off),
+      },
+    );
+    Ok(serialize_response(
+      &state,
+      cmd_id,
+      builder,
+      msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::RealpathRes,
+        ..Default::default()
+      },
+    ))
+  })
+}
+
+// Inputs up to this size are hashed on the main thread even when the op is
+// async, as handing them to the thread pool would take longer.
+const HASH_BLOCKING_THRESHOLD: usize = 64 * 1024;
+
+fn op_hash(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  let inner = base.inner_as_hash().unwrap();
+  let cmd_id = base.cmd_id();
+  let name = inner.algorithm().unwrap();
+  let algorithm: &'static digest::Algorithm = match name {
+    "sha1" => &digest::SHA1,
+    "sha256" => &digest::SHA256,
+    _ => {
+      return odd_future(errors::new(
+        ErrorKind::InvalidInput,
+        format!("Unsupported hash algorithm: {}", name),
+      ))
+    }
+  };
+  let on_main_thread = base.sync() || data.len() <= HASH_BLOCKING_THRESHOLD;
+
+  blocking!(on_main_thread, || -> OpResult {
+    debug!("op_hash {} bytes", data.len());
+    let digest = digest::digest(algorithm, data);
+    let builder = &mut FlatBufferBuilder::new();
+    let digest_off = builder.create_vector(digest.as_ref());
+    let inner = msg::HashRes::create(
+      builder,
+      &msg::HashResArgs {
+        digest: Some(digest_off),
+      },
+    );
+    Ok(serialize_response(
+      &state,
+      cmd_id,
+      builder,
+      msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::HashRes,
+        ..Default::default()
+      },
+    ))
+  })
+}
+
+#[cfg(any(unix))]
+fn get_exit_signal(status: &ExitStatus) -> Option<i32> {
+  status.signal()
+}
+
+#[cfg(not(any(unix)))]
+fn get_exit_signal(_status: &ExitStatus) -> Option<i32> {
+  None
+}
+
+fn subprocess_stdio(stdio: msg::ProcessStdio) -> Stdio {
+  match stdio {
+    msg::ProcessStdio::Inherit => Stdio::inherit(),
+    msg::ProcessStdio::Piped => Stdio::piped(),
+    msg::ProcessStdio::Null => Stdio::null(),
+  }
+}
+
+fn op_run(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let base = Box::new(*base);
+  let inner = base.inner_as_run().unwrap();
+  let cmd_id = base.cmd_id();
+
+  let args = inner.args().unwrap();
+  if args.len() == 0 {
+    return odd_future(errors::new(
+      ErrorKind::InvalidInput,
+      "run() needs the program to run".to_string(),
+    ));
+  }
+  let program = args.get(0);
+  if let Err(err) = state.check_permission(Permission::Run, program) {
+    return odd_future(err);
+  }
+
+  let mut c = Command::new(program);
+  for i in 1..args.len() {
+    c.arg(args.get(i));
+  }
+  if let Some(cwd) = inner.cwd() {
+    c.current_dir(cwd);
+  }
+  c.stdin(subprocess_stdio(inner.stdin()));
+  c.stdout(subprocess_stdio(inner.stdout()));
+  c.stderr(subprocess_stdio(inner.stderr()));
+
+  blocking!(base.sync(), || -> OpResult {
+    debug!("op_run {:?}", c);
+    let child = c.spawn()?;
+    let pid = child.id();
+    let resources = resources::add_child(child);
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::RunRes::create(
+      builder,
+      &msg::RunResArgs {
+        rid: resources.child_rid,
+        pid,
+        stdin_rid: resources.stdin_rid.unwrap_or(-1),
+        stdout_rid: resources.stdout_rid.unwrap_or(-1),
+        stderr_rid: resources.stderr_rid.unwrap_or(-1),
+      },
+    );
+    Ok(serialize_response(
+      &state,
+      cmd_id,
+      builder,
+      msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::RunRes,
+        ..Default::default()
+      },
+    ))
+  })
+}
+
+fn op_run_status(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let base = Box::new(*base);
+  let inner = base.inner_as_run_status().unwrap();
+  let cmd_id = base.cmd_id();
+  let rid = inner.rid();
+
+  blocking!(base.sync(), || -> OpResult {
+    let status = resources::child_status(rid)?;
+    let exit_signal = get_exit_signal(&status);
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::RunStatusRes::create(
+      builder,
+      &msg::RunStatusResArgs {
+        got_signal: exit_signal.is_some(),
+        exit_code: status.code().unwrap_or(-1),
+        exit_signal: exit_signal.unwrap_or(-1),
+      },
+    );
+    Ok(serialize_response(
+      &state,
+      cmd_id,
+      builder,
+      msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::RunStatusRes,
+        ..Default::default()
+      },
+    ))
+  })
+}
+
+fn op_truncate(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+
+  let inner = base.inner_as_truncate().unwrap();
+  let len = inner.len();
+  let rid = inner.rid();
+  if rid >= 0 {
+    if let Err(err) = state.check_permission(Permission::Write, "") {
+      return odd_future(err);
+    }
+    if base.sync() {
+      return odd_future(errors::new(
+        ErrorKind::InvalidInput,
+        "Truncating by rid must be async".to_string(),
+      ));
+    }
+    debug!("op_truncate rid {} {}", rid, len);
+    let op = poll_fn(move || resources::poll_set_len(rid, u64::from(len)));
+    return Box::new(op.map(|()| empty_buf()));
+  }
+  let filename = String::from(inner.name().unwrap());
+  if let Err(err) = state.check_permission(Permission::Write, &filename) {
+    return odd_future(err);
+  }
+  blocking!(base.sync(), || {
+    debug!("op_truncate {} {}", filename, len);
+    let f = fs::OpenOptions::new().write(true).open(&filename)?;
+    f.set_len(len as u64)?;
+    Ok(empty_buf())
+  })
+}
+
+// Seeking needs no permission of its own, it only moves around a file that
+// has already been opened.
+fn op_seek(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let cmd_id = base.cmd_id();
+  let inner = base.inner_as_seek().unwrap();
+  let rid = inner.rid();
+  let offset = inner.offset();
+  let pos = match inner.whence() {
+    msg::SeekMode::Start if offset < 0 => {
+      return odd_future(errors::new(
+        ErrorKind::InvalidInput,
+        "Cannot seek before the start of the file".to_string(),
+      ))
+    }
+    msg::SeekMode::Start => SeekFrom::Start(offset as u64),
+    msg::SeekMode::Current => SeekFrom::Current(offset),
+    msg::SeekMode::End => SeekFrom::End(offset),
+  };
+  if base.sync() {
+    return odd_future(errors::new(
+      ErrorKind::InvalidInput,
+      "Seeking must be async".to_string(),
+    ));
+  }
+  debug!("op_seek rid {} {:?}", rid, pos);
+  let op = poll_fn(move || resources::poll_seek(rid, pos));
+  Box::new(op.map(move |position| {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner =
+      msg::SeekRes::create(builder, &msg::SeekResArgs { position });
+    serialize_response(
+      &state,
+      cmd_id,
+      builder,
+      msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::SeekRes,
+        ..Default::default()
+      },
+    )
+  }))
+}
+
+fn op_fsync(
+  _state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let inner = base.inner_as_fsync().unwrap();
+  let rid = inner.rid();
+  let data_only = inner.data_only();
+  if base.sync() {
+    return odd_future(errors::new(
+      ErrorKind::InvalidInput,
+      "Fsync must be async".to_string(),
+    ));
+  }
+  debug!("op_fsync rid {} data_only {}", rid, data_only);
+  let op = poll_fn(move || resources::poll_fsync(rid, data_only));
+  Box::new(op.map(|()| empty_buf()))
+}
+
+fn op_listen(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let cmd_id = base.cmd_id();
+  let inner = base.inner_as_listen().unwrap();
+  let network = inner.network().unwrap();
+  assert_eq!(network, "tcp");
+  let address = inner.address().unwrap();
+
+  if let Err(err) = state.check_permission(Permission::Net, address) {
+    return odd_future(err);
+  }
+
+  Box::new(futures::future::result((move || {
+    let addr = parse_socket_addr(address)?;
+
+    let listener = TcpListener::bind(&addr)?;
+    let local_addr = listener.local_addr()?.to_string();
+    let table = state.resource_table.lock().unwrap();
+    let resource = resources::add_tcp_listener(&table, listener)?;
+
+    let builder = &mut FlatBufferBuilder::new();
+    let local_addr = builder.create_string(&local_addr);
+    let inner = msg::ListenRes::create(
+      builder,
+      &msg::ListenResArgs {
+        rid: resource.rid,
+        local_addr: Some(local_addr),
+      },
+    );
+    Ok(serialize_response(
+      &state,
+      cmd_id,
+      builder,
+      msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::ListenRes,
+        ..Default::default()
+      },
+    ))
+  })()))
+}
+
+// Only literal "ip:port" addresses are supported; host names aren't resolved.
+fn parse_socket_addr(address: &str) -> DenoResult<SocketAddr> {
+  SocketAddr::from_str(address).map_err(|_| {
+    errors::new(
+      ErrorKind::InvalidInput,
+      format!("Invalid address: {}", address),
+    )
+  })
+}
+
+fn new_conn(
+  state: &IsolateState,
+  cmd_id: u32,
+  tcp_stream: TcpStream,
+) -> OpResult {
+  let remote_addr = tcp_stream.peer_addr()?.to_string();
+  let local_addr = tcp_stream.local_addr()?.to_string();
+  let table = state.resource_table.lock().unwrap();
+  let tcp_stream_resource = resources::add_tcp_stream(&table, tcp_stream)?;
+
+  let builder = &mut FlatBufferBuilder::new();
+  let remote_addr = builder.create_string(&remote_addr);
+  let local_addr = builder.create_string(&local_addr);
+  let inner = msg::NewConn::create(
+    builder,
+    &msg::NewConnArgs {
+      rid: tcp_stream_resource.rid,
+      remote_addr: Some(remote_addr),
+      local_addr: Some(local_addr),
+    },
+  );
+  Ok(serialize_response(
+    state,
+    cmd_id,
+    builder,
+    msg::BaseArgs {
+      inner: Some(inner.as_union_value()),
+      inner_type: msg::Any::NewConn,
+      ..Default::default()
+    },
+  ))
+}
+
+fn op_accept(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  if let Err(err) = state.check_permission(Permission::Net, "") {
+    return odd_future(err);
+  }
+
+  let cmd_id = base.cmd_id();
+  let inner = base.inner_as_accept().unwrap();
+  let server_rid = inner.rid();
+
+  match resources::lookup(server_rid) {
+    None => odd_future(errors::bad_resource()),
+    Some(server_resource) => {
+      let op = tokio_util::accept(server_resource).and_then(
+        move |(tcp_stream, _socket_addr)| {
+          new_conn(&state, cmd_id, tcp_stream)
+        },
+      );
+      Box::new(op)
+    }
+  }
+}
+
+fn op_dial(
+  state: Arc<IsolateState>,
+  base: &msg::Base,
+  data: &'static mut [u8],
+) -> Box<Op> {
+  assert_eq!(data.len(), 0);
+  let cmd_id = base.cmd_id();
+  let inner = base.inner_as_dial().unwrap();
+  let network = inner.network().unwrap();
+  assert_eq!(network, "tcp");
+  let address = inner.address().unwrap();
+
+  if let Err(err) = state.check_permission(Permission::Net, address) {
+    return odd_future(err);
+  }
+
+  let addr = match parse_socket_addr(address) {
+    Ok(addr) => addr,
+    Err(err) => return odd_future(err),
+  };
+
+  // Connecting, and reading and writing the connection with op_read and
+  // op_write, is driven by the reactor rather than the blocking pool.
+  let op = TcpStream::connect(&addr)
+    .map_err(|err| err.into())
+    .and_then(move |tcp_stream| new_conn(&state, cmd_id, tcp_stream));
+  Box::new(op)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use deno_dir::CodeFetchOutput;
+  use flatbuffers::{UnionWIPOffset, WIPOffset};
+  use isolate::Direction;
+  use std::collections::{HashMap, HashSet};
+  use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+  use tempfile::TempDir;
+
+  // Adds file to the global resource table, without a limit.
+  fn add_fs_file(file: tokio::fs::File) -> resources::Resource {
+    let table = resources::ResourceTable::default();
+    resources::add_fs_file(&table, file).unwrap()
+  }
+
+  fn finish_msg(
+    builder: &mut FlatBufferBuilder,
+    inner_type: msg::Any,
+    inner: WIPOffset<UnionWIPOffset>,
+  ) -> Vec<u8> {
+    let base = msg::Base::create(
+      builder,
+      &msg::BaseArgs {
+        inner: Some(inner),
+        inner_type,
+        sync: true,
+        ..Default::default()
+      },
+    );
+    msg::finish_base_buffer(builder, base);
+    builder.finished_data().to_vec()
+  }
+
+  fn start_msg() -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::Start::create(builder, &msg::StartArgs::default());
+    finish_msg(builder, msg::Any::Start, inner.as_union_value())
+  }
+
+  fn set_timeout_msg(timeout: f64) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner =
+      msg::SetTimeout::create(builder, &msg::SetTimeoutArgs { timeout });
+    finish_msg(builder, msg::Any::SetTimeout, inner.as_union_value())
+  }
+
+  fn perf_mark_msg(name: &str) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let name = builder.create_string(name);
+    let inner = msg::PerfMark::create(
+      builder,
+      &msg::PerfMarkArgs {
+        name: Some(name),
+        ..Default::default()
+      },
+    );
+    finish_msg(builder, msg::Any::PerfMark, inner.as_union_value())
+  }
+
+  fn code_fetch_msg(module_specifier: &str, containing_file: &str) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let module_specifier = builder.create_string(module_specifier);
+    let containing_file = builder.create_string(containing_file);
+    let inner = msg::CodeFetch::create(
+      builder,
+      &msg::CodeFetchArgs {
+        module_specifier: Some(module_specifier),
+        containing_file: Some(containing_file),
+      },
+    );
+    finish_msg(builder, msg::Any::CodeFetch, inner.as_union_value())
+  }
+
+  fn isolate_status_msg() -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner =
+      msg::IsolateStatus::create(builder, &msg::IsolateStatusArgs {});
+    finish_msg(builder, msg::Any::IsolateStatus, inner.as_union_value())
+  }
+
+  fn hostname_msg() -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::Hostname::create(builder, &msg::HostnameArgs {});
+    finish_msg(builder, msg::Any::Hostname, inner.as_union_value())
+  }
+
+  fn os_release_msg() -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::OsRelease::create(builder, &msg::OsReleaseArgs {});
+    finish_msg(builder, msg::Any::OsRelease, inner.as_union_value())
+  }
+
+  fn realpath_msg(path: &Path) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let path = builder.create_string(&path.to_string_lossy());
+    let inner =
+      msg::Realpath::create(builder, &msg::RealpathArgs { path: Some(path) });
+    finish_msg(builder, msg::Any::Realpath, inner.as_union_value())
+  }
+
+  fn mkdir_msg(path: &Path, recursive: bool) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let path = builder.create_string(&path.to_string_lossy());
+    let inner = msg::Mkdir::create(
+      builder,
+      &msg::MkdirArgs {
+        path: Some(path),
+        mode: 0o755,
+        recursive,
+      },
+    );
+    finish_msg(builder, msg::Any::Mkdir, inner.as_union_value())
+  }
+
+  fn remove_msg(path: &Path, recursive: bool) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let path = builder.create_string(&path.to_string_lossy());
+    let inner = msg::Remove::create(
+      builder,
+      &msg::RemoveArgs {
+        path: Some(path),
+        recursive,
+      },
+    );
+    finish_msg(builder, msg::Any::Remove, inner.as_union_value())
+  }
+
+  fn compile_wasm_msg() -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::CompileWasm::create(builder, &msg::CompileWasmArgs {});
+    finish_msg(builder, msg::Any::CompileWasm, inner.as_union_value())
+  }
+
+  fn read_file_msg(filename: &str) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let filename = builder.create_string(filename);
+    let inner = msg::ReadFile::create(
+      builder,
+      &msg::ReadFileArgs {
+        filename: Some(filename),
+        ..Default::default()
+      },
+    );
+    finish_msg(builder, msg::Any::ReadFile, inner.as_union_value())
+  }
+
+  fn hash_msg(algorithm: &str) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let algorithm = builder.create_string(algorithm);
+    let inner = msg::Hash::create(
+      builder,
+      &msg::HashArgs {
+        algorithm: Some(algorithm),
+      },
+    );
+    finish_msg(builder, msg::Any::Hash, inner.as_union_value())
+  }
+
+  fn read_dir_msg(path: &Path) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let path = builder.create_string(&path.to_string_lossy());
+    let inner =
+      msg::ReadDir::create(builder, &msg::ReadDirArgs { path: Some(path) });
+    finish_msg(builder, msg::Any::ReadDir, inner.as_union_value())
+  }
+
+  fn stat_msg(filename: &Path, lstat: bool) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let filename = builder.create_string(&filename.to_string_lossy());
+    let inner = msg::Stat::create(
+      builder,
+      &msg::StatArgs {
+        filename: Some(filename),
+        lstat,
+      },
+    );
+    finish_msg(builder, msg::Any::Stat, inner.as_union_value())
+  }
+
+  fn write_file_msg(filename: &Path, append: bool, atomic: bool) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let filename = builder.create_string(&filename.to_string_lossy());
+    let inner = msg::WriteFile::create(
+      builder,
+      &msg::WriteFileArgs {
+        filename: Some(filename),
+        perm: 0o666,
+        create: true,
+        append,
+        atomic,
+        ..Default::default()
+      },
+    );
+    finish_msg(builder, msg::Any::WriteFile, inner.as_union_value())
+  }
+
+  fn run_msg(args: &[&str], stdout: msg::ProcessStdio) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let args: Vec<_> = args.iter().map(|a| builder.create_string(a)).collect();
+    let args = builder.create_vector(&args);
+    let inner = msg::Run::create(
+      builder,
+      &msg::RunArgs {
+        args: Some(args),
+        stdout,
+        ..Default::default()
+      },
+    );
+    finish_msg(builder, msg::Any::Run, inner.as_union_value())
+  }
+
+  fn run_status_msg(rid: i32) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::RunStatus::create(builder, &msg::RunStatusArgs { rid });
+    finish_msg(builder, msg::Any::RunStatus, inner.as_union_value())
+  }
+
+  fn dial_msg(address: &str) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let network = builder.create_string("tcp");
+    let address = builder.create_string(address);
+    let inner = msg::Dial::create(
+      builder,
+      &msg::DialArgs {
+        network: Some(network),
+        address: Some(address),
+      },
+    );
+    finish_msg(builder, msg::Any::Dial, inner.as_union_value())
+  }
+
+  fn listen_msg(address: &str) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let network = builder.create_string("tcp");
+    let address = builder.create_string(address);
+    let inner = msg::Listen::create(
+      builder,
+      &msg::ListenArgs {
+        network: Some(network),
+        address: Some(address),
+      },
+    );
+    finish_msg(builder, msg::Any::Listen, inner.as_union_value())
+  }
+
+  fn accept_msg(rid: i32) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::Accept::create(builder, &msg::AcceptArgs { rid });
+    finish_msg(builder, msg::Any::Accept, inner.as_union_value())
+  }
+
+  fn read_msg(rid: i32) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::Read::create(builder, &msg::ReadArgs { rid });
+    finish_msg(builder, msg::Any::Read, inner.as_union_value())
+  }
+
+  fn open_msg(
+    filename: &Path,
+    read: bool,
+    write: bool,
+    create: bool,
+  ) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let filename = builder.create_string(&filename.to_string_lossy());
+    let inner = msg::Open::create(
+      builder,
+      &msg::OpenArgs {
+        filename: Some(filename),
+        perm: 0o600,
+        read,
+        write,
+        create,
+        ..Default::default()
+      },
+    );
+    finish_msg(builder, msg::Any::Open, inner.as_union_value())
+  }
+
+  fn write_msg(rid: i32) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::Write::create(builder, &msg::WriteArgs { rid });
+    finish_msg(builder, msg::Any::Write, inner.as_union_value())
+  }
+
+  fn copy_file_msg(from: &Path, to: &Path) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let from = builder.create_string(&from.to_string_lossy());
+    let to = builder.create_string(&to.to_string_lossy());
+    let inner = msg::CopyFile::create(
+      builder,
+      &msg::CopyFileArgs {
+        from: Some(from),
+        to: Some(to),
+      },
+    );
+    finish_msg(builder, msg::Any::CopyFile, inner.as_union_value())
+  }
+
+  fn chmod_msg(path: &Path, mode: u32) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let path = builder.create_string(&path.to_string_lossy());
+    let inner = msg::Chmod::create(
+      builder,
+      &msg::ChmodArgs {
+        path: Some(path),
+        mode,
+      },
+    );
+    finish_msg(builder, msg::Any::Chmod, inner.as_union_value())
+  }
+
+  fn utime_msg(path: &Path, atime: (i64, u32), mtime: (i64, u32)) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let path = builder.create_string(&path.to_string_lossy());
+    let inner = msg::Utime::create(
+      builder,
+      &msg::UtimeArgs {
+        path: Some(path),
+        atime_sec: atime.0,
+        atime_nsec: atime.1,
+        mtime_sec: mtime.0,
+        mtime_nsec: mtime.1,
+      },
+    );
+    finish_msg(builder, msg::Any::Utime, inner.as_union_value())
+  }
+
+  fn disk_usage_msg(path: &Path) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let path = builder.create_string(&path.to_string_lossy());
+    let inner = msg::DiskUsage::create(
+      builder,
+      &msg::DiskUsageArgs { path: Some(path) },
+    );
+    finish_msg(builder, msg::Any::DiskUsage, inner.as_union_value())
+  }
+
+  fn symlink_msg(
+    oldname: &Path,
+    newname: &Path,
+    kind: Option<&str>,
+  ) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let oldname = builder.create_string(&oldname.to_string_lossy());
+    let newname = builder.create_string(&newname.to_string_lossy());
+    let kind = kind.map(|kind| builder.create_string(kind));
+    let inner = msg::Symlink::create(
+      builder,
+      &msg::SymlinkArgs {
+        oldname: Some(oldname),
+        newname: Some(newname),
+        kind,
+      },
+    );
+    finish_msg(builder, msg::Any::Symlink, inner.as_union_value())
+  }
+
+  fn link_msg(oldname: &Path, newname: &Path) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let oldname = builder.create_string(&oldname.to_string_lossy());
+    let newname = builder.create_string(&newname.to_string_lossy());
+    let inner = msg::Link::create(
+      builder,
+      &msg::LinkArgs {
+        oldname: Some(oldname),
+        newname: Some(newname),
+      },
+    );
+    finish_msg(builder, msg::Any::Link, inner.as_union_value())
+  }
+
+  fn rename_msg(oldpath: &Path, newpath: &Path) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let oldpath = builder.create_string(&oldpath.to_string_lossy());
+    let newpath = builder.create_string(&newpath.to_string_lossy());
+    let inner = msg::Rename::create(
+      builder,
+      &msg::RenameArgs {
+        oldpath: Some(oldpath),
+        newpath: Some(newpath),
+      },
+    );
+    finish_msg(builder, msg::Any::Rename, inner.as_union_value())
+  }
+
+  // Seeking is async.
+  fn seek_msg(rid: i32, offset: i64, whence: msg::SeekMode) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::Seek::create(
+      builder,
+      &msg::SeekArgs {
+        rid,
+        offset,
+        whence,
+      },
+    );
+    let base = msg::Base::create(
+      builder,
+      &msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::Seek,
+        ..Default::default()
+      },
+    );
+    msg::finish_base_buffer(builder, base);
+    builder.finished_data().to_vec()
+  }
+
+  fn fsync_msg(rid: i32, data_only: bool) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner =
+      msg::Fsync::create(builder, &msg::FsyncArgs { rid, data_only });
+    let base = msg::Base::create(
+      builder,
+      &msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::Fsync,
+        ..Default::default()
+      },
+    );
+    msg::finish_base_buffer(builder, base);
+    builder.finished_data().to_vec()
+  }
+
+  // Truncating by rid is async.
+  fn truncate_msg(name: &Path, len: u32, rid: i32) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let name = builder.create_string(&name.to_string_lossy());
+    let inner = msg::Truncate::create(
+      builder,
+      &msg::TruncateArgs {
+        name: Some(name),
+        len,
+        rid,
+      },
+    );
+    let base = msg::Base::create(
+      builder,
+      &msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::Truncate,
+        sync: rid < 0,
+        ..Default::default()
+      },
+    );
+    msg::finish_base_buffer(builder, base);
+    builder.finished_data().to_vec()
+  }
+
+  fn make_temp_file_msg(dir: &Path, prefix: &str) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let dir = builder.create_string(&dir.to_string_lossy());
+    let prefix = builder.create_string(prefix);
+    let inner = msg::MakeTempFile::create(
+      builder,
+      &msg::MakeTempFileArgs {
+        dir: Some(dir),
+        prefix: Some(prefix),
+        ..Default::default()
+      },
+    );
+    finish_msg(builder, msg::Any::MakeTempFile, inner.as_union_value())
+  }
+
+  fn read_text_file_msg(filename: &Path) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let filename = builder.create_string(&filename.to_string_lossy());
+    let inner = msg::ReadTextFile::create(
+      builder,
+      &msg::ReadTextFileArgs {
+        filename: Some(filename),
+      },
+    );
+    finish_msg(builder, msg::Any::ReadTextFile, inner.as_union_value())
+  }
+
+  fn close_msg(rid: i32) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::Close::create(builder, &msg::CloseArgs { rid });
+    finish_msg(builder, msg::Any::Close, inner.as_union_value())
+  }
+
+  fn open_lines_msg(filename: &Path) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let filename = builder.create_string(&filename.to_string_lossy());
+    let inner = msg::OpenLines::create(
+      builder,
+      &msg::OpenLinesArgs {
+        filename: Some(filename),
+      },
+    );
+    finish_msg(builder, msg::Any::OpenLines, inner.as_union_value())
+  }
+
+  fn read_line_msg(rid: i32) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::ReadLine::create(builder, &msg::ReadLineArgs { rid });
+    finish_msg(builder, msg::Any::ReadLine, inner.as_union_value())
+  }
+
+  fn fs_events_msg(paths: &[&Path]) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let paths: Vec<_> = paths
+      .iter()
+      .map(|path| builder.create_string(&path.to_string_lossy()))
+      .collect();
+    let paths = builder.create_vector(&paths);
+    let inner = msg::FsEvents::create(
+      builder,
+      &msg::FsEventsArgs { paths: Some(paths) },
+    );
+    finish_msg(builder, msg::Any::FsEvents, inner.as_union_value())
+  }
+
+  // Async, since the events are streamed.
+  fn fs_events_poll_msg(rid: i32) -> Vec<u8> {
+    let builder = &mut FlatBufferBuilder::new();
+    let inner =
+      msg::FsEventsPoll::create(builder, &msg::FsEventsPollArgs { rid });
+    let base = msg::Base::create(
+      builder,
+      &msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::FsEventsPoll,
+        sync: false,
+        ..Default::default()
+      },
+    );
+    msg::finish_base_buffer(builder, base);
+    builder.finished_data().to_vec()
+  }
+
+  fn empty_data() -> &'static mut [u8] {
+    Box::leak(empty_buf())
+  }
+
+  // Dispatches control, with no data, and runs the op on the runtime until it
+  // completes.
+  fn dispatch_response(isolate: &mut Isolate, control: &[u8]) -> Buf {
+    let (_, op) = dispatch(isolate, control, empty_data());
+    let mut buf = None;
+    tokio_util::init(|| buf = Some(tokio_util::block_on(op).unwrap()));
+    buf.unwrap()
+  }
+
+  // Like dispatch_response(), but returns the error kind of the response.
+  // Ops that succeed with no result respond with an empty Buf, which is
+  // NoError.
+  fn dispatch_error_kind(isolate: &mut Isolate, control: &[u8]) -> ErrorKind {
+    let buf = dispatch_response(isolate, control);
+    if buf.is_empty() {
+      ErrorKind::NoError
+    } else {
+      msg::get_root_as_base(&buf).error_kind()
+    }
+  }
+
+  #[test]
+  fn test_start_versions() {
+    let mut isolate = Isolate::from_flags(Default::default(), vec![], dispatch);
+    let control = start_msg();
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    let res = base.inner_as_start_res().unwrap();
+    assert_eq!(res.deno_version(), Some(version::DENO_VERSION));
+    assert_eq!(res.v8_version(), Some(version::v8_version().as_str()));
+  }
+
+  #[test]
+  fn test_op_panic_is_an_error_response() {
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    // op_read_file asserts that it isn't given a data buffer.
+    let data: &'static mut [u8] = Box::leak(vec![1u8].into_boxed_slice());
+    let (is_sync, op) =
+      dispatch(&mut isolate, &read_file_msg("package.json"), data);
+    assert!(is_sync);
+    tokio_util::init(|| {
+      let buf = tokio_util::block_on(op).unwrap();
+      let base = msg::get_root_as_base(&buf);
+      assert_eq!(base.error_kind(), ErrorKind::OpPanicked);
+    });
+  }
+
+  // Serves modules from a map of module name to source code.
+  struct MemoryLoader {
+    modules: HashMap<String, String>,
+  }
+
+  impl ModuleLoader for MemoryLoader {
+    fn resolve(
+      &self,
+      module_specifier: &str,
+      _containing_file: &str,
+    ) -> DenoResult<(String, String)> {
+      let name = format!("mem:{}", module_specifier);
+      Ok((name.clone(), name))
+    }
+
+    fn load(
+      &self,
+      module_name: &str,
+      filename: &str,
+    ) -> DenoResult<CodeFetchOutput> {
+      match self.modules.get(module_name) {
+        Some(source_code) => Ok(CodeFetchOutput {
+          module_name: module_name.to_string(),
+          filename: filename.to_string(),
+          source_code: source_code.clone(),
+          maybe_output_code: None,
+        }),
+        None => {
+          Err(errors::new(ErrorKind::NotFound, module_name.to_string()))
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn test_module_loader() {
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    let mut modules = HashMap::new();
+    modules.insert("mem:foo.ts".to_string(), "export const x = 1;".to_string());
+    isolate.set_module_loader(Box::new(MemoryLoader { modules }));
+
+    let control = code_fetch_msg("foo.ts", ".");
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::NoError);
+    let res = base.inner_as_code_fetch_res().unwrap();
+    assert_eq!(res.module_name(), Some("mem:foo.ts"));
+    assert_eq!(res.source_code(), Some("export const x = 1;"));
+    assert_eq!(res.output_code(), None);
+
+    let control = code_fetch_msg("bar.ts", ".");
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::NotFound);
+    assert!(base.error().unwrap().contains("Cannot resolve module"));
+
+    // Nothing went through the DenoDir.
+    assert_eq!(isolate.state.dir.cache_stats().modules_resolved, 0);
+  }
+
+  #[test]
+  fn test_specifier_rewrite() {
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    let mut modules = HashMap::new();
+    let react = "export const version = 16;";
+    modules.insert("mem:./vendor/react.ts".to_string(), react.to_string());
+    isolate.set_module_loader(Box::new(MemoryLoader { modules }));
+    let referrers = Arc::new(Mutex::new(Vec::new()));
+    let referrers_ = referrers.clone();
+    isolate.set_specifier_rewrite(Box::new(move |specifier, referrer| {
+      referrers_.lock().unwrap().push(referrer.to_string());
+      if specifier == "react" {
+        Some("./vendor/react.ts".to_string())
+      } else {
+        None
+      }
+    }));
+
+    let control = code_fetch_msg("react", "mem:./main.ts");
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::NoError);
+    let res = base.inner_as_code_fetch_res().unwrap();
+    assert_eq!(res.module_name(), Some("mem:./vendor/react.ts"));
+    assert_eq!(res.source_code(), Some(react));
+
+    // Other specifiers are resolved as they are.
+    let control = code_fetch_msg("./vendor/react.ts", "mem:./main.ts");
+    assert_eq!(dispatch_error_kind(&mut isolate, &control), ErrorKind::NoError);
+    let control = code_fetch_msg("preact", "mem:./main.ts");
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::NotFound
+    );
+
+    assert_eq!(*referrers.lock().unwrap(), vec!["mem:./main.ts"; 3]);
+  }
+
+  #[test]
+  fn test_on_module_load() {
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    let mut modules = HashMap::new();
+    let main = "import { b } from './b.ts';\nimport './c.ts';";
+    modules.insert("mem:./main.ts".to_string(), main.to_string());
+    let b = "export { c as b } from './c.ts';";
+    modules.insert("mem:./b.ts".to_string(), b.to_string());
+    modules.insert("mem:./c.ts".to_string(), "export const c = 1;".to_string());
+    isolate.set_module_loader(Box::new(MemoryLoader { modules }));
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let log_ = log.clone();
+    isolate.set_on_module_load(Box::new(move |module_name, imports| {
+      log_
+        .lock()
+        .unwrap()
+        .push((module_name.to_string(), imports.to_vec()));
+    }));
+
+    // Fetched in the order the compiler would: c.ts is imported twice.
+    for (specifier, containing_file) in &[
+      ("./main.ts", "."),
+      ("./b.ts", "mem:./main.ts"),
+      ("./c.ts", "mem:./b.ts"),
+      ("./c.ts", "mem:./main.ts"),
+    ] {
+      let control = code_fetch_msg(specifier, containing_file);
+      assert_eq!(
+        dispatch_error_kind(&mut isolate, &control),
+        ErrorKind::NoError
+      );
+    }
+    // Failed fetches aren't loads.
+    let control = code_fetch_msg("./missing.ts", "mem:./main.ts");
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    op.wait().unwrap();
+
+    let log = log.lock().unwrap();
+    assert_eq!(
+      *log,
+      vec![
+        (
+          "mem:./main.ts".to_string(),
+          vec!["./b.ts".to_string(), "./c.ts".to_string()]
+        ),
+        ("mem:./b.ts".to_string(), vec!["./c.ts".to_string()]),
+        ("mem:./c.ts".to_string(), vec![]),
+      ]
+    );
+  }
+
+  #[test]
+  fn test_isolate_status() {
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    dispatch(&mut isolate, &perf_mark_msg("m"), empty_data());
+    let control = isolate_status_msg();
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    let json = base.inner_as_isolate_status_res().unwrap().json().unwrap();
+    assert!(json.contains("\"pending_ops\":0,"));
+    // The bytes of the perf mark are included.
+    let perf_mark = format!("\"{}\":", msg::Any::PerfMark as u32);
+    assert!(json.contains(&perf_mark));
+  }
+
+  #[test]
+  fn test_realpath() {
+    let root = TempDir::new().unwrap();
+    let outside = TempDir::new().unwrap();
+    let real_root = fs::canonicalize(root.path()).unwrap();
+    fs::create_dir(root.path().join("dir")).unwrap();
+    fs::write(root.path().join("file"), b"hello").unwrap();
+    fs::write(outside.path().join("secret"), b"secret").unwrap();
+    std::os::unix::fs::symlink(
+      outside.path().join("secret"),
+      root.path().join("escape"),
+    ).unwrap();
+
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      read_roots: vec![root.path().to_path_buf()],
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+
+    let control = realpath_msg(&root.path().join("dir/../file"));
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::NoError);
+    let res = base.inner_as_realpath_res().unwrap();
+    assert_eq!(Path::new(res.path().unwrap()), real_root.join("file"));
+
+    let control = realpath_msg(&root.path().join("escape"));
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+
+    // A path that can't be sent back as a string.
+    use std::os::unix::ffi::OsStrExt;
+    let not_unicode = root.path().join(std::ffi::OsStr::from_bytes(b"\xff"));
+    fs::write(&not_unicode, b"hello").unwrap();
+    std::os::unix::fs::symlink(&not_unicode, root.path().join("link")).unwrap();
+    let control = realpath_msg(&root.path().join("link"));
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::InvalidData
+    );
+  }
+
+  #[test]
+  fn test_mkdir() {
+    let root = TempDir::new().unwrap();
+    let dir = root.path().join("dir");
+    let nested = root.path().join("a/b/c");
+
+    let flags = flags::DenoFlags {
+      allow_write: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let mut mkdir = |path: &Path, recursive: bool| {
+      dispatch_error_kind(&mut isolate, &mkdir_msg(path, recursive))
+    };
+
+    assert_eq!(mkdir(&dir, false), ErrorKind::NoError);
+    assert!(dir.is_dir());
+    assert_eq!(mkdir(&dir, false), ErrorKind::AlreadyExists);
+    assert_eq!(mkdir(&dir, true), ErrorKind::NoError);
+
+    assert_eq!(mkdir(&nested, false), ErrorKind::NotFound);
+    assert_eq!(mkdir(&nested, true), ErrorKind::NoError);
+    assert!(nested.is_dir());
+
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    let denied = root.path().join("denied");
+    let control = mkdir_msg(&denied, false);
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+    assert!(!denied.exists());
+  }
+
+  #[test]
+  fn test_remove() {
+    let root = TempDir::new().unwrap();
+    let outside = TempDir::new().unwrap();
+    let file = root.path().join("file");
+    let dir = root.path().join("dir");
+    fs::write(&file, b"hello").unwrap();
+    fs::create_dir_all(dir.join("subdir")).unwrap();
+    fs::write(dir.join("subdir/file"), b"hello").unwrap();
+    fs::create_dir(outside.path().join("dir")).unwrap();
+    fs::write(outside.path().join("dir/file"), b"hello").unwrap();
+
+    let flags = flags::DenoFlags {
+      allow_write: true,
+      write_roots: vec![root.path().to_path_buf()],
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let mut remove = |path: &Path, recursive: bool| {
+      dispatch_error_kind(&mut isolate, &remove_msg(path, recursive))
+    };
+
+    assert_eq!(remove(&file, false), ErrorKind::NoError);
+    assert!(!file.exists());
+
+    // A non-empty directory is only removed when asked to recurse.
+    assert_eq!(remove(&dir, false), ErrorKind::Other);
+    assert!(dir.join("subdir/file").exists());
+    assert_eq!(remove(&dir, true), ErrorKind::NoError);
+    assert!(!dir.exists());
+
+    let outside_dir = outside.path().join("dir");
+    assert_eq!(remove(&outside_dir, true), ErrorKind::PermissionDenied);
+    assert!(outside_dir.join("file").exists());
+
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    let control = remove_msg(&outside_dir, true);
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+    assert!(outside_dir.join("file").exists());
+  }
+
+  #[test]
+  fn test_system_info() {
+    let mut isolate = Isolate::from_flags(Default::default(), vec![], dispatch);
+    isolate.load_op_module(SystemInfoOps);
+    let hostname_id = msg::Any::Hostname as u32;
+    assert_eq!(isolate.registered_op_name(hostname_id), Some("Hostname"));
+    for control in &[hostname_msg(), os_release_msg()] {
+      let (_, op) = dispatch(&mut isolate, control, empty_data());
+      let buf = op.wait().unwrap();
+      let base = msg::get_root_as_base(&buf);
+      assert_eq!(base.error_kind(), ErrorKind::PermissionDenied);
+    }
+
+    let flags = flags::DenoFlags {
+      allow_env: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    isolate.load_op_module(SystemInfoOps);
+    let (_, op) = dispatch(&mut isolate, &hostname_msg(), empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    let hostname = base.inner_as_hostname_res().unwrap().hostname().unwrap();
+    assert!(!hostname.is_empty());
+    let (_, op) = dispatch(&mut isolate, &os_release_msg(), empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    let release = base.inner_as_os_release_res().unwrap().release().unwrap();
+    assert!(!release.is_empty());
+  }
+
+  // Counts what it hands out and gets back.
+  struct CountingAllocator {
+    allocs: Arc<AtomicUsize>,
+    frees: Arc<AtomicUsize>,
+  }
+
+  impl isolate::BufAllocator for CountingAllocator {
+    fn alloc(&self, len: usize) -> Buf {
+      self.allocs.fetch_add(1, Ordering::SeqCst);
+      vec![0; len].into_boxed_slice()
+    }
+
+    fn free(&self, buf: Buf) {
+      self.frees.fetch_add(1, Ordering::SeqCst);
+      drop(buf);
+    }
+  }
+
+  #[test]
+  fn test_buf_allocator() {
+    let allocs = Arc::new(AtomicUsize::new(0));
+    let frees = Arc::new(AtomicUsize::new(0));
+    let mut isolate = Isolate::from_flags(Default::default(), vec![], dispatch);
+    isolate.set_buf_allocator(Box::new(CountingAllocator {
+      allocs: allocs.clone(),
+      frees: frees.clone(),
+    }));
+    let control: Vec<String> =
+      isolate_status_msg().iter().map(|b| b.to_string()).collect();
+    let source = format!(
+      "const res = libdeno.send(new Uint8Array([{}]));
+      if (!(res instanceof Uint8Array)) throw Error('got ' + res);",
+      control.join(",")
+    );
+    tokio_util::init(|| {
+      isolate.execute("status.js", &source).expect("execute error");
+    });
+    // The response was allocated by it, and given back once JS had a copy.
+    assert_eq!(allocs.load(Ordering::SeqCst), 1);
+    assert_eq!(frees.load(Ordering::SeqCst), 1);
+  }
+
+  #[test]
+  fn test_permission_prompt() {
+    let dir = TempDir::new().unwrap();
+    let allowed = dir.path().join("allowed");
+    let denied = dir.path().join("denied");
+    fs::write(&allowed, b"hello").unwrap();
+    fs::write(&denied, b"hello").unwrap();
+
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    assert!(!isolate.state.flags.allow_read);
+    let allowed_str = String::from(allowed.to_str().unwrap());
+    isolate.set_permission_prompt(Box::new(move |perm, what| {
+      perm == Permission::Read && what == allowed_str
+    }));
+
+    let control = read_file_msg(allowed.to_str().unwrap());
+    assert_eq!(dispatch_error_kind(&mut isolate, &control), ErrorKind::NoError);
+
+    let control = read_file_msg(denied.to_str().unwrap());
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+  }
+
+  #[test]
+  fn test_grant_revoke_permission() {
+    let dir = TempDir::new().unwrap();
+    let filename = dir.path().join("file.txt");
+    fs::write(&filename, b"hello").unwrap();
+    let control = read_file_msg(filename.to_str().unwrap());
+
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    let read_file = |isolate: &mut Isolate| {
+      let (_, op) = dispatch(isolate, &control, empty_data());
+      let buf = op.wait().unwrap();
+      msg::get_root_as_base(&buf).error_kind()
+    };
+    assert!(!isolate.permissions().read);
+    assert_eq!(read_file(&mut isolate), ErrorKind::PermissionDenied);
+
+    isolate.grant_permission(Permission::Read);
+    assert!(isolate.permissions().read);
+    assert!(!isolate.permissions().write);
+    assert_eq!(read_file(&mut isolate), ErrorKind::NoError);
+
+    isolate.revoke_permission(Permission::Read);
+    assert!(!isolate.permissions().read);
+    assert_eq!(read_file(&mut isolate), ErrorKind::PermissionDenied);
+    // The flags stay as they were.
+    assert!(!isolate.state.flags.allow_read);
+  }
+
+  #[test]
+  fn test_hash() {
+    fn hex_digest(isolate: &mut Isolate, algorithm: &str) -> String {
+      let data = Box::leak(b"abc".to_vec().into_boxed_slice());
+      let (_, op) = dispatch(isolate, &hash_msg(algorithm), data);
+      let buf = op.wait().unwrap();
+      let base = msg::get_root_as_base(&buf);
+      assert_eq!(base.error_kind(), ErrorKind::NoError);
+      let res = base.inner_as_hash_res().unwrap();
+      let digest = res.digest().unwrap();
+      digest.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    assert_eq!(
+      hex_digest(&mut isolate, "sha256"),
+      "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+    assert_eq!(
+      hex_digest(&mut isolate, "sha1"),
+      "a9993e364706816aba3e25717850c26c9cd0d89d"
+    );
+
+    let (_, op) = dispatch(&mut isolate, &hash_msg("md5"), empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::InvalidInput);
+    assert_eq!(base.error().unwrap(), "Unsupported hash algorithm: md5");
+  }
+
+  #[test]
+  fn test_read_dir() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("a.txt"), b"hello").unwrap();
+    fs::create_dir(dir.path().join("subdir")).unwrap();
+    std::os::unix::fs::symlink("a.txt", dir.path().join("link")).unwrap();
+
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let control = read_dir_msg(dir.path());
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::NoError);
+    let entries = base.inner_as_read_dir_res().unwrap().entries().unwrap();
+    let mut listing: Vec<_> = (0..entries.len())
+      .map(|i| {
+        let entry = entries.get(i);
+        let name = String::from(entry.name().unwrap());
+        (name, entry.is_file(), entry.is_symlink())
+      }).collect();
+    listing.sort();
+    assert_eq!(
+      listing,
+      vec![
+        (String::from("a.txt"), true, false),
+        (String::from("link"), false, true),
+        (String::from("subdir"), false, false),
+      ]
+    );
+    let a = (0..entries.len())
+      .map(|i| entries.get(i))
+      .find(|entry| entry.name() == Some("a.txt"))
+      .unwrap();
+    assert_eq!(a.len(), 5);
+
+    // A file where a directory is expected.
+    let control = read_dir_msg(&dir.path().join("a.txt"));
+    assert_eq!(dispatch_error_kind(&mut isolate, &control), ErrorKind::Other);
+
+    // An entry whose name can't be sent back as a string.
+    use std::os::unix::ffi::OsStrExt;
+    let odd = dir.path().join("odd");
+    fs::create_dir(&odd).unwrap();
+    fs::write(odd.join(std::ffi::OsStr::from_bytes(b"\xff")), b"").unwrap();
+    let control = read_dir_msg(&odd);
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::InvalidData
+    );
+
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+  }
+
+  #[test]
+  fn test_stat() {
+    let dir = TempDir::new().unwrap();
+    fs::write(dir.path().join("a.txt"), b"hello").unwrap();
+    std::os::unix::fs::symlink("a.txt", dir.path().join("link")).unwrap();
+
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let mut stat = |filename: &Path, lstat: bool| {
+      let control = stat_msg(filename, lstat);
+      let (_, op) = dispatch(&mut isolate, &control, empty_data());
+      op.wait().unwrap()
+    };
+
+    let buf = stat(&dir.path().join("a.txt"), false);
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::NoError);
+    let res = base.inner_as_stat_res().unwrap();
+    assert!(res.is_file());
+    assert!(!res.is_symlink());
+    assert_eq!(res.len(), 5);
+
+    // stat follows the link, lstat doesn't.
+    let buf = stat(&dir.path().join("link"), false);
+    let res = msg::get_root_as_base(&buf).inner_as_stat_res().unwrap();
+    assert!(res.is_file());
+    assert!(!res.is_symlink());
+    let buf = stat(&dir.path().join("link"), true);
+    let res = msg::get_root_as_base(&buf).inner_as_stat_res().unwrap();
+    assert!(!res.is_file());
+    assert!(res.is_symlink());
+
+    let buf = stat(&dir.path().join("missing"), false);
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::NotFound);
+
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    let control = stat_msg(&dir.path().join("a.txt"), false);
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+  }
+
+  #[test]
+  fn test_write_file() {
+    fn write(
+      isolate: &mut Isolate,
+      filename: &Path,
+      contents: &str,
+      append: bool,
+      atomic: bool,
+    ) -> ErrorKind {
+      let control = write_file_msg(filename, append, atomic);
+      let data = Box::leak(contents.as_bytes().to_vec().into_boxed_slice());
+      let (_, op) = dispatch(isolate, &control, data);
+      let buf = op.wait().unwrap();
+      msg::get_root_as_base(&buf).error_kind()
+    }
+
+    let dir = TempDir::new().unwrap();
+    let filename = dir.path().join("test.txt");
+    let flags = flags::DenoFlags {
+      allow_write: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+
+    let r = write(&mut isolate, &filename, "hello", false, false);
+    assert_eq!(r, ErrorKind::NoError);
+    assert_eq!(fs::read_to_string(&filename).unwrap(), "hello");
+
+    let r = write(&mut isolate, &filename, " world", true, false);
+    assert_eq!(r, ErrorKind::NoError);
+    assert_eq!(fs::read_to_string(&filename).unwrap(), "hello world");
+
+    let r = write(&mut isolate, &filename, "replaced", false, true);
+    assert_eq!(r, ErrorKind::NoError);
+    assert_eq!(fs::read_to_string(&filename).unwrap(), "replaced");
+
+    // Renaming the temporary file over a directory fails, and the temporary
+    // file must not be left behind.
+    let subdir = dir.path().join("subdir");
+    fs::create_dir(&subdir).unwrap();
+    let r = write(&mut isolate, &subdir, "partial", false, true);
+    assert!(r != ErrorKind::NoError);
+    assert!(subdir.is_dir());
+    let mut names: Vec<_> = fs::read_dir(dir.path())
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+      .collect();
+    names.sort();
+    assert_eq!(names, vec!["subdir", "test.txt"]);
+
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    let denied = dir.path().join("denied.txt");
+    let r = write(&mut isolate, &denied, "hello", false, false);
+    assert_eq!(r, ErrorKind::PermissionDenied);
+    assert!(!denied.exists());
+  }
+
+  #[test]
+  fn test_seed_metrics() {
+    let op_id = msg::Any::PerfMark as u32;
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv.clone(), dispatch);
+    for _ in 0..2 {
+      let (_, op) = dispatch(&mut isolate, &perf_mark_msg("m"), empty_data());
+      op.wait().unwrap();
+    }
+    let snapshot = isolate.export_metrics();
+    let two_marks = snapshot.op_bytes[&op_id];
+    assert!(two_marks > 0);
+
+    let mut isolate = Isolate::new(argv, dispatch);
+    isolate.seed_metrics(&snapshot);
+    assert_eq!(isolate.export_metrics(), snapshot);
+    let (_, op) = dispatch(&mut isolate, &perf_mark_msg("m"), empty_data());
+    op.wait().unwrap();
+    // Every mark is the same size.
+    assert_eq!(isolate.state.metrics.op_bytes(op_id) * 2, two_marks * 3);
+  }
+
+  #[test]
+  fn test_compile_wasm() {
+    // (module (func (export "add") (param i32 i32) (result i32)
+    //   get_local 0 get_local 1 i32.add))
+    let add: Vec<u8> = vec![
+      0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+      0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f, // types
+      0x03, 0x02, 0x01, 0x00, // functions
+      0x07, 0x07, 0x01, 0x03, 0x61, 0x64, 0x64, 0x00, 0x00, // exports
+      0x0a, 0x09, 0x01, 0x07, 0x00, 0x20, 0x00, 0x20, 0x01, 0x6a, 0x0b, // code
+    ];
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+
+    let data = Box::leak(add.into_boxed_slice());
+    let (is_sync, op) = dispatch(&mut isolate, &compile_wasm_msg(), data);
+    assert!(is_sync);
+    assert_eq!(op.wait().unwrap().len(), 0);
+
+    let invalid = vec![0x00, 0x61, 0x73, 0x6d, 0xff];
+    let data = Box::leak(invalid.into_boxed_slice());
+    let (_, op) = dispatch(&mut isolate, &compile_wasm_msg(), data);
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::InvalidWasm);
+    assert!(base.error().unwrap().contains("CompileError"));
+  }
+
+  #[test]
+  fn test_op_byte_quota() {
+    let op_id = msg::Any::PerfMark as u32;
+    let mut flags = flags::DenoFlags::default();
+    flags.op_byte_quotas.insert(op_id, 200);
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+
+    let mut results = Vec::new();
+    for _ in 0..10 {
+      let (_, op) = dispatch(&mut isolate, &perf_mark_msg("m"), empty_data());
+      let buf = op.wait().unwrap();
+      let base = msg::get_root_as_base(&buf);
+      results.push(base.error_kind());
+    }
+    // Some marks succeed, then the budget is used up and all fail.
+    let ok = results
+      .iter()
+      .take_while(|kind| **kind == ErrorKind::NoError)
+      .count();
+    assert!(ok > 0 && ok < results.len());
+    assert!(
+      results[ok..]
+        .iter()
+        .all(|kind| *kind == ErrorKind::QuotaExceeded)
+    );
+    assert!(isolate.state.metrics.op_bytes(op_id) > 0);
+    // Other ops are not limited.
+    assert_eq!(isolate.state.metrics.op_bytes(msg::Any::PerfNow as u32), 0);
+  }
+
+  #[test]
+  fn test_catch_panics() {
+    let op = futures::future::lazy(|| -> DenoResult<Buf> { panic!("boom") });
+    let op = catch_panics(Box::new(op));
+    let err = op.wait().unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::OpPanicked);
+    assert!(format!("{}", err).contains("boom"));
+  }
+
+  // Responds with [1] if the op timed out.
+  fn dispatch_never_completes(
+    _isolate: &mut Isolate,
+    _control: &[u8],
+    _data: &'static mut [u8],
+  ) -> (bool, Box<Op>) {
+    let never = futures::future::empty::<Buf, DenoError>();
+    let op = with_op_timeout(Duration::from_millis(10), Box::new(never))
+      .or_else(|err| -> DenoResult<Buf> {
+        let timed_out = err.kind() == ErrorKind::OpTimedOut;
+        let buf: Buf = Box::new([timed_out as u8]);
+        Ok(buf)
+      });
+    (false, Box::new(op))
+  }
+
+  #[test]
+  fn test_with_op_timeout() {
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch_never_completes);
+    tokio_util::init(|| {
+      isolate
+        .execute(
+          "timeout.js",
+          r#"
+          libdeno.recv((msg) => { timedOut = msg[0] === 1; });
+          libdeno.send(new Uint8Array([]));
+        "#,
+        ).expect("execute error");
+      // event_loop() only returns once there are no pending ops.
+      isolate.event_loop().unwrap();
+    });
+    isolate
+      .execute("check.js", "if (!timedOut) throw Error('no timeout');")
+      .expect("execute error");
+  }
+
+  fn never_completes(
+    _isolate: &mut Isolate,
+    _control: &[u8],
+    _data: &'static mut [u8],
+  ) -> Box<Op> {
+    Box::new(futures::future::empty())
+  }
+
+  #[test]
+  fn test_op_response_timeout() {
+    let flags = flags::DenoFlags {
+      op_response_timeout: Some(Duration::from_millis(10)),
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    isolate.register_op(msg::Any::PerfNow as u32, None, never_completes);
+    let responses = Arc::new(Mutex::new(Vec::new()));
+    let responses_ = responses.clone();
+    isolate.set_inspector(Box::new(move |direction, _req_id, buf| {
+      if direction == Direction::Outgoing {
+        responses_.lock().unwrap().push(buf.to_vec());
+      }
+    }));
+
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::PerfNow::create(builder, &msg::PerfNowArgs {});
+    let base = msg::Base::create(
+      builder,
+      &msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::PerfNow,
+        cmd_id: 7,
+        sync: false,
+        ..Default::default()
+      },
+    );
+    msg::finish_base_buffer(builder, base);
+    let control: Vec<String> =
+      builder.finished_data().iter().map(|b| b.to_string()).collect();
+    let source = format!(
+      "libdeno.recv(() => {{}});
+      libdeno.send(new Uint8Array([{}]));",
+      control.join(",")
+    );
+    tokio_util::init(|| {
+      isolate.execute("stuck.js", &source).expect("execute error");
+      // Returns rather than waiting for the op forever.
+      isolate.event_loop().unwrap();
+    });
+
+    let responses = responses.lock().unwrap();
+    assert_eq!(responses.len(), 1);
+    let base = msg::get_root_as_base(&responses[0]);
+    assert_eq!(base.cmd_id(), 7);
+    assert_eq!(base.error_kind(), ErrorKind::OpTimedOut);
+  }
+
+  #[test]
+  fn test_copy_file() {
+    fn copy_file(isolate: &mut Isolate, from: &Path, to: &Path) -> ErrorKind {
+      dispatch_error_kind(isolate, &copy_file_msg(from, to))
+    }
+
+    let dir = TempDir::new().unwrap();
+    let from = dir.path().join("from.txt");
+    let to = dir.path().join("to.txt");
+    fs::write(&from, b"hello world").unwrap();
+    fs::set_permissions(&from, fs::Permissions::from_mode(0o640)).unwrap();
+
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      allow_write: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    assert_eq!(copy_file(&mut isolate, &from, &to), ErrorKind::NoError);
+    assert_eq!(fs::read(&to).unwrap(), b"hello world");
+    let mode = fs::metadata(&to).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+
+    let missing = dir.path().join("missing.txt");
+    assert_eq!(copy_file(&mut isolate, &missing, &to), ErrorKind::NotFound);
+
+    // Reading the source is allowed, writing the destination isn't.
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let denied = dir.path().join("denied.txt");
+    assert_eq!(
+      copy_file(&mut isolate, &from, &denied),
+      ErrorKind::PermissionDenied
+    );
+    assert!(!denied.exists());
+
+    // The source must be in the read roots, and the destination in the write
+    // roots.
+    let outside = TempDir::new().unwrap();
+    let outside_file = outside.path().join("outside.txt");
+    fs::write(&outside_file, b"hello").unwrap();
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      allow_write: true,
+      read_roots: vec![dir.path().to_path_buf()],
+      write_roots: vec![dir.path().to_path_buf()],
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let copied = dir.path().join("copied.txt");
+    assert_eq!(copy_file(&mut isolate, &from, &copied), ErrorKind::NoError);
+    let escaped = outside.path().join("escaped.txt");
+    assert_eq!(
+      copy_file(&mut isolate, &from, &escaped),
+      ErrorKind::PermissionDenied
+    );
+    assert!(!escaped.exists());
+    let leaked = dir.path().join("leaked.txt");
+    assert_eq!(
+      copy_file(&mut isolate, &outside_file, &leaked),
+      ErrorKind::PermissionDenied
+    );
+    assert!(!leaked.exists());
+  }
+
+  #[test]
+  fn test_chmod() {
+    fn chmod(isolate: &mut Isolate, path: &Path, mode: u32) -> ErrorKind {
+      dispatch_error_kind(isolate, &chmod_msg(path, mode))
+    }
+
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("file.txt");
+    fs::write(&file, b"hello").unwrap();
+
+    let flags = flags::DenoFlags {
+      allow_write: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    assert_eq!(chmod(&mut isolate, &file, 0o444), ErrorKind::NoError);
+    let permissions = fs::metadata(&file).unwrap().permissions();
+    if cfg!(unix) {
+      assert!(permissions.readonly());
+      assert_eq!(permissions.mode() & 0o777, 0o444);
+      // Only the permission bits are set.
+      assert_eq!(chmod(&mut isolate, &file, 0o4640), ErrorKind::NoError);
+      let mode = fs::metadata(&file).unwrap().permissions().mode();
+      assert_eq!(mode & 0o7777, 0o640);
+    } else {
+      assert!(!permissions.readonly());
+    }
+
+    let missing = dir.path().join("missing.txt");
+    assert_eq!(chmod(&mut isolate, &missing, 0o644), ErrorKind::NotFound);
+
+    let mut isolate =
+      Isolate::from_flags(flags::DenoFlags::default(), vec![], dispatch);
+    assert_eq!(
+      chmod(&mut isolate, &file, 0o777),
+      ErrorKind::PermissionDenied
+    );
+  }
+
+  #[test]
+  fn test_utime() {
+    fn utime(
+      isolate: &mut Isolate,
+      path: &Path,
+      atime: (i64, u32),
+      mtime: (i64, u32),
+    ) -> ErrorKind {
+      dispatch_error_kind(isolate, &utime_msg(path, atime, mtime))
+    }
+
+    let dir = TempDir::new().unwrap();
+    let file = dir.path().join("file.txt");
+    fs::write(&file, b"hello").unwrap();
+
+    let flags = flags::DenoFlags {
+      allow_write: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let atime = (1_000_000_000, 250_000_000);
+    let mtime = (1_500_000_000, 0);
+    if cfg!(unix) {
+      assert_eq!(utime(&mut isolate, &file, atime, mtime), ErrorKind::NoError);
+      let metadata = fs::metadata(&file).unwrap();
+      let accessed = metadata.accessed().unwrap();
+      let modified = metadata.modified().unwrap();
+      assert_eq!(
+        accessed.duration_since(UNIX_EPOCH).unwrap(),
+        Duration::new(1_000_000_000, 250_000_000)
+      );
+      assert_eq!(
+        modified.duration_since(UNIX_EPOCH).unwrap(),
+        Duration::from_secs(1_500_000_000)
+      );
+    }
+
+    let missing = dir.path().join("missing.txt");
+    assert_eq!(
+      utime(&mut isolate, &missing, atime, mtime),
+      ErrorKind::NotFound
+    );
+    assert_eq!(
+      utime(&mut isolate, &file, (0, 1_000_000_000), mtime),
+      ErrorKind::InvalidInput
+    );
+
+    let mut isolate =
+      Isolate::from_flags(flags::DenoFlags::default(), vec![], dispatch);
+    assert_eq!(
+      utime(&mut isolate, &file, atime, mtime),
+      ErrorKind::PermissionDenied
+    );
+  }
+
+  #[test]
+  fn test_set_exit_code() {
+    let mut isolate =
+      Isolate::from_flags(flags::DenoFlags::default(), vec![], dispatch);
+    let builder = &mut FlatBufferBuilder::new();
+    let inner =
+      msg::SetExitCode::create(builder, &msg::SetExitCodeArgs { code: 3 });
+    let control =
+      finish_msg(builder, msg::Any::SetExitCode, inner.as_union_value());
+    let (is_sync, op) = dispatch(&mut isolate, &control, empty_data());
+    assert!(is_sync);
+    assert!(op.wait().unwrap().is_empty());
+    assert_eq!(isolate.exit_code(), 3);
+  }
+
+  #[test]
+  fn test_record() {
+    fn op_double(
+      _isolate: &mut Isolate,
+      request: &codec::Record,
+    ) -> DenoResult<codec::Record> {
+      let n: i64 = request["n"].parse().unwrap();
+      let mut response = codec::Record::new();
+      response.insert("n".to_string(), (2 * n).to_string());
+      Ok(response)
+    }
+
+    let mut isolate =
+      Isolate::from_flags(flags::DenoFlags::default(), vec![], dispatch);
+    isolate.set_codec(Box::new(codec::JsonCodec));
+    isolate.register_record_op("double", op_double);
+    let builder = &mut FlatBufferBuilder::new();
+    let inner = msg::Record::create(builder, &msg::RecordArgs {});
+    let control = finish_msg(builder, msg::Any::Record, inner.as_union_value());
+    let request = br#"{"op":"double","n":"21"}"#.to_vec();
+    let data = Box::leak(request.into_boxed_slice());
+    let (is_sync, op) = dispatch(&mut isolate, &control, data);
+    assert!(is_sync);
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    let res = base.inner_as_record_res().unwrap();
+    assert_eq!(res.data().unwrap(), br#"{"n":"42"}"#);
+  }
+
+  #[test]
+  fn test_disk_usage() {
+    fn disk_usage(
+      isolate: &mut Isolate,
+      path: &Path,
+    ) -> Result<(u64, u64), ErrorKind> {
+      let buf = dispatch_response(isolate, &disk_usage_msg(path));
+      let base = msg::get_root_as_base(&buf);
+      match base.inner_as_disk_usage_res() {
+        Some(res) => Ok((res.available(), res.total())),
+        None => Err(base.error_kind()),
+      }
+    }
+
+    let dir = TempDir::new().unwrap();
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    if cfg!(unix) {
+      let (available, total) = disk_usage(&mut isolate, dir.path()).unwrap();
+      assert!(total > 0);
+      assert!(available <= total);
+    }
+    let missing = dir.path().join("missing");
+    assert_eq!(disk_usage(&mut isolate, &missing), Err(ErrorKind::NotFound));
+
+    let mut isolate =
+      Isolate::from_flags(flags::DenoFlags::default(), vec![], dispatch);
+    assert_eq!(
+      disk_usage(&mut isolate, dir.path()),
+      Err(ErrorKind::PermissionDenied)
+    );
+  }
+
+  #[test]
+  fn test_symlink_and_link() {
+    let dir = TempDir::new().unwrap();
+    let target = dir.path().join("target.txt");
+    fs::write(&target, b"hello").unwrap();
+
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      allow_write: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let symlink = dir.path().join("symlink");
+    let control = symlink_msg(&target, &symlink, None);
+    assert_eq!(dispatch_error_kind(&mut isolate, &control), ErrorKind::NoError);
+    let metadata = fs::symlink_metadata(&symlink).unwrap();
+    assert!(metadata.file_type().is_symlink());
+    assert_eq!(fs::read_link(&symlink).unwrap(), target);
+
+    let control = symlink_msg(&target, &dir.path().join("bad"), Some("pipe"));
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::InvalidInput
+    );
+
+    let link = dir.path().join("link.txt");
+    let control = link_msg(&target, &link);
+    assert_eq!(dispatch_error_kind(&mut isolate, &control), ErrorKind::NoError);
+    fs::write(&link, b"changed").unwrap();
+    assert_eq!(fs::read(&target).unwrap(), b"changed");
+    assert!(!fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+
+    let missing = dir.path().join("missing.txt");
+    let control = link_msg(&missing, &dir.path().join("link2.txt"));
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::NotFound
+    );
+
+    // Writing the link is allowed, reading the target isn't.
+    let flags = flags::DenoFlags {
+      allow_write: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let denied = dir.path().join("denied");
+    let control = symlink_msg(&target, &denied, None);
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+    let control = link_msg(&target, &denied);
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+    assert!(fs::symlink_metadata(&denied).is_err());
+  }
+
+  #[test]
+  fn test_rename() {
+    fn rename(isolate: &mut Isolate, from: &Path, to: &Path) -> ErrorKind {
+      dispatch_error_kind(isolate, &rename_msg(from, to))
+    }
+
+    let root = TempDir::new().unwrap();
+    let outside = TempDir::new().unwrap();
+    let old = root.path().join("old.txt");
+    let new = root.path().join("new.txt");
+    let subdir = root.path().join("subdir");
+    fs::write(&old, b"hello").unwrap();
+    fs::create_dir(&subdir).unwrap();
+
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      allow_write: true,
+      write_roots: vec![root.path().to_path_buf()],
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    assert_eq!(rename(&mut isolate, &old, &new), ErrorKind::NoError);
+    assert!(!old.exists());
+    assert_eq!(fs::read(&new).unwrap(), b"hello");
+
+    let moved = subdir.join("moved.txt");
+    assert_eq!(rename(&mut isolate, &new, &moved), ErrorKind::NoError);
+    assert_eq!(fs::read(&moved).unwrap(), b"hello");
+
+    let missing = root.path().join("missing.txt");
+    assert_eq!(rename(&mut isolate, &missing, &new), ErrorKind::NotFound);
+
+    // The destination must be in the write roots.
+    let escaped = outside.path().join("escaped.txt");
+    assert_eq!(
+      rename(&mut isolate, &moved, &escaped),
+      ErrorKind::PermissionDenied
+    );
+    assert!(moved.exists());
+    assert!(!escaped.exists());
+
+    // So must the source, which is removed.
+    let outside_file = outside.path().join("outside.txt");
+    fs::write(&outside_file, b"hello").unwrap();
+    assert_eq!(
+      rename(&mut isolate, &outside_file, &new),
+      ErrorKind::PermissionDenied
+    );
+    assert!(outside_file.exists());
+    assert!(!new.exists());
+
+    // Both read and write are needed.
+    let flags = flags::DenoFlags {
+      allow_write: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    assert_eq!(
+      rename(&mut isolate, &moved, &new),
+      ErrorKind::PermissionDenied
+    );
+    assert!(moved.exists());
+  }
+
+  #[test]
+  fn test_move_by_copy() {
+    let from = TempDir::new().unwrap();
+    let to = TempDir::new().unwrap();
+    let dir = from.path().join("dir");
+    fs::create_dir_all(dir.join("a/b")).unwrap();
+    fs::write(dir.join("a/b/file"), b"hello").unwrap();
+    std::os::unix::fs::symlink("a/b/file", dir.join("link")).unwrap();
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o750)).unwrap();
+
+    let moved = to.path().join("moved");
+    move_by_copy(&dir, &moved).unwrap();
+    assert!(!dir.exists());
+    assert_eq!(fs::read(moved.join("a/b/file")).unwrap(), b"hello");
+    let link = fs::read_link(moved.join("link")).unwrap();
+    assert_eq!(link, Path::new("a/b/file"));
+    let mode = fs::metadata(&moved).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o750);
+
+    let file = moved.join("a/b/file");
+    let moved_file = to.path().join("file");
+    move_by_copy(&file, &moved_file).unwrap();
+    assert!(!file.exists());
+    assert_eq!(fs::read(&moved_file).unwrap(), b"hello");
+
+    // A socket can't be copied, so the move fails part way. Nothing is left
+    // behind at the destination.
+    let failing = from.path().join("failing");
+    fs::create_dir(&failing).unwrap();
+    fs::write(failing.join("file"), b"hello").unwrap();
+    let _socket =
+      std::os::unix::net::UnixListener::bind(failing.join("socket")).unwrap();
+    let moved = to.path().join("failed");
+    assert!(move_by_copy(&failing, &moved).is_err());
+    assert!(failing.join("file").exists());
+    let mut left: Vec<_> = fs::read_dir(to.path())
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name())
+      .collect();
+    left.sort();
+    assert_eq!(left, vec![OsString::from("file"), OsString::from("moved")]);
+  }
+
+  #[test]
+  fn test_truncate() {
+    let dir = TempDir::new().unwrap();
+    let filename = dir.path().join("file.txt");
+    fs::write(&filename, b"hello world").unwrap();
+    let flags = flags::DenoFlags {
+      allow_write: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+
+    let control = truncate_msg(&filename, 5, -1);
+    assert_eq!(dispatch_error_kind(&mut isolate, &control), ErrorKind::NoError);
+    assert_eq!(fs::read(&filename).unwrap(), b"hello");
+    // Extending fills with zeros.
+    let control = truncate_msg(&filename, 8, -1);
+    assert_eq!(dispatch_error_kind(&mut isolate, &control), ErrorKind::NoError);
+    assert_eq!(fs::read(&filename).unwrap(), b"hello\0\0\0");
+
+    let missing = dir.path().join("missing.txt");
+    let control = truncate_msg(&missing, 5, -1);
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::NotFound
+    );
+    assert!(!missing.exists());
+
+    let file = fs::OpenOptions::new()
+      .read(true)
+      .write(true)
+      .open(&filename)
+      .unwrap();
+    let mut resource = add_fs_file(tokio::fs::File::from_std(file));
+    let control = truncate_msg(&missing, 2, resource.rid);
+    assert_eq!(dispatch_error_kind(&mut isolate, &control), ErrorKind::NoError);
+    assert_eq!(fs::read(&filename).unwrap(), b"he");
+    resource.close();
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::BadFileDescriptor
+    );
+
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    let control = truncate_msg(&filename, 0, -1);
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+    let control = truncate_msg(&filename, 0, resource.rid);
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+    assert_eq!(fs::read(&filename).unwrap(), b"he");
+  }
+
+  #[test]
+  fn test_seek() {
+    fn seek(
+      isolate: &mut Isolate,
+      rid: i32,
+      offset: i64,
+      whence: msg::SeekMode,
+    ) -> Result<u64, ErrorKind> {
+      let control = seek_msg(rid, offset, whence);
+      let (_, op) = dispatch(isolate, &control, empty_data());
+      let mut buf = None;
+      tokio_util::init(|| buf = Some(tokio_util::block_on(op).unwrap()));
+      let buf = buf.unwrap();
+      let base = msg::get_root_as_base(&buf);
+      if base.error_kind() != ErrorKind::NoError {
+        return Err(base.error_kind());
+      }
+      Ok(base.inner_as_seek_res().unwrap().position())
+    }
+
+    let dir = TempDir::new().unwrap();
+    let filename = dir.path().join("file.txt");
+    fs::write(&filename, b"hello world").unwrap();
+    let file = fs::File::open(&filename).unwrap();
+    // Shares the position with the resource.
+    let mut clone = file.try_clone().unwrap();
+    let mut resource = add_fs_file(tokio::fs::File::from_std(file));
+    let rid = resource.rid;
+    let mut isolate = Isolate::from_flags(Default::default(), vec![], dispatch);
+
+    let read = |clone: &mut fs::File| {
+      let mut buf = [0u8; 5];
+      let n = clone.read(&mut buf).unwrap();
+      buf[..n].to_vec()
+    };
+    assert_eq!(seek(&mut isolate, rid, 6, msg::SeekMode::Start), Ok(6));
+    assert_eq!(read(&mut clone), b"world");
+    assert_eq!(seek(&mut isolate, rid, -5, msg::SeekMode::Current), Ok(6));
+    assert_eq!(seek(&mut isolate, rid, -11, msg::SeekMode::End), Ok(0));
+    assert_eq!(read(&mut clone), b"hello");
+    // Offsets don't have to fit in 32 bits.
+    let far = 5_000_000_000;
+    let pos = seek(&mut isolate, rid, far, msg::SeekMode::Start);
+    assert_eq!(pos, Ok(far as u64));
+    assert_eq!(seek(&mut isolate, rid, -far, msg::SeekMode::Current), Ok(0));
+
+    assert_eq!(
+      seek(&mut isolate, rid, -1, msg::SeekMode::Start),
+      Err(ErrorKind::InvalidInput)
+    );
+    assert_eq!(
+      seek(&mut isolate, rid, -6, msg::SeekMode::Current),
+      Err(ErrorKind::InvalidInput)
+    );
+    resource.close();
+    assert_eq!(
+      seek(&mut isolate, rid, 0, msg::SeekMode::Start),
+      Err(ErrorKind::BadFileDescriptor)
+    );
+  }
+
+  #[test]
+  fn test_read_text_file() {
+    let dir = TempDir::new().unwrap();
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let mut read = |contents: &[u8]| -> Result<String, (ErrorKind, String)> {
+      let filename = dir.path().join("file.txt");
+      fs::write(&filename, contents).unwrap();
+      let control = read_text_file_msg(&filename);
+      let (_, op) = dispatch(&mut isolate, &control, empty_data());
+      let buf = op.wait().unwrap();
+      let base = msg::get_root_as_base(&buf);
+      if base.error_kind() != ErrorKind::NoError {
+        return Err((base.error_kind(), base.error().unwrap().to_string()));
+      }
+      let res = base.inner_as_read_text_file_res().unwrap();
+      Ok(res.text().unwrap().to_string())
+    };
+    assert_eq!(read(b"hello world\n"), Ok("hello world\n".to_string()));
+    assert_eq!(read("héllo 🦕".as_bytes()), Ok("héllo 🦕".to_string()));
+    assert_eq!(read(b""), Ok("".to_string()));
+    // 0xff never appears in UTF-8.
+    assert_eq!(
+      read(b"ab\xffcd"),
+      Err((
+        ErrorKind::InvalidUtf8,
+        "invalid UTF-8 at byte offset 2".to_string()
+      ))
+    );
+    // An "é" that is cut short at the end.
+    assert_eq!(
+      read(b"caf\xc3"),
+      Err((
+        ErrorKind::InvalidUtf8,
+        "invalid UTF-8 at byte offset 3".to_string()
+      ))
+    );
+
+    let mut isolate = Isolate::from_flags(Default::default(), vec![], dispatch);
+    let control = read_text_file_msg(&dir.path().join("file.txt"));
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+  }
+
+  #[test]
+  fn test_make_temp_file() {
+    let dir = TempDir::new().unwrap();
+    let flags = flags::DenoFlags {
+      allow_write: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let mut paths = HashSet::new();
+    for _ in 0..10 {
+      let control = make_temp_file_msg(dir.path(), "tmp");
+      let (_, op) = dispatch(&mut isolate, &control, empty_data());
+      let buf = op.wait().unwrap();
+      let base = msg::get_root_as_base(&buf);
+      assert_eq!(base.error_kind(), ErrorKind::NoError);
+      let res = base.inner_as_make_temp_file_res().unwrap();
+      let path = PathBuf::from(res.path().unwrap());
+      assert_eq!(path.parent(), Some(dir.path()));
+      assert!(path.file_name().unwrap().to_str().unwrap().starts_with("tmp"));
+      let metadata = fs::metadata(&path).unwrap();
+      assert!(metadata.is_file());
+      assert_eq!(metadata.len(), 0);
+      #[cfg(any(unix))]
+      assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+      paths.insert(path);
+    }
+    assert_eq!(paths.len(), 10);
+
+    let control = make_temp_file_msg(&dir.path().join("missing"), "tmp");
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::NotFound
+    );
+
+    // Without --allow-write.
+    let mut isolate = Isolate::from_flags(Default::default(), vec![], dispatch);
+    let control = make_temp_file_msg(dir.path(), "denied");
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 10);
+  }
+
+  #[test]
+  fn test_fsync() {
+    fn fsync(
+      isolate: &mut Isolate,
+      rid: i32,
+      data_only: bool,
+    ) -> Result<(), ErrorKind> {
+      match dispatch_error_kind(isolate, &fsync_msg(rid, data_only)) {
+        ErrorKind::NoError => Ok(()),
+        kind => Err(kind),
+      }
+    }
+
+    let dir = TempDir::new().unwrap();
+    let filename = dir.path().join("file.txt");
+    fs::write(&filename, b"hello world").unwrap();
+    let file = fs::OpenOptions::new().write(true).open(&filename).unwrap();
+    let mut resource = add_fs_file(tokio::fs::File::from_std(file));
+    let rid = resource.rid;
+    let mut isolate = Isolate::from_flags(Default::default(), vec![], dispatch);
+    assert_eq!(fsync(&mut isolate, rid, false), Ok(()));
+    assert_eq!(fsync(&mut isolate, rid, true), Ok(()));
+    assert_eq!(fs::read(&filename).unwrap(), b"hello world");
+
+    // Stdin isn't a file.
+    assert_eq!(fsync(&mut isolate, 0, false), Err(ErrorKind::InvalidInput));
+    resource.close();
+    assert_eq!(
+      fsync(&mut isolate, rid, false),
+      Err(ErrorKind::BadFileDescriptor)
+    );
+  }
+
+  #[test]
+  fn test_read_lines() {
+    let dir = TempDir::new().unwrap();
+    let filename = dir.path().join("lines.txt");
+    let long_line = "x".repeat(100_000);
+    let contents = format!("one\ntwo\r\n\n{}\nlast", long_line);
+    fs::write(&filename, contents).unwrap();
+
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let control = open_lines_msg(&filename);
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::NoError);
+    let rid = base.inner_as_open_lines_res().unwrap().rid();
+
+    fn read_line(
+      isolate: &mut Isolate,
+      rid: i32,
+    ) -> Result<Option<String>, ErrorKind> {
+      let control = read_line_msg(rid);
+      let (_, op) = dispatch(isolate, &control, empty_data());
+      let buf = op.wait().unwrap();
+      let base = msg::get_root_as_base(&buf);
+      if base.error_kind() != ErrorKind::NoError {
+        return Err(base.error_kind());
+      }
+      let inner = base.inner_as_read_line_res().unwrap();
+      Ok(inner.line().map(|line| String::from_utf8(line.to_vec()).unwrap()))
+    }
+    assert_eq!(read_line(&mut isolate, rid), Ok(Some("one".to_string())));
+    assert_eq!(read_line(&mut isolate, rid), Ok(Some("two".to_string())));
+    assert_eq!(read_line(&mut isolate, rid), Ok(Some("".to_string())));
+    assert_eq!(read_line(&mut isolate, rid), Ok(Some(long_line)));
+    // The last line has no newline.
+    assert_eq!(read_line(&mut isolate, rid), Ok(Some("last".to_string())));
+    assert_eq!(read_line(&mut isolate, rid), Ok(None));
+    assert_eq!(read_line(&mut isolate, rid), Ok(None));
+
+    let control = close_msg(rid);
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    assert!(op.wait().unwrap().is_empty());
+    assert_eq!(read_line(&mut isolate, rid), Err(ErrorKind::BadFileDescriptor));
+
+    let mut isolate = Isolate::from_flags(Default::default(), vec![], dispatch);
+    let control = open_lines_msg(&filename);
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+  }
+
+  #[test]
+  fn test_fs_events() {
+    let dir = TempDir::new().unwrap();
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let control = fs_events_msg(&[dir.path()]);
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::NoError);
+    let rid = base.inner_as_fs_events_res().unwrap().rid();
+
+    let filename = dir.path().join("created.txt");
+    fs::write(&filename, b"hello").unwrap();
+    let control = fs_events_poll_msg(rid);
+    let (is_sync, poll) = dispatch(&mut isolate, &control, empty_data());
+    assert!(!is_sync);
+    // Only one poll can stream the events.
+    assert_eq!(dispatch_error_kind(&mut isolate, &control), ErrorKind::Other);
+
+    // Closing the watch completes the poll, after the changes that were seen
+    // before have been sent as chunks.
+    let control = close_msg(rid);
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    assert!(op.wait().unwrap().is_empty());
+    let buf = poll.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::NoError);
+    let chunk = base.inner_as_response_chunk().unwrap();
+    assert!(chunk.end());
+    assert!(chunk.seq() >= 1);
+
+    let control = fs_events_poll_msg(rid);
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::BadFileDescriptor
+    );
+
+    let mut isolate = Isolate::from_flags(Default::default(), vec![], dispatch);
+    let control = fs_events_msg(&[dir.path()]);
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+  }
+
+  #[test]
+  fn test_read_file_chunked() {
+    let dir = TempDir::new().unwrap();
+    let filename = dir.path().join("big.bin");
+    let contents: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+    fs::write(&filename, &contents).unwrap();
+
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let responses = Arc::new(Mutex::new(Vec::new()));
+    let responses_ = responses.clone();
+    isolate.set_inspector(Box::new(move |direction, req_id, buf| {
+      if direction == Direction::Outgoing {
+        responses_.lock().unwrap().push((req_id, buf.to_vec()));
+      }
+    }));
+
+    let builder = &mut FlatBufferBuilder::new();
+    let filename = builder.create_string(&filename.to_string_lossy());
+    let inner = msg::ReadFile::create(
+      builder,
+      &msg::ReadFileArgs {
+        filename: Some(filename),
+        chunk_size: 4096,
+      },
+    );
+    let base = msg::Base::create(
+      builder,
+      &msg::BaseArgs {
+        inner: Some(inner.as_union_value()),
+        inner_type: msg::Any::ReadFile,
+        cmd_id: 3,
+        sync: false,
+        ..Default::default()
+      },
+    );
+    msg::finish_base_buffer(builder, base);
+    let control: Vec<String> =
+      builder.finished_data().iter().map(|b| b.to_string()).collect();
+    let source = format!(
+      "libdeno.recv(() => {{}});
+      libdeno.send(new Uint8Array([{}]));",
+      control.join(",")
+    );
+    tokio_util::init(|| {
+      isolate.execute("chunked.js", &source).expect("execute error");
+      isolate.event_loop().unwrap();
+    });
+
+    let responses = responses.lock().unwrap();
+    // 25 chunks of data and the end marker, which completes the op.
+    assert_eq!(responses.len(), 26);
+    let mut data = Vec::new();
+    for (i, &(req_id, ref buf)) in responses.iter().enumerate() {
+      let last = i == responses.len() - 1;
+      assert_eq!(req_id == isolate::CHUNK_RESPONSE_ID, !last);
+      let base = msg::get_root_as_base(buf);
+      assert_eq!(base.cmd_id(), 3);
+      assert_eq!(base.error_kind(), ErrorKind::NoError);
+      let chunk = base.inner_as_response_chunk().unwrap();
+      assert_eq!(chunk.seq(), i as u32);
+      assert_eq!(chunk.end(), last);
+      if let Some(chunk_data) = chunk.data() {
+        assert!(chunk_data.len() <= 4096);
+        data.extend_from_slice(chunk_data);
+      }
+    }
+    assert_eq!(data, contents);
+  }
+
+  #[test]
+  fn test_next_deadline() {
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    assert!(isolate.next_deadline().is_none());
+
+    let before = Instant::now();
+    // timers.ts only sends SetTimeout when a newly scheduled timer fires
+    // sooner than all existing ones, so the last value wins.
+    dispatch(&mut isolate, &set_timeout_msg(1000.0), empty_data());
+    dispatch(&mut isolate, &set_timeout_msg(10.0), empty_data());
+    let deadline = isolate.next_deadline().unwrap();
+    assert!(deadline >= before + Duration::from_millis(10));
+    assert!(deadline < before + Duration::from_millis(1000));
+
+    // A negative timeout clears the global timer.
+    dispatch(&mut isolate, &set_timeout_msg(-1.0), empty_data());
+    assert!(isolate.next_deadline().is_none());
+  }
+
+  lazy_static! {
+    static ref FAKE_EPOCH: Instant = Instant::now();
+  }
+  static FAKE_TICKS: AtomicUsize = ATOMIC_USIZE_INIT;
+
+  // Advances by exactly 5ms every time it is read.
+  fn fake_clock() -> Instant {
+    let ticks = FAKE_TICKS.fetch_add(1, Ordering::SeqCst) as u64;
+    *FAKE_EPOCH + Duration::from_millis(5 * ticks)
+  }
+
+  #[test]
+  fn test_performance_marks() {
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    isolate.set_clock(fake_clock);
+    dispatch(&mut isolate, &perf_mark_msg("start"), empty_data());
+    dispatch(&mut isolate, &perf_mark_msg("end"), empty_data());
+
+    let marks = isolate.performance_marks();
+    assert_eq!(marks.len(), 2);
+    assert_eq!(marks[0].0, "start");
+    assert_eq!(marks[1].0, "end");
+    // The measure between the two marks.
+    assert_eq!(marks[1].1 - marks[0].1, Duration::from_millis(5));
+  }
+
+  #[test]
+  fn test_run() {
+    let flags = flags::DenoFlags {
+      allow_run: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let control = run_msg(&["echo", "hello"], msg::ProcessStdio::Piped);
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::NoError);
+    let res = base.inner_as_run_res().unwrap();
+    assert!(res.pid() > 0);
+    assert_eq!(res.stdin_rid(), -1);
+    assert_eq!(res.stderr_rid(), -1);
+    let rid = res.rid();
+
+    let stdout = resources::lookup(res.stdout_rid()).unwrap();
+    let (_, output) = tokio_io::io::read_to_end(stdout, Vec::new())
+      .wait()
+      .unwrap();
+    assert_eq!(output, b"hello\n");
+
+    let control = run_status_msg(rid);
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::NoError);
+    let res = base.inner_as_run_status_res().unwrap();
+    assert!(!res.got_signal());
+    assert_eq!(res.exit_code(), 0);
+
+    let control = run_msg(&["false"], msg::ProcessStdio::Inherit);
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let rid = msg::get_root_as_base(&buf).inner_as_run_res().unwrap().rid();
+    let control = run_status_msg(rid);
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let res = msg::get_root_as_base(&buf).inner_as_run_status_res().unwrap();
+    assert_eq!(res.exit_code(), 1);
+
+    // Without --allow-run.
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    let control = run_msg(&["echo", "hello"], msg::ProcessStdio::Piped);
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+  }
+
+  #[test]
+  fn test_dial() {
+    // Echoes back whatever it reads from one connection.
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let server_addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+      use std::io::{Read, Write};
+      let (mut stream, _) = listener.accept().unwrap();
+      let mut buf = [0; 64];
+      let n = stream.read(&mut buf).unwrap();
+      stream.write_all(&buf[..n]).unwrap();
+    });
+
+    let flags = flags::DenoFlags {
+      allow_net: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let control = dial_msg(&server_addr.to_string());
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::NoError);
+    let conn = base.inner_as_new_conn().unwrap();
+    assert_eq!(conn.remote_addr(), Some(server_addr.to_string().as_str()));
+    assert!(conn.local_addr().unwrap().starts_with("127.0.0.1:"));
+    let rid = conn.rid();
+
+    let request: &'static mut [u8] = Box::leak(b"ping".to_vec().into());
+    let control = write_msg(rid);
+    let (_, op) = dispatch(&mut isolate, &control, request);
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.inner_as_write_res().unwrap().nbyte(), 4);
+
+    let response: &'static mut [u8] = Box::leak(vec![0; 64].into());
+    let response_ptr = response.as_ptr();
+    let control = read_msg(rid);
+    let (_, op) = dispatch(&mut isolate, &control, response);
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    let nread = base.inner_as_read_res().unwrap().nread() as usize;
+    let response = unsafe { std::slice::from_raw_parts(response_ptr, nread) };
+    assert_eq!(response, b"ping");
+    server.join().unwrap();
+    resources::lookup(rid).unwrap().close();
+
+    let control = dial_msg("not an address");
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::InvalidInput
+    );
+
+    // Without --allow-net.
+    let argv = vec![String::from("./deno")];
+    let mut isolate = Isolate::new(argv, dispatch);
+    let control = dial_msg(&server_addr.to_string());
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+  }
+
+  #[test]
+  fn test_listen_accept() {
+    let flags = flags::DenoFlags {
+      allow_net: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let control = listen_msg("127.0.0.1:0");
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::NoError);
+    let listen_res = base.inner_as_listen_res().unwrap();
+    let listener_rid = listen_res.rid();
+    let addr: SocketAddr = listen_res.local_addr().unwrap().parse().unwrap();
+    assert_ne!(addr.port(), 0);
+
+    // Sends a byte and waits for it to be echoed.
+    let client = std::thread::spawn(move || {
+      use std::io::{Read, Write};
+      let mut stream = std::net::TcpStream::connect(addr).unwrap();
+      stream.write_all(b"x").unwrap();
+      let mut buf = [0; 1];
+      stream.read_exact(&mut buf).unwrap();
+      buf
+    });
+
+    let control = accept_msg(listener_rid);
+    let (_, op) = dispatch(&mut isolate, &control, empty_data());
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.error_kind(), ErrorKind::NoError);
+    let conn = base.inner_as_new_conn().unwrap();
+    assert_eq!(conn.local_addr(), Some(addr.to_string().as_str()));
+    let rid = conn.rid();
+
+    let request: &'static mut [u8] = Box::leak(vec![0; 1].into());
+    let request_ptr = request.as_ptr();
+    let control = read_msg(rid);
+    let (_, op) = dispatch(&mut isolate, &control, request);
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.inner_as_read_res().unwrap().nread(), 1);
+    let byte = unsafe { *request_ptr };
+    let response: &'static mut [u8] = Box::leak(vec![byte].into());
+    let control = write_msg(rid);
+    let (_, op) = dispatch(&mut isolate, &control, response);
+    let buf = op.wait().unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.inner_as_write_res().unwrap().nbyte(), 1);
+    assert_eq!(&client.join().unwrap(), b"x");
+    resources::lookup(rid).unwrap().close();
+
+    // Closing the listener fails every accept that is waiting on it, rather
+    // than leaving them waiting forever.
+    let control = accept_msg(listener_rid);
+    let waiters: Vec<_> = (0..2)
+      .map(|_| {
+        let (_, op) = dispatch(&mut isolate, &control, empty_data());
+        std::thread::spawn(move || {
+          let buf = op.wait().unwrap();
+          msg::get_root_as_base(&buf).error_kind()
+        })
+      }).collect();
+    std::thread::sleep(Duration::from_millis(50));
+    resources::lookup(listener_rid).unwrap().close();
+    for waiter in waiters {
+      assert_eq!(waiter.join().unwrap(), ErrorKind::BadFileDescriptor);
+    }
+
+    // Without --allow-net.
+    let mut isolate = Isolate::from_flags(Default::default(), vec![], dispatch);
+    let control = listen_msg("127.0.0.1:0");
+    assert_eq!(
+      dispatch_error_kind(&mut isolate, &control),
+      ErrorKind::PermissionDenied
+    );
+  }
+
+  #[test]
+  fn test_read_write() {
+    // The same ops work for every kind of resource, driven by the reactor or
+    // the blocking pool as the resource needs.
+    fn run(isolate: &mut Isolate, control: &[u8], data: Vec<u8>) -> Buf {
+      let data: &'static mut [u8] = Box::leak(data.into());
+      let (_, op) = dispatch(isolate, control, data);
+      let mut buf = None;
+      tokio_util::init(|| buf = Some(tokio_util::block_on(op).unwrap()));
+      buf.unwrap()
+    }
+    fn write(isolate: &mut Isolate, rid: i32, bytes: &[u8]) -> ErrorKind {
+      let buf = run(isolate, &write_msg(rid), bytes.to_vec());
+      let base = msg::get_root_as_base(&buf);
+      if base.error_kind() == ErrorKind::NoError {
+        let nbyte = base.inner_as_write_res().unwrap().nbyte() as usize;
+        assert_eq!(nbyte, bytes.len());
+      }
+      base.error_kind()
+    }
+    fn read(isolate: &mut Isolate, rid: i32) -> Result<Vec<u8>, ErrorKind> {
+      let response: &'static mut [u8] = Box::leak(vec![0; 64].into());
+      let response_ptr = response.as_ptr();
+      let (_, op) = dispatch(isolate, &read_msg(rid), response);
+      let mut buf = None;
+      tokio_util::init(|| buf = Some(tokio_util::block_on(op).unwrap()));
+      let buf = buf.unwrap();
+      let base = msg::get_root_as_base(&buf);
+      if base.error_kind() != ErrorKind::NoError {
+        return Err(base.error_kind());
+      }
+      let nread = base.inner_as_read_res().unwrap().nread() as usize;
+      Ok(unsafe { std::slice::from_raw_parts(response_ptr, nread) }.to_vec())
+    }
+
+    let flags = flags::DenoFlags {
+      allow_net: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+
+    let dir = TempDir::new().unwrap();
+    let filename = dir.path().join("file.txt");
+    let file = fs::File::create(&filename).unwrap();
+    let rid = add_fs_file(tokio::fs::File::from_std(file)).rid;
+    assert_eq!(write(&mut isolate, rid, b"hello"), ErrorKind::NoError);
+    // Not readable, as it was only opened for writing.
+    assert!(read(&mut isolate, rid).is_err());
+    resources::lookup(rid).unwrap().close();
+    let file = fs::File::open(&filename).unwrap();
+    let rid = add_fs_file(tokio::fs::File::from_std(file)).rid;
+    assert_eq!(read(&mut isolate, rid), Ok(b"hello".to_vec()));
+    resources::lookup(rid).unwrap().close();
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let server_addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+      use std::io::{Read, Write};
+      let (mut stream, _) = listener.accept().unwrap();
+      let mut buf = [0; 5];
+      stream.read_exact(&mut buf).unwrap();
+      stream.write_all(&buf).unwrap();
+    });
+    let buf = run(&mut isolate, &dial_msg(&server_addr.to_string()), vec![]);
+    let rid = msg::get_root_as_base(&buf).inner_as_new_conn().unwrap().rid();
+    assert_eq!(write(&mut isolate, rid, b"hello"), ErrorKind::NoError);
+    assert_eq!(read(&mut isolate, rid), Ok(b"hello".to_vec()));
+    server.join().unwrap();
+    resources::lookup(rid).unwrap().close();
+
+    // Closed rids, and resources that can't be read or written, are errors
+    // rather than panics.
+    assert_eq!(read(&mut isolate, rid), Err(ErrorKind::BadFileDescriptor));
+    let err = write(&mut isolate, rid, b"hello");
+    assert_eq!(err, ErrorKind::BadFileDescriptor);
+    let addr = SocketAddr::from_str("127.0.0.1:0").unwrap();
+    let listener = TcpListener::bind(&addr).unwrap();
+    let table = resources::ResourceTable::default();
+    let mut listener =
+      resources::add_tcp_listener(&table, listener).unwrap();
+    assert_eq!(
+      read(&mut isolate, listener.rid),
+      Err(ErrorKind::BadFileDescriptor)
+    );
+    assert_eq!(
+      write(&mut isolate, listener.rid, b"hello"),
+      ErrorKind::BadFileDescriptor
+    );
+    listener.close();
+    assert_eq!(read(&mut isolate, 1), Err(ErrorKind::BadFileDescriptor));
+    assert_eq!(write(&mut isolate, 0, b"hello"), ErrorKind::BadFileDescriptor);
+  }
+
+  #[test]
+  fn test_open() {
+    fn open(
+      isolate: &mut Isolate,
+      filename: &Path,
+      read: bool,
+      write: bool,
+      create: bool,
+    ) -> Result<i32, ErrorKind> {
+      let control = open_msg(filename, read, write, create);
+      let (_, op) = dispatch(isolate, &control, empty_data());
+      let buf = op.wait().unwrap();
+      let base = msg::get_root_as_base(&buf);
+      if base.error_kind() != ErrorKind::NoError {
+        return Err(base.error_kind());
+      }
+      Ok(base.inner_as_open_res().unwrap().rid())
+    }
+
+    let dir = TempDir::new().unwrap();
+    let filename = dir.path().join("file.txt");
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      allow_write: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    assert_eq!(
+      open(&mut isolate, &filename, true, false, false),
+      Err(ErrorKind::NotFound)
+    );
+
+    let rid = open(&mut isolate, &filename, false, true, true).unwrap();
+    let request: &'static mut [u8] = Box::leak(b"hello".to_vec().into());
+    let control = write_msg(rid);
+    let (_, op) = dispatch(&mut isolate, &control, request);
+    let mut buf = None;
+    tokio_util::init(|| buf = Some(tokio_util::block_on(op).unwrap()));
+    let buf = buf.unwrap();
+    let base = msg::get_root_as_base(&buf);
+    assert_eq!(base.inner_as_write_res().unwrap().nbyte(), 5);
+    resources::lookup(rid).unwrap().close();
+    assert_eq!(fs::read(&filename).unwrap(), b"hello");
+    #[cfg(any(unix))]
+    assert_eq!(
+      fs::metadata(&filename).unwrap().permissions().mode() & 0o077,
+      0
+    );
+
+    let rid = open(&mut isolate, &filename, true, false, false).unwrap();
+    let response: &'static mut [u8] = Box::leak(vec![0; 64].into());
+    let response_ptr = response.as_ptr();
+    let control = read_msg(rid);
+    let (_, op) = dispatch(&mut isolate, &control, response);
+    let mut buf = None;
+    tokio_util::init(|| buf = Some(tokio_util::block_on(op).unwrap()));
+    let buf = buf.unwrap();
+    let base = msg::get_root_as_base(&buf);
+    let nread = base.inner_as_read_res().unwrap().nread() as usize;
+    let response = unsafe { std::slice::from_raw_parts(response_ptr, nread) };
+    assert_eq!(response, b"hello");
+    resources::lookup(rid).unwrap().close();
+
+    // Reading needs --allow-read, and creating needs --allow-write.
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let other = dir.path().join("other.txt");
+    assert_eq!(
+      open(&mut isolate, &other, false, true, true),
+      Err(ErrorKind::PermissionDenied)
+    );
+    assert!(!other.exists());
+    let mut isolate = Isolate::from_flags(Default::default(), vec![], dispatch);
+    assert_eq!(
+      open(&mut isolate, &filename, true, false, false),
+      Err(ErrorKind::PermissionDenied)
+    );
+
+    // Open files count against max_open_resources, though they are in the
+    // global table.
+    let flags = flags::DenoFlags {
+      allow_read: true,
+      max_open_resources: Some(1),
+      ..Default::default()
+    };
+    let mut isolate = Isolate::from_flags(flags, vec![], dispatch);
+    let rid = open(&mut isolate, &filename, true, false, false).unwrap();
+    assert_eq!(
+      open(&mut isolate, &filename, true, false, false),
+      Err(ErrorKind::TooManyOpenResources)
+    );
+    resources::lookup(rid).unwrap().close();
+    let rid = open(&mut isolate, &filename, true, false, false).unwrap();
+    resources::lookup(rid).unwrap().close();
+  }
 }
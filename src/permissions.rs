@@ -0,0 +1,117 @@
+// Copyright 2018 the Deno authors. All rights reserved. MIT license.
+use errors::permission_denied;
+use errors::DenoResult;
+use flags::DenoFlags;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Permission {
+  Read,
+  Write,
+  Net,
+  Env,
+  Run,
+}
+
+// Asked about permissions that aren't granted. Gets the permission and what
+// it is needed for: a path, a URL, an address, an environment variable name
+// or a program, or "" if the op isn't about one thing, like env(). Returning
+// true allows that one op; it is asked again next time.
+pub type PermissionPrompt = Box<Fn(Permission, &str) -> bool + Send + Sync>;
+
+// The permissions that are granted to an isolate. They start out as the
+// --allow-* flags and can be changed at runtime, see
+// Isolate::grant_permission().
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Permissions {
+  pub read: bool,
+  pub write: bool,
+  pub net: bool,
+  pub env: bool,
+  pub run: bool,
+}
+
+impl Permissions {
+  pub fn from_flags(flags: &DenoFlags) -> Self {
+    Permissions {
+      read: flags.allow_read,
+      write: flags.allow_write,
+      net: flags.allow_net,
+      env: flags.allow_env,
+      run: flags.allow_run,
+    }
+  }
+
+  pub fn is_granted(&self, perm: Permission) -> bool {
+    match perm {
+      Permission::Read => self.read,
+      Permission::Write => self.write,
+      Permission::Net => self.net,
+      Permission::Env => self.env,
+      Permission::Run => self.run,
+    }
+  }
+
+  pub fn set(&mut self, perm: Permission, granted: bool) {
+    let field = match perm {
+      Permission::Read => &mut self.read,
+      Permission::Write => &mut self.write,
+      Permission::Net => &mut self.net,
+      Permission::Env => &mut self.env,
+      Permission::Run => &mut self.run,
+    };
+    *field = granted;
+  }
+}
+
+// Without a prompt, anything that isn't granted is denied.
+pub fn check(
+  permissions: &Permissions,
+  prompt: Option<&PermissionPrompt>,
+  perm: Permission,
+  what: &str,
+) -> DenoResult<()> {
+  if permissions.is_granted(perm) {
+    return Ok(());
+  }
+  match prompt {
+    Some(prompt) if prompt(perm, what) => Ok(()),
+    _ => Err(permission_denied()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_check() {
+    let mut flags = DenoFlags::default();
+    flags.allow_net = true;
+    let perms = Permissions::from_flags(&flags);
+    assert!(check(&perms, None, Permission::Net, "").is_ok());
+    assert!(check(&perms, None, Permission::Read, "/etc/passwd").is_err());
+
+    let prompt: PermissionPrompt =
+      Box::new(|perm, what| perm == Permission::Read && what == "/tmp");
+    assert!(check(&perms, Some(&prompt), Permission::Read, "/tmp").is_ok());
+    assert!(check(&perms, Some(&prompt), Permission::Read, "/etc").is_err());
+    assert!(check(&perms, Some(&prompt), Permission::Write, "/tmp").is_err());
+  }
+
+  #[test]
+  fn test_set() {
+    let mut perms = Permissions::default();
+    assert!(!perms.is_granted(Permission::Run));
+    perms.set(Permission::Run, true);
+    assert!(perms.is_granted(Permission::Run));
+    assert_eq!(
+      perms,
+      Permissions {
+        run: true,
+        ..Default::default()
+      }
+    );
+    perms.set(Permission::Run, false);
+    assert_eq!(perms, Permissions::default());
+  }
+}
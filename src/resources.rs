@@ -8,21 +8,31 @@
 // descriptors". This module implements a global resource table. Ops (AKA
 // handlers) look up resources by their integer id here.
 
+use errors;
+use errors::bad_resource;
+use errors::too_many_open_resources;
 use errors::DenoError;
+use errors::DenoResult;
 
 use futures;
 use futures::Poll;
+#[cfg(any(unix))]
+use libc;
 use std;
+use std::any::Any;
 use std::collections::HashMap;
 use std::io::Error;
-use std::io::{Read, Write};
+use std::io::{Read, SeekFrom, Write};
 use std::net::{Shutdown, SocketAddr};
+use std::process::{Child, ChildStderr, ChildStdin, ChildStdout, ExitStatus};
 use std::sync::atomic::AtomicIsize;
+use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use tokio;
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpStream;
+use tokio_threadpool;
 
 pub type ResourceId = i32; // Sometimes referred to RID.
 
@@ -41,6 +51,10 @@ lazy_static! {
     m.insert(2, Repr::Stderr(tokio::io::stderr()));
     m
   });
+  // The places that resources in RESOURCE_TABLE take up in the limit of the
+  // ResourceTable they were added for, by rid.
+  static ref SLOTS: Mutex<HashMap<ResourceId, Slot>> =
+    Mutex::new(HashMap::new());
 }
 
 // Internal representation of Resource.
@@ -49,8 +63,68 @@ enum Repr {
   Stdout(tokio::io::Stdout),
   Stderr(tokio::io::Stderr),
   FsFile(tokio::fs::File),
-  TcpListener(tokio::net::TcpListener),
+  // With the tasks of the accept()s that are waiting for a connection, which
+  // are woken when the listener is closed.
+  TcpListener(tokio::net::TcpListener, Vec<futures::task::Task>),
   TcpStream(tokio::net::TcpStream),
+  Child(Arc<Mutex<Child>>),
+  ChildPipe(ChildPipe),
+}
+
+// The stdio of a child process, if it was piped. These are std's blocking
+// pipes, so they are read and written on the blocking pool, without holding
+// the lock of RESOURCE_TABLE.
+#[derive(Clone)]
+enum ChildPipe {
+  Stdin(Arc<Mutex<ChildStdin>>),
+  Stdout(Arc<Mutex<ChildStdout>>),
+  Stderr(Arc<Mutex<ChildStderr>>),
+}
+
+impl ChildPipe {
+  fn poll_read(&self, buf: &mut [u8]) -> Poll<usize, Error> {
+    match self {
+      ChildPipe::Stdout(pipe) => {
+        poll_blocking(|| pipe.lock().unwrap().read(buf))
+      }
+      ChildPipe::Stderr(pipe) => {
+        poll_blocking(|| pipe.lock().unwrap().read(buf))
+      }
+      ChildPipe::Stdin(_) => Err(errors::to_io_error(bad_resource())),
+    }
+  }
+
+  fn poll_write(&self, buf: &[u8]) -> Poll<usize, Error> {
+    match self {
+      ChildPipe::Stdin(pipe) => {
+        poll_blocking(|| pipe.lock().unwrap().write(buf))
+      }
+      _ => Err(errors::to_io_error(bad_resource())),
+    }
+  }
+}
+
+// Runs f on the blocking pool if this is one of its threads. Otherwise, as for
+// sync ops on the main thread, f just blocks.
+fn poll_blocking<F, T>(f: F) -> Poll<T, Error>
+where
+  F: FnOnce() -> std::io::Result<T>,
+{
+  let mut f = Some(f);
+  let r = tokio_threadpool::blocking(|| f.take().unwrap()());
+  match r {
+    Ok(futures::Async::Ready(result)) => result.map(futures::Async::Ready),
+    Ok(futures::Async::NotReady) => Ok(futures::Async::NotReady),
+    Err(_) => f.take().unwrap()().map(futures::Async::Ready),
+  }
+}
+
+fn lookup_child_pipe(rid: ResourceId) -> Option<ChildPipe> {
+  let table = RESOURCE_TABLE.lock().unwrap();
+  match table.get(&rid) {
+    Some(Repr::ChildPipe(pipe)) => Some(pipe.clone()),
+    _ => None,
+  }
 }
 
 // Abstract async file interface.
@@ -62,24 +136,44 @@ pub struct Resource {
 
 impl Resource {
   // TODO Should it return a Resource instead of net::TcpStream?
-  pub fn poll_accept(&mut self) -> Poll<(TcpStream, SocketAddr), Error> {
+  // Fails with BadResource if rid isn't a listener, which is also what an
+  // accept() that is waiting fails with once the listener is closed.
+  pub fn poll_accept(&mut self) -> Poll<(TcpStream, SocketAddr), DenoError> {
     let mut table = RESOURCE_TABLE.lock().unwrap();
-    let maybe_repr = table.get_mut(&self.rid);
-    match maybe_repr {
-      None => panic!("bad rid"),
-      Some(repr) => match repr {
-        Repr::TcpListener(ref mut s) => s.poll_accept(),
-        _ => panic!("Cannot accept"),
-      },
+    match table.get_mut(&self.rid) {
+      Some(Repr::TcpListener(ref mut s, ref mut tasks)) => {
+        match s.poll_accept() {
+          Ok(futures::Async::NotReady) => {
+            if !tasks.iter().any(|task| task.will_notify_current()) {
+              tasks.push(futures::task::current());
+            }
+            Ok(futures::Async::NotReady)
+          }
+          r => {
+            // This accept isn't waiting anymore.
+            tasks.retain(|task| !task.will_notify_current());
+            r.map_err(DenoError::from)
+          }
+        }
+      }
+      _ => Err(bad_resource()),
     }
   }
 
   // close(2) is done by dropping the value. Therefore we just need to remove
   // the resource from the RESOURCE_TABLE.
   pub fn close(&mut self) {
-    let mut table = RESOURCE_TABLE.lock().unwrap();
-    let r = table.remove(&self.rid);
-    assert!(r.is_some());
+    SLOTS.lock().unwrap().remove(&self.rid);
+    let r = RESOURCE_TABLE.lock().unwrap().remove(&self.rid);
+    match r {
+      Some(Repr::TcpListener(_, tasks)) => {
+        for task in tasks {
+          task.notify();
+        }
+      }
+      Some(_) => {}
+      None => panic!("bad rid"),
+    }
   }
 
   pub fn shutdown(&mut self, how: Shutdown) -> Result<(), DenoError> {
@@ -103,21 +197,19 @@ impl Read for Resource {
   }
 }
 
+// Reading and writing fail with BadResource if the resource has been closed,
+// or can't be read or written, like a listener or stdout for reading.
 impl AsyncRead for Resource {
   fn poll_read(&mut self, buf: &mut [u8]) -> Poll<usize, Error> {
+    if let Some(pipe) = lookup_child_pipe(self.rid) {
+      return pipe.poll_read(buf);
+    }
     let mut table = RESOURCE_TABLE.lock().unwrap();
-    let maybe_repr = table.get_mut(&self.rid);
-    match maybe_repr {
-      None => panic!("bad rid"),
-      Some(repr) => match repr {
-        Repr::FsFile(ref mut f) => f.poll_read(buf),
-        Repr::Stdin(ref mut f) => f.poll_read(buf),
-        Repr::TcpStream(ref mut f) => f.poll_read(buf),
-        Repr::Stdout(_) | Repr::Stderr(_) => {
-          panic!("Cannot read from stdout/stderr")
-        }
-        Repr::TcpListener(_) => panic!("Cannot read"),
-      },
+    match table.get_mut(&self.rid) {
+      Some(Repr::FsFile(ref mut f)) => f.poll_read(buf),
+      Some(Repr::Stdin(ref mut f)) => f.poll_read(buf),
+      Some(Repr::TcpStream(ref mut f)) => f.poll_read(buf),
+      _ => Err(errors::to_io_error(bad_resource())),
     }
   }
 }
@@ -134,18 +226,16 @@ impl Write for Resource {
 
 impl AsyncWrite for Resource {
   fn poll_write(&mut self, buf: &[u8]) -> Poll<usize, Error> {
+    if let Some(pipe) = lookup_child_pipe(self.rid) {
+      return pipe.poll_write(buf);
+    }
     let mut table = RESOURCE_TABLE.lock().unwrap();
-    let maybe_repr = table.get_mut(&self.rid);
-    match maybe_repr {
-      None => panic!("bad rid"),
-      Some(repr) => match repr {
-        Repr::FsFile(ref mut f) => f.poll_write(buf),
-        Repr::Stdout(ref mut f) => f.poll_write(buf),
-        Repr::Stderr(ref mut f) => f.poll_write(buf),
-        Repr::TcpStream(ref mut f) => f.poll_write(buf),
-        Repr::Stdin(_) => panic!("Cannot write to stdin"),
-        Repr::TcpListener(_) => panic!("Cannot write"),
-      },
+    match table.get_mut(&self.rid) {
+      Some(Repr::FsFile(ref mut f)) => f.poll_write(buf),
+      Some(Repr::Stdout(ref mut f)) => f.poll_write(buf),
+      Some(Repr::Stderr(ref mut f)) => f.poll_write(buf),
+      Some(Repr::TcpStream(ref mut f)) => f.poll_write(buf),
+      _ => Err(errors::to_io_error(bad_resource())),
     }
   }
 
@@ -159,32 +249,383 @@ fn new_rid() -> ResourceId {
   next_rid as ResourceId
 }
 
-pub fn add_fs_file(fs_file: tokio::fs::File) -> Resource {
-  let rid = new_rid();
-  let mut tg = RESOURCE_TABLE.lock().unwrap();
-  match tg.insert(rid, Repr::FsFile(fs_file)) {
-    Some(_) => panic!("There is already a file with that rid"),
-    None => Resource { rid },
+// Files and sockets are kept in the global table, but count against the limit
+// of the isolate's table, as if they were in it. If it is full they fail with
+// TooManyOpenResources, and are dropped, which closes them.
+pub fn add_fs_file(
+  table: &ResourceTable,
+  fs_file: tokio::fs::File,
+) -> DenoResult<Resource> {
+  add_counted(table, Repr::FsFile(fs_file))
+}
+
+pub fn add_tcp_listener(
+  table: &ResourceTable,
+  listener: tokio::net::TcpListener,
+) -> DenoResult<Resource> {
+  add_counted(table, Repr::TcpListener(listener, Vec::new()))
+}
+
+pub fn add_tcp_stream(
+  table: &ResourceTable,
+  stream: tokio::net::TcpStream,
+) -> DenoResult<Resource> {
+  add_counted(table, Repr::TcpStream(stream))
+}
+
+fn add_counted(table: &ResourceTable, repr: Repr) -> DenoResult<Resource> {
+  table.check_room()?;
+  let rid = add_repr(repr);
+  SLOTS.lock().unwrap().insert(rid, Slot::new(&table.global));
+  Ok(Resource { rid })
+}
+
+// Counts a resource in the global table as one of a ResourceTable's until it
+// is dropped.
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+  fn new(count: &Arc<AtomicUsize>) -> Slot {
+    count.fetch_add(1, Ordering::SeqCst);
+    Slot(count.clone())
   }
 }
 
-pub fn add_tcp_listener(listener: tokio::net::TcpListener) -> Resource {
-  let rid = new_rid();
-  let mut tg = RESOURCE_TABLE.lock().unwrap();
-  let r = tg.insert(rid, Repr::TcpListener(listener));
-  assert!(r.is_none());
-  Resource { rid }
+impl Drop for Slot {
+  fn drop(&mut self) {
+    self.0.fetch_sub(1, Ordering::SeqCst);
+  }
+}
+
+// The resources of a child process started by op_run. The rids of its stdio
+// are only set for the streams that were piped.
+pub struct ChildResources {
+  pub child_rid: ResourceId,
+  pub stdin_rid: Option<ResourceId>,
+  pub stdout_rid: Option<ResourceId>,
+  pub stderr_rid: Option<ResourceId>,
 }
 
-pub fn add_tcp_stream(stream: tokio::net::TcpStream) -> Resource {
+fn add_repr(repr: Repr) -> ResourceId {
   let rid = new_rid();
   let mut tg = RESOURCE_TABLE.lock().unwrap();
-  let r = tg.insert(rid, Repr::TcpStream(stream));
+  let r = tg.insert(rid, repr);
   assert!(r.is_none());
-  Resource { rid }
+  rid
+}
+
+pub fn add_child(mut child: Child) -> ChildResources {
+  let stdin_rid = child.stdin.take().map(|stdin| {
+    add_repr(Repr::ChildPipe(ChildPipe::Stdin(Arc::new(Mutex::new(stdin)))))
+  });
+  let stdout_rid = child.stdout.take().map(|stdout| {
+    add_repr(Repr::ChildPipe(ChildPipe::Stdout(Arc::new(Mutex::new(stdout)))))
+  });
+  let stderr_rid = child.stderr.take().map(|stderr| {
+    add_repr(Repr::ChildPipe(ChildPipe::Stderr(Arc::new(Mutex::new(stderr)))))
+  });
+  let child_rid = add_repr(Repr::Child(Arc::new(Mutex::new(child))));
+  ChildResources {
+    child_rid,
+    stdin_rid,
+    stdout_rid,
+    stderr_rid,
+  }
+}
+
+// Waits for the child process with rid to exit. This blocks, so it should be
+// called on the blocking pool. The child stays in the table until it is
+// closed, and asking again returns the same status.
+pub fn child_status(rid: ResourceId) -> DenoResult<ExitStatus> {
+  let child = {
+    let table = RESOURCE_TABLE.lock().unwrap();
+    match table.get(&rid) {
+      Some(Repr::Child(child)) => child.clone(),
+      _ => return Err(bad_resource()),
+    }
+  };
+  let status = child.lock().unwrap().wait()?;
+  Ok(status)
+}
+
+// Duplicates the open file rid, so that slow calls can be made on the copy
+// without holding the table's lock. The copy shares the file's position.
+fn poll_clone_fs_file(
+  rid: ResourceId,
+) -> Poll<Option<tokio::fs::File>, DenoError> {
+  let mut table = RESOURCE_TABLE.lock().unwrap();
+  match table.get_mut(&rid) {
+    Some(Repr::FsFile(ref mut f)) => {
+      let file = try_ready!(f.poll_try_clone());
+      Ok(futures::Async::Ready(Some(file)))
+    }
+    Some(_) => Ok(futures::Async::Ready(None)),
+    None => Err(bad_resource()),
+  }
+}
+
+// Truncates or extends the open file rid to len bytes. Must be polled on the
+// blocking pool. The table is only locked while the file is duplicated, not
+// across set_len(), which can be slow.
+pub fn poll_set_len(rid: ResourceId, len: u64) -> Poll<(), DenoError> {
+  match try_ready!(poll_clone_fs_file(rid)) {
+    Some(mut file) => file.poll_set_len(len).map_err(DenoError::from),
+    None => Err(bad_resource()),
+  }
+}
+
+// Moves the position of the open file rid, and returns the new position from
+// the start of the file. Must be polled on the blocking pool. Like
+// poll_set_len(), this seeks a duplicate of the file.
+pub fn poll_seek(rid: ResourceId, pos: SeekFrom) -> Poll<u64, DenoError> {
+  match try_ready!(poll_clone_fs_file(rid)) {
+    Some(mut file) => file.poll_seek(pos).map_err(DenoError::from),
+    None => Err(bad_resource()),
+  }
+}
+
+// Flushes the open file rid to disk, see msg::Fsync. Must be polled on the
+// blocking pool. Like poll_set_len(), this flushes a duplicate of the file.
+pub fn poll_fsync(rid: ResourceId, data_only: bool) -> Poll<(), DenoError> {
+  match try_ready!(poll_clone_fs_file(rid)) {
+    Some(mut file) if data_only => {
+      file.poll_sync_data().map_err(DenoError::from)
+    }
+    Some(mut file) => file.poll_sync_all().map_err(DenoError::from),
+    None => Err(errors::new(
+      errors::ErrorKind::InvalidInput,
+      String::from("not a file"),
+    )),
+  }
+}
+
+// The columns and rows of the terminal that rid is. Only stdio can be a
+// terminal; anything else is NotATerminal.
+pub fn console_size(rid: ResourceId) -> DenoResult<(u32, u32)> {
+  let fd = match RESOURCE_TABLE.lock().unwrap().get(&rid) {
+    Some(Repr::Stdin(_)) => 0,
+    Some(Repr::Stdout(_)) => 1,
+    Some(Repr::Stderr(_)) => 2,
+    Some(_) => return Err(errors::not_a_terminal()),
+    None => return Err(bad_resource()),
+  };
+  fd_console_size(fd)
+}
+
+#[cfg(any(unix))]
+fn fd_console_size(fd: libc::c_int) -> DenoResult<(u32, u32)> {
+  if unsafe { libc::isatty(fd) } == 0 {
+    return Err(errors::not_a_terminal());
+  }
+  let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+  if unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) } != 0 {
+    return Err(DenoError::from(Error::last_os_error()));
+  }
+  Ok((u32::from(size.ws_col), u32::from(size.ws_row)))
+}
+
+#[cfg(not(any(unix)))]
+fn fd_console_size(_fd: i32) -> DenoResult<(u32, u32)> {
+  Err(errors::new(
+    errors::ErrorKind::Other,
+    "Not implemented".to_string(),
+  ))
 }
 
 pub fn lookup(rid: ResourceId) -> Option<Resource> {
   let table = RESOURCE_TABLE.lock().unwrap();
   table.get(&rid).map(|_| Resource { rid })
 }
+
+// A table of resources owned by one isolate, for ops that hand out handles to
+// things of their own, such as ops registered by embedders. See
+// IsolateState::resource_table. Its rids come from the same sequence as the
+// global table's, so the two never collide.
+#[derive(Default)]
+pub struct ResourceTable {
+  resources: HashMap<ResourceId, Box<Any + Send>>,
+  max: Option<usize>,
+  // How many resources in the global table were added for this one, see
+  // add_fs_file(). They count against max too.
+  global: Arc<AtomicUsize>,
+}
+
+impl ResourceTable {
+  // A table that holds at most max resources at once, or any number if max
+  // is None.
+  pub fn new(max: Option<usize>) -> Self {
+    ResourceTable {
+      resources: HashMap::new(),
+      max,
+      global: Arc::new(AtomicUsize::new(0)),
+    }
+  }
+
+  // Fails with TooManyOpenResources if the table is full, in which case
+  // resource is dropped.
+  pub fn add(&mut self, resource: Box<Any + Send>) -> DenoResult<ResourceId> {
+    self.check_room()?;
+    let rid = new_rid();
+    let r = self.resources.insert(rid, resource);
+    assert!(r.is_none());
+    Ok(rid)
+  }
+
+  // Fails with BadResource if rid isn't open or isn't a T.
+  pub fn get_mut<T: Any>(&mut self, rid: ResourceId) -> DenoResult<&mut T> {
+    self
+      .resources
+      .get_mut(&rid)
+      .and_then(|resource| resource.downcast_mut::<T>())
+      .ok_or_else(bad_resource)
+  }
+
+  // Drops the resource, which closes it.
+  pub fn close(&mut self, rid: ResourceId) -> DenoResult<()> {
+    match self.resources.remove(&rid) {
+      Some(_) => Ok(()),
+      None => Err(bad_resource()),
+    }
+  }
+
+  // Closes every resource.
+  pub fn clear(&mut self) {
+    self.resources.clear();
+  }
+
+  fn check_room(&self) -> DenoResult<()> {
+    let open = self.resources.len() + self.global.load(Ordering::SeqCst);
+    match self.max {
+      Some(max) if open >= max => Err(too_many_open_resources()),
+      _ => Ok(()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use errors::ErrorKind;
+  use std::sync::atomic::AtomicBool;
+  use tempfile;
+
+  // Sets its flag when dropped.
+  struct Dummy {
+    value: u32,
+    dropped: Arc<AtomicBool>,
+  }
+
+  impl Drop for Dummy {
+    fn drop(&mut self) {
+      self.dropped.store(true, Ordering::SeqCst);
+    }
+  }
+
+  #[test]
+  fn test_resource_table() {
+    let mut table = ResourceTable::default();
+    let dropped = Arc::new(AtomicBool::new(false));
+    let rid = table
+      .add(Box::new(Dummy {
+        value: 1,
+        dropped: dropped.clone(),
+      }))
+      .unwrap();
+    // Doesn't collide with the global table.
+    assert!(lookup(rid).is_none());
+
+    table.get_mut::<Dummy>(rid).unwrap().value += 1;
+    assert_eq!(table.get_mut::<Dummy>(rid).unwrap().value, 2);
+    let err = table.get_mut::<String>(rid).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::BadFileDescriptor);
+
+    table.close(rid).unwrap();
+    assert!(dropped.load(Ordering::SeqCst));
+    let err = table.get_mut::<Dummy>(rid).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::BadFileDescriptor);
+    let err = table.close(rid).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::BadFileDescriptor);
+    let unknown = rid + 1000;
+    assert!(table.get_mut::<Dummy>(unknown).is_err());
+  }
+
+  #[test]
+  fn test_resource_table_max() {
+    let mut table = ResourceTable::new(Some(2));
+    let a = table.add(Box::new(1u32)).unwrap();
+    table.add(Box::new(2u32)).unwrap();
+    let dropped = Arc::new(AtomicBool::new(false));
+    let err = table
+      .add(Box::new(Dummy {
+        value: 3,
+        dropped: dropped.clone(),
+      }))
+      .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TooManyOpenResources);
+    assert!(dropped.load(Ordering::SeqCst));
+
+    // Closing one makes room for another.
+    table.close(a).unwrap();
+    let c = table.add(Box::new(4u32)).unwrap();
+    assert_eq!(*table.get_mut::<u32>(c).unwrap(), 4);
+    assert!(table.add(Box::new(5u32)).is_err());
+
+    // Without a limit there is always room.
+    let mut table = ResourceTable::default();
+    for i in 0..100u32 {
+      table.add(Box::new(i)).unwrap();
+    }
+  }
+
+  #[cfg(any(unix))]
+  #[test]
+  fn test_console_size() {
+    // A pipe isn't a terminal.
+    let mut fds = [0; 2];
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    let err = fd_console_size(fds[1]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotATerminal);
+    unsafe {
+      libc::close(fds[0]);
+      libc::close(fds[1]);
+    }
+
+    // Neither is a file.
+    let file = tempfile::tempfile().unwrap();
+    let table = ResourceTable::default();
+    let rid = add_fs_file(&table, tokio::fs::File::from_std(file))
+      .unwrap()
+      .rid;
+    let err = console_size(rid).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NotATerminal);
+    let err = console_size(rid + 1000).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::BadFileDescriptor);
+
+    // The slave side of a pty is one, whose size is set on the master side.
+    let master = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY) };
+    if master < 0 {
+      // No ptys in this environment.
+      return;
+    }
+    let slave = unsafe {
+      assert_eq!(libc::grantpt(master), 0);
+      assert_eq!(libc::unlockpt(master), 0);
+      let name = libc::ptsname(master);
+      assert!(!name.is_null());
+      libc::open(name, libc::O_RDWR | libc::O_NOCTTY)
+    };
+    assert!(slave >= 0);
+    let size = libc::winsize {
+      ws_row: 24,
+      ws_col: 80,
+      ws_xpixel: 0,
+      ws_ypixel: 0,
+    };
+    assert_eq!(unsafe { libc::ioctl(master, libc::TIOCSWINSZ, &size) }, 0);
+    assert_eq!(fd_console_size(slave).unwrap(), (80, 24));
+    unsafe {
+      libc::close(slave);
+      libc::close(master);
+    }
+  }
+}
@@ -1,26 +1,35 @@
 // Copyright 2018 the Deno authors. All rights reserved. MIT license.
 
+use errors;
+use errors::DenoError;
+use errors::DenoResult;
 use resources::Resource;
 
 use futures;
 use futures::Future;
 use futures::Poll;
-use std::io;
 use std::mem;
 use std::net::SocketAddr;
+use std::panic::AssertUnwindSafe;
 use tokio;
 use tokio::net::TcpStream;
 use tokio_executor;
 
-pub fn block_on<F, R, E>(future: F) -> Result<R, E>
+// Runs future on the runtime and waits for it. If the future panics the panic
+// is returned as an OpPanicked error instead, since block_on() is called from
+// libdeno callbacks and unwinding across the FFI boundary is undefined.
+pub fn block_on<F, R>(future: F) -> DenoResult<R>
 where
-  F: Send + 'static + Future<Item = R, Error = E>,
+  F: Send + 'static + Future<Item = R, Error = DenoError>,
   R: Send + 'static,
-  E: Send + 'static,
 {
   let (tx, rx) = futures::sync::oneshot::channel();
+  let future = AssertUnwindSafe(future).catch_unwind();
   tokio::spawn(future.then(move |r| tx.send(r).map_err(|_| unreachable!())));
-  rx.wait().unwrap()
+  match rx.wait().unwrap() {
+    Ok(result) => result,
+    Err(payload) => Err(errors::op_panicked(payload)),
+  }
 }
 
 // Set the default executor so we can use tokio::spawn(). It's difficult to
@@ -60,7 +69,7 @@ pub struct Accept {
 
 impl Future for Accept {
   type Item = (TcpStream, SocketAddr);
-  type Error = io::Error;
+  type Error = DenoError;
 
   fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
     let (stream, addr) = match self.state {
@@ -3,12 +3,37 @@ use libdeno;
 use std::ffi::CStr;
 
 // This is the source of truth for the Deno version. Ignore the value in Cargo.toml.
-const DENO_VERSION: &str = "0.1.7";
+pub const DENO_VERSION: &str = "0.1.7";
 
-pub fn print_version() {
+// The version of V8 that libdeno was built with, like "7.0.247".
+pub fn v8_version() -> String {
   let v = unsafe { libdeno::deno_v8_version() };
   let c_str = unsafe { CStr::from_ptr(v) };
-  let version = c_str.to_str().unwrap();
+  c_str.to_str().unwrap().to_string()
+}
+
+pub fn print_version() {
   println!("deno: {}", DENO_VERSION);
-  println!("v8: {}", version);
+  println!("v8: {}", v8_version());
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Versions are numbers separated by dots, maybe followed by a tag like
+  // "-canary".
+  fn is_dotted_version(version: &str) -> bool {
+    let numbers = version.split('-').next().unwrap();
+    numbers.split('.').count() >= 3
+      && numbers.split('.').all(|n| n.parse::<u32>().is_ok())
+  }
+
+  #[test]
+  fn test_versions() {
+    assert!(is_dotted_version(&v8_version()));
+    assert!(is_dotted_version(DENO_VERSION));
+    assert!(!is_dotted_version(""));
+    assert!(!is_dotted_version("7.x.1"));
+  }
 }
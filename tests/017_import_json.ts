@@ -0,0 +1,4 @@
+import config from "./subdir/config.json";
+
+console.log(config.name);
+console.log(config.features);
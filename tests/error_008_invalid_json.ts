@@ -0,0 +1,3 @@
+import config from "./subdir/invalid.json";
+
+console.log(config.name);
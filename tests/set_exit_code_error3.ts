@@ -0,0 +1,7 @@
+import * as deno from "deno";
+
+deno.setExitCode(3);
+console.log("before");
+setTimeout(() => {
+  console.log("after");
+}, 10);
@@ -5,29 +5,47 @@ import sys
 
 # We want to test many ops in deno which have different behavior depending on
 # the permissions set. These tests can specify which permissions they expect,
-# which appends a special string like "permW1N0" to the end of the test name.
-# Here we run several copies of deno with different permissions, filtering the
-# tests by the special string. permW0N0 means allow-write but not allow-net.
+# which appends a special string like "permR0W1N0E0U0" to the end of the test
+# name. Here we run several copies of deno with different permissions,
+# filtering the tests by the special string. permR0W1N0E0U0 means allow-write
+# but not allow-read, allow-net, allow-env or allow-run.
 # See js/test_util.ts for more details.
 def unit_tests(deno_exe):
-    run([deno_exe, "--reload", "js/unit_tests.ts", "permW0N0E0"])
+    run([deno_exe, "--reload", "js/unit_tests.ts", "permR0W0N0E0U0"])
     run([
-        deno_exe, "--reload", "js/unit_tests.ts", "permW1N0E0", "--allow-write"
+        deno_exe, "--reload", "js/unit_tests.ts", "permR1W0N0E0U0",
+        "--allow-read"
     ])
     run([
-        deno_exe, "--reload", "js/unit_tests.ts", "permW0N1E0", "--allow-net"
+        deno_exe, "--reload", "js/unit_tests.ts", "permR0W1N0E0U0",
+        "--allow-write"
     ])
     run([
-        deno_exe, "--reload", "js/unit_tests.ts", "permW0N0E1", "--allow-env"
+        deno_exe, "--reload", "js/unit_tests.ts", "permR1W1N0E0U0",
+        "--allow-read", "--allow-write"
+    ])
+    run([
+        deno_exe, "--reload", "js/unit_tests.ts", "permR0W0N1E0U0",
+        "--allow-net"
+    ])
+    run([
+        deno_exe, "--reload", "js/unit_tests.ts", "permR0W0N0E1U0",
+        "--allow-env"
+    ])
+    run([
+        deno_exe, "--reload", "js/unit_tests.ts", "permR0W0N0E0U1",
+        "--allow-run"
     ])
     run([
         deno_exe,
         "--reload",
         "js/unit_tests.ts",
-        "permW1N1E1",
+        "permR1W1N1E1U1",
+        "--allow-read",
         "--allow-write",
         "--allow-net",
         "--allow-env",
+        "--allow-run",
     ])
 
 